@@ -192,6 +192,8 @@ struct RawRepoConfig {
   // deep-sharded: In addition to requests, repo is also sharded, i.e. present
   // on select servers.
   54: optional RawShardingModeConfig deep_sharding_config;
+  // Bookmark namespaces with their own write ACLs
+  55: optional list<RawBookmarkNamespaceConfig> bookmark_namespaces;
 } (rust.exhaustive)
 
 // Config determining if deep sharding mode is enabled for a service.
@@ -581,6 +583,15 @@ struct RawBookmarkConfig {
   11: optional bool allow_move_to_public_commits_without_hooks;
 } (rust.exhaustive)
 
+struct RawBookmarkNamespaceConfig {
+  // Prefix of the names of the bookmarks in the namespace, like "team-A/"
+  1: string prefix;
+  // JSON file with the ACLs that control writes to the namespace, in
+  // addition to the default ACL. Writes to the namespace check the ACL
+  // named after the repo's hipster_acl.
+  2: optional string acl_file;
+} (rust.exhaustive)
+
 struct RawAllowlistIdentity {
   1: string identity_type;
   2: string identity_data;
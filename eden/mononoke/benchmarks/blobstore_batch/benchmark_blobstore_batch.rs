@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Compare fetching keys one at a time against `Blobstore::get_many`.
+
+use anyhow::Result;
+use blobstore::Blobstore;
+use context::CoreContext;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use delayblob::DelayedBlobstore;
+use delayblob::Normal;
+use memblob::Memblob;
+use mononoke_types::BlobstoreBytes;
+use tokio::runtime::Runtime;
+
+const NUM_KEYS: usize = 100;
+
+/// Mean and standard deviation of the simulated round-trip time, in seconds.
+const LATENCY_MEAN: f64 = 0.001;
+const LATENCY_STDDEV: f64 = 0.0002;
+
+#[fbinit::main]
+fn main(fb: fbinit::FacebookInit) -> Result<()> {
+    let runtime = Runtime::new()?;
+    let ctx = CoreContext::test_mock(fb);
+    let latency = Normal::new(LATENCY_MEAN, LATENCY_STDDEV)?;
+    let blobstore = DelayedBlobstore::new(Memblob::default(), latency, latency);
+
+    let keys: Vec<String> = (0..NUM_KEYS).map(|i| format!("key{}", i)).collect();
+    runtime.block_on(async {
+        let entries = keys
+            .iter()
+            .map(|key| (key.clone(), BlobstoreBytes::from_bytes(key.clone())))
+            .collect();
+        blobstore.put_many(&ctx, entries).await
+    })?;
+
+    let mut criterion = Criterion::default();
+    let mut group = criterion.benchmark_group("get");
+    group.throughput(Throughput::Elements(NUM_KEYS as u64));
+    group.bench_with_input(
+        BenchmarkId::new("sequential", NUM_KEYS),
+        &keys,
+        |b, keys| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    for key in keys {
+                        blobstore.get(&ctx, key).await.expect("get failed");
+                    }
+                })
+            })
+        },
+    );
+    group.bench_with_input(BenchmarkId::new("get_many", NUM_KEYS), &keys, |b, keys| {
+        b.iter(|| {
+            runtime
+                .block_on(blobstore.get_many(&ctx, keys.clone()))
+                .expect("get_many failed")
+        })
+    });
+    group.finish();
+
+    criterion.final_summary();
+
+    Ok(())
+}
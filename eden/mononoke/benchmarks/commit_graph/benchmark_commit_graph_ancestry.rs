@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! This benchmark builds an in-memory commit graph of 100k commits, made of
+//! a mainline with short branches merged back into it, and then compares
+//! answering ancestry queries with `CommitGraph::is_ancestor` against a
+//! naive breadth-first search of the ancestors of the descendant.
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use anyhow::Result;
+use commit_graph::CommitGraph;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures_stats::TimedFutureExt;
+use in_memory_commit_graph_storage::InMemoryCommitGraphStorage;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use smallvec::smallvec;
+
+const COMMIT_COUNT: usize = 100_000;
+const BRANCH_INTERVAL: usize = 50;
+const BRANCH_LENGTH: usize = 10;
+const QUERY_COUNT: usize = 100;
+
+fn gen_cs_id(index: usize) -> ChangesetId {
+    let mut bytes = [0; 32];
+    bytes[..8].copy_from_slice(&(index as u64).to_le_bytes());
+    ChangesetId::from_bytes(bytes).expect("Changeset ID should be valid")
+}
+
+/// Build the commit graph, returning the commits of the mainline and of the
+/// branches, in the order they were added.
+async fn make_graph(
+    ctx: &CoreContext,
+    graph: &CommitGraph,
+) -> Result<(Vec<ChangesetId>, Vec<ChangesetId>)> {
+    let mut mainline = vec![gen_cs_id(0)];
+    let mut branches = vec![];
+    graph.add(ctx, mainline[0], smallvec![]).await?;
+
+    let mut index = 1;
+    while index < COMMIT_COUNT {
+        let head = *mainline.last().unwrap();
+        let cs_id = gen_cs_id(index);
+        index += 1;
+        if mainline.len() % BRANCH_INTERVAL == 0 {
+            // Fork a branch from the mainline, and merge it back.
+            let mut branch_head = head;
+            for _ in 0..BRANCH_LENGTH {
+                let branch_cs_id = gen_cs_id(index);
+                index += 1;
+                graph.add(ctx, branch_cs_id, smallvec![branch_head]).await?;
+                branches.push(branch_cs_id);
+                branch_head = branch_cs_id;
+            }
+            graph.add(ctx, cs_id, smallvec![head, branch_head]).await?;
+        } else {
+            graph.add(ctx, cs_id, smallvec![head]).await?;
+        }
+        mainline.push(cs_id);
+    }
+
+    Ok((mainline, branches))
+}
+
+/// Whether ancestor is an ancestor of descendant, by visiting all ancestors
+/// of descendant until finding it.
+async fn naive_is_ancestor(
+    ctx: &CoreContext,
+    graph: &CommitGraph,
+    ancestor: ChangesetId,
+    descendant: ChangesetId,
+) -> Result<bool> {
+    let mut visited = HashSet::from([descendant]);
+    let mut queue = VecDeque::from([descendant]);
+    while let Some(cs_id) = queue.pop_front() {
+        if cs_id == ancestor {
+            return Ok(true);
+        }
+        for parent in graph.changeset_parents_required(ctx, cs_id).await? {
+            if visited.insert(parent) {
+                queue.push_back(parent);
+            }
+        }
+    }
+    Ok(false)
+}
+
+#[fbinit::main]
+async fn main(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let graph = CommitGraph::new(Arc::new(InMemoryCommitGraphStorage::new(
+        RepositoryId::new(1),
+    )));
+
+    let (stats, res) = make_graph(&ctx, &graph).timed().await;
+    let (mainline, branches) = res?;
+    println!(
+        "Built graph of {} commits: {:?}",
+        mainline.len() + branches.len(),
+        stats.completion_time
+    );
+
+    // Pairs of commits chosen at random, in both directions, so that about
+    // half of them are ancestors of each other. Branch commits make sure
+    // there are also unrelated pairs that are close in generation.
+    let mut rng = thread_rng();
+    let queries = (0..QUERY_COUNT)
+        .map(|_| {
+            let u = *mainline.choose(&mut rng).unwrap();
+            let v = *mainline.choose(&mut rng).unwrap();
+            let w = *branches.choose(&mut rng).unwrap();
+            [(u, v), (v, u), (w, u)]
+        })
+        .collect::<Vec<_>>()
+        .concat();
+
+    let (stats, res) = async {
+        let mut results = vec![];
+        for (ancestor, descendant) in &queries {
+            results.push(graph.is_ancestor(&ctx, *ancestor, *descendant).await?);
+        }
+        anyhow::Ok(results)
+    }
+    .timed()
+    .await;
+    let results = res?;
+    println!(
+        "is_ancestor: {} queries in {:?}",
+        queries.len(),
+        stats.completion_time
+    );
+
+    let (stats, res) = async {
+        let mut results = vec![];
+        for (ancestor, descendant) in &queries {
+            results.push(naive_is_ancestor(&ctx, &graph, *ancestor, *descendant).await?);
+        }
+        anyhow::Ok(results)
+    }
+    .timed()
+    .await;
+    let naive_results = res?;
+    println!(
+        "naive breadth-first search: {} queries in {:?}",
+        queries.len(),
+        stats.completion_time
+    );
+
+    assert_eq!(results, naive_results, "both methods should agree");
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! This benchmark builds an in-memory linear commit graph of 50k commits,
+//! and then compares finding the ancestors of a batch of commits with
+//! `CommitGraph::ancestors_batch` against traversing from each commit in
+//! turn with `CommitGraph::reachable_set`.
+
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Result;
+use commit_graph::BatchTraversalOptions;
+use commit_graph::CommitGraph;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::TryStreamExt;
+use futures_stats::TimedFutureExt;
+use in_memory_commit_graph_storage::InMemoryCommitGraphStorage;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use smallvec::smallvec;
+
+const COMMIT_COUNT: usize = 50_000;
+const QUERY_COUNT: usize = 100;
+const MAX_DEPTH: usize = 1_000;
+
+fn gen_cs_id(index: usize) -> ChangesetId {
+    let mut bytes = [0; 32];
+    bytes[..8].copy_from_slice(&(index as u64).to_le_bytes());
+    ChangesetId::from_bytes(bytes).expect("Changeset ID should be valid")
+}
+
+#[fbinit::main]
+async fn main(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let graph = CommitGraph::new(Arc::new(InMemoryCommitGraphStorage::new(
+        RepositoryId::new(1),
+    )));
+
+    let cs_ids = (0..COMMIT_COUNT).map(gen_cs_id).collect::<Vec<_>>();
+    let (stats, res) = async {
+        graph.add(&ctx, cs_ids[0], smallvec![]).await?;
+        for window in cs_ids.windows(2) {
+            graph.add(&ctx, window[1], smallvec![window[0]]).await?;
+        }
+        anyhow::Ok(())
+    }
+    .timed()
+    .await;
+    res?;
+    println!(
+        "Built graph of {} commits: {:?}",
+        cs_ids.len(),
+        stats.completion_time
+    );
+
+    let mut rng = thread_rng();
+    let queries = cs_ids
+        .choose_multiple(&mut rng, QUERY_COUNT)
+        .copied()
+        .collect::<Vec<_>>();
+
+    let options = BatchTraversalOptions::default();
+    let (stats, res) = graph
+        .ancestors_batch(&ctx, queries.clone(), Some(MAX_DEPTH), options.clone())
+        .try_collect::<Vec<_>>()
+        .timed()
+        .await;
+    let results = res?;
+    println!(
+        "ancestors_batch: {} queries in {:?} ({} cache hits)",
+        queries.len(),
+        stats.completion_time,
+        options.cache_hits.load(Ordering::Relaxed),
+    );
+
+    let (stats, res) = async {
+        let mut results = vec![];
+        for cs_id in &queries {
+            results.push((*cs_id, graph.reachable_set(&ctx, *cs_id, MAX_DEPTH).await?));
+        }
+        anyhow::Ok(results)
+    }
+    .timed()
+    .await;
+    let mut sequential_results = res?;
+    println!(
+        "sequential reachable_set: {} queries in {:?}",
+        queries.len(),
+        stats.completion_time
+    );
+
+    let mut results = results
+        .into_iter()
+        .map(|(cs_id, ancestors)| (cs_id, ancestors.into_iter().collect::<HashSet<_>>()))
+        .collect::<Vec<_>>();
+    results.sort_by_key(|(cs_id, _)| *cs_id);
+    sequential_results.sort_by_key(|(cs_id, _)| *cs_id);
+    assert_eq!(results, sequential_results, "both methods should agree");
+
+    Ok(())
+}
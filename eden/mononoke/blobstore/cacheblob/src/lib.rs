@@ -27,3 +27,6 @@ pub use crate::memcache_cache_lease::MemcacheOps;
 
 mod mem_writes;
 pub use crate::mem_writes::MemWritesBlobstore;
+
+mod negative_cache;
+pub use crate::negative_cache::NegativeCacheBlobstore;
@@ -0,0 +1,304 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::BlobstoreUnlinkOps;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use moka::sync::Cache;
+use mononoke_types::BlobstoreBytes;
+
+/// A blobstore wrapper that remembers which keys were missing from the
+/// underlying blobstore for `negative_cache_ttl`, and answers `get` and
+/// `is_present` for them without going to the underlying blobstore.
+///
+/// Keys are removed from the negative cache when they are written through
+/// this wrapper. Writes that bypass it are only seen once the TTL expires.
+#[derive(Debug)]
+pub struct NegativeCacheBlobstore<B> {
+    inner: B,
+    negative_cache: Cache<String, ()>,
+    // Bumped by every write, so that a miss that raced with a write is not
+    // cached. Held while recording a miss, so that a write cannot happen
+    // between the check and the insert.
+    generation: Mutex<u64>,
+}
+
+impl<B: std::fmt::Display> std::fmt::Display for NegativeCacheBlobstore<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "NegativeCacheBlobstore<{}>", self.inner)
+    }
+}
+
+impl<B> NegativeCacheBlobstore<B> {
+    pub fn new(inner: B, negative_cache_ttl: Duration) -> Self {
+        Self {
+            inner,
+            negative_cache: Cache::builder().time_to_live(negative_cache_ttl).build(),
+            generation: Mutex::new(0),
+        }
+    }
+
+    /// Number of keys currently known to be absent.
+    pub fn negative_cache_size(&self) -> usize {
+        self.negative_cache.run_pending_tasks();
+        self.negative_cache.entry_count() as usize
+    }
+
+    fn is_known_absent(&self, key: &str) -> bool {
+        self.negative_cache.contains_key(key)
+    }
+
+    fn generation(&self) -> u64 {
+        *self.generation.lock().expect("lock poisoned")
+    }
+
+    /// Remember that `key` is absent, unless something was written since
+    /// `generation`, when the lookup started.
+    fn record_absent(&self, key: &str, generation: u64) {
+        let current = self.generation.lock().expect("lock poisoned");
+        if *current == generation {
+            self.negative_cache.insert(key.to_owned(), ());
+        }
+    }
+
+    fn invalidate(&self, key: &str) {
+        let mut current = self.generation.lock().expect("lock poisoned");
+        *current += 1;
+        self.negative_cache.invalidate(key);
+    }
+}
+
+#[async_trait]
+impl<B: BlobstorePutOps> Blobstore for NegativeCacheBlobstore<B> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        if self.is_known_absent(key) {
+            return Ok(None);
+        }
+        let generation = self.generation();
+        let value = self.inner.get(ctx, key).await?;
+        if value.is_none() {
+            self.record_absent(key, generation);
+        }
+        Ok(value)
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.put_impl(ctx, key, value, None).await?;
+        Ok(())
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        if self.is_known_absent(key) {
+            return Ok(BlobstoreIsPresent::Absent);
+        }
+        let generation = self.generation();
+        let present = self.inner.is_present(ctx, key).await?;
+        if let BlobstoreIsPresent::Absent = present {
+            self.record_absent(key, generation);
+        }
+        Ok(present)
+    }
+}
+
+impl<B: BlobstorePutOps> NegativeCacheBlobstore<B> {
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        let result = if let Some(put_behaviour) = put_behaviour {
+            self.inner
+                .put_explicit(ctx, key.clone(), value, put_behaviour)
+                .await
+        } else {
+            self.inner.put_with_status(ctx, key.clone(), value).await
+        };
+        // Invalidate even if the put failed, as it may have been partially
+        // applied.
+        self.invalidate(&key);
+        result
+    }
+}
+
+#[async_trait]
+impl<B: BlobstorePutOps> BlobstorePutOps for NegativeCacheBlobstore<B> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, Some(put_behaviour)).await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, None).await
+    }
+}
+
+#[async_trait]
+impl<B: BlobstoreUnlinkOps> BlobstoreUnlinkOps for NegativeCacheBlobstore<B> {
+    async fn unlink<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<()> {
+        self.inner.unlink(ctx, key).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    use super::*;
+
+    /// Blobstore that counts the gets that reach it.
+    #[derive(Debug, Default)]
+    struct CountingBlobstore {
+        inner: Memblob,
+        gets: AtomicU64,
+    }
+
+    impl std::fmt::Display for CountingBlobstore {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "CountingBlobstore")
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for CountingBlobstore {
+        async fn get<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            self.inner.get(ctx, key).await
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.inner.put(ctx, key, value).await
+        }
+    }
+
+    #[async_trait]
+    impl BlobstorePutOps for CountingBlobstore {
+        async fn put_explicit<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+            put_behaviour: PutBehaviour,
+        ) -> Result<OverwriteStatus> {
+            self.inner
+                .put_explicit(ctx, key, value, put_behaviour)
+                .await
+        }
+
+        async fn put_with_status<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<OverwriteStatus> {
+            self.inner.put_with_status(ctx, key, value).await
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_negative_cache(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let blobstore =
+            NegativeCacheBlobstore::new(CountingBlobstore::default(), Duration::from_secs(3600));
+        let gets = || blobstore.inner.gets.load(Ordering::SeqCst);
+
+        assert!(blobstore.get(ctx, "foo").await.unwrap().is_none());
+        assert!(blobstore.get(ctx, "foo").await.unwrap().is_none());
+        assert_eq!(gets(), 1);
+        assert_eq!(blobstore.negative_cache_size(), 1);
+
+        let value = BlobstoreBytes::from_bytes("foobar");
+        blobstore
+            .put(ctx, "foo".to_owned(), value.clone())
+            .await
+            .unwrap();
+        assert_eq!(blobstore.negative_cache_size(), 0);
+
+        let fetched = blobstore.get(ctx, "foo").await.unwrap();
+        assert_eq!(fetched.map(BlobstoreGetData::into_bytes), Some(value));
+        assert_eq!(gets(), 2);
+    }
+
+    #[test]
+    fn test_negative_cache_write_during_lookup() {
+        let blobstore =
+            NegativeCacheBlobstore::new(CountingBlobstore::default(), Duration::from_secs(3600));
+
+        // A miss is not cached if the key was written after the lookup
+        // started, as the lookup may have missed the write.
+        let generation = blobstore.generation();
+        blobstore.invalidate("foo");
+        blobstore.record_absent("foo", generation);
+        assert_eq!(blobstore.negative_cache_size(), 0);
+
+        blobstore.record_absent("foo", blobstore.generation());
+        assert_eq!(blobstore.negative_cache_size(), 1);
+    }
+
+    #[fbinit::test]
+    async fn test_negative_cache_ttl(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let blobstore =
+            NegativeCacheBlobstore::new(CountingBlobstore::default(), Duration::from_millis(10));
+        let gets = || blobstore.inner.gets.load(Ordering::SeqCst);
+
+        assert!(blobstore.get(ctx, "foo").await.unwrap().is_none());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(blobstore.get(ctx, "foo").await.unwrap().is_none());
+        assert_eq!(gets(), 2);
+    }
+}
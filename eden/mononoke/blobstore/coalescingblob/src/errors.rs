@@ -5,10 +5,13 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::Arc;
+
+use anyhow::Error;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ErrorKind {
-    #[error("Attempt to put to ReadOnlyBlobstore for key {0}")]
-    ReadOnlyPut(String),
+    #[error("Coalesced get from CoalescingBlobstore for key {key} failed: {error:#}")]
+    CoalescedGetFailed { key: String, error: Arc<Error> },
 }
@@ -0,0 +1,428 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Error;
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::BlobstoreUnlinkOps;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use mononoke_types::BlobstoreBytes;
+use tokio::sync::OnceCell;
+
+mod errors;
+pub use crate::errors::ErrorKind;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CoalescingBlobstoreOptions {
+    /// Maximum number of `get` calls for the same key that can share a single
+    /// fetch from the underlying blobstore. Once a fetch has this many callers
+    /// attached, the next caller starts a new one.
+    pub max_in_flight_per_key: usize,
+}
+
+impl CoalescingBlobstoreOptions {
+    pub fn new(max_in_flight_per_key: usize) -> Self {
+        Self {
+            max_in_flight_per_key,
+        }
+    }
+}
+
+type GetResult = Result<Option<BlobstoreGetData>, Arc<Error>>;
+
+#[derive(Debug, Default)]
+struct InFlightGet {
+    result: OnceCell<GetResult>,
+    callers: AtomicUsize,
+}
+
+/// A layer over an existing blobstore that coalesces concurrent `get` calls
+/// for the same key, so that only one of them reaches the underlying
+/// blobstore and the others share its result.
+#[derive(Debug)]
+pub struct CoalescingBlobstore<T> {
+    blobstore: T,
+    options: CoalescingBlobstoreOptions,
+    in_flight: DashMap<String, Arc<InFlightGet>>,
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for CoalescingBlobstore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CoalescingBlobstore<{}>", &self.blobstore)
+    }
+}
+
+impl<T> CoalescingBlobstore<T> {
+    pub fn new(blobstore: T, options: CoalescingBlobstoreOptions) -> Self {
+        Self {
+            blobstore,
+            options,
+            in_flight: DashMap::new(),
+        }
+    }
+
+    /// Find the in-flight fetch for `key` that this caller should wait on,
+    /// starting a new one if there is none or if the current one is full.
+    fn join_in_flight(&self, key: &str) -> Arc<InFlightGet> {
+        match self.in_flight.entry(key.to_owned()) {
+            Entry::Occupied(mut entry) => {
+                let callers = entry.get().callers.fetch_add(1, Ordering::Relaxed);
+                if callers < self.options.max_in_flight_per_key {
+                    entry.get().clone()
+                } else {
+                    let in_flight = Arc::new(InFlightGet::default());
+                    in_flight.callers.store(1, Ordering::Relaxed);
+                    entry.insert(in_flight.clone());
+                    in_flight
+                }
+            }
+            Entry::Vacant(entry) => {
+                let in_flight = Arc::new(InFlightGet::default());
+                in_flight.callers.store(1, Ordering::Relaxed);
+                entry.insert(in_flight.clone());
+                in_flight
+            }
+        }
+    }
+
+    /// Stop sharing `in_flight` with new callers, unless it has already been
+    /// replaced by a newer fetch.
+    fn finish_in_flight(&self, key: &str, in_flight: &Arc<InFlightGet>) {
+        self.in_flight
+            .remove_if(key, |_, current| Arc::ptr_eq(current, in_flight));
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore + BlobstorePutOps> Blobstore for CoalescingBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let in_flight = self.join_in_flight(key);
+        in_flight
+            .result
+            .get_or_init(|| async { self.blobstore.get(ctx, key).await.map_err(Arc::new) })
+            .await;
+        self.finish_in_flight(key, &in_flight);
+
+        // A caller that ends up as the only holder of the result gets the
+        // original error back. Otherwise the error is shared with the other
+        // callers of the same fetch.
+        let result = match Arc::try_unwrap(in_flight) {
+            Ok(in_flight) => in_flight.result.into_inner(),
+            Err(in_flight) => in_flight.result.get().cloned(),
+        }
+        .expect("in-flight get must be initialized");
+        result.map_err(|error| match Arc::try_unwrap(error) {
+            Ok(error) => error,
+            Err(error) => ErrorKind::CoalescedGetFailed {
+                key: key.to_owned(),
+                error,
+            }
+            .into(),
+        })
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.put_impl(ctx, key, value, None).await?;
+        Ok(())
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.blobstore.is_present(ctx, key).await
+    }
+}
+
+impl<T: BlobstorePutOps> CoalescingBlobstore<T> {
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        let result = if let Some(put_behaviour) = put_behaviour {
+            self.blobstore
+                .put_explicit(ctx, key.clone(), value, put_behaviour)
+                .await
+        } else {
+            self.blobstore
+                .put_with_status(ctx, key.clone(), value)
+                .await
+        };
+
+        // Gets started after this put must not be served a result fetched
+        // before it completed, including by a fetch started while it ran.
+        self.in_flight.remove(&key);
+        result
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for CoalescingBlobstore<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, Some(put_behaviour)).await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, None).await
+    }
+}
+
+#[async_trait]
+impl<T: BlobstoreUnlinkOps> BlobstoreUnlinkOps for CoalescingBlobstore<T> {
+    async fn unlink<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<()> {
+        let result = self.blobstore.unlink(ctx, key).await;
+        self.in_flight.remove(key);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use fbinit::FacebookInit;
+    use futures::future::try_join_all;
+    use memblob::Memblob;
+
+    use super::*;
+
+    /// Blobstore that counts its gets and takes a while to answer them. Gets
+    /// return the value stored when they started, and puts optionally wait
+    /// for `put_delay` before storing their value.
+    #[derive(Debug, Default)]
+    struct SlowBlobstore {
+        inner: Memblob,
+        gets: AtomicUsize,
+        fail: bool,
+        put_delay: Duration,
+    }
+
+    impl std::fmt::Display for SlowBlobstore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "SlowBlobstore")
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for SlowBlobstore {
+        async fn get<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            let result = self.inner.get(ctx, key).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if self.fail {
+                return Err(blobstore::ErrorKind::StateOpen.into());
+            }
+            result
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.inner.put(ctx, key, value).await
+        }
+    }
+
+    #[async_trait]
+    impl BlobstorePutOps for SlowBlobstore {
+        async fn put_explicit<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+            put_behaviour: PutBehaviour,
+        ) -> Result<OverwriteStatus> {
+            tokio::time::sleep(self.put_delay).await;
+            self.inner
+                .put_explicit(ctx, key, value, put_behaviour)
+                .await
+        }
+
+        async fn put_with_status<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<OverwriteStatus> {
+            tokio::time::sleep(self.put_delay).await;
+            self.inner.put_with_status(ctx, key, value).await
+        }
+    }
+
+    async fn concurrent_gets(
+        fb: FacebookInit,
+        options: CoalescingBlobstoreOptions,
+        count: usize,
+    ) -> usize {
+        let ctx = CoreContext::test_mock(fb);
+        let wrapper = Arc::new(CoalescingBlobstore::new(SlowBlobstore::default(), options));
+        let value = BlobstoreBytes::from_bytes("test foobar");
+        wrapper
+            .put(&ctx, "foobar".to_owned(), value.clone())
+            .await
+            .unwrap();
+
+        let results = try_join_all((0..count).map(|_| {
+            let ctx = ctx.clone();
+            let wrapper = wrapper.clone();
+            tokio::spawn(async move { wrapper.get(&ctx, "foobar").await.unwrap() })
+        }))
+        .await
+        .unwrap();
+        for result in results {
+            assert_eq!(
+                result.map(BlobstoreGetData::into_bytes),
+                Some(value.clone())
+            );
+        }
+
+        wrapper.blobstore.gets.load(Ordering::SeqCst)
+    }
+
+    #[fbinit::test]
+    async fn test_concurrent_gets_coalesced(fb: FacebookInit) {
+        let gets = concurrent_gets(fb, CoalescingBlobstoreOptions::new(100), 100).await;
+        assert_eq!(gets, 1);
+    }
+
+    #[fbinit::test]
+    async fn test_max_in_flight_per_key(fb: FacebookInit) {
+        let gets = concurrent_gets(fb, CoalescingBlobstoreOptions::new(10), 100).await;
+        assert_eq!(gets, 10);
+    }
+
+    fn is_state_open(error: &Error) -> bool {
+        matches!(
+            error.downcast_ref::<blobstore::ErrorKind>(),
+            Some(blobstore::ErrorKind::StateOpen)
+        )
+    }
+
+    #[fbinit::test]
+    async fn test_get_error(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = SlowBlobstore {
+            fail: true,
+            ..Default::default()
+        };
+        let wrapper = CoalescingBlobstore::new(blobstore, CoalescingBlobstoreOptions::new(100));
+
+        let error = wrapper.get(&ctx, "foobar").await.unwrap_err();
+        assert!(is_state_open(&error), "unexpected error: {:#}", error);
+    }
+
+    #[fbinit::test]
+    async fn test_get_error_shared(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = SlowBlobstore {
+            fail: true,
+            ..Default::default()
+        };
+        let wrapper = Arc::new(CoalescingBlobstore::new(
+            blobstore,
+            CoalescingBlobstoreOptions::new(100),
+        ));
+
+        let errors = try_join_all((0..10).map(|_| {
+            let ctx = ctx.clone();
+            let wrapper = wrapper.clone();
+            tokio::spawn(async move { wrapper.get(&ctx, "foobar").await.unwrap_err() })
+        }))
+        .await
+        .unwrap();
+        for error in errors {
+            let shared = match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::CoalescedGetFailed { error, .. }) => is_state_open(error),
+                None => is_state_open(&error),
+            };
+            assert!(shared, "unexpected error: {:#}", error);
+        }
+        assert_eq!(wrapper.blobstore.gets.load(Ordering::SeqCst), 1);
+    }
+
+    #[fbinit::test]
+    async fn test_get_after_put_sees_new_value(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = SlowBlobstore {
+            put_delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let wrapper = Arc::new(CoalescingBlobstore::new(
+            blobstore,
+            CoalescingBlobstoreOptions::new(100),
+        ));
+        let old = BlobstoreBytes::from_bytes("old");
+        let new = BlobstoreBytes::from_bytes("new");
+        wrapper
+            .put(&ctx, "foobar".to_owned(), old.clone())
+            .await
+            .unwrap();
+
+        let put = tokio::spawn({
+            let ctx = ctx.clone();
+            let wrapper = wrapper.clone();
+            let new = new.clone();
+            async move { wrapper.put(&ctx, "foobar".to_owned(), new).await }
+        });
+        // This fetch starts while the put is running, reads the old value,
+        // and is still in flight when the put completes.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let concurrent_get = tokio::spawn({
+            let ctx = ctx.clone();
+            let wrapper = wrapper.clone();
+            async move { wrapper.get(&ctx, "foobar").await }
+        });
+        put.await.unwrap().unwrap();
+
+        let fetched = wrapper.get(&ctx, "foobar").await.unwrap();
+        assert_eq!(fetched.map(BlobstoreGetData::into_bytes), Some(new));
+        let concurrent = concurrent_get.await.unwrap().unwrap();
+        assert_eq!(concurrent.map(BlobstoreGetData::into_bytes), Some(old));
+    }
+}
@@ -17,6 +17,7 @@ use clap::Args;
 use metaconfig_types::PackFormat;
 use rand_distr::Normal;
 
+use crate::CoalescingBlobstoreOptions;
 use crate::PutBehaviour;
 
 /// Options for controlling the blobstore
@@ -100,6 +101,11 @@ pub struct BlobstoreArgs {
     /// Desired blobstore behaviour when a put is made to an existing key.
     #[clap(long)]
     pub blobstore_put_behaviour: Option<PutBehaviour>,
+
+    /// Share concurrent gets of the same key, with at most this many callers
+    /// waiting on a single fetch from the underlying blobstore.
+    #[clap(long)]
+    pub blobstore_coalesce_max_in_flight_per_key: Option<NonZeroUsize>,
 }
 
 impl BlobstoreArgs {
@@ -133,6 +139,13 @@ impl BlobstoreArgs {
         )
         .context("Failed to create blobstore put delay distribution")
     }
+
+    pub fn coalescing_options(&self) -> Option<CoalescingBlobstoreOptions> {
+        self.blobstore_coalesce_max_in_flight_per_key
+            .map(|max_in_flight_per_key| {
+                CoalescingBlobstoreOptions::new(max_in_flight_per_key.get())
+            })
+    }
 }
 
 #[derive(Default, Debug)]
@@ -5,8 +5,10 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -26,6 +28,8 @@ use cacheblob::CachelibBlobstoreOptions;
 use cached_config::ConfigStore;
 use chaosblob::ChaosBlobstore;
 use chaosblob::ChaosOptions;
+use coalescingblob::CoalescingBlobstore;
+use coalescingblob::CoalescingBlobstoreOptions;
 use delayblob::DelayOptions;
 use delayblob::DelayedBlobstore;
 use fbinit::FacebookInit;
@@ -34,6 +38,9 @@ use futures::future;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
 use futures_watchdog::WatchdogExt;
+use integrityblob::IntegrityAlgorithm;
+use integrityblob::IntegrityBlobstore;
+use integrityblob::IntegrityOptions;
 use logblob::LogBlob;
 #[cfg(fbcode_build)]
 use manifoldblob::ManifoldOptions;
@@ -53,10 +60,10 @@ use multiplexedblob_wal::Scuba as WalScuba;
 use multiplexedblob_wal::WalMultiplexedBlobstore;
 use packblob::PackBlob;
 use packblob::PackOptions;
-use readonlyblob::ReadOnlyBlobstore;
 use samplingblob::ComponentSamplingHandler;
 use samplingblob::SamplingBlobstoreUnlinkOps;
 use scuba_ext::MononokeScubaSampleBuilder;
+use slog::o;
 use slog::Logger;
 use sql_construct::SqlConstructFromShardedDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
@@ -65,6 +72,7 @@ use sqlblob::Sqlblob;
 use throttledblob::ThrottleOptions;
 use throttledblob::ThrottledBlob;
 
+use crate::ReadOnlyEnforcingBlobstore;
 use crate::ReadOnlyStorage;
 
 #[derive(Clone, Debug)]
@@ -79,6 +87,11 @@ pub struct BlobstoreOptions {
     pub put_behaviour: PutBehaviour,
     pub scrub_options: Option<ScrubOptions>,
     pub sqlblob_mysql_options: MysqlOptions,
+    pub integrity_options: IntegrityOptions,
+    pub coalescing_options: Option<CoalescingBlobstoreOptions>,
+    /// While set, blobstores built with these options accept writes despite
+    /// read-only storage. Shared by every clone of these options.
+    pub readonly_bypass: Arc<AtomicBool>,
 }
 
 impl BlobstoreOptions {
@@ -105,6 +118,9 @@ impl BlobstoreOptions {
             // These are added via the builder methods
             scrub_options: None,
             sqlblob_mysql_options,
+            integrity_options: Default::default(),
+            coalescing_options: None,
+            readonly_bypass: Default::default(),
         }
     }
 
@@ -123,6 +139,9 @@ impl BlobstoreOptions {
             cachelib_options: Default::default(),
             scrub_options: None,
             sqlblob_mysql_options: Default::default(),
+            integrity_options: Default::default(),
+            coalescing_options: None,
+            readonly_bypass: Default::default(),
         }
     }
 
@@ -178,6 +197,97 @@ impl BlobstoreOptions {
             self
         }
     }
+
+    /// Store the digest of every written blob, and verify the content of
+    /// every fetched blob against it. Blobs without a digest fail to load.
+    pub fn with_integrity_check(self, algorithm: IntegrityAlgorithm) -> Self {
+        Self {
+            integrity_options: self.integrity_options.with_algorithm(algorithm),
+            ..self
+        }
+    }
+
+    /// Like `with_integrity_check`, but blobs without a digest are loaded,
+    /// and only logged and counted. Use this to enable the check on a
+    /// repo whose existing blobs were written without a digest.
+    pub fn with_lenient_integrity_check(self, algorithm: IntegrityAlgorithm) -> Self {
+        Self {
+            integrity_options: self
+                .integrity_options
+                .with_algorithm(algorithm)
+                .with_lenient(true),
+            ..self
+        }
+    }
+
+    /// Also read every blob back after it is written and verify it against
+    /// the digest stored by `with_integrity_check`.
+    pub fn verify_on_put(self, verify_on_put: bool) -> Self {
+        Self {
+            integrity_options: self.integrity_options.with_verify_on_put(verify_on_put),
+            ..self
+        }
+    }
+
+    /// Flag that lets writes through blobstores built with these options
+    /// despite read-only storage while set. Only meant for privileged admin
+    /// operations.
+    pub fn readonly_bypass(&self) -> Arc<AtomicBool> {
+        self.readonly_bypass.clone()
+    }
+
+    /// Share the result of concurrent gets of the same key between callers.
+    pub fn with_coalescing(self, coalescing_options: CoalescingBlobstoreOptions) -> Self {
+        Self {
+            coalescing_options: Some(coalescing_options),
+            ..self
+        }
+    }
+}
+
+/// Blobstore options for a server hosting multiple repos, where some repos
+/// need different options from the rest.
+#[derive(Clone, Debug)]
+pub struct PerRepoBlobstoreOptions {
+    /// Options used by every repo without an override.
+    pub base: BlobstoreOptions,
+    /// Options for specific repos, keyed by repo name.
+    pub overrides: HashMap<String, BlobstoreOptions>,
+}
+
+impl PerRepoBlobstoreOptions {
+    pub fn new(base: BlobstoreOptions) -> Self {
+        Self {
+            base,
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn with_override(
+        mut self,
+        repo_name: impl Into<String>,
+        options: BlobstoreOptions,
+    ) -> Self {
+        self.overrides.insert(repo_name.into(), options);
+        self
+    }
+
+    /// Options to use for the blobstore of `repo_name`.
+    pub fn resolve(&self, repo_name: &str) -> &BlobstoreOptions {
+        self.overrides.get(repo_name).unwrap_or(&self.base)
+    }
+
+    pub fn has_override(&self, repo_name: &str) -> bool {
+        self.overrides.contains_key(repo_name)
+    }
+
+    /// Set the scrub options of the base and of every override.
+    pub fn set_scrub_options(&mut self, scrub_options: ScrubOptions) {
+        for options in self.overrides.values_mut() {
+            options.set_scrub_options(scrub_options.clone());
+        }
+        self.base.set_scrub_options(scrub_options);
+    }
 }
 
 /// Construct a blobstore according to the specification. The multiplexed blobstore
@@ -209,6 +319,14 @@ pub fn make_blobstore<'a>(
             None,
         )
         .await?;
+        // Coalesce gets once for the whole repo rather than in each member
+        // of a multiplex.
+        let store = if let Some(coalescing_options) = blobstore_options.coalescing_options {
+            Arc::new(CoalescingBlobstore::new(store, coalescing_options))
+                as Arc<dyn BlobstoreUnlinkOps>
+        } else {
+            store
+        };
         // Workaround for trait A {} trait B:A {} but Arc<dyn B> is not a Arc<dyn A>
         // See https://github.com/rust-lang/rfcs/issues/2765 if interested
         Ok(Arc::new(store) as Arc<dyn Blobstore>)
@@ -659,6 +777,19 @@ pub fn make_blobstore_unlink_ops<'a>(
         };
 
         let store = if needs_wrappers {
+            let store = if let Some(algorithm) = blobstore_options.integrity_options.algorithm() {
+                Arc::new(
+                    IntegrityBlobstore::new(
+                        store,
+                        algorithm,
+                        blobstore_options.integrity_options.verify_on_put(),
+                    )
+                    .with_lenient(blobstore_options.integrity_options.lenient()),
+                ) as Arc<dyn BlobstoreUnlinkOps>
+            } else {
+                store
+            };
+
             let store = if let Some(component_sampler) = component_sampler {
                 Arc::new(SamplingBlobstoreUnlinkOps::new(
                     store,
@@ -670,7 +801,14 @@ pub fn make_blobstore_unlink_ops<'a>(
             };
 
             let store = if readonly_storage.0 {
-                Arc::new(ReadOnlyBlobstore::new(store)) as Arc<dyn BlobstoreUnlinkOps>
+                Arc::new(
+                    ReadOnlyEnforcingBlobstore::new(
+                        store,
+                        readonly_storage,
+                        logger.new(o!("audit" => "readonly_storage")),
+                    )
+                    .with_allow_bypass(blobstore_options.readonly_bypass()),
+                ) as Arc<dyn BlobstoreUnlinkOps>
             } else {
                 store
             };
@@ -852,3 +990,114 @@ async fn setup_inner_blobstores<'a>(
     }
     Ok((normal_components, write_only_components))
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+
+    use cached_config::TestSource;
+    use context::CoreContext;
+    use mononoke_types::BlobstoreBytes;
+    use multiplexedblob::scrub::default_scrub_handler;
+
+    use super::*;
+
+    fn options(put_behaviour: PutBehaviour) -> BlobstoreOptions {
+        BlobstoreOptions::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            #[cfg(fbcode_build)]
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Some(put_behaviour),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn test_per_repo_blobstore_options() {
+        let per_repo = PerRepoBlobstoreOptions::new(options(PutBehaviour::Overwrite))
+            .with_override("special", options(PutBehaviour::IfAbsent));
+
+        assert!(per_repo.has_override("special"));
+        assert_eq!(
+            per_repo.resolve("special").put_behaviour,
+            PutBehaviour::IfAbsent
+        );
+
+        assert!(!per_repo.has_override("other"));
+        assert_eq!(
+            per_repo.resolve("other").put_behaviour,
+            PutBehaviour::Overwrite
+        );
+        assert_eq!(per_repo.resolve("").put_behaviour, PutBehaviour::Overwrite);
+    }
+
+    #[test]
+    fn test_per_repo_blobstore_options_scrub() {
+        let mut per_repo = PerRepoBlobstoreOptions::new(options(PutBehaviour::Overwrite))
+            .with_override("special", options(PutBehaviour::IfAbsent));
+        per_repo.set_scrub_options(ScrubOptions::default());
+
+        assert!(per_repo.resolve("special").scrub_options.is_some());
+        assert!(per_repo.resolve("other").scrub_options.is_some());
+    }
+
+    #[fbinit::test]
+    async fn test_readonly_bypass(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let dir = tempfile::tempdir()?;
+        let blobstore_options = options(PutBehaviour::Overwrite);
+        let logger = Logger::root(slog::Discard, o!());
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let blobstore = make_blobstore_unlink_ops(
+            fb,
+            BlobConfig::Files {
+                path: dir.path().to_path_buf(),
+            },
+            &Default::default(),
+            ReadOnlyStorage(true),
+            &blobstore_options,
+            &logger,
+            &config_store,
+            &default_scrub_handler(),
+            None,
+            None,
+        )
+        .await?;
+        let value = BlobstoreBytes::from_bytes("test foobar");
+
+        let err = blobstore
+            .put(&ctx, "foobar".to_owned(), value.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ErrorKind>(),
+            Some(ErrorKind::ReadOnly(key)) if key == "foobar"
+        ));
+
+        // The flag is shared with every clone of the options.
+        blobstore_options
+            .clone()
+            .readonly_bypass()
+            .store(true, Ordering::Relaxed);
+        blobstore
+            .put(&ctx, "foobar".to_owned(), value.clone())
+            .await?;
+        assert_eq!(
+            blobstore
+                .get(&ctx, "foobar")
+                .await?
+                .map(|data| data.into_bytes()),
+            Some(value.clone())
+        );
+
+        blobstore_options
+            .readonly_bypass()
+            .store(false, Ordering::Relaxed);
+        assert!(blobstore.unlink(&ctx, "foobar").await.is_err());
+        Ok(())
+    }
+}
@@ -15,6 +15,7 @@ mod args;
 mod blobstore;
 #[cfg(fbcode_build)]
 mod facebook;
+mod readonly;
 mod sql;
 
 pub use ::blobstore::PutBehaviour;
@@ -22,9 +23,12 @@ pub use ::blobstore::DEFAULT_PUT_BEHAVIOUR;
 pub use blobstore_stats::OperationType;
 pub use cacheblob::CachelibBlobstoreOptions;
 pub use chaosblob::ChaosOptions;
+pub use coalescingblob::CoalescingBlobstoreOptions;
 pub use delayblob::DelayOptions;
 #[cfg(fbcode_build)]
 pub use facebook::ManifoldArgs;
+pub use integrityblob::IntegrityAlgorithm;
+pub use integrityblob::IntegrityOptions;
 #[cfg(fbcode_build)]
 pub use manifoldblob::ManifoldOptions;
 pub use multiplexedblob::scrub::default_scrub_handler;
@@ -48,10 +52,12 @@ pub use crate::blobstore::make_packblob;
 pub use crate::blobstore::make_sql_blobstore;
 pub use crate::blobstore::make_sql_blobstore_xdb;
 pub use crate::blobstore::BlobstoreOptions;
+pub use crate::blobstore::PerRepoBlobstoreOptions;
+pub use crate::readonly::ReadOnlyEnforcingBlobstore;
 pub use crate::sql::MetadataSqlFactory;
 pub use crate::sql::SqlTierInfo;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ReadOnlyStorage(pub bool);
 
 impl ArgDefaults for ReadOnlyStorage {
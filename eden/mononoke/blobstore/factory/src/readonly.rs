@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::panic::Location;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::BlobstoreUnlinkOps;
+use blobstore::ErrorKind;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use mononoke_types::BlobstoreBytes;
+use slog::warn;
+use slog::Logger;
+
+use crate::ReadOnlyStorage;
+
+/// A layer over an existing blobstore that rejects writes if the storage is
+/// read-only, and logs every rejected write to an audit logger.
+///
+/// The write methods are `#[track_caller]`, so the audit log records where
+/// the write came from when the blobstore is called directly. Calls through
+/// a trait object are logged with the location of this wrapper instead.
+#[derive(Debug)]
+pub struct ReadOnlyEnforcingBlobstore<B> {
+    inner: B,
+    read_only: ReadOnlyStorage,
+    audit_logger: Logger,
+    allow_bypass: Arc<AtomicBool>,
+}
+
+impl<B: std::fmt::Display> std::fmt::Display for ReadOnlyEnforcingBlobstore<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReadOnlyEnforcingBlobstore<{}>", &self.inner)
+    }
+}
+
+impl<B> ReadOnlyEnforcingBlobstore<B> {
+    pub fn new(inner: B, read_only: ReadOnlyStorage, audit_logger: Logger) -> Self {
+        Self {
+            inner,
+            read_only,
+            audit_logger,
+            allow_bypass: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Share the bypass flag with other holders, e.g. `BlobstoreOptions`.
+    pub fn with_allow_bypass(self, allow_bypass: Arc<AtomicBool>) -> Self {
+        Self {
+            allow_bypass,
+            ..self
+        }
+    }
+
+    /// Flag that lets writes through despite read-only storage while set.
+    /// Only meant for privileged admin operations.
+    pub fn allow_bypass(&self) -> Arc<AtomicBool> {
+        self.allow_bypass.clone()
+    }
+
+    #[track_caller]
+    fn check_write(&self, operation: &str, key: &str) -> Result<()> {
+        if !self.read_only.0 || self.allow_bypass.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        warn!(
+            self.audit_logger,
+            "Rejected {} of key {} to read-only storage", operation, key;
+            "caller" => Location::caller().to_string(),
+        );
+        Err(ErrorKind::ReadOnly(key.to_owned()).into())
+    }
+}
+
+#[async_trait]
+impl<B: Blobstore> Blobstore for ReadOnlyEnforcingBlobstore<B> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        self.inner.get(ctx, key).await
+    }
+
+    #[track_caller]
+    fn put<'a, 'async_trait>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<'async_trait, Result<()>>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        let check = self.check_write("put", &key);
+        async move {
+            check?;
+            self.inner.put(ctx, key, value).await
+        }
+        .boxed()
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.inner.is_present(ctx, key).await
+    }
+}
+
+#[async_trait]
+impl<B: BlobstorePutOps> BlobstorePutOps for ReadOnlyEnforcingBlobstore<B> {
+    #[track_caller]
+    fn put_explicit<'a, 'async_trait>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> BoxFuture<'async_trait, Result<OverwriteStatus>>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        let check = self.check_write("put", &key);
+        async move {
+            check?;
+            self.inner
+                .put_explicit(ctx, key, value, put_behaviour)
+                .await
+        }
+        .boxed()
+    }
+
+    #[track_caller]
+    fn put_with_status<'a, 'async_trait>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<'async_trait, Result<OverwriteStatus>>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        let check = self.check_write("put", &key);
+        async move {
+            check?;
+            self.inner.put_with_status(ctx, key, value).await
+        }
+        .boxed()
+    }
+}
+
+#[async_trait]
+impl<B: BlobstoreUnlinkOps> BlobstoreUnlinkOps for ReadOnlyEnforcingBlobstore<B> {
+    #[track_caller]
+    fn unlink<'a, 'async_trait>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> BoxFuture<'async_trait, Result<()>>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        let check = self.check_write("unlink", key);
+        async move {
+            check?;
+            self.inner.unlink(ctx, key).await
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    use super::*;
+
+    fn logger() -> Logger {
+        Logger::root(slog::Discard, slog::o!())
+    }
+
+    #[fbinit::test]
+    async fn test_read_only(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        base.put(
+            ctx,
+            "present".to_owned(),
+            BlobstoreBytes::from_bytes("test present"),
+        )
+        .await
+        .unwrap();
+        let wrapper =
+            ReadOnlyEnforcingBlobstore::new(base.clone(), ReadOnlyStorage(true), logger());
+
+        let fetched = wrapper.get(ctx, "present").await.unwrap();
+        assert_eq!(
+            fetched.map(BlobstoreGetData::into_bytes),
+            Some(BlobstoreBytes::from_bytes("test present"))
+        );
+
+        let err = wrapper
+            .put(
+                ctx,
+                "foobar".to_owned(),
+                BlobstoreBytes::from_bytes("test foobar"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ErrorKind>(),
+            Some(ErrorKind::ReadOnly(key)) if key == "foobar"
+        ));
+        assert!(wrapper.unlink(ctx, "present").await.is_err());
+
+        let base_present = base
+            .is_present(ctx, "foobar")
+            .await
+            .unwrap()
+            .assume_not_found_if_unsure();
+        assert!(!base_present);
+        assert!(base.get(ctx, "present").await.unwrap().is_some());
+    }
+
+    #[fbinit::test]
+    async fn test_bypass(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper =
+            ReadOnlyEnforcingBlobstore::new(base.clone(), ReadOnlyStorage(true), logger());
+        let value = BlobstoreBytes::from_bytes("test foobar");
+
+        assert!(
+            wrapper
+                .put(ctx, "foobar".to_owned(), value.clone())
+                .await
+                .is_err()
+        );
+
+        wrapper.allow_bypass().store(true, Ordering::Relaxed);
+        wrapper
+            .put(ctx, "foobar".to_owned(), value.clone())
+            .await
+            .unwrap();
+        let fetched = base.get(ctx, "foobar").await.unwrap();
+        assert_eq!(fetched.map(BlobstoreGetData::into_bytes), Some(value));
+        wrapper.unlink(ctx, "foobar").await.unwrap();
+    }
+
+    #[fbinit::test]
+    async fn test_writable(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let wrapper =
+            ReadOnlyEnforcingBlobstore::new(Memblob::default(), ReadOnlyStorage(false), logger());
+
+        wrapper
+            .put(
+                ctx,
+                "foobar".to_owned(),
+                BlobstoreBytes::from_bytes("test foobar"),
+            )
+            .await
+            .unwrap();
+        assert!(wrapper.get(ctx, "foobar").await.unwrap().is_some());
+    }
+}
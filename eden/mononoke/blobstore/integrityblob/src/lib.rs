@@ -0,0 +1,628 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::BlobstoreUnlinkOps;
+use blobstore::ErrorKind;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use futures::future::try_join;
+use mononoke_types::BlobstoreBytes;
+use sha2::Digest;
+use sha2::Sha256;
+use slog::warn;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.blobstore.integrityblob";
+    missing_digest: timeseries(Rate, Sum),
+}
+
+/// Hash used to verify the content of blobs read from the underlying store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegrityAlgorithm {
+    Sha256,
+    Xxh3,
+}
+
+impl IntegrityAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegrityAlgorithm::Sha256 => "sha256",
+            IntegrityAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(IntegrityAlgorithm::Sha256),
+            "xxh3" => Some(IntegrityAlgorithm::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Hex-encoded digest of `bytes`.
+    pub fn digest(&self, bytes: &[u8]) -> String {
+        match self {
+            IntegrityAlgorithm::Sha256 => hex::encode(Sha256::digest(bytes)),
+            IntegrityAlgorithm::Xxh3 => format!("{:016x}", twox_hash::xxh3::hash64(bytes)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IntegrityOptions {
+    algorithm: Option<IntegrityAlgorithm>,
+    verify_on_put: bool,
+    lenient: bool,
+}
+
+impl IntegrityOptions {
+    pub fn new(algorithm: Option<IntegrityAlgorithm>, verify_on_put: bool) -> Self {
+        Self {
+            algorithm,
+            verify_on_put,
+            lenient: false,
+        }
+    }
+
+    pub fn with_algorithm(self, algorithm: IntegrityAlgorithm) -> Self {
+        Self {
+            algorithm: Some(algorithm),
+            ..self
+        }
+    }
+
+    pub fn with_verify_on_put(self, verify_on_put: bool) -> Self {
+        Self {
+            verify_on_put,
+            ..self
+        }
+    }
+
+    pub fn with_lenient(self, lenient: bool) -> Self {
+        Self { lenient, ..self }
+    }
+
+    pub fn algorithm(&self) -> Option<IntegrityAlgorithm> {
+        self.algorithm
+    }
+
+    pub fn verify_on_put(&self) -> bool {
+        self.verify_on_put
+    }
+
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+
+    pub fn has_integrity_check(&self) -> bool {
+        self.algorithm.is_some()
+    }
+}
+
+/// A layer over an existing blobstore that verifies the content of fetched
+/// blobs against a digest stored alongside them.
+///
+/// The digest of `key` is stored as a separate blob under
+/// `{key}.integrity.{algorithm}`, written after every put of a new value and
+/// restored by a put of an already stored value whose digest is missing. A
+/// fetched blob whose digest is missing, unparseable or different from the
+/// digest of its content is reported as an error. In lenient mode a missing
+/// digest is only logged and counted, so that the check can be enabled on a
+/// store holding blobs written without it. When `verify_on_put` is set, every
+/// put also reads the blob back and checks it before returning.
+#[derive(Clone, Debug)]
+pub struct IntegrityBlobstore<T> {
+    blobstore: T,
+    algorithm: IntegrityAlgorithm,
+    verify_on_put: bool,
+    lenient: bool,
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for IntegrityBlobstore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "IntegrityBlobstore<{}, {}>",
+            self.algorithm.name(),
+            &self.blobstore
+        )
+    }
+}
+
+impl<T> IntegrityBlobstore<T> {
+    pub fn new(blobstore: T, algorithm: IntegrityAlgorithm, verify_on_put: bool) -> Self {
+        Self {
+            blobstore,
+            algorithm,
+            verify_on_put,
+            lenient: false,
+        }
+    }
+
+    /// Accept blobs without a stored digest instead of failing to load them.
+    pub fn with_lenient(self, lenient: bool) -> Self {
+        Self { lenient, ..self }
+    }
+
+    fn digest_key(&self, key: &str) -> String {
+        format!("{}.integrity.{}", key, self.algorithm.name())
+    }
+
+    fn verify(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        value: &[u8],
+        digest: Option<BlobstoreGetData>,
+    ) -> Result<()> {
+        let digest = match digest {
+            Some(digest) => digest,
+            None if self.lenient => {
+                STATS::missing_digest.add_value(1);
+                warn!(ctx.logger(), "No integrity digest stored for key {}", key);
+                return Ok(());
+            }
+            None => return Err(ErrorKind::DigestMissing(key.to_owned()).into()),
+        };
+        let expected = std::str::from_utf8(digest.as_raw_bytes())
+            .ok()
+            .filter(|expected| is_valid_digest(self.algorithm, expected))
+            .ok_or_else(|| ErrorKind::DigestInvalid(key.to_owned()))?;
+        let got = self.algorithm.digest(value);
+        if expected != got {
+            return Err(ErrorKind::CorruptionDetected {
+                key: key.to_owned(),
+                expected: expected.to_owned(),
+                got,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_digest(algorithm: IntegrityAlgorithm, digest: &str) -> bool {
+    let len = match algorithm {
+        IntegrityAlgorithm::Sha256 => 64,
+        IntegrityAlgorithm::Xxh3 => 16,
+    };
+    digest.len() == len && digest.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+impl<T: BlobstorePutOps> IntegrityBlobstore<T> {
+    async fn get_verified<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let digest_key = self.digest_key(key);
+        let (value, digest) = try_join(
+            self.blobstore.get(ctx, key),
+            self.blobstore.get(ctx, &digest_key),
+        )
+        .await?;
+
+        match value {
+            Some(value) => {
+                self.verify(ctx, key, value.as_raw_bytes(), digest)?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        let digest = BlobstoreBytes::from_bytes(self.algorithm.digest(value.as_bytes()));
+        let digest_key = self.digest_key(&key);
+
+        let status = match put_behaviour {
+            Some(put_behaviour) => {
+                self.blobstore
+                    .put_explicit(ctx, key.clone(), value, put_behaviour)
+                    .await?
+            }
+            None => {
+                self.blobstore
+                    .put_with_status(ctx, key.clone(), value)
+                    .await?
+            }
+        };
+
+        // The digest always follows the stored value. When the value was
+        // kept, its digest may still be missing or stale if an earlier put of
+        // it failed between the two writes, so restore it if the kept value
+        // is the one being written.
+        if status == OverwriteStatus::Prevented {
+            self.restore_digest(ctx, &key, digest_key, digest).await?;
+        } else {
+            self.blobstore
+                .put_explicit(ctx, digest_key, digest, PutBehaviour::Overwrite)
+                .await?;
+        }
+
+        if self.verify_on_put {
+            self.get_verified(ctx, &key)
+                .await?
+                .ok_or_else(|| ErrorKind::NotFound(key.clone()))?;
+        }
+
+        Ok(status)
+    }
+
+    async fn restore_digest<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+        digest_key: String,
+        digest: BlobstoreBytes,
+    ) -> Result<()> {
+        let stored_digest = self.blobstore.get(ctx, &digest_key).await?;
+        if stored_digest.map_or(false, |stored| stored.as_raw_bytes() == digest.as_bytes()) {
+            return Ok(());
+        }
+
+        // Only vouch for the stored value if it has the content being written:
+        // a different value without a matching digest is reported on get.
+        let matches = match self.blobstore.get(ctx, key).await? {
+            Some(stored) => {
+                self.algorithm.digest(stored.as_raw_bytes()).as_bytes() == digest.as_bytes()
+            }
+            None => false,
+        };
+        if matches {
+            self.blobstore
+                .put_explicit(ctx, digest_key, digest, PutBehaviour::Overwrite)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> Blobstore for IntegrityBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        self.get_verified(ctx, key).await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.put_impl(ctx, key, value, None).await?;
+        Ok(())
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.blobstore.is_present(ctx, key).await
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for IntegrityBlobstore<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, Some(put_behaviour)).await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, None).await
+    }
+}
+
+#[async_trait]
+impl<T: BlobstoreUnlinkOps> BlobstoreUnlinkOps for IntegrityBlobstore<T> {
+    async fn unlink<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<()> {
+        self.blobstore.unlink(ctx, key).await?;
+        let digest_key = self.digest_key(key);
+        if self
+            .blobstore
+            .is_present(ctx, &digest_key)
+            .await?
+            .fail_if_unsure()?
+        {
+            self.blobstore.unlink(ctx, &digest_key).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    use super::*;
+
+    async fn get(
+        ctx: &CoreContext,
+        store: &IntegrityBlobstore<Memblob>,
+        key: &str,
+    ) -> Option<BlobstoreBytes> {
+        store
+            .get(ctx, key)
+            .await
+            .unwrap()
+            .map(BlobstoreGetData::into_bytes)
+    }
+
+    #[fbinit::test]
+    async fn test_roundtrip(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        for algorithm in [IntegrityAlgorithm::Sha256, IntegrityAlgorithm::Xxh3] {
+            for verify_on_put in [false, true] {
+                let base = Memblob::default();
+                let wrapper = IntegrityBlobstore::new(base.clone(), algorithm, verify_on_put);
+                let value = BlobstoreBytes::from_bytes("test foobar");
+
+                wrapper
+                    .put(ctx, "foobar".to_owned(), value.clone())
+                    .await
+                    .unwrap();
+                assert_eq!(get(ctx, &wrapper, "foobar").await, Some(value.clone()));
+
+                // The value itself is stored unchanged.
+                let stored = base.get(ctx, "foobar").await.unwrap();
+                assert_eq!(stored.map(BlobstoreGetData::into_bytes), Some(value));
+            }
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_corruption_detected(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = IntegrityBlobstore::new(base.clone(), IntegrityAlgorithm::Sha256, true);
+        let key = "foobar";
+
+        wrapper
+            .put(
+                ctx,
+                key.to_owned(),
+                BlobstoreBytes::from_bytes("test foobar"),
+            )
+            .await
+            .unwrap();
+
+        // Corrupt the stored bytes behind the wrapper's back.
+        base.put_explicit(
+            ctx,
+            key.to_owned(),
+            BlobstoreBytes::from_bytes("test f00bar"),
+            PutBehaviour::Overwrite,
+        )
+        .await
+        .unwrap();
+
+        let err = wrapper.get(ctx, key).await.unwrap_err();
+        match err.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::CorruptionDetected {
+                key: err_key,
+                expected,
+                got,
+            }) => {
+                assert_eq!(err_key, key);
+                assert_eq!(expected, &IntegrityAlgorithm::Sha256.digest(b"test foobar"));
+                assert_eq!(got, &IntegrityAlgorithm::Sha256.digest(b"test f00bar"));
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_missing_or_invalid_digest(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = IntegrityBlobstore::new(base.clone(), IntegrityAlgorithm::Xxh3, false);
+        let key = "foobar";
+
+        base.put(
+            ctx,
+            key.to_owned(),
+            BlobstoreBytes::from_bytes("test foobar"),
+        )
+        .await
+        .unwrap();
+        let err = wrapper.get(ctx, key).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<ErrorKind>(), Some(ErrorKind::DigestMissing(k)) if k == key),
+            "unexpected error: {:?}",
+            err
+        );
+
+        base.put(
+            ctx,
+            wrapper.digest_key(key),
+            BlobstoreBytes::from_bytes("not a digest"),
+        )
+        .await
+        .unwrap();
+        let err = wrapper.get(ctx, key).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<ErrorKind>(), Some(ErrorKind::DigestInvalid(k)) if k == key),
+            "unexpected error: {:?}",
+            err
+        );
+
+        // Missing values are still reported as missing.
+        assert_eq!(get(ctx, &wrapper, "missing").await, None);
+    }
+
+    #[fbinit::test]
+    async fn test_lenient_missing_digest(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = IntegrityBlobstore::new(base.clone(), IntegrityAlgorithm::Sha256, false)
+            .with_lenient(true);
+        let key = "foobar";
+        let value = BlobstoreBytes::from_bytes("test foobar");
+
+        // A blob written before the check was enabled still loads.
+        base.put(ctx, key.to_owned(), value.clone()).await.unwrap();
+        assert_eq!(get(ctx, &wrapper, key).await, Some(value));
+
+        // Other failures are still reported.
+        base.put(
+            ctx,
+            wrapper.digest_key(key),
+            BlobstoreBytes::from_bytes("not a digest"),
+        )
+        .await
+        .unwrap();
+        let err = wrapper.get(ctx, key).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<ErrorKind>(), Some(ErrorKind::DigestInvalid(k)) if k == key),
+            "unexpected error: {:?}",
+            err
+        );
+    }
+
+    #[fbinit::test]
+    async fn test_put_restores_missing_digest(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::new(PutBehaviour::IfAbsent);
+        let wrapper = IntegrityBlobstore::new(base.clone(), IntegrityAlgorithm::Sha256, false);
+        let key = "foobar";
+        let value = BlobstoreBytes::from_bytes("test foobar");
+
+        // An earlier put stored the value, but failed before its digest.
+        base.put(ctx, key.to_owned(), value.clone()).await.unwrap();
+        assert!(wrapper.get(ctx, key).await.is_err());
+
+        // Retrying the put is prevented, but restores the digest.
+        let status = wrapper
+            .put_with_status(ctx, key.to_owned(), value.clone())
+            .await
+            .unwrap();
+        assert_eq!(status, OverwriteStatus::Prevented);
+        assert_eq!(get(ctx, &wrapper, key).await, Some(value));
+
+        // A different value does not get a digest for the stored one.
+        let other = "other";
+        base.put(ctx, other.to_owned(), BlobstoreBytes::from_bytes("stored"))
+            .await
+            .unwrap();
+        let status = wrapper
+            .put_with_status(ctx, other.to_owned(), BlobstoreBytes::from_bytes("new"))
+            .await
+            .unwrap();
+        assert_eq!(status, OverwriteStatus::Prevented);
+        let err = wrapper.get(ctx, other).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<ErrorKind>(), Some(ErrorKind::DigestMissing(k)) if k == other),
+            "unexpected error: {:?}",
+            err
+        );
+    }
+
+    #[fbinit::test]
+    async fn test_overwrite(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = IntegrityBlobstore::new(base.clone(), IntegrityAlgorithm::Sha256, false);
+        let key = "foobar";
+
+        wrapper
+            .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("first"))
+            .await
+            .unwrap();
+
+        // A prevented overwrite keeps the digest of the stored value.
+        let status = wrapper
+            .put_explicit(
+                ctx,
+                key.to_owned(),
+                BlobstoreBytes::from_bytes("second"),
+                PutBehaviour::IfAbsent,
+            )
+            .await
+            .unwrap();
+        assert_eq!(status, OverwriteStatus::Prevented);
+        assert_eq!(
+            get(ctx, &wrapper, key).await,
+            Some(BlobstoreBytes::from_bytes("first"))
+        );
+
+        wrapper
+            .put_explicit(
+                ctx,
+                key.to_owned(),
+                BlobstoreBytes::from_bytes("third"),
+                PutBehaviour::Overwrite,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            get(ctx, &wrapper, key).await,
+            Some(BlobstoreBytes::from_bytes("third"))
+        );
+    }
+
+    #[fbinit::test]
+    async fn test_unlink(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = IntegrityBlobstore::new(base.clone(), IntegrityAlgorithm::Sha256, false);
+        let key = "foobar";
+
+        wrapper
+            .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("first"))
+            .await
+            .unwrap();
+        wrapper.unlink(ctx, key).await.unwrap();
+
+        assert_eq!(get(ctx, &wrapper, key).await, None);
+        assert!(
+            !base
+                .is_present(ctx, &wrapper.digest_key(key))
+                .await
+                .unwrap()
+                .assume_not_found_if_unsure()
+        );
+    }
+}
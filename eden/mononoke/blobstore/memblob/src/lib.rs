@@ -14,6 +14,7 @@ use std::sync::Mutex;
 use anyhow::format_err;
 use anyhow::Result;
 use async_trait::async_trait;
+use blobstore::BatchBlobstore;
 use blobstore::Blobstore;
 use blobstore::BlobstoreEnumerationData;
 use blobstore::BlobstoreGetData;
@@ -189,8 +190,41 @@ impl Blobstore for Memblob {
         let mut inner = state.lock().expect("lock poison");
         inner.link(old_key, new_key)
     }
+
+    async fn get_many<'a>(
+        &'a self,
+        _ctx: &'a CoreContext,
+        keys: Vec<String>,
+    ) -> Result<HashMap<String, Option<BlobstoreGetData>>> {
+        let state = self.state.clone();
+
+        let inner = state.lock().expect("lock poison");
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = inner.get(&key).map(|bytes| bytes.clone().into());
+                (key, value)
+            })
+            .collect())
+    }
+
+    async fn put_many<'a>(
+        &'a self,
+        _ctx: &'a CoreContext,
+        entries: Vec<(String, BlobstoreBytes)>,
+    ) -> Result<()> {
+        let state = self.state.clone();
+
+        let mut inner = state.lock().expect("lock poison");
+        for (key, value) in entries {
+            inner.put(key, value, self.put_behaviour);
+        }
+        Ok(())
+    }
 }
 
+impl BatchBlobstore for Memblob {}
+
 #[async_trait]
 impl BlobstoreUnlinkOps for Memblob {
     async fn unlink<'a>(&'a self, _ctx: &'a CoreContext, key: &'a str) -> Result<()> {
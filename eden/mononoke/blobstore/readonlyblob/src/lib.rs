@@ -12,12 +12,11 @@ use blobstore::BlobstoreGetData;
 use blobstore::BlobstoreIsPresent;
 use blobstore::BlobstorePutOps;
 use blobstore::BlobstoreUnlinkOps;
+use blobstore::ErrorKind;
 use blobstore::OverwriteStatus;
 use blobstore::PutBehaviour;
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
-mod errors;
-pub use crate::errors::ErrorKind;
 
 /// A layer over an existing blobstore that prevents writes.
 #[derive(Debug)]
@@ -55,7 +54,7 @@ impl<T: Blobstore> Blobstore for ReadOnlyBlobstore<T> {
         key: String,
         _value: BlobstoreBytes,
     ) -> Result<()> {
-        Err(ErrorKind::ReadOnlyPut(key).into())
+        Err(ErrorKind::ReadOnly(key).into())
     }
 
     #[inline]
@@ -77,7 +76,7 @@ impl<T: BlobstorePutOps> BlobstorePutOps for ReadOnlyBlobstore<T> {
         _value: BlobstoreBytes,
         _put_behaviour: PutBehaviour,
     ) -> Result<OverwriteStatus> {
-        Err(ErrorKind::ReadOnlyPut(key).into())
+        Err(ErrorKind::ReadOnly(key).into())
     }
 
     async fn put_with_status<'a>(
@@ -86,14 +85,14 @@ impl<T: BlobstorePutOps> BlobstorePutOps for ReadOnlyBlobstore<T> {
         key: String,
         _value: BlobstoreBytes,
     ) -> Result<OverwriteStatus> {
-        Err(ErrorKind::ReadOnlyPut(key).into())
+        Err(ErrorKind::ReadOnly(key).into())
     }
 }
 
 #[async_trait]
 impl<T: BlobstoreUnlinkOps> BlobstoreUnlinkOps for ReadOnlyBlobstore<T> {
     async fn unlink<'a>(&'a self, _ctx: &'a CoreContext, key: &'a str) -> Result<()> {
-        Err(ErrorKind::ReadOnlyPut(key.to_string()).into())
+        Err(ErrorKind::ReadOnly(key.to_string()).into())
     }
 }
 
@@ -152,7 +152,7 @@ impl Sqlblob {
             move || {
                 create_mysql_connections_sharded(
                     fb,
-                    mysql_options,
+                    mysql_options.for_connections(readonly),
                     SQLBLOB_LABEL.into(),
                     shardmap,
                     0..shard_count,
@@ -211,7 +211,7 @@ impl Sqlblob {
             move |_shard_id| {
                 let res = create_mysql_connections_unsharded(
                     fb,
-                    mysql_options.clone(),
+                    mysql_options.for_connections(readonly),
                     SQLBLOB_LABEL.into(),
                     db_address.clone(),
                     readonly,
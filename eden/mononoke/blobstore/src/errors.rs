@@ -13,4 +13,16 @@ pub enum ErrorKind {
     NotFound(String),
     #[error("Error while opening state for blob store")]
     StateOpen,
+    #[error("Attempt to write key {0} to read-only storage")]
+    ReadOnly(String),
+    #[error("No integrity digest stored for key {0}")]
+    DigestMissing(String),
+    #[error("Invalid integrity digest stored for key {0}")]
+    DigestInvalid(String),
+    #[error("Corruption detected for key {key}: expected digest {expected}, got {got}")]
+    CorruptionDetected {
+        key: String,
+        expected: String,
+        got: String,
+    },
 }
@@ -10,6 +10,7 @@ mod disabled;
 mod errors;
 pub mod macros;
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt;
 use std::io::Cursor;
@@ -29,6 +30,8 @@ use auto_impl::auto_impl;
 use bytes::Bytes;
 use clap::ValueEnum;
 use context::CoreContext;
+use futures::stream::FuturesUnordered;
+use futures::stream::TryStreamExt;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use strum::AsRefStr;
@@ -396,8 +399,45 @@ pub trait Blobstore: fmt::Display + fmt::Debug + Send + Sync {
             .with_context(|| format!("key {} not present", old_key))?;
         Ok(self.put(ctx, new_key, value.bytes).await?)
     }
+    /// Fetch the values associated with each of `keys`. The result has an entry for every key,
+    /// which is None if no value is present. The default behaviour is to `get` all the keys
+    /// concurrently; blobstores with a native batch API should override this and implement
+    /// `BatchBlobstore`.
+    async fn get_many<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        keys: Vec<String>,
+    ) -> Result<HashMap<String, Option<BlobstoreGetData>>> {
+        keys.into_iter()
+            .map(|key| async move {
+                let value = self.get(ctx, &key).await?;
+                Ok::<_, Error>((key, value))
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect()
+            .await
+    }
+    /// Associate each value in `entries` with its key, as with `put`. The default behaviour is
+    /// to `put` all the entries concurrently; blobstores with a native batch API should override
+    /// this and implement `BatchBlobstore`.
+    async fn put_many<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        entries: Vec<(String, BlobstoreBytes)>,
+    ) -> Result<()> {
+        entries
+            .into_iter()
+            .map(|(key, value)| self.put(ctx, key, value))
+            .collect::<FuturesUnordered<_>>()
+            .try_collect()
+            .await
+    }
 }
 
+/// Marker for blobstores whose `get_many` and `put_many` use a native batch API, rather than
+/// issuing one request per key.
+pub trait BatchBlobstore: Blobstore {}
+
 /// Mononoke binaries will not overwrite existing blobstore keys by default
 pub const DEFAULT_PUT_BEHAVIOUR: PutBehaviour = PutBehaviour::IfAbsent;
 
@@ -166,6 +166,42 @@ async fn missing<B: Blobstore>(fb: FacebookInit, blobstore: B) -> Result<(), Err
     Ok(())
 }
 
+async fn get_many_partial<B: Blobstore>(fb: FacebookInit, blobstore: B) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+
+    let value1 = BlobstoreBytes::from_bytes(Bytes::copy_from_slice(b"appleveldata1"));
+    let value2 = BlobstoreBytes::from_bytes(Bytes::copy_from_slice(b"appleveldata2"));
+    blobstore
+        .put_many(
+            ctx,
+            vec![
+                ("present1".to_owned(), value1.clone()),
+                ("present2".to_owned(), value2.clone()),
+            ],
+        )
+        .await?;
+
+    let keys = vec![
+        "present1".to_owned(),
+        "missing".to_owned(),
+        "present2".to_owned(),
+    ];
+    let mut out = blobstore.get_many(ctx, keys).await?;
+
+    assert_eq!(out.len(), 3);
+    assert_eq!(
+        out.remove("present1").flatten().map(|v| v.into_bytes()),
+        Some(value1)
+    );
+    assert_eq!(
+        out.remove("present2").flatten().map(|v| v.into_bytes()),
+        Some(value2)
+    );
+    assert!(out.remove("missing").unwrap().is_none());
+    Ok(())
+}
+
 macro_rules! blobstore_test_impl {
     ($mod_name: ident => {
         state: $state: expr,
@@ -207,6 +243,13 @@ macro_rules! blobstore_test_impl {
                 missing(fb, factory(state, PutBehaviour::Overwrite)?).await
             }
 
+            #[fbinit::test]
+            async fn test_get_many_partial(fb: FacebookInit) -> Result<(), Error> {
+                let state = $state;
+                let factory = $new_cb;
+                get_many_partial(fb, factory(state, PutBehaviour::Overwrite)?).await
+            }
+
             #[fbinit::test]
             async fn test_boxable(_fb: FacebookInit) -> Result<(), Error> {
                 let state = $state;
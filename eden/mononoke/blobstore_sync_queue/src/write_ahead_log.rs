@@ -465,6 +465,7 @@ impl SqlShardedConstruct for SqlBlobstoreWal {
                 ConfigurableRendezVousController::new(
                     RendezVousOptions {
                         free_connections: 1,
+                        ..Default::default()
                     },
                     || Duration::from_secs(5),
                     || DEL_CHUNK,
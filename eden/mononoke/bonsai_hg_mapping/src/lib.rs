@@ -163,22 +163,20 @@ struct RendezVousConnection {
 }
 
 impl RendezVousConnection {
-    fn new(conn: Connection, name: &str, opts: RendezVousOptions) -> Self {
+    fn new(conn: Connection, name: &str, opts: &RendezVousOptions) -> Self {
+        let bonsai_endpoint = format!("bonsai_hg_mapping.bonsai.{}", name);
+        let hg_endpoint = format!("bonsai_hg_mapping.hg.{}", name);
         Self {
             conn,
-            bonsai: RendezVous::new(
-                TunablesRendezVousController::new(opts),
-                Arc::new(RendezVousStats::new(format!(
-                    "bonsai_hg_mapping.bonsai.{}",
-                    name,
-                ))),
+            bonsai: RendezVous::new_with_endpoint_options(
+                TunablesRendezVousController::new(opts.clone()),
+                Arc::new(RendezVousStats::new(bonsai_endpoint.clone())),
+                opts.for_endpoint(&bonsai_endpoint),
             ),
-            hg: RendezVous::new(
-                TunablesRendezVousController::new(opts),
-                Arc::new(RendezVousStats::new(format!(
-                    "bonsai_hg_mapping.hg.{}",
-                    name,
-                ))),
+            hg: RendezVous::new_with_endpoint_options(
+                TunablesRendezVousController::new(opts.clone()),
+                Arc::new(RendezVousStats::new(hg_endpoint.clone())),
+                opts.for_endpoint(&hg_endpoint),
             ),
         }
     }
@@ -281,11 +279,15 @@ impl SqlBonsaiHgMappingBuilder {
 
         SqlBonsaiHgMapping {
             write_connection: connections.write_connection,
-            read_connection: RendezVousConnection::new(connections.read_connection, "reader", opts),
+            read_connection: RendezVousConnection::new(
+                connections.read_connection,
+                "reader",
+                &opts,
+            ),
             read_master_connection: RendezVousConnection::new(
                 connections.read_master_connection,
                 "read_master",
-                opts,
+                &opts,
             ),
             repo_id,
             overwrite,
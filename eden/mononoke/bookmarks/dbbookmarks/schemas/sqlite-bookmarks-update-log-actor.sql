@@ -0,0 +1,10 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+-- Adds the column recording who made each bookmark change to databases
+-- created before it was part of sqlite-bookmarks.sql.
+ALTER TABLE bookmarks_update_log ADD COLUMN actor VARCHAR(255) NULL;
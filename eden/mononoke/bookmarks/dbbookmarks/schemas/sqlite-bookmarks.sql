@@ -30,5 +30,6 @@ CREATE TABLE IF NOT EXISTS bookmarks_update_log (
   reason VARCHAR(32) NOT NULL, -- enum is used in mysql
   timestamp BIGINT NOT NULL,
   category VARCHAR(32) NOT NULL DEFAULT (CAST('branch' AS BLOB)),
+  actor VARCHAR(255) NULL,
   PRIMARY KEY (repo_id, id)
 );
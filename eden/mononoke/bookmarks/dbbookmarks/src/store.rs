@@ -11,6 +11,9 @@ use anyhow::Result;
 use async_trait::async_trait;
 use bookmarks::Bookmark;
 use bookmarks::BookmarkCategory;
+use bookmarks::BookmarkHistoryEntry;
+use bookmarks::BookmarkHistoryOptions;
+use bookmarks::BookmarkHistoryQuery;
 use bookmarks::BookmarkKey;
 use bookmarks::BookmarkKind;
 use bookmarks::BookmarkName;
@@ -269,6 +272,25 @@ mononoke_queries! {
          LIMIT {max_records}"
     }
 
+    read SelectBookmarkHistory(
+        repo_id: RepositoryId,
+        name: BookmarkName,
+        category: BookmarkCategory,
+        min_ts: Timestamp,
+        max_records: u32
+    ) -> (
+        u64, Option<ChangesetId>, Option<ChangesetId>, BookmarkUpdateReason, Timestamp, Option<String>
+    ) {
+        "SELECT id, from_changeset_id, to_changeset_id, reason, timestamp, actor
+         FROM bookmarks_update_log
+         WHERE repo_id = {repo_id}
+           AND name = {name}
+           AND category = {category}
+           AND timestamp >= {min_ts}
+         ORDER BY id DESC
+         LIMIT {max_records}"
+    }
+
     read SelectBookmarkLogsWithOffset(repo_id: RepositoryId, name: BookmarkName, category: BookmarkCategory, max_records: u32, offset: u32, tok: i32) -> (
         u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp, i32
     ) {
@@ -294,6 +316,7 @@ mononoke_queries! {
 pub struct SqlBookmarks {
     pub(crate) repo_id: RepositoryId,
     pub(crate) connections: SqlConnections,
+    pub(crate) history_options: BookmarkHistoryOptions,
 }
 
 impl SqlBookmarks {
@@ -301,6 +324,14 @@ impl SqlBookmarks {
         Self {
             repo_id,
             connections,
+            history_options: BookmarkHistoryOptions::default(),
+        }
+    }
+
+    pub fn with_history_options(self, history_options: BookmarkHistoryOptions) -> Self {
+        Self {
+            history_options,
+            ..self
         }
     }
 
@@ -660,6 +691,50 @@ impl BookmarkUpdateLog for SqlBookmarks {
         .boxed()
     }
 
+    fn bookmark_history(
+        &self,
+        ctx: CoreContext,
+        query: BookmarkHistoryQuery,
+    ) -> BoxFuture<'static, Result<Vec<BookmarkHistoryEntry>>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let conn = self.connections.read_connection.clone();
+        let repo_id = self.repo_id;
+        // Changes older than the retention period are not returned, even if
+        // the query asks for them.
+        let min_ts = query
+            .since
+            .max(self.history_options.retention_start())
+            .unwrap_or_else(|| Timestamp::from_timestamp_nanos(0));
+
+        async move {
+            let rows = SelectBookmarkHistory::query(
+                &conn,
+                &repo_id,
+                query.name.name(),
+                query.name.category(),
+                &min_ts,
+                &query.limit,
+            )
+            .await?;
+            Ok(rows
+                .into_iter()
+                .map(
+                    |(id, old_value, new_value, reason, timestamp, actor)| BookmarkHistoryEntry {
+                        id,
+                        old_value,
+                        new_value,
+                        reason,
+                        timestamp,
+                        actor,
+                    },
+                )
+                .collect())
+        }
+        .boxed()
+    }
+
     fn count_further_bookmark_log_entries(
         &self,
         ctx: CoreContext,
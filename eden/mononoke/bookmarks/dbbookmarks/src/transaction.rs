@@ -113,11 +113,12 @@ mononoke_queries! {
             to_changeset_id: Option<ChangesetId>,
             reason: BookmarkUpdateReason,
             timestamp: Timestamp,
+            actor: Option<String>,
         ),
     ) {
         none,
         "INSERT INTO bookmarks_update_log
-         (id, repo_id, name, category, from_changeset_id, to_changeset_id, reason, timestamp)
+         (id, repo_id, name, category, from_changeset_id, to_changeset_id, reason, timestamp, actor)
          VALUES {values}"
     }
 }
@@ -148,6 +149,9 @@ struct SqlBookmarksTransactionPayload {
     /// The repository we are updating.
     repo_id: RepositoryId,
 
+    /// Who is making the changes, recorded in the log entries.
+    actor: Option<String>,
+
     /// Operations to force-set a bookmark to a changeset.
     force_sets: Vec<(BookmarkKey, ChangesetId, NewUpdateLogEntry)>,
 
@@ -200,9 +204,10 @@ impl<'a> TransactionLogUpdates<'a> {
 }
 
 impl SqlBookmarksTransactionPayload {
-    fn new(repo_id: RepositoryId) -> Self {
+    fn new(repo_id: RepositoryId, actor: Option<String>) -> Self {
         SqlBookmarksTransactionPayload {
             repo_id,
+            actor,
             force_sets: Vec::new(),
             creates: Vec::new(),
             updates: Vec::new(),
@@ -248,6 +253,7 @@ impl SqlBookmarksTransactionPayload {
                 &log_entry.new,
                 &log_entry.reason,
                 &timestamp,
+                &self.actor,
             )];
             txn = AddBookmarkLog::query_with_transaction(txn, &data[..])
                 .await?
@@ -446,11 +452,12 @@ impl SqlBookmarksTransaction {
         write_connection: Connection,
         repo_id: RepositoryId,
     ) -> Self {
+        let actor = ctx.metadata().unix_name().map(str::to_string);
         Self {
             write_connection,
             ctx,
             seen: HashSet::new(),
-            payload: SqlBookmarksTransactionPayload::new(repo_id),
+            payload: SqlBookmarksTransactionPayload::new(repo_id, actor),
         }
     }
 
@@ -589,6 +596,10 @@ impl BookmarkTransaction for SqlBookmarksTransaction {
         Ok(())
     }
 
+    fn set_actor(&mut self, actor: String) {
+        self.payload.actor = Some(actor);
+    }
+
     fn commit(self: Box<Self>) -> BoxFuture<'static, Result<bool>> {
         self.commit_with_hook(Arc::new(|_ctx, txn| future::ok(txn).boxed()))
     }
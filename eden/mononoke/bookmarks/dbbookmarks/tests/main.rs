@@ -13,6 +13,9 @@ use anyhow::Error;
 use anyhow::Result;
 use bookmarks::Bookmark;
 use bookmarks::BookmarkCategory;
+use bookmarks::BookmarkHistoryEntry;
+use bookmarks::BookmarkHistoryOptions;
+use bookmarks::BookmarkHistoryQuery;
 use bookmarks::BookmarkKey;
 use bookmarks::BookmarkKind;
 use bookmarks::BookmarkPagination;
@@ -29,9 +32,14 @@ use futures::stream::TryStreamExt;
 use maplit::hashmap;
 use mononoke_types::ChangesetId;
 use mononoke_types::Timestamp;
+use mononoke_types_mocks::changesetid::AS_CSID;
+use mononoke_types_mocks::changesetid::BS_CSID;
+use mononoke_types_mocks::changesetid::EIGHTS_CSID;
 use mononoke_types_mocks::changesetid::FIVES_CSID;
 use mononoke_types_mocks::changesetid::FOURS_CSID;
+use mononoke_types_mocks::changesetid::NINES_CSID;
 use mononoke_types_mocks::changesetid::ONES_CSID;
+use mononoke_types_mocks::changesetid::SEVENS_CSID;
 use mononoke_types_mocks::changesetid::SIXES_CSID;
 use mononoke_types_mocks::changesetid::THREES_CSID;
 use mononoke_types_mocks::changesetid::TWOS_CSID;
@@ -420,6 +428,160 @@ async fn test_update_existing_bookmark_with_incorrect_commit(fb: FacebookInit) {
     assert!(!txn.commit().await.unwrap());
 }
 
+#[fbinit::test]
+async fn test_compare_and_swap(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bookmarks = SqlBookmarksBuilder::with_sqlite_in_memory()
+        .unwrap()
+        .with_repo_id(REPO_ZERO);
+    let key_1 = create_bookmark_name("book");
+
+    let mut txn = bookmarks.create_transaction(ctx.clone());
+    txn.compare_and_swap(&key_1, None, ONES_CSID, BookmarkUpdateReason::TestMove)
+        .unwrap();
+    assert!(txn.commit().await.unwrap());
+
+    // The bookmark already exists.
+    let mut txn = bookmarks.create_transaction(ctx.clone());
+    txn.compare_and_swap(&key_1, None, TWOS_CSID, BookmarkUpdateReason::TestMove)
+        .unwrap();
+    assert!(!txn.commit().await.unwrap());
+
+    // Concurrent writers all expect the same value, only one of them wins.
+    let new_values = [
+        TWOS_CSID,
+        THREES_CSID,
+        FOURS_CSID,
+        FIVES_CSID,
+        SIXES_CSID,
+        SEVENS_CSID,
+        EIGHTS_CSID,
+        NINES_CSID,
+        AS_CSID,
+        BS_CSID,
+    ];
+    let handles = new_values
+        .iter()
+        .map(|new_cs| {
+            let mut txn = bookmarks.create_transaction(ctx.clone());
+            txn.compare_and_swap(
+                &key_1,
+                Some(ONES_CSID),
+                *new_cs,
+                BookmarkUpdateReason::TestMove,
+            )
+            .unwrap();
+            tokio::spawn(txn.commit())
+        })
+        .collect::<Vec<_>>();
+    let mut winners = Vec::new();
+    for (new_cs, handle) in new_values.iter().zip(handles) {
+        if handle.await.unwrap().unwrap() {
+            winners.push(*new_cs);
+        }
+    }
+    assert_eq!(winners.len(), 1);
+    assert_eq!(
+        bookmarks.get(ctx.clone(), &key_1).await.unwrap(),
+        Some(winners[0])
+    );
+}
+
+#[fbinit::test]
+async fn test_bookmark_history(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bookmarks = SqlBookmarksBuilder::with_sqlite_in_memory()
+        .unwrap()
+        .with_repo_id(REPO_ZERO);
+    let key_1 = create_bookmark_name("book");
+    let key_2 = create_bookmark_name("other");
+
+    let mut txn = bookmarks.create_transaction(ctx.clone());
+    txn.set_actor("creator".to_string());
+    txn.create(&key_1, ONES_CSID, BookmarkUpdateReason::TestMove)
+        .unwrap();
+    txn.create(&key_2, ONES_CSID, BookmarkUpdateReason::TestMove)
+        .unwrap();
+    assert!(txn.commit().await.unwrap());
+
+    let values = [
+        ONES_CSID,
+        TWOS_CSID,
+        THREES_CSID,
+        FOURS_CSID,
+        FIVES_CSID,
+        SIXES_CSID,
+    ];
+    for (i, window) in values.windows(2).enumerate() {
+        let mut txn = bookmarks.create_transaction(ctx.clone());
+        txn.set_actor(format!("mover{}", i));
+        txn.update(&key_1, window[1], window[0], BookmarkUpdateReason::TestMove)
+            .unwrap();
+        assert!(txn.commit().await.unwrap());
+    }
+
+    let query = BookmarkHistoryQuery {
+        name: key_1.clone(),
+        since: None,
+        limit: 10,
+    };
+    let history = bookmarks
+        .bookmark_history(ctx.clone(), query.clone())
+        .await
+        .unwrap();
+    let actual = history
+        .iter()
+        .map(|entry| (entry.old_value, entry.new_value, entry.actor.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            (Some(FIVES_CSID), Some(SIXES_CSID), Some("mover4")),
+            (Some(FOURS_CSID), Some(FIVES_CSID), Some("mover3")),
+            (Some(THREES_CSID), Some(FOURS_CSID), Some("mover2")),
+            (Some(TWOS_CSID), Some(THREES_CSID), Some("mover1")),
+            (Some(ONES_CSID), Some(TWOS_CSID), Some("mover0")),
+            (None, Some(ONES_CSID), Some("creator")),
+        ]
+    );
+
+    // The limit keeps the most recent changes.
+    let latest = bookmarks
+        .bookmark_history(
+            ctx.clone(),
+            BookmarkHistoryQuery {
+                limit: 2,
+                ..query.clone()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(latest, history[..2].to_vec());
+
+    // No changes were made after now.
+    let since_now = bookmarks
+        .bookmark_history(
+            ctx.clone(),
+            BookmarkHistoryQuery {
+                since: Some(Timestamp::from_timestamp_secs(
+                    Timestamp::now().timestamp_seconds() + 1,
+                )),
+                ..query.clone()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(since_now, Vec::<BookmarkHistoryEntry>::new());
+
+    // All changes are within the retention period.
+    let retained = bookmarks
+        .with_history_options(BookmarkHistoryOptions { retain_days: 1 })
+        .bookmark_history(ctx.clone(), query)
+        .await
+        .unwrap();
+    assert_eq!(retained, history);
+}
+
 #[fbinit::test]
 async fn test_force_delete(fb: FacebookInit) {
     let ctx = CoreContext::test_mock(fb);
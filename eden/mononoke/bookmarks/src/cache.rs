@@ -442,6 +442,10 @@ impl BookmarkTransaction for CachedBookmarksTransaction {
         self.transaction.create_publishing(bookmark, new_cs, reason)
     }
 
+    fn set_actor(&mut self, actor: String) {
+        self.transaction.set_actor(actor)
+    }
+
     fn commit(self: Box<Self>) -> BoxFuture<'static, Result<bool>> {
         let CachedBookmarksTransaction {
             transaction,
@@ -682,6 +686,8 @@ mod tests {
             Ok(())
         }
 
+        fn set_actor(&mut self, _actor: String) {}
+
         fn commit(self: Box<Self>) -> BoxFuture<'static, Result<bool>> {
             future::ok(true).boxed()
         }
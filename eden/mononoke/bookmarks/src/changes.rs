@@ -0,0 +1,419 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use bookmarks_types::BookmarkKey;
+use bookmarks_types::Freshness;
+use context::CoreContext;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::MissedTickBehavior;
+
+use crate::log::ArcBookmarkUpdateLog;
+use crate::log::BookmarkUpdateLogEntry;
+
+/// Maximum number of entries read from the bookmark update log at once.
+const READ_BATCH_SIZE: u64 = 1000;
+
+/// A change to a bookmark, as seen by a subscriber to bookmark changes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BookmarkChange {
+    /// The bookmark was moved from `old_value` to `new_value`.  A value of
+    /// `None` means the bookmark didn't exist (or, for `old_value`, that its
+    /// previous position is unknown).
+    Update {
+        name: BookmarkKey,
+        old_value: Option<ChangesetId>,
+        new_value: Option<ChangesetId>,
+        timestamp: Timestamp,
+    },
+
+    /// The subscriber fell behind and some changes were dropped.  The
+    /// subscriber should reload the bookmarks it is interested in.
+    Reset,
+}
+
+impl From<BookmarkUpdateLogEntry> for BookmarkChange {
+    fn from(entry: BookmarkUpdateLogEntry) -> Self {
+        BookmarkChange::Update {
+            name: entry.bookmark_name,
+            old_value: entry.from_changeset_id,
+            new_value: entry.to_changeset_id,
+            timestamp: entry.timestamp,
+        }
+    }
+}
+
+/// Options for subscribing to bookmark changes.
+#[derive(Clone, Debug)]
+pub struct BookmarkChangesOptions {
+    /// How often the bookmark update log is polled for new changes.
+    pub interval: Duration,
+    /// Number of changes that can be waiting for the subscriber before
+    /// further changes are dropped and replaced by `BookmarkChange::Reset`.
+    pub buffer_size: usize,
+    /// Freshness of the reads from the bookmark update log.
+    pub freshness: Freshness,
+}
+
+impl Default for BookmarkChangesOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            buffer_size: 1000,
+            freshness: Freshness::MostRecent,
+        }
+    }
+}
+
+/// Subscribe to changes to bookmarks made after the subscription.
+///
+/// If `names` is given, only changes to those bookmarks are returned.
+/// Changes are returned in the order they appear in the bookmark update log.
+/// The update log is polled in the background until the returned stream is
+/// dropped.
+pub async fn subscribe_bookmark_changes(
+    ctx: &CoreContext,
+    update_log: ArcBookmarkUpdateLog,
+    names: Option<Vec<BookmarkKey>>,
+    options: BookmarkChangesOptions,
+) -> Result<BoxStream<'static, Result<BookmarkChange>>> {
+    let last_id = update_log
+        .get_largest_log_id(ctx.clone(), options.freshness)
+        .await?
+        .unwrap_or(0);
+    let names = names.map(HashSet::from_iter);
+    let (sender, receiver) = mpsc::channel(options.buffer_size.max(1));
+
+    tokio::spawn(poll_bookmark_changes(
+        ctx.clone(),
+        update_log,
+        last_id,
+        names,
+        options,
+        sender,
+    ));
+
+    Ok(stream::unfold(receiver, |mut receiver| async move {
+        let change = receiver.recv().await?;
+        Some((change, receiver))
+    })
+    .boxed())
+}
+
+async fn poll_bookmark_changes(
+    ctx: CoreContext,
+    update_log: ArcBookmarkUpdateLog,
+    mut last_id: u64,
+    names: Option<HashSet<BookmarkKey>>,
+    options: BookmarkChangesOptions,
+    sender: mpsc::Sender<Result<BookmarkChange>>,
+) {
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Set when a change couldn't be sent because the subscriber fell behind.
+    // Until the subscriber has been told with `BookmarkChange::Reset`, no
+    // further changes are sent.
+    let mut overflowed = false;
+
+    loop {
+        interval.tick().await;
+        if sender.is_closed() {
+            return;
+        }
+        if overflowed {
+            match sender.try_send(Ok(BookmarkChange::Reset)) {
+                Ok(()) => overflowed = false,
+                Err(TrySendError::Full(_)) => {}
+                Err(TrySendError::Closed(_)) => return,
+            }
+        }
+
+        loop {
+            let entries = update_log
+                .read_next_bookmark_log_entries(
+                    ctx.clone(),
+                    last_id,
+                    READ_BATCH_SIZE,
+                    options.freshness,
+                )
+                .try_collect::<Vec<_>>()
+                .await;
+            let entries = match entries {
+                Ok(entries) => entries,
+                Err(err) => {
+                    match sender.try_send(Err(err)) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => overflowed = true,
+                        Err(TrySendError::Closed(_)) => return,
+                    }
+                    break;
+                }
+            };
+            let count = entries.len() as u64;
+
+            for entry in entries {
+                last_id = entry.id as u64;
+                if overflowed
+                    || names
+                        .as_ref()
+                        .is_some_and(|names| !names.contains(&entry.bookmark_name))
+                {
+                    continue;
+                }
+                match sender.try_send(Ok(entry.into())) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => overflowed = true,
+                    Err(TrySendError::Closed(_)) => return,
+                }
+            }
+
+            if count < READ_BATCH_SIZE {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use fbinit::FacebookInit;
+    use futures::future;
+    use futures::future::BoxFuture;
+    use futures::future::FutureExt;
+    use mononoke_types::RepositoryId;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+
+    use super::*;
+    use crate::log::BookmarkHistoryEntry;
+    use crate::log::BookmarkHistoryQuery;
+    use crate::log::BookmarkUpdateLog;
+    use crate::log::BookmarkUpdateReason;
+
+    #[derive(Default)]
+    struct MockBookmarkUpdateLog {
+        entries: Mutex<Vec<BookmarkUpdateLogEntry>>,
+    }
+
+    impl MockBookmarkUpdateLog {
+        fn push(&self, name: &str, to: Option<ChangesetId>) -> BookmarkChange {
+            let mut entries = self.entries.lock().unwrap();
+            let name = BookmarkKey::new(name).unwrap();
+            let from = entries
+                .iter()
+                .rev()
+                .find(|entry| entry.bookmark_name == name)
+                .and_then(|entry| entry.to_changeset_id);
+            let entry = BookmarkUpdateLogEntry {
+                id: entries.len() as i64 + 1,
+                repo_id: RepositoryId::new(0),
+                bookmark_name: name,
+                from_changeset_id: from,
+                to_changeset_id: to,
+                reason: BookmarkUpdateReason::TestMove,
+                timestamp: Timestamp::now(),
+            };
+            entries.push(entry.clone());
+            entry.into()
+        }
+    }
+
+    impl BookmarkUpdateLog for MockBookmarkUpdateLog {
+        fn read_next_bookmark_log_entries(
+            &self,
+            _ctx: CoreContext,
+            id: u64,
+            limit: u64,
+            _freshness: Freshness,
+        ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+            let entries = self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| entry.id as u64 > id)
+                .take(limit as usize)
+                .cloned()
+                .map(Ok)
+                .collect::<Vec<_>>();
+            stream::iter(entries).boxed()
+        }
+
+        fn read_next_bookmark_log_entries_same_bookmark_and_reason(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+            _limit: u64,
+        ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+            unimplemented!()
+        }
+
+        fn list_bookmark_log_entries(
+            &self,
+            _ctx: CoreContext,
+            _name: BookmarkKey,
+            _max_rec: u32,
+            _offset: Option<u32>,
+            _freshness: Freshness,
+        ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
+        {
+            unimplemented!()
+        }
+
+        fn list_bookmark_log_entries_ts_in_range(
+            &self,
+            _ctx: CoreContext,
+            _name: BookmarkKey,
+            _max_rec: u32,
+            _min_ts: Timestamp,
+            _max_ts: Timestamp,
+        ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
+        {
+            unimplemented!()
+        }
+
+        fn bookmark_history(
+            &self,
+            _ctx: CoreContext,
+            _query: BookmarkHistoryQuery,
+        ) -> BoxFuture<'static, Result<Vec<BookmarkHistoryEntry>>> {
+            unimplemented!()
+        }
+
+        fn count_further_bookmark_log_entries(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+            _exclude_reason: Option<BookmarkUpdateReason>,
+        ) -> BoxFuture<'static, Result<u64>> {
+            unimplemented!()
+        }
+
+        fn count_further_bookmark_log_entries_by_reason(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+        ) -> BoxFuture<'static, Result<Vec<(BookmarkUpdateReason, u64)>>> {
+            unimplemented!()
+        }
+
+        fn skip_over_bookmark_log_entries_with_reason(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+            _reason: BookmarkUpdateReason,
+        ) -> BoxFuture<'static, Result<Option<u64>>> {
+            unimplemented!()
+        }
+
+        fn get_largest_log_id(
+            &self,
+            _ctx: CoreContext,
+            _freshness: Freshness,
+        ) -> BoxFuture<'static, Result<Option<u64>>> {
+            let largest = self
+                .entries
+                .lock()
+                .unwrap()
+                .last()
+                .map(|entry| entry.id as u64);
+            future::ok(largest).boxed()
+        }
+    }
+
+    fn test_options(buffer_size: usize) -> BookmarkChangesOptions {
+        BookmarkChangesOptions {
+            interval: Duration::from_millis(10),
+            buffer_size,
+            freshness: Freshness::MostRecent,
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_subscribe_bookmark_changes(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let log = Arc::new(MockBookmarkUpdateLog::default());
+
+        // Changes made before subscribing are not returned.
+        log.push("a", Some(ONES_CSID));
+        let changes = subscribe_bookmark_changes(
+            &ctx,
+            log.clone(),
+            Some(vec![BookmarkKey::new("a")?, BookmarkKey::new("b")?]),
+            test_options(100),
+        )
+        .await?;
+
+        // Move the bookmarks while the subscription is polling.
+        let writer = tokio::spawn({
+            let log = log.clone();
+            async move {
+                let mut expected = vec![];
+                for i in 0..30 {
+                    let cs_id = if i % 2 == 0 { TWOS_CSID } else { ONES_CSID };
+                    let change = log.push(["a", "b", "c"][i % 3], Some(cs_id));
+                    if i % 3 != 2 {
+                        expected.push(change);
+                    }
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                expected
+            }
+        });
+
+        let received = changes.take(20).try_collect::<Vec<_>>().await?;
+        let expected = writer.await?;
+        assert_eq!(received, expected);
+        assert!(matches!(
+            &received[0],
+            BookmarkChange::Update {
+                old_value: Some(ONES_CSID),
+                new_value: Some(TWOS_CSID),
+                ..
+            }
+        ));
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_subscribe_bookmark_changes_overflow(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let log = Arc::new(MockBookmarkUpdateLog::default());
+
+        let mut changes =
+            subscribe_bookmark_changes(&ctx, log.clone(), None, test_options(2)).await?;
+        let expected = (0..10)
+            .map(|_| log.push("a", Some(ONES_CSID)))
+            .collect::<Vec<_>>();
+        // Give the subscription time to fall behind.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(changes.try_next().await?.as_ref(), Some(&expected[0]));
+        assert_eq!(changes.try_next().await?.as_ref(), Some(&expected[1]));
+        assert_eq!(changes.try_next().await?, Some(BookmarkChange::Reset));
+
+        // Once the subscriber has caught up, changes are returned again.
+        let change = log.push("b", None);
+        assert_eq!(changes.try_next().await?, Some(change));
+
+        Ok(())
+    }
+}
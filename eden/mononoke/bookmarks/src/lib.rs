@@ -20,6 +20,7 @@ use futures::StreamExt;
 use mononoke_types::ChangesetId;
 
 mod cache;
+mod changes;
 mod log;
 mod subscription;
 mod transaction;
@@ -34,7 +35,13 @@ pub use bookmarks_types::BookmarkPrefix;
 pub use bookmarks_types::BookmarkPrefixRange;
 pub use bookmarks_types::Freshness;
 pub use cache::CachedBookmarks;
+pub use changes::subscribe_bookmark_changes;
+pub use changes::BookmarkChange;
+pub use changes::BookmarkChangesOptions;
 pub use log::ArcBookmarkUpdateLog;
+pub use log::BookmarkHistoryEntry;
+pub use log::BookmarkHistoryOptions;
+pub use log::BookmarkHistoryQuery;
 pub use log::BookmarkUpdateLog;
 pub use log::BookmarkUpdateLogArc;
 pub use log::BookmarkUpdateLogEntry;
@@ -44,6 +44,56 @@ pub struct BookmarkUpdateLogEntry {
     pub timestamp: Timestamp,
 }
 
+/// Query for the history of changes to a bookmark.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BookmarkHistoryQuery {
+    /// Name of the bookmark
+    pub name: BookmarkKey,
+    /// If set, only changes made at or after this time are returned
+    pub since: Option<Timestamp>,
+    /// Maximum number of changes to return
+    pub limit: u32,
+}
+
+/// Entry in the history of changes to a bookmark
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BookmarkHistoryEntry {
+    /// Id of the corresponding bookmark update log entry
+    pub id: u64,
+    /// Previous position of the bookmark, if known
+    pub old_value: Option<ChangesetId>,
+    /// New position of the bookmark, or None if it was deleted
+    pub new_value: Option<ChangesetId>,
+    /// Reason for the change
+    pub reason: BookmarkUpdateReason,
+    /// When the change happened
+    pub timestamp: Timestamp,
+    /// Who made the change, if known
+    pub actor: Option<String>,
+}
+
+/// Options for the history of changes to bookmarks
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BookmarkHistoryOptions {
+    /// Number of days for which changes are returned by history queries, or
+    /// 0 to return changes regardless of their age
+    pub retain_days: u32,
+}
+
+impl BookmarkHistoryOptions {
+    /// Earliest time of the changes that should be returned by history
+    /// queries, if any.
+    pub fn retention_start(&self) -> Option<Timestamp> {
+        if self.retain_days == 0 {
+            return None;
+        }
+        let retain_secs = i64::from(self.retain_days) * 24 * 60 * 60;
+        Some(Timestamp::from_timestamp_secs(
+            Timestamp::now().timestamp_seconds() - retain_secs,
+        ))
+    }
+}
+
 #[facet::facet]
 pub trait BookmarkUpdateLog: Send + Sync + 'static {
     /// Read the next up to `limit` entries from Bookmark update log. It either returns
@@ -86,6 +136,13 @@ pub trait BookmarkUpdateLog: Send + Sync + 'static {
         max_ts: Timestamp,
     ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>;
 
+    /// Read the history of changes to a bookmark, most recent first.
+    fn bookmark_history(
+        &self,
+        ctx: CoreContext,
+        query: BookmarkHistoryQuery,
+    ) -> BoxFuture<'static, Result<Vec<BookmarkHistoryEntry>>>;
+
     /// Count the number of BookmarkUpdateLog entries with id greater than the given value,
     /// possibly excluding a given reason.
     fn count_further_bookmark_log_entries(
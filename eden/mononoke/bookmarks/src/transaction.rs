@@ -114,6 +114,29 @@ pub trait BookmarkTransaction: Send + Sync + 'static {
         reason: BookmarkUpdateReason,
     ) -> Result<()>;
 
+    /// Adds a compare-and-swap operation to the transaction set.
+    /// Sets the bookmark to `new_cs` if it currently points to `expected`, or creates it if
+    /// `expected` is None.  If the bookmark has been moved by another writer in the meantime,
+    /// committing the transaction returns `false` rather than an error, so that the caller can
+    /// read the new value and retry.
+    fn compare_and_swap(
+        &mut self,
+        bookmark: &BookmarkKey,
+        expected: Option<ChangesetId>,
+        new_cs: ChangesetId,
+        reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        match expected {
+            Some(old_cs) => self.update(bookmark, new_cs, old_cs, reason),
+            None => self.create(bookmark, new_cs, reason),
+        }
+    }
+
+    /// Sets who is making the changes in this transaction, to be recorded in the bookmark update
+    /// log.  By default, this is the unix name of the client of the context the transaction was
+    /// created with.
+    fn set_actor(&mut self, actor: String);
+
     /// Commits the transaction. Future succeeds if transaction has been
     /// successful, or errors if transaction has failed. Logical failure is indicated by
     /// returning a successful `false` value; infrastructure failure is reported via an Error.
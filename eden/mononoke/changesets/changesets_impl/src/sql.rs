@@ -67,12 +67,14 @@ struct RendezVousConnection {
 }
 
 impl RendezVousConnection {
-    fn new(conn: Connection, name: &str, opts: RendezVousOptions) -> Self {
+    fn new(conn: Connection, name: &str, opts: &RendezVousOptions) -> Self {
+        let endpoint = format!("changesets.{}", name);
         Self {
             conn,
-            rdv: RendezVous::new(
-                TunablesRendezVousController::new(opts),
-                Arc::new(RendezVousStats::new(format!("changesets.{}", name,))),
+            rdv: RendezVous::new_with_endpoint_options(
+                TunablesRendezVousController::new(opts.clone()),
+                Arc::new(RendezVousStats::new(endpoint.clone())),
+                opts.for_endpoint(&endpoint),
             ),
         }
     }
@@ -240,11 +242,11 @@ impl SqlChangesetsBuilder {
 
         SqlChangesets {
             repo_id,
-            read_connection: RendezVousConnection::new(read_connection, "read", opts),
+            read_connection: RendezVousConnection::new(read_connection, "read", &opts),
             read_master_connection: RendezVousConnection::new(
                 read_master_connection,
                 "read_master",
-                opts,
+                &opts,
             ),
             write_connection,
         }
@@ -9,7 +9,9 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use blobstore_factory::BlobstoreOptions;
+use anyhow::ensure;
+use anyhow::Result;
+use blobstore_factory::PerRepoBlobstoreOptions;
 use blobstore_factory::ReadOnlyStorage;
 use cached_config::ConfigStore;
 use clap::ValueEnum;
@@ -18,6 +20,8 @@ use fbinit::FacebookInit;
 use megarepo_config::MononokeMegarepoConfigsOptions;
 use observability::ObservabilityContext;
 use permission_checker::AclProvider;
+use rate_limiting::ArcRateLimiter;
+use rendezvous::HealthCheckHandle;
 use rendezvous::RendezVousOptions;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::Logger;
@@ -25,13 +29,13 @@ use sql_ext::facebook::MysqlOptions;
 use strum::EnumString;
 use tokio::runtime::Handle;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct LocalCacheConfig {
     /// Number of shards in the local blobstore cache
     pub blobstore_cache_shards: usize,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Caching {
     /// Caching is fully enabled, with both local and shared caches.
     Enabled(LocalCacheConfig),
@@ -43,6 +47,61 @@ pub enum Caching {
     Disabled,
 }
 
+impl Caching {
+    /// Caching for the given repo, where the number of shards in the local
+    /// blobstore cache is taken from `shard_overrides` if the repo is listed
+    /// there.
+    pub fn for_repo(self, repo_name: &str, shard_overrides: &HashMap<String, usize>) -> Self {
+        let local_cache_config = match shard_overrides.get(repo_name) {
+            Some(&blobstore_cache_shards) => LocalCacheConfig {
+                blobstore_cache_shards,
+            },
+            None => return self,
+        };
+        match self {
+            Caching::Enabled(_) => Caching::Enabled(local_cache_config),
+            Caching::LocalOnly(_) => Caching::LocalOnly(local_cache_config),
+            Caching::Disabled => Caching::Disabled,
+        }
+    }
+
+    /// Whether the local cache is enabled, which is the case unless caching
+    /// is disabled entirely.
+    pub fn local_enabled(&self) -> bool {
+        self.local_config().is_some()
+    }
+
+    /// Whether the shared cache is enabled.
+    pub fn shared_enabled(&self) -> bool {
+        matches!(self, Caching::Enabled(_))
+    }
+
+    /// Configuration of the local cache, if it is enabled.
+    pub fn local_config(&self) -> Option<LocalCacheConfig> {
+        match self {
+            Caching::Enabled(local_cache_config) | Caching::LocalOnly(local_cache_config) => {
+                Some(*local_cache_config)
+            }
+            Caching::Disabled => None,
+        }
+    }
+}
+
+/// Check that per-repo overrides of the number of shards in the local
+/// blobstore cache are valid.
+pub fn check_blobstore_cache_shard_overrides(
+    shard_overrides: &HashMap<String, usize>,
+) -> Result<()> {
+    for (repo_name, shards) in shard_overrides {
+        ensure!(
+            *shards > 0,
+            "Number of blobstore cache shards for repo {} must be nonzero",
+            repo_name
+        );
+    }
+    Ok(())
+}
+
 #[derive(Copy, Clone, Debug, ValueEnum, EnumString, strum::Display)]
 pub enum WarmBookmarksCacheDerivedData {
     HgOnly,
@@ -50,6 +109,18 @@ pub enum WarmBookmarksCacheDerivedData {
     NoDerivation,
 }
 
+/// Function determining whether a repo, identified by name, should be loaded.
+pub type RepoFilter = Arc<dyn Fn(&str) -> bool + Send + Sync + 'static>;
+
+/// Combine a repo filter with an existing one, if any, so that only repos
+/// allowed by both are loaded.
+fn and_repo_filters(existing: Option<RepoFilter>, filter: RepoFilter) -> RepoFilter {
+    match existing {
+        Some(existing) => Arc::new(move |repo_name| existing(repo_name) && filter(repo_name)),
+        None => filter,
+    }
+}
+
 /// Struct representing the configuration associated with a MononokeApp instance which
 /// is immutable post the point of app construction.
 pub struct MononokeEnvironment {
@@ -59,10 +130,13 @@ pub struct MononokeEnvironment {
     pub warm_bookmarks_cache_scuba_sample_builder: MononokeScubaSampleBuilder,
     pub config_store: ConfigStore,
     pub caching: Caching,
+    /// Number of shards in the local blobstore cache for specific repos,
+    /// keyed by repo name, overriding the number in `caching`.
+    pub blobstore_cache_shard_overrides: HashMap<String, usize>,
     pub observability_context: ObservabilityContext,
     pub runtime: Handle,
     pub mysql_options: MysqlOptions,
-    pub blobstore_options: BlobstoreOptions,
+    pub blobstore_options: PerRepoBlobstoreOptions,
     pub readonly_storage: ReadOnlyStorage,
     pub rendezvous_options: RendezVousOptions,
     pub megarepo_configs_options: MononokeMegarepoConfigsOptions,
@@ -71,5 +145,140 @@ pub struct MononokeEnvironment {
     pub acl_provider: Arc<dyn AclProvider>,
     pub warm_bookmarks_cache_derived_data: Option<WarmBookmarksCacheDerivedData>,
     /// Function determining whether given repo (identified by name) should be loaded
-    pub filter_repos: Option<Arc<dyn Fn(&str) -> bool + Send + Sync + 'static>>,
+    pub filter_repos: Option<RepoFilter>,
+    /// Health check server for load balancers, if enabled. Servers should
+    /// call `set_ready` once they are ready to accept requests.
+    pub health_check: Option<Arc<HealthCheckHandle>>,
+    /// Rate limiter shared by all the repos loaded from this environment,
+    /// to protect the backends they have in common.
+    pub rate_limiter: Option<ArcRateLimiter>,
+}
+
+impl MononokeEnvironment {
+    /// Caching for the blobstore of the given repo, taking
+    /// `blobstore_cache_shard_overrides` into account.
+    pub fn repo_caching(&self, repo_name: Option<&str>) -> Caching {
+        match repo_name {
+            Some(repo_name) => self
+                .caching
+                .for_repo(repo_name, &self.blobstore_cache_shard_overrides),
+            None => self.caching,
+        }
+    }
+
+    /// The rate limiter shared by all the repos, if any. None means that
+    /// there is no limit.
+    pub fn rate_limiter(&self) -> Option<&ArcRateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Further restrict the repos to load to those for which `filter` also
+    /// returns true.
+    pub fn with_additional_repo_filter(
+        mut self,
+        filter: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.add_repo_filter(filter);
+        self
+    }
+
+    /// Same as `with_additional_repo_filter`, for an environment that is
+    /// borrowed mutably.
+    pub fn add_repo_filter(&mut self, filter: impl Fn(&str) -> bool + Send + Sync + 'static) {
+        self.filter_repos = Some(and_repo_filters(self.filter_repos.take(), Arc::new(filter)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+
+    use super::*;
+
+    fn local_cache_config(blobstore_cache_shards: usize) -> LocalCacheConfig {
+        LocalCacheConfig {
+            blobstore_cache_shards,
+        }
+    }
+
+    #[test]
+    fn test_caching_for_repo() {
+        let shard_overrides = hashmap! { "hot".to_string() => 64 };
+
+        let caching = Caching::Enabled(local_cache_config(4));
+        assert_eq!(
+            caching.for_repo("hot", &shard_overrides),
+            Caching::Enabled(local_cache_config(64))
+        );
+        assert_eq!(caching.for_repo("cold", &shard_overrides), caching);
+
+        let caching = Caching::LocalOnly(local_cache_config(4));
+        assert_eq!(
+            caching.for_repo("hot", &shard_overrides),
+            Caching::LocalOnly(local_cache_config(64))
+        );
+        assert_eq!(caching.for_repo("cold", &shard_overrides), caching);
+
+        assert_eq!(
+            Caching::Disabled.for_repo("hot", &shard_overrides),
+            Caching::Disabled
+        );
+    }
+
+    #[test]
+    fn test_caching_local_enabled() {
+        assert!(Caching::Enabled(local_cache_config(4)).local_enabled());
+        assert!(Caching::LocalOnly(local_cache_config(4)).local_enabled());
+        assert!(!Caching::Disabled.local_enabled());
+    }
+
+    #[test]
+    fn test_caching_shared_enabled() {
+        assert!(Caching::Enabled(local_cache_config(4)).shared_enabled());
+        assert!(!Caching::LocalOnly(local_cache_config(4)).shared_enabled());
+        assert!(!Caching::Disabled.shared_enabled());
+    }
+
+    #[test]
+    fn test_caching_local_config() {
+        assert_eq!(
+            Caching::Enabled(local_cache_config(4)).local_config(),
+            Some(local_cache_config(4))
+        );
+        assert_eq!(
+            Caching::LocalOnly(local_cache_config(8)).local_config(),
+            Some(local_cache_config(8))
+        );
+        assert_eq!(Caching::Disabled.local_config(), None);
+    }
+
+    #[test]
+    fn test_and_repo_filters() {
+        let filter = and_repo_filters(None, Arc::new(|repo_name| repo_name.starts_with("team/")));
+        assert!(filter("team/repo"));
+        assert!(!filter("other/repo"));
+
+        let filter = and_repo_filters(
+            Some(Arc::new(|repo_name| repo_name.ends_with("-allowed"))),
+            Arc::new(|repo_name| repo_name.starts_with("team/")),
+        );
+        assert!(filter("team/repo-allowed"));
+        assert!(!filter("team/repo"));
+        assert!(!filter("other/repo-allowed"));
+    }
+
+    #[test]
+    fn test_check_blobstore_cache_shard_overrides() {
+        assert!(check_blobstore_cache_shard_overrides(&HashMap::new()).is_ok());
+        assert!(
+            check_blobstore_cache_shard_overrides(&hashmap! { "hot".to_string() => 64 }).is_ok()
+        );
+        assert!(
+            check_blobstore_cache_shard_overrides(&hashmap! {
+                "hot".to_string() => 64,
+                "broken".to_string() => 0,
+            })
+            .is_err()
+        );
+    }
 }
@@ -230,7 +230,9 @@ impl MononokeApp {
     /// called.  This should perform any steps required to quiesce the server,
     /// for example by removing this instance from routing configuration, or
     /// asking the load balancer to stop sending requests to this instance.
-    /// Requests that do arrive should still be accepted.
+    /// Requests that do arrive should still be accepted. If a health check
+    /// server is running, it starts reporting that this instance is draining
+    /// before `quiesce` is called.
     ///
     /// After the `shutdown_grace_period`, the `shutdown` future is awaited.
     /// This should do any additional work to stop accepting connections and wait
@@ -259,6 +261,13 @@ impl MononokeApp {
             .runtime
             .take()
             .ok_or_else(|| anyhow!("MononokeApp already started"))?;
+        let health_check = self.env.health_check.clone();
+        let quiesce = move || {
+            if let Some(health_check) = health_check {
+                health_check.set_draining();
+            }
+            quiesce();
+        };
         let server = async move { server(self).await };
         runtime.block_on(run_until_terminated(
             server,
@@ -360,7 +369,7 @@ impl MononokeApp {
 
     /// The blobstore options for this app.
     pub fn blobstore_options(&self) -> &BlobstoreOptions {
-        &self.env.blobstore_options
+        &self.env.blobstore_options.base
     }
 
     /// The readonly storage options for this app.
@@ -746,14 +755,15 @@ impl MononokeApp {
     ) -> Result<Arc<dyn Blobstore>> {
         let repo_configs = self.repo_configs();
         let storage_configs = self.storage_configs();
-        let (mut repo_id, redaction, mut storage_config) =
+        let (mut repo_id, repo_name, redaction, mut storage_config) =
             if let Some(repo_id) = repo_blobstore_args.repo_id {
                 let repo_id = RepositoryId::new(repo_id);
-                let (_repo_name, repo_config) = repo_configs
+                let (repo_name, repo_config) = repo_configs
                     .get_repo_config(repo_id)
                     .ok_or_else(|| anyhow!("unknown repoid: {:?}", repo_id))?;
                 (
                     Some(repo_id),
+                    Some(repo_name.as_str()),
                     repo_config.redaction,
                     repo_config.storage_config.clone(),
                 )
@@ -764,6 +774,7 @@ impl MononokeApp {
                     .ok_or_else(|| anyhow!("unknown reponame: {:?}", repo_name))?;
                 (
                     Some(repo_config.repoid),
+                    Some(repo_name.as_str()),
                     repo_config.redaction,
                     repo_config.storage_config.clone(),
                 )
@@ -772,7 +783,7 @@ impl MononokeApp {
                     .storage
                     .get(storage_name)
                     .ok_or_else(|| anyhow!("unknown storage name: {:?}", storage_name))?;
-                (None, Redaction::Enabled, storage_config.clone())
+                (None, None, Redaction::Enabled, storage_config.clone())
             } else {
                 return Err(anyhow!("Expected a storage argument"));
             };
@@ -785,12 +796,17 @@ impl MononokeApp {
             repo_id = None;
         }
 
+        let blobstore_options = match repo_name {
+            Some(repo_name) => self.env.blobstore_options.resolve(repo_name),
+            None => &self.env.blobstore_options.base,
+        };
+
         let blobstore = blobstore_factory::make_blobstore(
             self.env.fb,
             storage_config.blobstore,
             &self.env.mysql_options,
             self.env.readonly_storage,
-            &self.env.blobstore_options,
+            blobstore_options,
             &self.env.logger,
             &self.env.config_store,
             &blobstore_factory::default_scrub_handler(),
@@ -33,6 +33,12 @@ pub struct ConfigArgs {
     /// Mononoke's crypto project
     #[clap(long)]
     pub crypto_path_regex: Option<Vec<String>>,
+
+    /// Allow overriding configs with environment variables named after the
+    /// config keys with this prefix, e.g. `MONONOKE_CACHE_TTL` for the key
+    /// `mononoke.cache.ttl` with the prefix `MONONOKE`. For local testing
+    #[clap(long)]
+    pub config_env_override_prefix: Option<String>,
 }
 
 const PRODUCTION_PREFIX: &str = "configerator://scm/mononoke/repos/tiers/";
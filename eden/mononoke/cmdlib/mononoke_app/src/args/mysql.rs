@@ -36,6 +36,16 @@ pub struct MysqlArgs {
     #[clap(long, default_value = "4000")]
     pub mysql_pool_idle_timeout: u64,
 
+    /// Size of a separate MySql connection pool for read-only connections.
+    /// If unset, they use the main connection pool
+    #[clap(long)]
+    pub mysql_read_pool_limit: Option<usize>,
+
+    /// Size of a separate MySql connection pool for connections that can
+    /// write. If unset, they use the main connection pool
+    #[clap(long)]
+    pub mysql_write_pool_limit: Option<usize>,
+
     /// Size of the MySql connection pool for SqlBlob
     #[clap(long, default_value = "10000", alias = "mysql-sqblob-pool-limit")]
     pub mysql_sqlblob_pool_limit: usize,
@@ -64,4 +74,12 @@ pub struct MysqlArgs {
     /// Mysql query time limit in millisecs
     #[clap(long, default_value = "10000", alias = "mysql-max-query-time")]
     pub mysql_query_time_limit: u64,
+
+    /// Timeout of read queries in millisecs, after which they are cancelled
+    #[clap(long)]
+    pub mysql_read_query_timeout: Option<u64>,
+
+    /// Timeout of write queries in millisecs, after which they are cancelled
+    #[clap(long)]
+    pub mysql_write_query_timeout: Option<u64>,
 }
@@ -5,8 +5,6 @@
  * GNU General Public License version 2.
  */
 
-use std::sync::Arc;
-
 use anyhow::Result;
 use clap::Parser;
 use environment::MononokeEnvironment;
@@ -29,13 +27,7 @@ impl AppExtension for RepoFilterAppExtension {
 
     fn environment_hook(&self, args: &Self::Args, env: &mut MononokeEnvironment) -> Result<()> {
         if let Some(filter_repos) = args.filter_repos.clone() {
-            if let Some(current_filter_fn) = env.filter_repos.clone() {
-                env.filter_repos = Some(Arc::new(move |name: &str| {
-                    filter_repos.is_match(name) & current_filter_fn(name)
-                }));
-            } else {
-                env.filter_repos = Some(Arc::new(move |name: &str| filter_repos.is_match(name)));
-            }
+            env.add_repo_filter(move |name| filter_repos.is_match(name));
         }
 
         Ok(())
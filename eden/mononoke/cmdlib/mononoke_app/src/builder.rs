@@ -21,6 +21,7 @@ use blobstore_factory::DelayOptions;
 #[cfg(fbcode_build)]
 use blobstore_factory::ManifoldArgs;
 use blobstore_factory::PackOptions;
+use blobstore_factory::PerRepoBlobstoreOptions;
 use blobstore_factory::ReadOnlyStorage;
 use blobstore_factory::ReadOnlyStorageArgs;
 use blobstore_factory::ThrottleOptions;
@@ -35,7 +36,9 @@ use cmdlib_caching::CachelibArgs;
 use cmdlib_caching::CachelibSettings;
 use cmdlib_logging::LoggingArgs;
 use cmdlib_logging::ScubaLoggingArgs;
+use config_keys::ConfigStoreExt;
 use derived_data_remote::RemoteDerivationArgs;
+use environment::check_blobstore_cache_shard_overrides;
 use environment::MononokeEnvironment;
 use environment::WarmBookmarksCacheDerivedData;
 use fbinit::FacebookInit;
@@ -45,6 +48,8 @@ use observability::DynamicLevelDrain;
 use permission_checker::AclProvider;
 use permission_checker::DefaultAclProvider;
 use permission_checker::InternalAclProvider;
+use rate_limiting::ArcRateLimiter;
+use rendezvous::RendezVous;
 use rendezvous::RendezVousArgs;
 use slog::debug;
 use slog::o;
@@ -55,6 +60,9 @@ use sql_ext::facebook::MysqlOptions;
 use sql_ext::facebook::PoolConfig;
 use sql_ext::facebook::ReadConnectionType;
 use sql_ext::facebook::SharedConnectionPool;
+use sql_ext::facebook::MYSQL_POOL_READ;
+use sql_ext::facebook::MYSQL_POOL_WRITE;
+use sql_ext::set_query_timeouts;
 use tokio::runtime::Handle;
 use tokio::runtime::Runtime;
 
@@ -75,9 +83,11 @@ pub struct MononokeAppBuilder {
     extensions: Vec<(TypeId, Box<dyn BoxedAppExtension>)>,
     arg_defaults: Vec<Box<dyn ArgDefaults>>,
     cachelib_settings: CachelibSettings,
+    blobstore_cache_shard_overrides: HashMap<String, usize>,
     default_scuba_dataset: Option<String>,
     defaults: HashMap<&'static str, String>,
     warm_bookmarks_cache_derived_data: Option<WarmBookmarksCacheDerivedData>,
+    rate_limiter: Option<ArcRateLimiter>,
 }
 
 #[derive(Args, Debug)]
@@ -130,9 +140,11 @@ impl MononokeAppBuilder {
             extensions: Vec::new(),
             arg_defaults: Vec::new(),
             cachelib_settings: CachelibSettings::default(),
+            blobstore_cache_shard_overrides: HashMap::new(),
             default_scuba_dataset: None,
             defaults: HashMap::new(),
             warm_bookmarks_cache_derived_data: None,
+            rate_limiter: None,
         }
     }
 
@@ -159,6 +171,22 @@ impl MononokeAppBuilder {
         self
     }
 
+    /// Use a different number of shards in the local blobstore cache for
+    /// the given repos, keyed by repo name.
+    pub fn with_blobstore_cache_shard_overrides(
+        mut self,
+        blobstore_cache_shard_overrides: HashMap<String, usize>,
+    ) -> Self {
+        self.blobstore_cache_shard_overrides = blobstore_cache_shard_overrides;
+        self
+    }
+
+    /// Share `rate_limiter` between all the repos loaded by the app.
+    pub fn with_rate_limiter(mut self, rate_limiter: ArcRateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
     pub fn with_app_extension<Ext>(mut self, ext: Ext) -> Self
     where
         Ext: AppExtension + 'static,
@@ -329,9 +357,34 @@ impl MononokeAppBuilder {
             .context("Failed to create warm bookmark cache scuba sample builder")?;
 
         let caching = init_cachelib(self.fb, &self.cachelib_settings, &cachelib_args);
+        check_blobstore_cache_shard_overrides(&self.blobstore_cache_shard_overrides)
+            .context("Invalid blobstore cache shard overrides")?;
 
-        let mysql_options =
-            create_mysql_options(&mysql_args, create_mysql_pool_config(&mysql_args));
+        let mut mysql_options = create_mysql_options(
+            &mysql_args,
+            create_mysql_pool_config(&mysql_args, mysql_args.mysql_pool_limit),
+        );
+        for (op_type, pool_limit) in [
+            (MYSQL_POOL_READ, mysql_args.mysql_read_pool_limit),
+            (MYSQL_POOL_WRITE, mysql_args.mysql_write_pool_limit),
+        ] {
+            if let Some(pool_limit) = pool_limit {
+                mysql_options = mysql_options.with_operation_pool(
+                    op_type,
+                    create_mysql_pool_config(&mysql_args, pool_limit),
+                );
+            }
+        }
+        for (op_type, query_timeout) in [
+            (MYSQL_POOL_READ, mysql_args.mysql_read_query_timeout),
+            (MYSQL_POOL_WRITE, mysql_args.mysql_write_query_timeout),
+        ] {
+            if let Some(query_timeout) = query_timeout {
+                mysql_options =
+                    mysql_options.with_query_timeout(op_type, Duration::from_millis(query_timeout));
+            }
+        }
+        set_query_timeouts(&mysql_options);
 
         let blobstore_options = create_blobstore_options(
             &blobstore_args,
@@ -343,6 +396,16 @@ impl MononokeAppBuilder {
 
         let readonly_storage = ReadOnlyStorage::from_args(&readonly_storage_args);
 
+        let health_check = match rendezvous_args.health_check_addr {
+            Some(addr) => {
+                let _guard = runtime.enter();
+                let handle = RendezVous::health_check_server(addr)
+                    .context("Failed to start health check server")?;
+                Some(Arc::new(handle))
+            }
+            None => None,
+        };
+
         let rendezvous_options = rendezvous_args.into();
 
         let megarepo_configs_options = MononokeMegarepoConfigsOptions::from_args(
@@ -369,10 +432,11 @@ impl MononokeAppBuilder {
             warm_bookmarks_cache_scuba_sample_builder,
             config_store,
             caching,
+            blobstore_cache_shard_overrides: self.blobstore_cache_shard_overrides.clone(),
             observability_context,
             runtime: runtime.handle().clone(),
             mysql_options,
-            blobstore_options,
+            blobstore_options: PerRepoBlobstoreOptions::new(blobstore_options),
             readonly_storage,
             acl_provider,
             rendezvous_options,
@@ -381,6 +445,8 @@ impl MononokeAppBuilder {
             disabled_hooks: HashMap::new(),
             warm_bookmarks_cache_derived_data: self.warm_bookmarks_cache_derived_data,
             filter_repos: None,
+            health_check,
+            rate_limiter: self.rate_limiter.clone(),
         })
     }
 }
@@ -394,13 +460,13 @@ fn create_config_store(
     const CONFIGERATOR_POLL_INTERVAL: Duration = Duration::from_secs(1);
     const CONFIGERATOR_REFRESH_TIMEOUT: Duration = Duration::from_secs(1);
 
-    if let Some(path) = &config_args.local_configerator_path {
-        Ok(ConfigStore::file(
+    let config_store = if let Some(path) = &config_args.local_configerator_path {
+        ConfigStore::file(
             logger,
             path.clone(),
             String::new(),
             CONFIGERATOR_POLL_INTERVAL,
-        ))
+        )
     } else {
         let crypto_regex_paths = match &config_args.crypto_path_regex {
             Some(paths) => paths.clone(),
@@ -420,7 +486,12 @@ fn create_config_store(
             crypto_regex,
             CONFIGERATOR_POLL_INTERVAL,
             CONFIGERATOR_REFRESH_TIMEOUT,
-        )
+        )?
+    };
+
+    match &config_args.config_env_override_prefix {
+        Some(prefix) => Ok(config_store.with_env_override_prefix(prefix)),
+        None => Ok(config_store),
     }
 }
 
@@ -446,12 +517,13 @@ fn create_mysql_options(mysql_args: &MysqlArgs, pool_config: PoolConfig) -> Mysq
         pool,
         pool_config,
         read_connection_type,
+        ..Default::default()
     }
 }
 
-fn create_mysql_pool_config(mysql_args: &MysqlArgs) -> PoolConfig {
+fn create_mysql_pool_config(mysql_args: &MysqlArgs, pool_limit: usize) -> PoolConfig {
     PoolConfig::new(
-        mysql_args.mysql_pool_limit,
+        pool_limit,
         mysql_args.mysql_pool_threads_num,
         mysql_args.mysql_pool_per_key_limit,
         mysql_args.mysql_pool_age_timeout,
@@ -520,6 +592,11 @@ fn create_blobstore_options(
         mysql_sqlblob_options,
     );
 
+    let blobstore_options = match blobstore_args.coalescing_options() {
+        Some(coalescing_options) => blobstore_options.with_coalescing(coalescing_options),
+        None => blobstore_options,
+    };
+
     Ok(blobstore_options)
 }
 
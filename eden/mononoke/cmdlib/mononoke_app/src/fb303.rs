@@ -14,6 +14,7 @@ use anyhow::Error;
 use anyhow::Result;
 use clap::Args;
 use fbinit::FacebookInit;
+use rendezvous::HealthCheckHandle;
 use services::Fb303Service;
 use services::FbStatus;
 use slog::info;
@@ -41,17 +42,31 @@ pub use services::AliveService;
 #[derive(Clone)]
 pub struct ReadyFlagService {
     ready: Arc<AtomicBool>,
+    health_check: Option<Arc<HealthCheckHandle>>,
 }
 
 impl ReadyFlagService {
     pub fn new() -> Self {
         Self {
             ready: Arc::new(AtomicBool::new(false)),
+            health_check: None,
+        }
+    }
+
+    /// Also make the `/ready` endpoint of `health_check` succeed once
+    /// set_ready has been called.
+    pub fn with_health_check(self, health_check: Option<Arc<HealthCheckHandle>>) -> Self {
+        Self {
+            health_check,
+            ..self
         }
     }
 
     pub fn set_ready(&self) {
         self.ready.store(true, Ordering::Relaxed);
+        if let Some(health_check) = &self.health_check {
+            health_check.set_ready();
+        }
     }
 }
 
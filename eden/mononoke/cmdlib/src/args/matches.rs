@@ -31,6 +31,7 @@ use blobstore_factory::CachelibBlobstoreOptions;
 use blobstore_factory::ChaosOptions;
 use blobstore_factory::DelayOptions;
 use blobstore_factory::PackOptions;
+use blobstore_factory::PerRepoBlobstoreOptions;
 use blobstore_factory::PutBehaviour;
 use blobstore_factory::ScrubAction;
 use blobstore_factory::SrubWriteOnly;
@@ -226,10 +227,11 @@ impl<'a> MononokeMatches<'a> {
                     warm_bookmarks_cache_scuba_sample_builder,
                     config_store,
                     caching,
+                    blobstore_cache_shard_overrides: HashMap::new(),
                     observability_context,
                     runtime: runtime.handle().clone(),
                     mysql_options,
-                    blobstore_options,
+                    blobstore_options: PerRepoBlobstoreOptions::new(blobstore_options),
                     readonly_storage,
                     acl_provider,
                     rendezvous_options,
@@ -238,6 +240,8 @@ impl<'a> MononokeMatches<'a> {
                     disabled_hooks: HashMap::new(),
                     warm_bookmarks_cache_derived_data: None,
                     filter_repos: None,
+                    health_check: None,
+                    rate_limiter: None,
                 }),
                 app_data,
             },
@@ -274,7 +278,7 @@ impl<'a> MononokeMatches<'a> {
     }
 
     pub fn blobstore_options(&self) -> &BlobstoreOptions {
-        &self.environment.blobstore_options
+        &self.environment.blobstore_options.base
     }
 
     pub fn readonly_storage(&self) -> &ReadOnlyStorage {
@@ -616,6 +620,7 @@ fn parse_mysql_options(
         pool,
         pool_config,
         read_connection_type,
+        ..Default::default()
     })
 }
 
@@ -685,6 +690,7 @@ fn parse_sqlblob_mysql_options(
         pool,
         pool_config,
         read_connection_type,
+        ..Default::default()
     })
 }
 
@@ -885,7 +891,10 @@ fn parse_rendezvous_options(matches: &ArgMatches<'_>) -> Result<RendezVousOption
         .expect("A default is set, should never be None")
         .parse()
         .with_context(|| format!("Provided {} is not an integer", RENDEZVOUS_FREE_CONNECTIONS))?;
-    Ok(RendezVousOptions { free_connections })
+    Ok(RendezVousOptions {
+        free_connections,
+        ..Default::default()
+    })
 }
 
 fn parse_mononoke_megarepo_configs_options(
@@ -1055,6 +1064,7 @@ fn parse_remote_derivation_options(
     Ok(RemoteDerivationOptions {
         derive_remotely,
         address,
+        fallback_to_local: false,
     })
 }
 
@@ -359,7 +359,7 @@ async fn async_main(app: MononokeApp) -> Result<(), Error> {
     let config_store = app.config_store();
     let mysql_options = &env.mysql_options;
     let readonly_storage = env.readonly_storage;
-    let blobstore_options = &env.blobstore_options;
+    let blobstore_options = &env.blobstore_options.base;
     let storage_configs = app.storage_configs();
     let storage_config = storage_configs
         .storage
@@ -109,10 +109,10 @@ fn main(fb: FacebookInit) -> Result<()> {
 
     let ctx = CoreContext::new_for_bulk_processing(fb, logger.clone());
     let readonly_storage = &env.readonly_storage;
-    let blobstore_options = &env.blobstore_options;
 
     let repo_arg = args.repo_args.as_repo_arg();
-    let (_repo_name, repo_config) = app.repo_config(repo_arg)?;
+    let (repo_name, repo_config) = app.repo_config(repo_arg)?;
+    let blobstore_options = env.blobstore_options.resolve(&repo_name);
     let blobconfig = repo_config.storage_config.blobstore;
     let repo_prefix = repo_config.repoid.prefix();
 
@@ -88,7 +88,7 @@ fn get_shard_range(
 
 async fn get_sqlblob(app: &MononokeApp, blobstore_config: BlobConfig) -> Result<Sqlblob> {
     let config_store = app.config_store();
-    let blobstore_options = app.environment().blobstore_options.clone();
+    let blobstore_options = app.environment().blobstore_options.base.clone();
 
     Ok(make_sql_blobstore(
         app.fb,
@@ -542,7 +542,7 @@ pub fn create_commit_syncer_lease(
     fb: FacebookInit,
     caching: Caching,
 ) -> Result<Arc<dyn LeaseOps>, Error> {
-    if let Caching::Enabled(_) = caching {
+    if caching.shared_enabled() {
         Ok(Arc::new(MemcacheOps::new(fb, "x-repo-sync-lease", "")?))
     } else {
         Ok(Arc::new(InProcessLease::new()))
@@ -0,0 +1,73 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+extern crate proc_macro;
+
+use quote::quote;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+use syn::parse_macro_input;
+use syn::Expr;
+use syn::Ident;
+use syn::LitStr;
+use syn::Token;
+use syn::Type;
+
+struct ConfigKeyInput {
+    store: Expr,
+    key: LitStr,
+    ty: Type,
+}
+
+impl Parse for ConfigKeyInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let store = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let key = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let ty = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { store, key, ty })
+    }
+}
+
+#[proc_macro]
+// This proc macro accepts a config store, a config key string literal and a
+// type, and returns a `ConfigHandle` of that type for the key. The key is
+// looked up as a constant in `config_keys::keys`, named after the key in
+// upper case with every non-alphanumeric character replaced by `_`, so an
+// unknown key or a type that doesn't match the schema fails to compile.
+pub fn config_key(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ConfigKeyInput { store, key, ty } = parse_macro_input!(input as ConfigKeyInput);
+
+    let key_name = key.value();
+    if !key_name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return syn::Error::new(key.span(), "config key must start with a letter")
+            .to_compile_error()
+            .into();
+    }
+    let const_name: String = key_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let const_ident = Ident::new(&const_name, key.span());
+
+    let expanded = quote! {
+        {
+            let key: &'static ::config_keys::ConfigKey<#ty> = &::config_keys::keys::#const_ident;
+            key.handle(&#store)
+        }
+    };
+
+    expanded.into()
+}
@@ -0,0 +1,196 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use cached_config::ConfigHandle;
+use cached_config::ConfigStore;
+use cached_config::Entity;
+use cached_config::ModificationTime;
+use cached_config::Source;
+use serde_json::Value;
+
+/// A config source whose values can be overridden by environment variables,
+/// for local testing. Values that are not overridden come from an inner
+/// `ConfigStore`.
+///
+/// The value of the key `mononoke.cache.ttl` is taken from the variable
+/// `<PREFIX>_MONONOKE_CACHE_TTL` if it is set, or `MONONOKE_CACHE_TTL` if the
+/// prefix is empty. The value can be JSON, a duration like `60s` or `1h 30m`,
+/// or else a plain string. Strings that would parse as JSON or as a
+/// duration need to be quoted, like `"\"60s\""`.
+///
+/// Overridden values never change, even if the variable is modified later.
+pub struct EnvOverrideSource {
+    inner: ConfigStore,
+    prefix: String,
+    /// Handles of the values taken from the inner store, with the value that
+    /// was last returned for each, to know when to refresh them.
+    inner_values: Mutex<HashMap<String, (ConfigHandle<Value>, Arc<Value>)>>,
+    mod_time: AtomicU64,
+}
+
+impl EnvOverrideSource {
+    pub fn new(inner: ConfigStore, prefix: &str) -> Self {
+        Self {
+            inner,
+            prefix: prefix.to_string(),
+            inner_values: Mutex::new(HashMap::new()),
+            mod_time: AtomicU64::new(0),
+        }
+    }
+
+    /// Name of the environment variable overriding `key`.
+    pub fn env_var(&self, key: &str) -> String {
+        let key: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        if self.prefix.is_empty() {
+            key
+        } else {
+            format!("{}_{}", self.prefix, key)
+        }
+    }
+
+    fn inner_value(&self, key: &str) -> Result<Arc<Value>> {
+        let handle = self.inner.get_config_handle::<Value>(key.to_string())?;
+        let value = handle.get();
+        self.inner_values
+            .lock()
+            .expect("lock poisoned")
+            .insert(key.to_string(), (handle, value.clone()));
+        Ok(value)
+    }
+}
+
+impl Source for EnvOverrideSource {
+    fn config_for_key(&self, key: &str) -> Result<Option<Entity>> {
+        let var = self.env_var(key);
+        let value = match env::var(&var) {
+            Ok(value) => parse_override(&value),
+            Err(env::VarError::NotPresent) => self.inner_value(key)?.as_ref().clone(),
+            Err(e) => return Err(anyhow!("invalid value for {}: {}", var, e)),
+        };
+        Ok(Some(Entity {
+            contents: value.to_string(),
+            mod_time: ModificationTime::UnixTimestamp(
+                self.mod_time.fetch_add(1, Ordering::Relaxed),
+            ),
+            version: None,
+        }))
+    }
+
+    fn paths_to_refresh<'a>(&self, paths: &mut dyn Iterator<Item = &'a String>) -> Vec<&'a String> {
+        // Only values from the inner store can change.
+        let inner_values = self.inner_values.lock().expect("lock poisoned");
+        paths
+            .filter(|path| match inner_values.get(path.as_str()) {
+                Some((handle, value)) => !Arc::ptr_eq(&handle.get(), value),
+                None => false,
+            })
+            .collect()
+    }
+}
+
+/// Interpret `value` as JSON, a duration, or else a plain string.
+fn parse_override(value: &str) -> Value {
+    if let Ok(json) = serde_json::from_str(value) {
+        return json;
+    }
+    if let Ok(duration) = humantime::parse_duration(value) {
+        if let Ok(json) = serde_json::to_value(duration) {
+            return json;
+        }
+    }
+    Value::String(value.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use cached_config::TestSource;
+
+    use super::*;
+    use crate::ConfigStoreExt;
+
+    fn store(configs: &[(&str, &str)]) -> ConfigStore {
+        let source = Arc::new(TestSource::new());
+        for (key, value) in configs {
+            source.insert_config(key, value, ModificationTime::UnixTimestamp(0));
+        }
+        ConfigStore::new(source, None, None)
+    }
+
+    #[test]
+    fn test_env_var() {
+        let source = EnvOverrideSource::new(store(&[]), "TEST");
+        assert_eq!(
+            source.env_var("mononoke.cache.ttl"),
+            "TEST_MONONOKE_CACHE_TTL"
+        );
+        assert_eq!(source.env_var("scm/mononoke/foo"), "TEST_SCM_MONONOKE_FOO");
+        let source = EnvOverrideSource::new(store(&[]), "");
+        assert_eq!(source.env_var("mononoke.cache.ttl"), "MONONOKE_CACHE_TTL");
+    }
+
+    #[test]
+    fn test_env_override() -> Result<()> {
+        // Each test uses its own prefix, as the environment is shared by all
+        // tests in the process.
+        let store = store(&[
+            ("a.duration", r#"{"secs": 1, "nanos": 0}"#),
+            ("a.int", "1"),
+            ("a.bool", "false"),
+            ("a.string", r#""inner""#),
+            ("a.quoted", r#""inner""#),
+            ("a.not_overridden", "1"),
+        ])
+        .with_env_override_prefix("TEST_ENV_OVERRIDE");
+        env::set_var("TEST_ENV_OVERRIDE_A_DURATION", "60s");
+        env::set_var("TEST_ENV_OVERRIDE_A_INT", "42");
+        env::set_var("TEST_ENV_OVERRIDE_A_BOOL", "true");
+        env::set_var("TEST_ENV_OVERRIDE_A_STRING", "overridden");
+        env::set_var("TEST_ENV_OVERRIDE_A_QUOTED", r#""10s""#);
+
+        let duration = store.get_config_handle::<Duration>("a.duration".to_string())?;
+        assert_eq!(*duration.get(), Duration::from_secs(60));
+        let int = store.get_config_handle::<u64>("a.int".to_string())?;
+        assert_eq!(*int.get(), 42);
+        let bool = store.get_config_handle::<bool>("a.bool".to_string())?;
+        assert!(*bool.get());
+        let string = store.get_config_handle::<String>("a.string".to_string())?;
+        assert_eq!(*string.get(), "overridden");
+        let quoted = store.get_config_handle::<String>("a.quoted".to_string())?;
+        assert_eq!(*quoted.get(), "10s");
+        let not_overridden = store.get_config_handle::<u64>("a.not_overridden".to_string())?;
+        assert_eq!(*not_overridden.get(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_override_invalid() {
+        let store = store(&[("a.int", "1")]).with_env_override_prefix("TEST_ENV_OVERRIDE_INVALID");
+        env::set_var("TEST_ENV_OVERRIDE_INVALID_A_INT", "sixty");
+        assert!(store.get_config_handle::<u64>("a.int".to_string()).is_err());
+    }
+}
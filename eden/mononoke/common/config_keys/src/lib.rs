@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Typed config keys for `ConfigStore`.
+//!
+//! Every key known to Mononoke is declared in [`keys`] along with the type of
+//! its value. Use [`config_key!`] to get a typed handle for a key: using an
+//! undeclared key, or a type that doesn't match the declaration, fails to
+//! compile.
+//!
+//! ```ignore
+//! let handle: ConfigHandle<Duration> =
+//!     config_key!(config_store, "mononoke.cache.ttl", Duration)?;
+//! ```
+//!
+//! [`ConfigStoreExt`] adds helpers to `ConfigStore`, such as subscribing to
+//! changes of a config, or overriding configs with environment variables.
+
+// Allow `::config_keys` paths generated by the macro to resolve within this
+// crate too.
+extern crate self as config_keys;
+
+mod env_override;
+mod subscribe;
+
+use std::any::type_name;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use cached_config::ConfigHandle;
+use cached_config::ConfigStore;
+pub use config_keys_derive::config_key;
+pub use env_override::EnvOverrideSource;
+use serde::de::DeserializeOwned;
+pub use subscribe::ConfigChangeReceiver;
+pub use subscribe::DEFAULT_POLL_INTERVAL;
+
+/// A config key whose value is of type `T`.
+pub struct ConfigKey<T> {
+    name: &'static str,
+    description: &'static str,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> ConfigKey<T> {
+    pub const fn new(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The key, as passed to `ConfigStore`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Human-readable description of what the config controls.
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Name of the type of the value.
+    pub fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+}
+
+impl<T> ConfigKey<T>
+where
+    T: Send + Sync + DeserializeOwned + 'static,
+{
+    /// Get a handle to the value of this key in `config_store`.
+    pub fn handle(&self, config_store: &ConfigStore) -> Result<ConfigHandle<T>> {
+        config_store.get_config_handle(self.name.to_string())
+    }
+}
+
+impl<T> fmt::Debug for ConfigKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConfigKey")
+            .field("name", &self.name)
+            .field("type", &self.type_name())
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Extension methods for `ConfigStore`.
+pub trait ConfigStoreExt {
+    /// Subscribe to changes of the config at `key`, checking for changes
+    /// every [`DEFAULT_POLL_INTERVAL`].
+    ///
+    /// Must be called from within a tokio runtime.
+    fn subscribe<T>(&self, key: &str) -> Result<ConfigChangeReceiver<T>>
+    where
+        T: Send + Sync + DeserializeOwned + Clone + PartialEq + 'static,
+    {
+        self.subscribe_with_interval(key, DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Subscribe to changes of the config at `key`, checking for changes
+    /// every `poll_interval`. Receivers are only notified when the parsed
+    /// value differs from the previous one.
+    ///
+    /// Must be called from within a tokio runtime. The polling stops once
+    /// the receiver is dropped.
+    fn subscribe_with_interval<T>(
+        &self,
+        key: &str,
+        poll_interval: Duration,
+    ) -> Result<ConfigChangeReceiver<T>>
+    where
+        T: Send + Sync + DeserializeOwned + Clone + PartialEq + 'static;
+
+    /// Wrap this store so that its values can be overridden by environment
+    /// variables named after the keys, prefixed by `prefix`. See
+    /// [`EnvOverrideSource`].
+    fn with_env_override_prefix(self, prefix: &str) -> ConfigStore;
+}
+
+impl ConfigStoreExt for ConfigStore {
+    fn subscribe_with_interval<T>(
+        &self,
+        key: &str,
+        poll_interval: Duration,
+    ) -> Result<ConfigChangeReceiver<T>>
+    where
+        T: Send + Sync + DeserializeOwned + Clone + PartialEq + 'static,
+    {
+        subscribe::subscribe(self, key, poll_interval)
+    }
+
+    fn with_env_override_prefix(self, prefix: &str) -> ConfigStore {
+        ConfigStore::new(
+            Arc::new(EnvOverrideSource::new(self, prefix)),
+            DEFAULT_POLL_INTERVAL,
+            None,
+        )
+    }
+}
+
+/// The known config keys.
+///
+/// Constants are named after the key in upper case, with every
+/// non-alphanumeric character replaced by `_`, which is what
+/// [`config_key!`] expects.
+pub mod keys {
+    use std::time::Duration;
+
+    use super::ConfigKey;
+
+    pub const MONONOKE_CACHE_TTL: ConfigKey<Duration> = ConfigKey::new(
+        "mononoke.cache.ttl",
+        "How long entries are kept in Mononoke caches",
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use cached_config::ModificationTime;
+    use cached_config::TestSource;
+
+    use super::*;
+
+    #[test]
+    fn test_config_key_roundtrip() -> Result<()> {
+        let ttl = Duration::from_secs(42);
+        let source = Arc::new(TestSource::new());
+        source.insert_config(
+            keys::MONONOKE_CACHE_TTL.name(),
+            &serde_json::to_string(&ttl)?,
+            ModificationTime::UnixTimestamp(0),
+        );
+        let config_store = ConfigStore::new(source, None, None);
+
+        let handle: ConfigHandle<Duration> =
+            config_key!(config_store, "mononoke.cache.ttl", Duration)?;
+        assert_eq!(*handle.get(), ttl);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_key_metadata() {
+        let key = &keys::MONONOKE_CACHE_TTL;
+        assert_eq!(key.name(), "mononoke.cache.ttl");
+        assert_eq!(key.type_name(), "core::time::Duration");
+        assert!(!key.description().is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use anyhow::Result;
+use cached_config::ConfigStore;
+use serde::de::DeserializeOwned;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+/// How often subscriptions check for changes, unless specified otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Receives the new value of a config whenever it changes.
+pub struct ConfigChangeReceiver<T> {
+    receiver: watch::Receiver<T>,
+}
+
+impl<T: Clone> ConfigChangeReceiver<T> {
+    /// The latest value of the config.
+    pub fn current(&self) -> T {
+        self.receiver.borrow().clone()
+    }
+
+    /// Wait until the value of the config changes from the one last seen by
+    /// this receiver.
+    ///
+    /// Only fails if the task polling for changes has stopped.
+    pub async fn changed(&mut self) -> Result<()> {
+        self.receiver.changed().await?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> watch::Receiver<T> {
+        self.receiver
+    }
+}
+
+pub(crate) fn subscribe<T>(
+    config_store: &ConfigStore,
+    key: &str,
+    poll_interval: Duration,
+) -> Result<ConfigChangeReceiver<T>>
+where
+    T: Send + Sync + DeserializeOwned + Clone + PartialEq + 'static,
+{
+    let handle = config_store.get_config_handle::<T>(key.to_string())?;
+    let (sender, receiver) = watch::channel(T::clone(&handle.get()));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                // Every receiver was dropped.
+                _ = sender.closed() => break,
+                _ = interval.tick() => {}
+            }
+            let value = handle.get();
+            sender.send_if_modified(|current| {
+                if *current == *value {
+                    false
+                } else {
+                    *current = T::clone(&value);
+                    true
+                }
+            });
+        }
+    });
+
+    Ok(ConfigChangeReceiver { receiver })
+}
@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Tests of config subscriptions against a mock config source.
+
+#![cfg_attr(not(fbcode_build), allow(unused_crate_dependencies))]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use cached_config::ConfigStore;
+use cached_config::ModificationTime;
+use cached_config::TestSource;
+use config_keys::ConfigStoreExt;
+use fbinit::FacebookInit;
+use serde::Deserialize;
+use serde::Serialize;
+
+const KEY: &str = "mononoke/test/subscribe";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct TestConfig {
+    value: u64,
+    name: String,
+}
+
+fn set_config(source: &TestSource, config: &TestConfig, mod_time: u64) -> Result<()> {
+    source.insert_config(
+        KEY,
+        &serde_json::to_string(config)?,
+        ModificationTime::UnixTimestamp(mod_time),
+    );
+    source.insert_to_refresh(KEY.to_string());
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_subscribe(_fb: FacebookInit) -> Result<()> {
+    let source = Arc::new(TestSource::new());
+    let first = TestConfig {
+        value: 1,
+        name: "first".to_string(),
+    };
+    set_config(&source, &first, 0)?;
+    let config_store = ConfigStore::new(source.clone(), Duration::from_millis(2), None);
+
+    let mut receiver = config_store.subscribe_with_interval::<TestConfig>(KEY, POLL_INTERVAL)?;
+    assert_eq!(receiver.current(), first);
+
+    let second = TestConfig {
+        value: 2,
+        name: "second".to_string(),
+    };
+    set_config(&source, &second, 1)?;
+    tokio::time::timeout(2 * POLL_INTERVAL, receiver.changed()).await??;
+    assert_eq!(receiver.current(), second);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_subscribe_ignores_unchanged_value(_fb: FacebookInit) -> Result<()> {
+    let source = Arc::new(TestSource::new());
+    let config = TestConfig {
+        value: 1,
+        name: "config".to_string(),
+    };
+    set_config(&source, &config, 0)?;
+    let config_store = ConfigStore::new(source.clone(), Duration::from_millis(2), None);
+
+    let mut receiver = config_store.subscribe_with_interval::<TestConfig>(KEY, POLL_INTERVAL)?;
+
+    // The source is updated, but the parsed value is the same.
+    set_config(&source, &config, 1)?;
+    assert!(
+        tokio::time::timeout(3 * POLL_INTERVAL, receiver.changed())
+            .await
+            .is_err()
+    );
+    assert_eq!(receiver.current(), config);
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Context;
+use anyhow::Error;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+use hyper::StatusCode;
+use tokio::sync::oneshot;
+
+#[derive(Default)]
+struct HealthState {
+    draining: AtomicBool,
+    ready: AtomicBool,
+}
+
+/// Controls the responses of a health check server. The server stops when
+/// the handle is dropped.
+pub struct HealthCheckHandle {
+    state: Arc<HealthState>,
+    local_addr: SocketAddr,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl HealthCheckHandle {
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Make `/health` fail, so that load balancers stop sending new requests
+    /// to this server.
+    pub fn set_draining(&self) {
+        self.state.draining.store(true, Ordering::Relaxed);
+    }
+
+    /// Make `/ready` succeed, once the server is ready to handle requests.
+    pub fn set_ready(&self) {
+        self.state.ready.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.state.draining.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.state.ready.load(Ordering::Relaxed)
+    }
+}
+
+/// Start serving health checks on `bind_addr`. Must be called from within a
+/// tokio runtime.
+pub(crate) fn serve(bind_addr: SocketAddr) -> Result<HealthCheckHandle, Error> {
+    let state = Arc::new(HealthState::default());
+
+    let make_service = make_service_fn({
+        let state = state.clone();
+        move |_conn| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let response = respond(&state, &req);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        }
+    });
+    let server = Server::try_bind(&bind_addr)
+        .with_context(|| format!("Failed to bind health check server to {}", bind_addr))?
+        .serve(make_service);
+    let local_addr = server.local_addr();
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    tokio::spawn(server.with_graceful_shutdown(async move {
+        // Either shutdown was requested, or the handle was dropped.
+        let _ = shutdown_rx.await;
+    }));
+
+    Ok(HealthCheckHandle {
+        state,
+        local_addr,
+        _shutdown: shutdown_tx,
+    })
+}
+
+fn respond(state: &HealthState, req: &Request<Body>) -> Response<Body> {
+    let (status, body) = match req.uri().path() {
+        "/health" if state.draining.load(Ordering::Relaxed) => {
+            (StatusCode::SERVICE_UNAVAILABLE, "draining")
+        }
+        "/health" => (StatusCode::OK, "ok"),
+        "/ready" if state.ready.load(Ordering::Relaxed) => (StatusCode::OK, "ready"),
+        "/ready" => (StatusCode::SERVICE_UNAVAILABLE, "not ready"),
+        _ => (StatusCode::NOT_FOUND, "not found"),
+    };
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::Client;
+
+    use super::*;
+    use crate::RendezVous;
+
+    async fn get(handle: &HealthCheckHandle, path: &str) -> Result<StatusCode, Error> {
+        let uri = format!("http://{}{}", handle.local_addr(), path).parse()?;
+        Ok(Client::new().get(uri).await?.status())
+    }
+
+    #[tokio::test]
+    async fn test_health_check_server() -> Result<(), Error> {
+        let handle = RendezVous::health_check_server("127.0.0.1:0".parse()?)?;
+
+        assert_eq!(get(&handle, "/health").await?, StatusCode::OK);
+        assert_eq!(
+            get(&handle, "/ready").await?,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(get(&handle, "/other").await?, StatusCode::NOT_FOUND);
+
+        handle.set_ready();
+        assert!(handle.is_ready());
+        assert_eq!(get(&handle, "/ready").await?, StatusCode::OK);
+        assert_eq!(get(&handle, "/health").await?, StatusCode::OK);
+
+        handle.set_draining();
+        assert!(handle.is_draining());
+        assert_eq!(
+            get(&handle, "/health").await?,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(get(&handle, "/ready").await?, StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_check_server_stops_on_drop() -> Result<(), Error> {
+        let handle = RendezVous::health_check_server("127.0.0.1:0".parse()?)?;
+        let addr = handle.local_addr();
+        drop(handle);
+
+        // Wait for the server to stop listening.
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(addr).await.is_err() {
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("health check server still running");
+    }
+}
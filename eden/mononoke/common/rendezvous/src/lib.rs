@@ -5,8 +5,13 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
 use clap::Args;
 
+mod health_check;
 mod multi_rendez_vous;
 mod rendez_vous;
 mod rendez_vous_stats;
@@ -15,6 +20,7 @@ mod tunables;
 #[cfg(test)]
 mod test;
 
+pub use health_check::HealthCheckHandle;
 pub use multi_rendez_vous::MultiRendezVous;
 pub use multi_rendez_vous::MultiRendezVousController;
 pub use rendez_vous::RendezVous;
@@ -25,15 +31,51 @@ pub use crate::tunables::ConfigurableRendezVousController;
 pub use crate::tunables::TunablesMultiRendezVousController;
 pub use crate::tunables::TunablesRendezVousController;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct RendezVousOptions {
     pub free_connections: usize,
+    /// Limits for the queries sent to specific endpoints, keyed by the name
+    /// of the endpoint. Endpoints are named after the stats of their
+    /// RendezVous, e.g. `changesets.read`.
+    pub per_endpoint: HashMap<String, RendezVousEndpointOptions>,
 }
 
 impl RendezVousOptions {
     pub fn for_test() -> Self {
         Self {
             free_connections: 0,
+            per_endpoint: HashMap::new(),
+        }
+    }
+
+    /// The limits for the queries sent to `endpoint_name`. Endpoints without
+    /// an override have no limits.
+    pub fn for_endpoint(&self, endpoint_name: &str) -> RendezVousEndpointOptions {
+        self.per_endpoint
+            .get(endpoint_name)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Limits for the queries a RendezVous sends to an endpoint.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RendezVousEndpointOptions {
+    /// How many queries can be in flight at the same time.
+    pub max_concurrent: usize,
+    /// How many queries can wait for one of the in-flight ones to complete.
+    /// Queries beyond that fail immediately.
+    pub queue_depth: usize,
+    /// How long a query can wait before failing.
+    pub timeout: Duration,
+}
+
+impl Default for RendezVousEndpointOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent: tokio::sync::Semaphore::MAX_PERMITS,
+            queue_depth: usize::MAX,
+            timeout: Duration::MAX,
         }
     }
 }
@@ -44,12 +86,17 @@ pub struct RendezVousArgs {
     /// How many concurrent connections to allow before batching kicks in
     #[clap(long, default_value = "5")]
     pub rendezvous_free_connections: usize,
+
+    /// Address to serve `/health` and `/ready` on, for load balancers
+    #[clap(long)]
+    pub health_check_addr: Option<SocketAddr>,
 }
 
 impl From<RendezVousArgs> for RendezVousOptions {
     fn from(args: RendezVousArgs) -> Self {
         RendezVousOptions {
             free_connections: args.rendezvous_free_connections,
+            per_endpoint: HashMap::new(),
         }
     }
 }
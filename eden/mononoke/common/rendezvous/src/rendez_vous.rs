@@ -8,9 +8,13 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::hash::Hash;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use anyhow::anyhow;
 use anyhow::Error;
 use fbinit::FacebookInit;
 use futures::future::BoxFuture;
@@ -23,7 +27,12 @@ use shared_error::anyhow::IntoSharedError;
 use shared_error::anyhow::SharedError;
 use time_ext::DurationExt;
 use tokio::sync::Notify;
+use tokio::sync::Semaphore;
+use tokio::sync::SemaphorePermit;
 
+use crate::health_check;
+use crate::HealthCheckHandle;
+use crate::RendezVousEndpointOptions;
 use crate::RendezVousStats;
 use crate::TunablesRendezVousController;
 
@@ -55,6 +64,59 @@ struct RendezVousInner<K, V, C> {
     >,
     controller: C,
     stats: Arc<RendezVousStats>,
+    limiter: Option<EndpointLimiter>,
+}
+
+/// Limits the number of queries in flight to an endpoint.
+struct EndpointLimiter {
+    semaphore: Semaphore,
+    queued: AtomicUsize,
+    opts: RendezVousEndpointOptions,
+}
+
+impl EndpointLimiter {
+    fn new(opts: RendezVousEndpointOptions) -> Self {
+        Self {
+            semaphore: Semaphore::new(opts.max_concurrent),
+            queued: AtomicUsize::new(0),
+            opts,
+        }
+    }
+
+    /// Wait until a query can be sent. The query must be sent while holding
+    /// the returned permit.
+    async fn acquire(&self) -> Result<SemaphorePermit<'_>, Error> {
+        if let Ok(permit) = self.semaphore.try_acquire() {
+            return Ok(permit);
+        }
+
+        if self.queued.fetch_add(1, Ordering::Relaxed) >= self.opts.queue_depth {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            return Err(anyhow!(
+                "Too many queued queries: {} queries are already waiting",
+                self.opts.queue_depth
+            ));
+        }
+        // Leave the queue even if this future is dropped.
+        let _queued = QueuedGuard(&self.queued);
+
+        match tokio::time::timeout(self.opts.timeout, self.semaphore.acquire()).await {
+            Ok(permit) => Ok(permit.expect("Semaphore is never closed")),
+            Err(_) => Err(anyhow!(
+                "Timed out after {:?} waiting for one of {} in-flight queries to complete",
+                self.opts.timeout,
+                self.opts.max_concurrent
+            )),
+        }
+    }
+}
+
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 /// RendezVous is a difficultly named library which can be used to batch together and deduplicate queries to a backend.
@@ -87,16 +149,49 @@ impl<K, V, C> Clone for RendezVous<K, V, C> {
 
 impl<K, V, C> RendezVous<K, V, C> {
     pub fn new(controller: C, stats: Arc<RendezVousStats>) -> Self {
+        Self::new_inner(controller, stats, None)
+    }
+
+    /// Same as `new`, but the queries dispatched to the endpoint are
+    /// subject to the limits of `endpoint_opts`. Queries exceeding the
+    /// concurrency limit wait for another one to complete.
+    pub fn new_with_endpoint_options(
+        controller: C,
+        stats: Arc<RendezVousStats>,
+        endpoint_opts: RendezVousEndpointOptions,
+    ) -> Self {
+        Self::new_inner(controller, stats, Some(EndpointLimiter::new(endpoint_opts)))
+    }
+
+    fn new_inner(
+        controller: C,
+        stats: Arc<RendezVousStats>,
+        limiter: Option<EndpointLimiter>,
+    ) -> Self {
         Self {
             inner: Arc::new(RendezVousInner {
                 staging: Mutex::new(None),
                 controller,
                 stats,
+                limiter,
             }),
         }
     }
 }
 
+impl RendezVous<(), ()> {
+    /// Start an HTTP server on `bind_addr` for load balancers to check the
+    /// state of this server:
+    /// - `/health` succeeds until the handle is set to draining.
+    /// - `/ready` succeeds once the handle is set to ready.
+    ///
+    /// Must be called from within a tokio runtime. The server stops when the
+    /// handle is dropped.
+    pub fn health_check_server(bind_addr: SocketAddr) -> Result<HealthCheckHandle, Error> {
+        health_check::serve(bind_addr)
+    }
+}
+
 impl<K, V, C> RendezVous<K, V, C>
 where
     K: Clone + Eq + Hash + Send + Sync + 'static,
@@ -169,7 +264,14 @@ where
                                 .take()
                                 .expect("Staging cannot be empty if a task was dispatched");
 
-                            let ret = dispatch_with_stats(fb, f1, keys, &inner.stats).await?;
+                            let ret = dispatch_with_stats(
+                                fb,
+                                f1,
+                                keys,
+                                &inner.stats,
+                                inner.limiter.as_ref(),
+                            )
+                            .await?;
 
                             std::mem::drop(token);
 
@@ -237,7 +339,9 @@ where
         async move {
             inner.stats.dispatch_no_batch.add_value(1);
 
-            let mut ret = dispatch_with_stats(fb, f0(), keys.clone(), &inner.stats).await?;
+            let mut ret =
+                dispatch_with_stats(fb, f0(), keys.clone(), &inner.stats, inner.limiter.as_ref())
+                    .await?;
 
             let ret = keys
                 .into_iter()
@@ -257,11 +361,17 @@ async fn dispatch_with_stats<K, V, F1, Fut>(
     f1: F1,
     keys: HashSet<K>,
     rdv_stats: &RendezVousStats,
+    limiter: Option<&EndpointLimiter>,
 ) -> Result<HashMap<K, V>, Error>
 where
     F1: FnOnce(HashSet<K>) -> Fut + Send + 'static,
     Fut: Future<Output = Result<HashMap<K, V>, Error>>,
 {
+    let _permit = match limiter {
+        Some(limiter) => Some(limiter.acquire().await?),
+        None => None,
+    };
+
     rdv_stats.keys_dispatched.add_value(keys.len() as i64);
 
     rdv_stats.inflight.increment_value(fb, 1);
@@ -10,6 +10,7 @@ use std::collections::HashSet;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Error;
 use fbinit::FacebookInit;
@@ -18,9 +19,12 @@ use futures::future::FutureExt;
 use maplit::hashmap;
 use maplit::hashset;
 use tokio::sync::Notify;
+use tokio::sync::Semaphore;
 
 pub use crate::RendezVous;
 pub use crate::RendezVousController;
+pub use crate::RendezVousEndpointOptions;
+pub use crate::RendezVousOptions;
 pub use crate::RendezVousStats;
 
 #[derive(Clone)]
@@ -182,3 +186,107 @@ async fn test_unbatched(fb: FacebookInit) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Dispatch queries for `key` that block until `gate` has a permit.
+fn dispatch_blocked(
+    fb: FacebookInit,
+    rdv: &RendezVous<u64, u64, MockController>,
+    key: u64,
+    started: &Arc<AtomicUsize>,
+    gate: &Arc<Semaphore>,
+) -> tokio::task::JoinHandle<Result<HashMap<u64, Option<u64>>, Error>> {
+    let started = started.clone();
+    let gate = gate.clone();
+    tokio::spawn(rdv.dispatch(fb, hashset! { key }, move || {
+        move |keys: HashSet<u64>| {
+            async move {
+                started.fetch_add(1, Ordering::Relaxed);
+                gate.acquire().await?.forget();
+                Ok(keys.into_iter().map(|k| (k, k)).collect())
+            }
+            .boxed()
+        }
+    }))
+}
+
+async fn wait_until_started(started: &AtomicUsize, count: usize) {
+    while started.load(Ordering::Relaxed) < count {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+#[fbinit::test]
+async fn test_endpoint_limit(fb: FacebookInit) -> Result<(), Error> {
+    let mut opts = RendezVousOptions::for_test();
+    opts.per_endpoint.insert(
+        "limited".to_string(),
+        RendezVousEndpointOptions {
+            max_concurrent: 5,
+            queue_depth: 10,
+            timeout: Duration::from_secs(60),
+        },
+    );
+    assert_eq!(
+        opts.for_endpoint("other"),
+        RendezVousEndpointOptions::default()
+    );
+
+    // Nothing is batched, so that every key is a separate query.
+    let rdv = RendezVous::new_with_endpoint_options(
+        MockController::new(0),
+        stats(),
+        opts.for_endpoint("limited"),
+    );
+    let started = Arc::new(AtomicUsize::new(0));
+    let gate = Arc::new(Semaphore::new(0));
+
+    let handles: Vec<_> = (0..6)
+        .map(|key| dispatch_blocked(fb, &rdv, key, &started, &gate))
+        .collect();
+
+    // The 6th query waits for one of the first 5 to complete.
+    tokio::time::timeout(Duration::from_secs(10), wait_until_started(&started, 5)).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(started.load(Ordering::Relaxed), 5);
+
+    gate.add_permits(1);
+    tokio::time::timeout(Duration::from_secs(10), wait_until_started(&started, 6)).await?;
+
+    gate.add_permits(5);
+    for (key, handle) in handles.into_iter().enumerate() {
+        let key = key as u64;
+        assert_eq!(handle.await??, hashmap! { key => Some(key) });
+    }
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_endpoint_queue_full(fb: FacebookInit) -> Result<(), Error> {
+    let rdv = RendezVous::new_with_endpoint_options(
+        MockController::new(0),
+        stats(),
+        RendezVousEndpointOptions {
+            max_concurrent: 1,
+            queue_depth: 0,
+            timeout: Duration::from_secs(60),
+        },
+    );
+    let started = Arc::new(AtomicUsize::new(0));
+    let gate = Arc::new(Semaphore::new(0));
+
+    let first = dispatch_blocked(fb, &rdv, 1, &started, &gate);
+    tokio::time::timeout(Duration::from_secs(10), wait_until_started(&started, 1)).await?;
+
+    // There is no room to wait for the first query to complete.
+    assert!(
+        dispatch_blocked(fb, &rdv, 2, &started, &gate)
+            .await?
+            .is_err()
+    );
+
+    gate.add_permits(1);
+    assert_eq!(first.await??, hashmap! { 1 => Some(1) });
+
+    Ok(())
+}
@@ -16,7 +16,7 @@ use crate::MultiRendezVousController;
 use crate::RendezVousController;
 use crate::RendezVousOptions;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct TunablesMultiRendezVousController {
     opts: RendezVousOptions,
 }
@@ -31,7 +31,7 @@ impl MultiRendezVousController for TunablesMultiRendezVousController {
     type Controller = TunablesRendezVousController;
 
     fn new_controller(&self) -> Self::Controller {
-        TunablesRendezVousController::new(self.opts)
+        TunablesRendezVousController::new(self.opts.clone())
     }
 }
 
@@ -0,0 +1,225 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::facebook::DeadlockRetryOptions;
+use crate::DeadlockRetryError;
+
+/// MySQL error code for a deadlock, after which the transaction should be
+/// restarted.
+pub const ER_LOCK_DEADLOCK: u32 = 1213;
+
+/// Start of the message of MySQL deadlock errors, for clients that don't
+/// expose the error code.
+#[cfg(not(fbcode_build))]
+const ER_LOCK_DEADLOCK_MESSAGE: &str = "Deadlock found when trying to get lock";
+
+/// Run a transaction, restarting it as configured by `options` whenever it
+/// fails because of a MySQL deadlock.
+///
+/// `transaction` is called with the attempt number, starting at 1, and should
+/// start, run and commit the whole transaction. Once the retries are exhausted,
+/// fails with `DeadlockRetryError`. Other errors are returned as they are.
+pub async fn with_deadlock_retry<T, Fut>(
+    options: &DeadlockRetryOptions,
+    transaction: impl FnMut(u32) -> Fut,
+) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    retry_transaction(options, is_deadlock, transaction).await
+}
+
+async fn retry_transaction<T, Fut>(
+    options: &DeadlockRetryOptions,
+    is_deadlock: impl Fn(&anyhow::Error) -> bool,
+    mut transaction: impl FnMut(u32) -> Fut,
+) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match transaction(attempt).await {
+            Ok(res) => return Ok(res),
+            Err(err) if !is_deadlock(&err) => return Err(err),
+            Err(err) if attempt > options.max_retries => {
+                return Err(DeadlockRetryError {
+                    attempts: attempt,
+                    last_error: err,
+                }
+                .into());
+            }
+            Err(_) => {
+                tokio::time::sleep(backoff(options, attempt)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Delay before retrying after the given attempt: exponential up to
+/// `max_delay`, with up to half of it randomly shaved off so that deadlocked
+/// transactions don't retry in lockstep.
+fn backoff(options: &DeadlockRetryOptions, attempt: u32) -> Duration {
+    let delay = options
+        .base_delay
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(options.max_delay);
+    delay.mul_f64(1.0 - rand::random::<f64>() / 2.0)
+}
+
+fn is_deadlock(err: &anyhow::Error) -> bool {
+    err.chain().any(is_mysql_deadlock)
+}
+
+#[cfg(fbcode_build)]
+fn is_mysql_deadlock(err: &(dyn std::error::Error + 'static)) -> bool {
+    use mysql_client::MysqlError;
+    use MysqlError::*;
+    match err.downcast_ref::<MysqlError>() {
+        Some(ConnectionOperationError { mysql_errno, .. })
+        | Some(QueryResultError { mysql_errno, .. }) => *mysql_errno == ER_LOCK_DEADLOCK,
+        _ => false,
+    }
+}
+
+#[cfg(not(fbcode_build))]
+fn is_mysql_deadlock(err: &(dyn std::error::Error + 'static)) -> bool {
+    let message = err.to_string();
+    message.contains(ER_LOCK_DEADLOCK_MESSAGE)
+        || message.contains(&format!("ERROR {}", ER_LOCK_DEADLOCK))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+
+    use thiserror::Error;
+
+    use super::*;
+
+    #[derive(Debug, Error)]
+    #[error("MySQL error {0}")]
+    struct MockMysqlError(u32);
+
+    fn is_mock_deadlock(err: &anyhow::Error) -> bool {
+        match err.downcast_ref::<MockMysqlError>() {
+            Some(MockMysqlError(errno)) => *errno == ER_LOCK_DEADLOCK,
+            None => false,
+        }
+    }
+
+    /// Connection whose transactions fail with the given errors before
+    /// succeeding.
+    struct MockConnection {
+        errors: Vec<u32>,
+        attempts: AtomicU32,
+    }
+
+    impl MockConnection {
+        fn new(errors: Vec<u32>) -> Self {
+            Self {
+                errors,
+                attempts: AtomicU32::new(0),
+            }
+        }
+
+        async fn transaction(&self) -> Result<&'static str> {
+            let attempt = self.attempts.fetch_add(1, Ordering::Relaxed) as usize;
+            match self.errors.get(attempt) {
+                Some(errno) => Err(MockMysqlError(*errno).into()),
+                None => Ok("committed"),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadlock_retry() -> Result<()> {
+        let connection = MockConnection::new(vec![ER_LOCK_DEADLOCK, ER_LOCK_DEADLOCK]);
+        let options = DeadlockRetryOptions::default();
+
+        let res =
+            retry_transaction(&options, is_mock_deadlock, |_| connection.transaction()).await?;
+        assert_eq!(res, "committed");
+        assert_eq!(connection.attempts.load(Ordering::Relaxed), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadlock_retry_exhausted() -> Result<()> {
+        let connection = MockConnection::new(vec![ER_LOCK_DEADLOCK; 5]);
+        let options = DeadlockRetryOptions {
+            max_retries: 2,
+            ..Default::default()
+        };
+
+        let err = retry_transaction(&options, is_mock_deadlock, |_| connection.transaction())
+            .await
+            .unwrap_err()
+            .downcast::<DeadlockRetryError>()?;
+        assert_eq!(err.attempts, 3);
+        assert!(is_mock_deadlock(&err.last_error));
+        assert_eq!(connection.attempts.load(Ordering::Relaxed), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadlock_retry_other_error() -> Result<()> {
+        // Lock wait timeout isn't a deadlock, and isn't retried.
+        let connection = MockConnection::new(vec![1205]);
+        let options = DeadlockRetryOptions::default();
+
+        let err = retry_transaction(&options, is_mock_deadlock, |_| connection.transaction())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MockMysqlError>(),
+            Some(MockMysqlError(1205))
+        ));
+        assert_eq!(connection.attempts.load(Ordering::Relaxed), 1);
+
+        Ok(())
+    }
+
+    #[cfg(not(fbcode_build))]
+    #[test]
+    fn test_is_deadlock() {
+        let deadlock = anyhow::anyhow!(
+            "ERROR 1213 (40001): Deadlock found when trying to get lock; try restarting transaction"
+        );
+        assert!(is_deadlock(&deadlock.context("while committing")));
+
+        // SQLite lock contention is not retried.
+        let busy = sql::rusqlite::Error::SqliteFailure(
+            sql::rusqlite::ffi::Error::new(sql::rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert!(!is_deadlock(&busy.into()));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let options = DeadlockRetryOptions {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        for (attempt, max) in [(1, 100), (2, 200), (3, 400), (4, 500), (10, 500)] {
+            let delay = backoff(&options, attempt);
+            assert!(delay <= Duration::from_millis(max), "{:?}", delay);
+            assert!(delay >= Duration::from_millis(max / 2), "{:?}", delay);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SqlError {
+    #[error("{op_type} query timed out after {elapsed:?}")]
+    QueryTimeout { op_type: String, elapsed: Duration },
+}
+
+/// A transaction kept deadlocking, and ran out of retries.
+#[derive(Debug, Error)]
+#[error("transaction deadlocked {attempts} times")]
+pub struct DeadlockRetryError {
+    pub attempts: u32,
+    #[source]
+    pub last_error: anyhow::Error,
+}
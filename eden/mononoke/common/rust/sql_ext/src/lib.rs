@@ -5,12 +5,19 @@
  * GNU General Public License version 2.
  */
 
+mod deadlock_retry;
+mod error;
 mod mononoke_queries;
 #[cfg(not(fbcode_build))]
 mod oss;
 pub mod replication;
 mod sqlite;
 
+pub use deadlock_retry::with_deadlock_retry;
+pub use deadlock_retry::ER_LOCK_DEADLOCK;
+pub use error::DeadlockRetryError;
+pub use error::SqlError;
+pub use mononoke_queries::set_query_timeouts;
 pub use sql::SqlConnections;
 pub use sql::SqlShardedConnections;
 use sql::Transaction;
@@ -48,8 +55,10 @@ pub mod facebook {
     #[cfg(fbcode_build)]
     mod r#impl;
 
+    use std::collections::HashMap;
     use std::fmt;
     use std::fmt::Debug;
+    use std::time::Duration;
 
     #[cfg(fbcode_build)]
     pub use r#impl::create_mysql_connections_sharded;
@@ -86,9 +95,104 @@ pub mod facebook {
         // pool config is used only once when the shared connection pool is being created
         pub pool_config: PoolConfig,
         pub read_connection_type: ReadConnectionType,
+        /// Separate connection pools for some types of operations, e.g.
+        /// `MYSQL_POOL_READ`, with the config they are created with.
+        /// Operation types without an entry use the shared pool.
+        pub operation_pools: HashMap<String, (SharedConnectionPool, PoolConfig)>,
+        /// Maximum duration of queries for each type of operation.
+        pub query_timeouts: HashMap<String, Duration>,
+        /// Maximum duration of queries for operation types without an entry
+        /// in `query_timeouts`.
+        pub default_query_timeout: Option<Duration>,
+        /// How to retry transactions that fail because of a deadlock.
+        pub deadlock_retry: DeadlockRetryOptions,
     }
 
+    /// Retries of deadlocked transactions, with exponential backoff.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct DeadlockRetryOptions {
+        /// Number of times a transaction is retried after the first attempt.
+        pub max_retries: u32,
+        /// Delay before the first retry, doubled for each retry.
+        pub base_delay: Duration,
+        /// Upper bound of the delay between retries.
+        pub max_delay: Duration,
+    }
+
+    impl Default for DeadlockRetryOptions {
+        fn default() -> Self {
+            Self {
+                max_retries: 3,
+                base_delay: Duration::from_millis(50),
+                max_delay: Duration::from_secs(1),
+            }
+        }
+    }
+
+    /// Operation type for pools of read-only connections.
+    pub const MYSQL_POOL_READ: &str = "read";
+    /// Operation type for pools of connections that can write.
+    pub const MYSQL_POOL_WRITE: &str = "write";
+    /// Operation type for pools of connections used by admin tooling.
+    pub const MYSQL_POOL_ADMIN: &str = "admin";
+
     impl MysqlOptions {
+        /// Use a separate connection pool, created with `pool_config`, for
+        /// the given operation type.
+        pub fn with_operation_pool(mut self, op_type: &str, pool_config: PoolConfig) -> Self {
+            self.operation_pools.insert(
+                op_type.to_string(),
+                (SharedConnectionPool::new(), pool_config),
+            );
+            self
+        }
+
+        /// Pool config for the given operation type, or the config of the
+        /// shared pool if it has no pool of its own.
+        pub fn pool_for(&self, op_type: &str) -> PoolConfig {
+            match self.operation_pools.get(op_type) {
+                Some((_, pool_config)) => *pool_config,
+                None => self.pool_config,
+            }
+        }
+
+        /// Options to create connections for the given operation type, which
+        /// use its own pool if it has one.
+        pub fn for_operation(&self, op_type: &str) -> MysqlOptions {
+            match self.operation_pools.get(op_type) {
+                Some((pool, pool_config)) => MysqlOptions {
+                    pool: pool.clone(),
+                    pool_config: *pool_config,
+                    ..self.clone()
+                },
+                None => self.clone(),
+            }
+        }
+
+        /// Set the maximum duration of queries for the given operation type.
+        pub fn with_query_timeout(mut self, op_type: &str, timeout: Duration) -> Self {
+            self.query_timeouts.insert(op_type.to_string(), timeout);
+            self
+        }
+
+        /// Maximum duration of queries for the given operation type, if any.
+        pub fn query_timeout_for(&self, op_type: &str) -> Option<Duration> {
+            self.query_timeouts
+                .get(op_type)
+                .copied()
+                .or(self.default_query_timeout)
+        }
+
+        /// Options to create connections: read-only connections use the
+        /// `MYSQL_POOL_READ` pool, others the `MYSQL_POOL_WRITE` one.
+        pub fn for_connections(&self, readonly: bool) -> MysqlOptions {
+            if readonly {
+                self.for_operation(MYSQL_POOL_READ)
+            } else {
+                self.for_operation(MYSQL_POOL_WRITE)
+            }
+        }
+
         pub fn per_key_limit(&self) -> Option<usize> {
             #[cfg(not(fbcode_build))]
             {
@@ -105,8 +209,15 @@ pub mod facebook {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(
                 f,
-                "MySQL pool with config {:?}, connection type: {:?}",
-                self.pool_config, self.read_connection_type
+                "MySQL pool with config {:?}, connection type: {:?}, operation pools: {:?}, query timeouts: {:?} (default {:?})",
+                self.pool_config,
+                self.read_connection_type,
+                self.operation_pools
+                    .iter()
+                    .map(|(op_type, (_, pool_config))| (op_type, pool_config))
+                    .collect::<HashMap<_, _>>(),
+                self.query_timeouts,
+                self.default_query_timeout
             )
         }
     }
@@ -133,3 +244,35 @@ pub mod facebook {
         ReadAfterWriteConsistency,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::facebook::*;
+
+    fn pool_config(size: usize) -> PoolConfig {
+        PoolConfig::new(size, 10, 100, 60_000, 4_000, 3_000, Duration::from_secs(10))
+    }
+
+    #[test]
+    fn test_pool_for() {
+        let options = MysqlOptions {
+            pool_config: pool_config(1000),
+            ..Default::default()
+        }
+        .with_operation_pool(MYSQL_POOL_READ, pool_config(5000));
+
+        assert_eq!(options.pool_for(MYSQL_POOL_READ), pool_config(5000));
+        assert_eq!(options.pool_for(MYSQL_POOL_WRITE), pool_config(1000));
+        assert_eq!(options.pool_for(MYSQL_POOL_ADMIN), pool_config(1000));
+
+        // Read-only connections are created from the read pool, and the
+        // others from the shared one.
+        assert_eq!(options.for_connections(true).pool_config, pool_config(5000));
+        assert_eq!(
+            options.for_connections(false).pool_config,
+            pool_config(1000)
+        );
+    }
+}
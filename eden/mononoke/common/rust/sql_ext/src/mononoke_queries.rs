@@ -8,12 +8,14 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration;
 
 use abomonation::Abomonation;
 use abomonation_derive::Abomonation;
 use anyhow::anyhow;
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
 use bytes::Bytes;
 use caching_ext::*;
@@ -21,11 +23,17 @@ use itertools::Itertools;
 use maplit::hashmap;
 use maplit::hashset;
 use memcache::KeyGen;
+use once_cell::sync::Lazy;
 use retry::retry;
 use retry::RetryLogic;
 use sql_query_config::CachingConfig;
+use tokio::time::Instant;
 use tunables::tunables;
 
+use crate::facebook::MysqlOptions;
+use crate::facebook::MYSQL_POOL_READ;
+use crate::SqlError;
+
 const RETRY_ATTEMPTS: usize = 2;
 
 // This wraps around rust/shed/sql::queries, check that macro: https://fburl.com/code/semq9xm3
@@ -107,6 +115,7 @@ macro_rules! mononoke_queries {
                     $( $lname: & [ $ltype ], )*
                 ) -> Result<Vec<($( $rtype, )*)>> {
                     query_with_retry_no_cache(
+                        $crate::facebook::MYSQL_POOL_READ,
                         || [<$name Impl>]::query(connection, $( $pname, )* $( $lname, )*),
                     ).await
                 }
@@ -229,6 +238,7 @@ macro_rules! mononoke_queries {
                     $( $pname: & $ptype ),*
                 ) -> Result<WriteResult> {
                     query_with_retry_no_cache(
+                        $crate::facebook::MYSQL_POOL_WRITE,
                         || [<$name Impl>]::query(connection, values $( , $pname )* ),
                     ).await
                 }
@@ -290,6 +300,7 @@ macro_rules! mononoke_queries {
                     $( $lname: & [ $ltype ], )*
                 ) -> Result<WriteResult> {
                     query_with_retry_no_cache(
+                        $crate::facebook::MYSQL_POOL_WRITE,
                         || [<$name Impl>]::query(connection, $( $pname, )* $( $lname, )*),
                     ).await
                 }
@@ -407,19 +418,49 @@ where
     }
 }
 
+/// Query timeouts applied to queries defined with `mononoke_queries!`.
+#[derive(Default)]
+struct QueryTimeouts {
+    timeouts: HashMap<String, Duration>,
+    default_timeout: Option<Duration>,
+}
+
+static QUERY_TIMEOUTS: Lazy<ArcSwap<QueryTimeouts>> = Lazy::new(Default::default);
+
+/// Use the query timeouts of `mysql_options` for all queries defined with
+/// `mononoke_queries!`, with read queries using the timeout for
+/// `MYSQL_POOL_READ`, and write queries the one for `MYSQL_POOL_WRITE`.
+pub fn set_query_timeouts(mysql_options: &MysqlOptions) {
+    QUERY_TIMEOUTS.store(Arc::new(QueryTimeouts {
+        timeouts: mysql_options.query_timeouts.clone(),
+        default_timeout: mysql_options.default_query_timeout,
+    }));
+}
+
+fn query_timeout_for(op_type: &str) -> Option<Duration> {
+    let query_timeouts = QUERY_TIMEOUTS.load();
+    query_timeouts
+        .timeouts
+        .get(op_type)
+        .copied()
+        .or(query_timeouts.default_timeout)
+}
+
 pub async fn query_with_retry_no_cache<T, Fut>(
+    op_type: &str,
     do_query: impl Fn() -> Fut + Send + Sync,
 ) -> Result<T>
 where
     T: Send + 'static,
     Fut: Future<Output = Result<T>>,
 {
+    let timeout = query_timeout_for(op_type);
     if tunables().disable_sql_auto_retries().unwrap_or_default() {
-        return do_query().await;
+        return query_with_timeout(timeout, op_type, do_query()).await;
     }
     Ok(retry(
         None,
-        |_| do_query(),
+        |_| query_with_timeout(timeout, op_type, do_query()),
         should_retry_mysql_query,
         // See https://fburl.com/7dmedu1u for backoff reasoning
         RetryLogic::ExponentialWithJitter {
@@ -442,9 +483,9 @@ where
     Fut: Future<Output = Result<T>> + Send,
 {
     if tunables().disable_sql_auto_cache().unwrap_or_default() {
-        return query_with_retry_no_cache(&do_query).await;
+        return query_with_retry_no_cache(MYSQL_POOL_READ, &do_query).await;
     }
-    let fetch = || query_with_retry_no_cache(&do_query);
+    let fetch = || query_with_retry_no_cache(MYSQL_POOL_READ, &do_query);
     let key = cache_data.key;
     if let Some(config) = cache_data.config.as_ref() {
         let store = QueryCacheStore {
@@ -465,8 +506,35 @@ where
     }
 }
 
+/// Run `query`, failing with `SqlError::QueryTimeout` if it takes longer than
+/// `timeout`.
+async fn query_with_timeout<T, Fut>(
+    timeout: Option<Duration>,
+    op_type: &str,
+    query: Fut,
+) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return query.await,
+    };
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, query).await {
+        Ok(res) => res,
+        Err(_) => Err(SqlError::QueryTimeout {
+            op_type: op_type.to_string(),
+            elapsed: start.elapsed(),
+        }
+        .into()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     mononoke_queries! {
         read TestQuery(param_str: String, param_uint: u64) -> (u64, Option<i32>, String, i64) {
             "SELECT 44, NULL, {param_str}, {param_uint}"
@@ -507,4 +575,54 @@ mod tests {
         TestQuery4::query(connection, &"hello").await?;
         Ok(())
     }
+
+    // Stands in for a MySQL backend that takes `delay` to respond.
+    async fn slow_query(delay: Duration) -> Result<u64> {
+        tokio::time::sleep(delay).await;
+        Ok(42)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_query_with_timeout() -> Result<()> {
+        let options = MysqlOptions::default().with_query_timeout("read", Duration::from_secs(1));
+
+        let timeout = options.query_timeout_for("read");
+        let res = query_with_timeout(timeout, "read", slow_query(Duration::from_secs(5))).await;
+        match res.unwrap_err().downcast::<SqlError>()? {
+            SqlError::QueryTimeout { op_type, elapsed } => {
+                assert_eq!(op_type, "read");
+                assert_eq!(elapsed, Duration::from_secs(1));
+            }
+        }
+
+        // Fast queries and operation types without a timeout are unaffected.
+        let res = query_with_timeout(timeout, "read", slow_query(Duration::from_millis(10)));
+        assert_eq!(res.await?, 42);
+        let timeout = options.query_timeout_for("write");
+        let res = query_with_timeout(timeout, "write", slow_query(Duration::from_secs(5)));
+        assert_eq!(res.await?, 42);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_query_with_default_timeout() -> Result<()> {
+        let options = MysqlOptions {
+            default_query_timeout: Some(Duration::from_secs(2)),
+            ..Default::default()
+        }
+        .with_query_timeout("admin", Duration::from_secs(10));
+
+        let timeout = options.query_timeout_for("write");
+        let res = query_with_timeout(timeout, "write", slow_query(Duration::from_secs(5))).await;
+        assert!(matches!(
+            res.unwrap_err().downcast::<SqlError>()?,
+            SqlError::QueryTimeout { elapsed, .. } if elapsed == Duration::from_secs(2)
+        ));
+        let timeout = options.query_timeout_for("admin");
+        let res = query_with_timeout(timeout, "admin", slow_query(Duration::from_secs(5)));
+        assert_eq!(res.await?, 42);
+
+        Ok(())
+    }
 }
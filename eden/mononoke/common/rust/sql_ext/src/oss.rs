@@ -24,23 +24,39 @@ macro_rules! fb_unimplemented {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
-pub struct PoolConfig;
+pub struct PoolConfig {
+    pub size: usize,
+    pub threads_num: i32,
+    pub per_key_limit: u64,
+    pub conn_age_timeout_ms: u64,
+    pub conn_idle_timeout_ms: u64,
+    pub conn_open_timeout_ms: u64,
+    pub query_time_limit: Duration,
+}
 
 impl PoolConfig {
     pub fn new(
-        _size: usize,
-        _threads_num: i32,
-        _per_key_limit: u64,
-        _conn_age_timeout_ms: u64,
-        _conn_idle_timeout_ms: u64,
-        _conn_open_timeout_ms: u64,
-        _query_time_limit: Duration,
+        size: usize,
+        threads_num: i32,
+        per_key_limit: u64,
+        conn_age_timeout_ms: u64,
+        conn_idle_timeout_ms: u64,
+        conn_open_timeout_ms: u64,
+        query_time_limit: Duration,
     ) -> Self {
-        Self
+        Self {
+            size,
+            threads_num,
+            per_key_limit,
+            conn_age_timeout_ms,
+            conn_idle_timeout_ms,
+            conn_open_timeout_ms,
+            query_time_limit,
+        }
     }
 
     pub fn default() -> Self {
-        Self
+        <Self as Default>::default()
     }
 }
 
@@ -85,13 +85,29 @@ impl MononokeScubaSampleBuilder {
         }
     }
 
-    pub fn with_observability_context(self, octx: ObservabilityContext) -> Self {
+    pub fn with_observability_context(mut self, octx: ObservabilityContext) -> Self {
+        if let Some(trace_id) = octx.trace_id() {
+            self.inner.add("trace_id", trace_id);
+        }
         Self {
             maybe_observability_context: Some(octx),
             ..self
         }
     }
 
+    /// Continue the trace of the caller, from the value of its W3C
+    /// `traceparent` header, and log its trace ID with the samples.
+    pub fn continue_trace(&mut self, traceparent: &str) -> Result<&mut Self> {
+        if let Some(octx) = &self.maybe_observability_context {
+            let octx = octx.clone().with_trace_parent(traceparent)?;
+            if let Some(trace_id) = octx.trace_id() {
+                self.inner.add("trace_id", trace_id);
+            }
+            self.maybe_observability_context = Some(octx);
+        }
+        Ok(self)
+    }
+
     fn get_scuba_sample_builder(
         fb: FacebookInit,
         scuba_logging_type: ScubaLoggingType,
@@ -132,6 +148,11 @@ impl MononokeScubaSampleBuilder {
     pub fn add_metadata(&mut self, metadata: &Metadata) -> &mut Self {
         self.inner
             .add("session_uuid", metadata.session_id().to_string());
+        // Sample all operations of a session together.
+        self.maybe_observability_context = self
+            .maybe_observability_context
+            .take()
+            .map(|octx| octx.with_request_id(&metadata.session_id().to_string()));
 
         self.inner.add(
             "client_identities",
@@ -179,7 +200,19 @@ impl MononokeScubaSampleBuilder {
         }
     }
 
+    /// Whether the sampling rules of the observability context keep the
+    /// samples of `operation`.
+    fn should_sample(&self, operation: &str) -> bool {
+        self.maybe_observability_context
+            .as_ref()
+            .map_or(true, |octx| octx.should_sample(operation))
+    }
+
     pub fn log_with_msg<S: Into<Option<String>>>(&mut self, log_tag: &str, msg: S) {
+        if !self.should_sample(log_tag) {
+            return;
+        }
+
         if self.fallback_sampled_out_to_verbose
             && self.should_log_with_level(ScubaVerbosityLevel::Verbose)
         {
@@ -304,6 +337,11 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn log(&mut self) -> bool {
+        if let Some(ScubaValue::Normal(log_tag)) = self.get("log_tag") {
+            if !self.should_sample(log_tag) {
+                return false;
+            }
+        }
         self.inner.log()
     }
 
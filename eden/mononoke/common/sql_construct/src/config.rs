@@ -34,9 +34,12 @@ pub trait SqlConstructFromDatabaseConfig: FbSqlConstruct + SqlConstruct {
             DatabaseConfig::Local(LocalDatabaseConfig { path }) => {
                 Self::with_sqlite_path(path.join("sqlite_dbs"), readonly)
             }
-            DatabaseConfig::Remote(config) => {
-                Self::with_mysql(fb, config.db_address.clone(), mysql_options, readonly)
-            }
+            DatabaseConfig::Remote(config) => Self::with_mysql(
+                fb,
+                config.db_address.clone(),
+                &mysql_options.for_connections(readonly),
+                readonly,
+            ),
         }
         .with_context(|| {
             format!(
@@ -65,12 +68,15 @@ pub trait SqlConstructFromShardedDatabaseConfig: FbSqlShardedConstruct {
                 fb,
                 config.shard_map.clone(),
                 config.shard_num,
-                mysql_options,
+                &mysql_options.for_connections(readonly),
+                readonly,
+            ),
+            ShardedDatabaseConfig::Unsharded(config) => Self::with_mysql(
+                fb,
+                config.db_address.clone(),
+                &mysql_options.for_connections(readonly),
                 readonly,
             ),
-            ShardedDatabaseConfig::Unsharded(config) => {
-                Self::with_mysql(fb, config.db_address.clone(), mysql_options, readonly)
-            }
         }
         .with_context(|| {
             format!(
@@ -109,7 +115,12 @@ pub trait SqlConstructFromMetadataDatabaseConfig: FbSqlConstruct + SqlConstruct
     ) -> Result<Self> {
         let config = Self::remote_database_config(remote)
             .ok_or_else(|| anyhow!("no configuration available"))?;
-        Self::with_mysql(fb, config.db_address.clone(), mysql_options, readonly)
+        Self::with_mysql(
+            fb,
+            config.db_address.clone(),
+            &mysql_options.for_connections(readonly),
+            readonly,
+        )
     }
 
     /// Get the remote database config for this type.  Override this to use a database other than
@@ -150,14 +161,17 @@ pub trait SqlShardableConstructFromMetadataDatabaseConfig:
         let config = Self::remote_database_config(remote)
             .ok_or_else(|| anyhow!("no configuration available"))?;
         match config {
-            ShardableRemoteDatabaseConfig::Unsharded(config) => {
-                Self::with_mysql(fb, config.db_address.clone(), mysql_options, readonly)
-            }
+            ShardableRemoteDatabaseConfig::Unsharded(config) => Self::with_mysql(
+                fb,
+                config.db_address.clone(),
+                &mysql_options.for_connections(readonly),
+                readonly,
+            ),
             ShardableRemoteDatabaseConfig::Sharded(config) => Self::with_sharded_mysql(
                 fb,
                 config.shard_map.clone(),
                 config.shard_num,
-                mysql_options,
+                &mysql_options.for_connections(readonly),
                 readonly,
             ),
         }
@@ -175,6 +175,19 @@ impl DerivedDataManager {
         }
     }
 
+    // For dangerous-override: allow replacement of the derivation service client
+    pub fn with_replaced_derivation_service_client(
+        &self,
+        derivation_service_client: Option<Arc<dyn DerivationClient>>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(DerivedDataManagerInner {
+                derivation_service_client,
+                ..self.inner.as_ref().clone()
+            }),
+        }
+    }
+
     pub fn with_replaced_config(
         &self,
         config_name: String,
@@ -21,6 +21,7 @@ use blobstore::Loadable;
 use borrowed::borrowed;
 use cloned::cloned;
 use context::CoreContext;
+use derived_data_remote::RemoteDerivationUnavailable;
 use derived_data_service_if::DerivationType;
 use derived_data_service_if::DeriveRequest;
 use derived_data_service_if::DeriveResponse;
@@ -578,7 +579,9 @@ impl DerivedDataManager {
                         }
                     },
                     Err(e) => {
-                        if attempt >= RETRY_ATTEMPTS_LIMIT {
+                        if attempt >= RETRY_ATTEMPTS_LIMIT
+                            || RemoteDerivationUnavailable::is_fallback(&e)
+                        {
                             derived_data_scuba
                                 .log_remote_derivation_end(ctx, Some(format!("{:#}", e)));
                             break;
@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::io;
+use std::sync::Arc;
+
+use anyhow::Error;
+use anyhow::Result;
+use async_trait::async_trait;
+use derived_data_service_if::types as thrift;
+use stats::prelude::*;
+use thiserror::Error;
+
+use crate::DerivationClient;
+
+define_stats! {
+    prefix = "mononoke.derived_data_remote";
+    derivation_remote_fallback_total: timeseries(Sum),
+}
+
+/// Context attached to errors of requests that failed because the derivation
+/// service couldn't be reached, meaning that the data should be derived
+/// locally instead.
+#[derive(Clone, Copy, Debug, Error)]
+#[error("Derivation service is unavailable, falling back to local derivation")]
+pub struct RemoteDerivationUnavailable;
+
+impl RemoteDerivationUnavailable {
+    /// Whether the request that failed with `err` should fall back to local
+    /// derivation.
+    pub fn is_fallback(err: &Error) -> bool {
+        err.downcast_ref::<RemoteDerivationUnavailable>().is_some()
+    }
+}
+
+/// Derivation client that marks requests that failed because the derivation
+/// service is down as [`RemoteDerivationUnavailable`], so that they are
+/// derived locally rather than returning an error to the user.
+///
+/// Only connection errors and timeouts are considered: any other error
+/// comes from the service itself, and is returned as is.
+pub struct FallbackDerivationClient {
+    remote: Arc<dyn DerivationClient>,
+}
+
+impl FallbackDerivationClient {
+    pub fn new(remote: Arc<dyn DerivationClient>) -> Self {
+        Self { remote }
+    }
+
+    fn check<T>(result: Result<T>) -> Result<T> {
+        match result {
+            Err(err) if is_service_unavailable(&err) => {
+                STATS::derivation_remote_fallback_total.add_value(1);
+                Err(err.context(RemoteDerivationUnavailable))
+            }
+            result => result,
+        }
+    }
+}
+
+fn is_service_unavailable(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<io::Error>() {
+            matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::AddrNotAvailable
+                    | io::ErrorKind::TimedOut
+            )
+        } else {
+            cause.is::<tokio::time::error::Elapsed>()
+        }
+    })
+}
+
+#[async_trait]
+impl DerivationClient for FallbackDerivationClient {
+    async fn derive_remotely(
+        &self,
+        request: &thrift::DeriveRequest,
+    ) -> Result<thrift::DeriveResponse> {
+        Self::check(self.remote.derive_remotely(request).await)
+    }
+
+    async fn poll(&self, request: &thrift::DeriveRequest) -> Result<thrift::DeriveResponse> {
+        Self::check(self.remote.poll(request).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    /// Derivation client for a service listening on `addr`, which fails
+    /// requests it can't send, and rejects the ones it can.
+    struct TcpDerivationClient {
+        addr: SocketAddr,
+    }
+
+    #[async_trait]
+    impl DerivationClient for TcpDerivationClient {
+        async fn derive_remotely(
+            &self,
+            _request: &thrift::DeriveRequest,
+        ) -> Result<thrift::DeriveResponse> {
+            TcpStream::connect(self.addr).await?;
+            Err(anyhow!("invalid changeset"))
+        }
+
+        async fn poll(&self, _request: &thrift::DeriveRequest) -> Result<thrift::DeriveResponse> {
+            tokio::time::timeout(Duration::from_millis(10), std::future::pending()).await?
+        }
+    }
+
+    fn request() -> thrift::DeriveRequest {
+        thrift::DeriveRequest {
+            repo_name: "repo".to_string(),
+            derived_data_type: thrift::DerivedDataType {
+                type_name: "unodes".to_string(),
+            },
+            changeset_id: vec![0; 32],
+            config_name: "default".to_string(),
+            derivation_type: thrift::DerivationType::derive_underived(thrift::DeriveUnderived {}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_service_unavailable() -> Result<()> {
+        // Bind to get a free port, and close it so that nothing listens on it.
+        let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let client = FallbackDerivationClient::new(Arc::new(TcpDerivationClient { addr }));
+
+        let err = client.derive_remotely(&request()).await.unwrap_err();
+        assert!(RemoteDerivationUnavailable::is_fallback(&err), "{:#}", err);
+        let err = client.poll(&request()).await.unwrap_err();
+        assert!(RemoteDerivationUnavailable::is_fallback(&err), "{:#}", err);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_service_error() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let client = FallbackDerivationClient::new(Arc::new(TcpDerivationClient {
+            addr: listener.local_addr()?,
+        }));
+
+        let err = client.derive_remotely(&request()).await.unwrap_err();
+        assert!(!RemoteDerivationUnavailable::is_fallback(&err), "{:#}", err);
+        Ok(())
+    }
+}
@@ -5,15 +5,22 @@
  * GNU General Public License version 2.
  */
 
+mod fallback;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use clap::Args;
 use derived_data_service_if::types as thrift;
 
+pub use crate::fallback::FallbackDerivationClient;
+pub use crate::fallback::RemoteDerivationUnavailable;
+
 #[derive(Clone, Debug)]
 pub struct RemoteDerivationOptions {
     pub derive_remotely: bool,
     pub address: Address,
+    /// Derive data locally when the derivation service can't be reached.
+    pub fallback_to_local: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -37,6 +44,10 @@ pub struct RemoteDerivationArgs {
     /// Specify Host:Port pair to connect to derived data service
     #[clap(long, value_name = "HOST:PORT", group = "Address")]
     pub derive_remotely_hostport: Option<String>,
+
+    /// Derive data locally if the derived data service is unavailable
+    #[clap(long, requires = "derive_remotely")]
+    pub derive_remotely_fallback_to_local: bool,
 }
 
 impl From<RemoteDerivationArgs> for RemoteDerivationOptions {
@@ -49,6 +60,7 @@ impl From<RemoteDerivationArgs> for RemoteDerivationOptions {
         RemoteDerivationOptions {
             derive_remotely: args.derive_remotely,
             address,
+            fallback_to_local: args.derive_remotely_fallback_to_local,
         }
     }
 }
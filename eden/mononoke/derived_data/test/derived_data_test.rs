@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+use std::net::SocketAddr;
+use std::net::TcpListener;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -28,6 +30,10 @@ use cloned::cloned;
 use context::CoreContext;
 use derived_data_manager::BonsaiDerivable;
 use derived_data_manager::DerivationError;
+use derived_data_remote::DerivationClient;
+use derived_data_remote::FallbackDerivationClient;
+use derived_data_service_if::DeriveRequest;
+use derived_data_service_if::DeriveResponse;
 use fbinit::FacebookInit;
 use filestore::FilestoreConfig;
 use fixtures::BranchEven;
@@ -470,3 +476,60 @@ async fn test_cancelling_slow_derivation(fb: FacebookInit) -> Result<(), Error>
 
     Ok(())
 }
+
+/// Derivation client for a derivation service that isn't listening.
+struct UnreachableDerivationClient {
+    addr: SocketAddr,
+}
+
+#[async_trait]
+impl DerivationClient for UnreachableDerivationClient {
+    async fn derive_remotely(&self, _request: &DeriveRequest) -> Result<DeriveResponse> {
+        tokio::net::TcpStream::connect(self.addr).await?;
+        Err(anyhow!("unexpected derivation service on {}", self.addr))
+    }
+
+    async fn poll(&self, request: &DeriveRequest) -> Result<DeriveResponse> {
+        self.derive_remotely(request).await
+    }
+}
+
+#[fbinit::test]
+async fn test_remote_derivation_fallback_to_local(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: TestRepo = make_test_repo_factory(fb).build().await?;
+
+    // Bind to get a free port, and close it so that nothing listens on it.
+    let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    let client = FallbackDerivationClient::new(Arc::new(UnreachableDerivationClient { addr }));
+    let manager = repo
+        .repo_derived_data()
+        .manager()
+        .with_replaced_derivation_service_client(Some(Arc::new(client)));
+
+    let tunables = MononokeTunables::default();
+    tunables.update_by_repo_bools(&hashmap! {
+        repo.repo_identity().name().to_string() => hashmap! {
+            "enable_remote_derivation".to_string() => true,
+        },
+    });
+    override_tunables(Some(Arc::new(tunables)));
+
+    let root = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file(MPath::new("file")?, "content")
+        .commit()
+        .await?;
+    let commit = CreateCommitContext::new(&ctx, &repo, vec![root])
+        .add_file(MPath::new("file")?, "content2")
+        .commit()
+        .await?;
+
+    let derived = manager
+        .derive::<DerivedGeneration>(&ctx, commit, None)
+        .await?;
+    assert_eq!(derived.generation, 2);
+
+    override_tunables(None);
+
+    Ok(())
+}
@@ -74,6 +74,7 @@ use skeleton_manifest::RootSkeletonManifestId;
 use topo_sort::sort_topological;
 use unodes::RootUnodeManifestId;
 
+pub mod migration;
 pub mod warmup;
 
 pub const POSSIBLE_DERIVED_TYPES: &[&str] = &[
@@ -0,0 +1,385 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::BlobstoreBytes;
+use blobstore::BlobstoreKeyParam;
+use blobstore::BlobstoreKeyRange;
+use blobstore::BlobstoreKeySource;
+use context::CoreContext;
+use derived_data::DerivedDataTypesConfig;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use slog::warn;
+
+const MIGRATION_CONCURRENCY: usize = 100;
+
+/// Counts of the keys processed by [`DerivedDataMigrationTool::migrate`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DerivedDataMigrationReport {
+    /// Values converted to the new version (or that would be, for dry runs).
+    pub migrated: u64,
+    /// Values that couldn't be read as the old version.
+    pub failed: u64,
+    /// Keys that were already migrated, or disappeared during the migration.
+    pub skipped: u64,
+}
+
+enum Outcome {
+    Migrated,
+    Failed,
+    Skipped,
+}
+
+/// Migrates the mapping of a derived data type after a change to the
+/// encoding of its values, from `Old` to `New`.
+///
+/// Mapping keys have the form `{root_prefix}{mapping_key_prefix}{changeset}`,
+/// where the mapping key prefix comes from the derived data config. Values
+/// under the current mapping key prefix are converted and written under a
+/// new mapping key prefix, so that existing values stay readable while the
+/// migration is running. Once it has succeeded, [`swap_namespaces`] switches
+/// the config to the new prefix.
+///
+/// [`swap_namespaces`]: DerivedDataMigrationTool::swap_namespaces
+pub struct DerivedDataMigrationTool<Old, New> {
+    blobstore: Arc<dyn BlobstoreKeySource>,
+    type_name: String,
+    source_prefix: String,
+    target_prefix: String,
+    new_mapping_key_prefix: String,
+    dry_run: bool,
+    on_progress: Option<Box<dyn Fn(u64, u64) + Send + Sync>>,
+    phantom: PhantomData<fn(Old) -> New>,
+}
+
+impl<Old, New> DerivedDataMigrationTool<Old, New>
+where
+    Old: TryFrom<BlobstoreBytes, Error = Error>,
+    New: From<Old> + Into<BlobstoreBytes>,
+{
+    pub fn new(
+        blobstore: Arc<dyn BlobstoreKeySource>,
+        config: &DerivedDataTypesConfig,
+        type_name: &str,
+        root_prefix: &str,
+        new_mapping_key_prefix: &str,
+    ) -> Self {
+        let mapping_key_prefix = config
+            .mapping_key_prefixes
+            .get(type_name)
+            .map_or("", String::as_str);
+        Self {
+            blobstore,
+            type_name: type_name.to_string(),
+            source_prefix: format!("{}{}", root_prefix, mapping_key_prefix),
+            target_prefix: format!("{}{}", root_prefix, new_mapping_key_prefix),
+            new_mapping_key_prefix: new_mapping_key_prefix.to_string(),
+            dry_run: false,
+            on_progress: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Only report what would be migrated, without writing anything.
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Call `on_progress` with the number of keys processed so far and the
+    /// total number of keys after each key.
+    pub fn with_progress(self, on_progress: Box<dyn Fn(u64, u64) + Send + Sync>) -> Self {
+        Self {
+            on_progress: Some(on_progress),
+            ..self
+        }
+    }
+
+    /// Convert all the values of the mapping to the new version.
+    ///
+    /// Values that were already migrated are skipped, so this can be run
+    /// again after an interrupted migration.
+    pub async fn migrate(&self, ctx: &CoreContext) -> Result<DerivedDataMigrationReport> {
+        if self.source_prefix == self.target_prefix {
+            bail!(
+                "Cannot migrate {} to the mapping key prefix it already uses",
+                self.type_name
+            );
+        }
+        let keys = self.source_keys(ctx).await?;
+        let total = keys.len() as u64;
+        let mut done = 0;
+        stream::iter(keys)
+            .map(|key| self.migrate_key(ctx, key))
+            .buffer_unordered(MIGRATION_CONCURRENCY)
+            .try_fold(
+                DerivedDataMigrationReport::default(),
+                |mut report, outcome| {
+                    match outcome {
+                        Outcome::Migrated => report.migrated += 1,
+                        Outcome::Failed => report.failed += 1,
+                        Outcome::Skipped => report.skipped += 1,
+                    }
+                    done += 1;
+                    if let Some(on_progress) = &self.on_progress {
+                        on_progress(done, total);
+                    }
+                    async move { Ok(report) }
+                },
+            )
+            .await
+    }
+
+    /// Return `config` updated to read the mapping from the new key prefix.
+    ///
+    /// Blobstores can't rename keys, so the namespaces are swapped by
+    /// switching the mapping key prefix of the type. The returned config can
+    /// be installed with `DerivedDataManager::with_replaced_config`.
+    pub fn swap_namespaces(
+        &self,
+        report: &DerivedDataMigrationReport,
+        config: &DerivedDataTypesConfig,
+    ) -> Result<DerivedDataTypesConfig> {
+        if self.dry_run {
+            bail!("Cannot swap namespaces after a dry run");
+        }
+        if report.failed > 0 {
+            bail!(
+                "Cannot swap namespaces: {} values of {} failed to migrate",
+                report.failed,
+                self.type_name
+            );
+        }
+        let mut config = config.clone();
+        config
+            .mapping_key_prefixes
+            .insert(self.type_name.clone(), self.new_mapping_key_prefix.clone());
+        Ok(config)
+    }
+
+    async fn source_keys(&self, ctx: &CoreContext) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut param = BlobstoreKeyParam::Start(BlobstoreKeyRange {
+            begin_key: self.source_prefix.clone(),
+            end_key: format!("{}\u{10ffff}", self.source_prefix),
+        });
+        loop {
+            let data = self.blobstore.enumerate(ctx, &param).await?;
+            // If the new prefix extends the old one, the keys that were
+            // already migrated are also in the range.
+            keys.extend(data.keys.into_iter().filter(|key| {
+                self.target_prefix.len() <= self.source_prefix.len()
+                    || !key.starts_with(&self.target_prefix)
+            }));
+            param = match data.next_token {
+                Some(next_token) => next_token,
+                None => break,
+            };
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn migrate_key(&self, ctx: &CoreContext, key: String) -> Result<Outcome> {
+        let target_key = format!("{}{}", self.target_prefix, &key[self.source_prefix.len()..]);
+        if self
+            .blobstore
+            .is_present(ctx, &target_key)
+            .await?
+            .assume_not_found_if_unsure()
+        {
+            return Ok(Outcome::Skipped);
+        }
+        let bytes = match self.blobstore.get(ctx, &key).await? {
+            Some(data) => data.into_bytes(),
+            None => return Ok(Outcome::Skipped),
+        };
+        let old = match Old::try_from(bytes) {
+            Ok(old) => old,
+            Err(e) => {
+                warn!(
+                    ctx.logger(),
+                    "Failed to read {} for migration: {:#}", key, e
+                );
+                return Ok(Outcome::Failed);
+            }
+        };
+        if !self.dry_run {
+            self.blobstore
+                .put(ctx, target_key.clone(), New::from(old).into())
+                .await
+                .with_context(|| format!("Failed to write migrated value to {}", target_key))?;
+        }
+        Ok(Outcome::Migrated)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+
+    use blobstore::Blobstore;
+    use bytes::Bytes;
+    use fbinit::FacebookInit;
+    use maplit::hashmap;
+    use memblob::Memblob;
+
+    use super::*;
+
+    const ROOT_PREFIX: &str = "derived_root_test.";
+
+    /// Generation stored as a decimal string.
+    struct GenerationV1(u64);
+
+    impl TryFrom<BlobstoreBytes> for GenerationV1 {
+        type Error = Error;
+
+        fn try_from(bytes: BlobstoreBytes) -> Result<Self> {
+            Ok(GenerationV1(
+                std::str::from_utf8(bytes.as_bytes())?.parse()?,
+            ))
+        }
+    }
+
+    /// Generation stored as big-endian bytes.
+    struct GenerationV2(u64);
+
+    impl From<GenerationV1> for GenerationV2 {
+        fn from(v1: GenerationV1) -> Self {
+            GenerationV2(v1.0)
+        }
+    }
+
+    impl From<GenerationV2> for BlobstoreBytes {
+        fn from(v2: GenerationV2) -> Self {
+            BlobstoreBytes::from_bytes(Bytes::copy_from_slice(&v2.0.to_be_bytes()))
+        }
+    }
+
+    async fn populate(ctx: &CoreContext, blobstore: &Memblob, count: u64) -> Result<()> {
+        for i in 0..count {
+            blobstore
+                .put(
+                    ctx,
+                    format!("{}{:04}", ROOT_PREFIX, i),
+                    BlobstoreBytes::from_bytes(i.to_string()),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn migrated_values(
+        ctx: &CoreContext,
+        blobstore: &Memblob,
+        prefix: &str,
+    ) -> Result<Vec<u64>> {
+        let data = blobstore
+            .enumerate(ctx, &BlobstoreKeyParam::from(prefix.to_string()..))
+            .await?;
+        let mut values = Vec::new();
+        for key in data.keys.into_iter().filter(|key| key.starts_with(prefix)) {
+            let bytes = blobstore.get(ctx, &key).await?.unwrap().into_bytes();
+            values.push(u64::from_be_bytes(bytes.as_bytes().as_ref().try_into()?));
+        }
+        values.sort();
+        Ok(values)
+    }
+
+    fn tool(
+        blobstore: &Arc<Memblob>,
+        config: &DerivedDataTypesConfig,
+    ) -> DerivedDataMigrationTool<GenerationV1, GenerationV2> {
+        DerivedDataMigrationTool::new(blobstore.clone(), config, "test", ROOT_PREFIX, "v2.")
+    }
+
+    #[fbinit::test]
+    async fn test_migrate(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Arc::new(Memblob::default());
+        let config = DerivedDataTypesConfig::default();
+        populate(&ctx, &blobstore, 1000).await?;
+
+        let progress = Arc::new(AtomicU64::new(0));
+        let report = tool(&blobstore, &config)
+            .with_progress(Box::new({
+                let progress = progress.clone();
+                move |done, total| {
+                    assert_eq!(total, 1000);
+                    progress.fetch_max(done, Ordering::Relaxed);
+                }
+            }))
+            .migrate(&ctx)
+            .await?;
+
+        assert_eq!(
+            report,
+            DerivedDataMigrationReport {
+                migrated: 1000,
+                failed: 0,
+                skipped: 0,
+            }
+        );
+        assert_eq!(progress.load(Ordering::Relaxed), 1000);
+        let prefix = format!("{}v2.", ROOT_PREFIX);
+        assert_eq!(
+            migrated_values(&ctx, &blobstore, &prefix).await?,
+            (0..1000).collect::<Vec<_>>()
+        );
+
+        // Running the migration again skips the migrated keys.
+        let tool = tool(&blobstore, &config);
+        let report = tool.migrate(&ctx).await?;
+        assert_eq!(report.skipped, 1000);
+
+        let config = tool.swap_namespaces(&report, &config)?;
+        assert_eq!(
+            config.mapping_key_prefixes,
+            hashmap! { "test".to_string() => "v2.".to_string() }
+        );
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_migrate_dry_run(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Arc::new(Memblob::default());
+        let config = DerivedDataTypesConfig::default();
+        populate(&ctx, &blobstore, 10).await?;
+        blobstore
+            .put(
+                &ctx,
+                format!("{}invalid", ROOT_PREFIX),
+                BlobstoreBytes::from_bytes("not a number"),
+            )
+            .await?;
+
+        let tool = tool(&blobstore, &config).with_dry_run(true);
+        let report = tool.migrate(&ctx).await?;
+
+        assert_eq!(
+            report,
+            DerivedDataMigrationReport {
+                migrated: 10,
+                failed: 1,
+                skipped: 0,
+            }
+        );
+        let prefix = format!("{}v2.", ROOT_PREFIX);
+        assert!(migrated_values(&ctx, &blobstore, &prefix).await?.is_empty());
+        assert!(tool.swap_namespaces(&report, &config).is_err());
+        Ok(())
+    }
+}
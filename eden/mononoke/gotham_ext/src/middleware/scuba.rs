@@ -38,6 +38,9 @@ use crate::state_ext::StateExt;
 /// HTTP header used to correlate the request with the client-side logging
 const CLIENT_CORRELATOR: &str = "x-client-correlator";
 
+/// W3C Trace Context header of the trace the request belongs to
+const TRACE_PARENT: &str = "traceparent";
+
 /// Common HTTP-related Scuba columns that the middlware will set automatically.
 /// Applications using the middleware are encouraged to follow a similar pattern
 /// when adding application-specific columns to the `ScubaMiddlewareState`.
@@ -226,6 +229,13 @@ fn populate_scuba(scuba: &mut MononokeScubaSampleBuilder, state: &mut State) {
             CLIENT_CORRELATOR,
             |header| header.to_string(),
         );
+
+        if let Some(traceparent) = headers.get(TRACE_PARENT) {
+            // An invalid header starts a new trace, as the W3C spec asks.
+            if let Ok(traceparent) = traceparent.to_str() {
+                let _ = scuba.continue_trace(traceparent);
+            }
+        }
     }
 
     if let Some(metadata_state) = MetadataState::try_borrow_from(state) {
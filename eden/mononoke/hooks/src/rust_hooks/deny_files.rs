@@ -278,4 +278,34 @@ mod test {
         .unwrap();
         assert!(matches!(r, HookExecution::Accepted));
     }
+
+    #[test]
+    fn test_denied_codeowners() {
+        let all: Vec<LuaPattern> = vec![
+            "^CODEOWNERS$".try_into().unwrap(),
+            "/CODEOWNERS$".try_into().unwrap(),
+        ];
+        let native: Vec<LuaPattern> = vec![];
+        for path in ["CODEOWNERS", "dir/CODEOWNERS"] {
+            let r = deny_unacceptable_patterns(
+                &all,
+                &native,
+                &mpath(path),
+                CrossRepoPushSource::NativeToThisRepo,
+                Some(&basic_change()),
+            )
+            .unwrap();
+            assert!(matches!(r, HookExecution::Rejected(_)));
+        }
+
+        let r = deny_unacceptable_patterns(
+            &all,
+            &native,
+            &mpath("dir/CODEOWNERS.md"),
+            CrossRepoPushSource::NativeToThisRepo,
+            Some(&basic_change()),
+        )
+        .unwrap();
+        assert!(matches!(r, HookExecution::Accepted));
+    }
 }
@@ -11,6 +11,8 @@ use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use anyhow::Error;
@@ -50,6 +52,7 @@ pub(crate) use crate::derive_manifest;
 pub(crate) use crate::find_intersection_of_diffs;
 pub(crate) use crate::Diff;
 pub(crate) use crate::Entry;
+pub(crate) use crate::LeafInfo;
 pub(crate) use crate::Manifest;
 pub(crate) use crate::ManifestOps;
 pub(crate) use crate::ManifestOrderedOps;
@@ -662,6 +665,87 @@ async fn test_derive_manifest(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_derive_manifest_incremental(fb: FacebookInit) -> Result<()> {
+    let blobstore: Arc<dyn Blobstore> = Arc::new(Memblob::default());
+    let ctx = CoreContext::test_mock(fb);
+
+    // Derive a manifest, returning it with the number of trees and leaves
+    // that had to be created.
+    let derive = |parents: Vec<TestManifestIdU64>, changes: Vec<(MPath, Option<TestLeaf>)>| {
+        let trees = Arc::new(AtomicUsize::new(0));
+        let leaves = Arc::new(AtomicUsize::new(0));
+        let derived = derive_manifest(
+            ctx.clone(),
+            blobstore.clone(),
+            parents,
+            changes,
+            {
+                cloned!(ctx, blobstore, trees);
+                move |TreeInfo { subentries, .. }| {
+                    trees.fetch_add(1, Ordering::Relaxed);
+                    let subentries = subentries
+                        .into_iter()
+                        .map(|(path, (_, id))| (path, id))
+                        .collect();
+                    cloned!(ctx, blobstore);
+                    async move {
+                        let id = TestManifestU64(subentries).store(&ctx, &blobstore).await?;
+                        Ok(((), id))
+                    }
+                }
+            },
+            {
+                cloned!(ctx, blobstore, leaves);
+                move |leaf_info: LeafInfo<_, TestLeaf>| {
+                    leaves.fetch_add(1, Ordering::Relaxed);
+                    cloned!(ctx, blobstore);
+                    async move {
+                        let leaf = leaf_info
+                            .leaf
+                            .ok_or_else(|| Error::msg("leaf only conflict"))?;
+                        let id = leaf.store(&ctx, &blobstore).await?;
+                        Ok(((), id))
+                    }
+                }
+            },
+        );
+        async move {
+            let mf = derived.await?.expect("expect non empty manifest");
+            Ok::<_, Error>((
+                mf,
+                trees.load(Ordering::Relaxed),
+                leaves.load(Ordering::Relaxed),
+            ))
+        }
+    };
+
+    // 10 * 10 * 100 files, in a tree of depth 3.
+    let mut changes = Vec::new();
+    for a in 0..10 {
+        for b in 0..10 {
+            for c in 0..100 {
+                let path = MPath::new(format!("a{}/b{}/c{}", a, b, c))?;
+                changes.push((path, Some(TestLeaf(format!("{}{}{}", a, b, c)))));
+            }
+        }
+    }
+    let (mf0, trees, leaves) = derive(vec![], changes).await?;
+    assert_eq!((trees, leaves), (111, 10000));
+
+    // Changing a single file only recreates the trees on its path.
+    let path = MPath::new("a3/b4/c5")?;
+    let changes = vec![(path.clone(), Some(TestLeaf("changed".to_string())))];
+    let (mf1, trees, leaves) = derive(vec![mf0], changes).await?;
+    assert_eq!((trees, leaves), (3, 1));
+
+    let files = Loadable::load(&Files(mf1), &ctx, &blobstore).await?;
+    assert_eq!(files.len(), 10000);
+    assert_eq!(files.get(&path).map(String::as_str), Some("changed"));
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn test_derive_stack_of_manifests(fb: FacebookInit) -> Result<()> {
     let blobstore: Arc<dyn Blobstore> = Arc::new(Memblob::default());
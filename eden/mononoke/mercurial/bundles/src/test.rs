@@ -11,6 +11,9 @@ use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Cursor;
 use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use anyhow::Result;
 use assert_matches::assert_matches;
@@ -26,6 +29,7 @@ use futures::TryStreamExt;
 use futures_old::stream;
 use futures_old::stream::Stream;
 use futures_old::stream::Stream as OldStream;
+use mercurial_types::HgBlobNode;
 use mercurial_types::HgChangesetId;
 use mercurial_types::HgNodeHash;
 use mercurial_types::MPath;
@@ -47,10 +51,13 @@ use crate::bundle2::Bundle2Stream;
 use crate::bundle2::StreamEvent;
 use crate::bundle2_encode::Bundle2EncodeBuilder;
 use crate::changegroup;
+use crate::changegroup::unpacker::CgVersion;
+use crate::create_bundle_stream;
 use crate::errors::ErrorKind;
 use crate::part_encode::PartEncodeBuilder;
 use crate::part_header::PartHeaderBuilder;
 use crate::part_header::PartHeaderType;
+use crate::parts::changegroup_part;
 use crate::parts::phases_part;
 use crate::types::StreamHeader;
 use crate::utils::get_compression_param;
@@ -275,6 +282,39 @@ fn unknown_part(ct: Option<CompressorType>) {
                     if header.part_type() == &PartHeaderType::Listkeys && header.mandatory());
 }
 
+#[test]
+fn test_changegroup_part_streaming() {
+    const CHANGESETS: usize = 100_000;
+
+    let runtime = Runtime::new().unwrap();
+    let _guard = runtime.enter();
+
+    // Count the changesets pulled out of the stream, as if they were loaded
+    // from the blobstore.
+    let loaded = Arc::new(AtomicUsize::new(0));
+    let node = HgNodeHash::from_str(CHANGESET1_HASH_STR).unwrap();
+    let changesets = stream::iter_ok(0..CHANGESETS).map({
+        let loaded = loaded.clone();
+        move |i| {
+            loaded.fetch_add(1, Ordering::SeqCst);
+            let blob = format!("changeset {:06}", i).into_bytes();
+            (node, HgBlobNode::new(blob, None, None))
+        }
+    });
+    let part = changegroup_part(changesets, None, CgVersion::Cg2Version).unwrap();
+
+    // The first changeset is sent before the others are loaded.
+    let mut sent = Vec::new();
+    for bytes in create_bundle_stream(vec![part], None).wait() {
+        sent.extend_from_slice(&bytes.unwrap());
+        if sent.windows(16).any(|w| w == b"changeset 000000") {
+            break;
+        }
+    }
+    assert!(sent.windows(16).any(|w| w == b"changeset 000000"));
+    assert!(loaded.load(Ordering::SeqCst) < CHANGESETS);
+}
+
 fn parse_bundle(
     input: &[u8],
     compression: Option<&str>,
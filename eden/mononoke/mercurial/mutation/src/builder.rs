@@ -10,6 +10,7 @@ use metaconfig_types::RemoteMetadataDatabaseConfig;
 use mononoke_types::RepositoryId;
 use sql_construct::SqlConstruct;
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::facebook::DeadlockRetryOptions;
 use sql_ext::SqlConnections;
 
 use crate::store::SqlHgMutationStore;
@@ -20,6 +21,7 @@ const DEFAULT_MUTATION_CHAIN_LIMIT: usize = 500;
 pub struct SqlHgMutationStoreBuilder {
     pub(crate) connections: SqlConnections,
     pub(crate) mutation_chain_limit: usize,
+    pub(crate) deadlock_retry: DeadlockRetryOptions,
 }
 
 impl SqlConstruct for SqlHgMutationStoreBuilder {
@@ -31,6 +33,7 @@ impl SqlConstruct for SqlHgMutationStoreBuilder {
         Self {
             connections,
             mutation_chain_limit: DEFAULT_MUTATION_CHAIN_LIMIT,
+            deadlock_retry: DeadlockRetryOptions::default(),
         }
     }
 }
@@ -45,7 +48,12 @@ impl SqlConstructFromMetadataDatabaseConfig for SqlHgMutationStoreBuilder {
 
 impl SqlHgMutationStoreBuilder {
     pub fn with_repo_id(self, repo_id: RepositoryId) -> SqlHgMutationStore {
-        SqlHgMutationStore::new(repo_id, self.connections, self.mutation_chain_limit)
+        SqlHgMutationStore::new(
+            repo_id,
+            self.connections,
+            self.mutation_chain_limit,
+            self.deadlock_retry,
+        )
     }
 
     pub fn with_mutation_limit(self, mutation_chain_limit: usize) -> Self {
@@ -54,4 +62,11 @@ impl SqlHgMutationStoreBuilder {
             ..self
         }
     }
+
+    pub fn with_deadlock_retry(self, deadlock_retry: DeadlockRetryOptions) -> Self {
+        Self {
+            deadlock_retry,
+            ..self
+        }
+    }
 }
@@ -26,7 +26,9 @@ use mercurial_types::HgChangesetId;
 use mononoke_types::RepositoryId;
 use slog::debug;
 use sql::Connection;
+use sql_ext::facebook::DeadlockRetryOptions;
 use sql_ext::mononoke_queries;
+use sql_ext::with_deadlock_retry;
 use sql_ext::SqlConnections;
 
 use crate::entry::HgMutationEntry;
@@ -42,6 +44,7 @@ pub struct SqlHgMutationStore {
     repo_id: RepositoryId,
     connections: SqlConnections,
     mutation_chain_limit: usize,
+    deadlock_retry: DeadlockRetryOptions,
 }
 
 /// Convenience alias for the type of each row returned from the entries queries.
@@ -65,11 +68,13 @@ impl SqlHgMutationStore {
         repo_id: RepositoryId,
         connections: SqlConnections,
         mutation_chain_limit: usize,
+        deadlock_retry: DeadlockRetryOptions,
     ) -> Self {
         Self {
             repo_id,
             connections,
             mutation_chain_limit,
+            deadlock_retry,
         }
     }
 
@@ -88,12 +93,6 @@ impl SqlHgMutationStore {
         entry_set: &HgMutationEntrySet,
         changeset_ids: impl IntoIterator<Item = &'a HgChangesetId>,
     ) -> Result<()> {
-        let txn = self
-            .connections
-            .write_connection
-            .start_transaction()
-            .await?;
-
         let mut db_csets = Vec::new();
         let mut db_entries = Vec::new();
         let mut db_preds = Vec::new();
@@ -209,12 +208,22 @@ impl SqlHgMutationStore {
             })
             .collect();
 
-        let (txn, _) = AddChangesets::query_with_transaction(txn, db_csets.as_slice()).await?;
-        let (txn, _) = AddEntries::query_with_transaction(txn, ref_db_entries.as_slice()).await?;
-        let (txn, _) = AddPreds::query_with_transaction(txn, ref_db_preds.as_slice()).await?;
-        let (txn, _) = AddSplits::query_with_transaction(txn, ref_db_splits.as_slice()).await?;
-
-        txn.commit().await?;
+        let write_connection = &self.connections.write_connection;
+        let (db_csets, ref_db_entries, ref_db_preds, ref_db_splits) = (
+            db_csets.as_slice(),
+            ref_db_entries.as_slice(),
+            ref_db_preds.as_slice(),
+            ref_db_splits.as_slice(),
+        );
+        with_deadlock_retry(&self.deadlock_retry, |_| async move {
+            let txn = write_connection.start_transaction().await?;
+            let (txn, _) = AddChangesets::query_with_transaction(txn, db_csets).await?;
+            let (txn, _) = AddEntries::query_with_transaction(txn, ref_db_entries).await?;
+            let (txn, _) = AddPreds::query_with_transaction(txn, ref_db_preds).await?;
+            let (txn, _) = AddSplits::query_with_transaction(txn, ref_db_splits).await?;
+            txn.commit().await
+        })
+        .await?;
 
         debug!(
             ctx.logger(),
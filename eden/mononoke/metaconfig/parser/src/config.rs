@@ -192,6 +192,7 @@ fn parse_with_repo_definition(
         storage_config,
         storage,
         bookmarks,
+        bookmark_namespaces,
         hook_manager_params,
         hooks,
         redaction,
@@ -259,6 +260,7 @@ fn parse_with_repo_definition(
     let hook_manager_params = hook_manager_params.convert()?;
 
     let bookmarks = bookmarks.unwrap_or_default().convert()?;
+    let bookmark_namespaces = bookmark_namespaces.unwrap_or_default().convert()?;
 
     let push = push.convert()?.unwrap_or_default();
 
@@ -349,6 +351,7 @@ fn parse_with_repo_definition(
         cache_warmup,
         hook_manager_params,
         bookmarks,
+        bookmark_namespaces,
         hooks,
         push,
         pushrebase,
@@ -495,6 +498,7 @@ mod test {
     use metaconfig_types::BlameVersion;
     use metaconfig_types::BlobConfig;
     use metaconfig_types::BlobstoreId;
+    use metaconfig_types::BookmarkNamespaceParams;
     use metaconfig_types::BookmarkParams;
     use metaconfig_types::BubbleDeletionMode;
     use metaconfig_types::CacheWarmupParams;
@@ -818,6 +822,10 @@ mod test {
             ensure_ancestor_of="master"
             allow_move_to_public_commits_without_hooks=true
 
+            [[bookmark_namespaces]]
+            prefix="team-A/"
+            acl_file="/tmp/team-A.json"
+
             [[hooks]]
             name="hook1"
             bypass_commit_string="@allow_hook1"
@@ -1115,6 +1123,10 @@ mod test {
                         allow_move_to_public_commits_without_hooks: true,
                     },
                 ],
+                bookmark_namespaces: vec![BookmarkNamespaceParams {
+                    prefix: "team-A/".to_string(),
+                    acl_file: Some("/tmp/team-A.json".into()),
+                }],
                 hooks: vec![
                     HookParams {
                         name: "hook1".to_string(),
@@ -1321,6 +1333,7 @@ mod test {
                 cache_warmup: None,
                 hook_manager_params: None,
                 bookmarks: vec![],
+                bookmark_namespaces: vec![],
                 hooks: vec![],
                 push: Default::default(),
                 pushrebase: Default::default(),
@@ -6,6 +6,7 @@
  */
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -15,6 +16,7 @@ use anyhow::Result;
 use bookmarks_types::BookmarkKey;
 use metaconfig_types::Address;
 use metaconfig_types::BlameVersion;
+use metaconfig_types::BookmarkNamespaceParams;
 use metaconfig_types::BookmarkOrRegex;
 use metaconfig_types::BookmarkParams;
 use metaconfig_types::CacheWarmupParams;
@@ -58,6 +60,7 @@ use mononoke_types::PrefixTrie;
 use mononoke_types::RepositoryId;
 use regex::Regex;
 use repos::RawBookmarkConfig;
+use repos::RawBookmarkNamespaceConfig;
 use repos::RawCacheWarmupConfig;
 use repos::RawCommitGraphConfig;
 use repos::RawCommitIdentityScheme;
@@ -221,6 +224,23 @@ impl Convert for RawBookmarkConfig {
     }
 }
 
+impl Convert for RawBookmarkNamespaceConfig {
+    type Output = BookmarkNamespaceParams;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.prefix.is_empty() {
+            return Err(ConfigurationError::InvalidConfig(
+                "bookmark namespace prefix must not be empty".into(),
+            )
+            .into());
+        }
+        Ok(BookmarkNamespaceParams {
+            prefix: self.prefix,
+            acl_file: self.acl_file.map(PathBuf::from),
+        })
+    }
+}
+
 impl Convert for RawPushParams {
     type Output = PushParams;
 
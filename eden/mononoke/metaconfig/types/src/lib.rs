@@ -158,6 +158,8 @@ pub struct RepoConfig {
     pub cache_warmup: Option<CacheWarmupParams>,
     /// Configuration for bookmarks
     pub bookmarks: Vec<BookmarkParams>,
+    /// Bookmark namespaces with their own write ACLs
+    pub bookmark_namespaces: Vec<BookmarkNamespaceParams>,
     /// Infinitepush configuration
     pub infinitepush: InfinitepushParams,
     /// Configuration for hooks
@@ -468,6 +470,16 @@ impl From<Regex> for BookmarkOrRegex {
     }
 }
 
+/// Configuration for a bookmark namespace
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BookmarkNamespaceParams {
+    /// Prefix of the names of the bookmarks in the namespace
+    pub prefix: String,
+    /// File with the ACLs that control writes to the namespace, in addition
+    /// to the default ACL
+    pub acl_file: Option<PathBuf>,
+}
+
 /// Configuration for a bookmark
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct BookmarkParams {
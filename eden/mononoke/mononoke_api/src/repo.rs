@@ -127,6 +127,7 @@ use repo_blobstore::RepoBlobstore;
 use repo_blobstore::RepoBlobstoreArc;
 use repo_blobstore::RepoBlobstoreRef;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
+use repo_bookmark_attrs::RepoBookmarkAttrsRef;
 use repo_cross_repo::RepoCrossRepo;
 use repo_derived_data::RepoDerivedData;
 use repo_derived_data::RepoDerivedDataArc;
@@ -184,6 +185,7 @@ pub mod delete_bookmark;
 pub mod git;
 pub mod land_stack;
 pub mod move_bookmark;
+pub mod search_bookmarks;
 
 pub use git::upload_git_object;
 
@@ -1271,14 +1273,46 @@ impl RepoContext {
     }
 
     /// Get a list of bookmarks.
+    ///
+    /// If a namespace is given, only bookmarks whose names start with its
+    /// prefix are listed.
     pub async fn list_bookmarks(
         &self,
         include_scratch: bool,
         prefix: Option<&str>,
+        namespace: Option<&str>,
         after: Option<&str>,
         limit: Option<u64>,
     ) -> Result<impl Stream<Item = Result<(String, ChangesetId), MononokeError>> + '_, MononokeError>
     {
+        let prefix = match namespace {
+            Some(namespace) => {
+                let namespace_prefix = self
+                    .repo()
+                    .repo_bookmark_attrs()
+                    .namespace_by_prefix(namespace)
+                    .ok_or_else(|| {
+                        MononokeError::InvalidRequest(format!(
+                            "unknown bookmark namespace '{}'",
+                            namespace
+                        ))
+                    })?
+                    .prefix
+                    .as_str();
+                match prefix {
+                    Some(prefix) if prefix.starts_with(namespace_prefix) => Some(prefix),
+                    Some(prefix) if !namespace_prefix.starts_with(prefix) => {
+                        return Err(MononokeError::InvalidRequest(format!(
+                            "bookmark prefix '{}' is outside of namespace '{}'",
+                            prefix, namespace
+                        )));
+                    }
+                    _ => Some(namespace_prefix),
+                }
+            }
+            None => prefix,
+        };
+
         if include_scratch {
             if prefix.is_none() {
                 return Err(MononokeError::InvalidRequest(
@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+/// Source of bookmark usage information, used to rank the bookmarks that
+/// were used recently higher in fuzzy searches.
+pub trait BookmarkUsageTracker: Send + Sync {
+    /// Amount added to the score of the bookmark, between 0.0 and 1.0.
+    fn usage_boost(&self, bookmark: &str) -> f64;
+}
+
+impl RepoContext {
+    /// Find the public bookmarks with names closest to `query`.
+    ///
+    /// Returns up to `limit` bookmarks along with their score, best matches
+    /// first.  The score is 1.0 minus the normalized edit distance between
+    /// the name of the bookmark and the query, where swapping two adjacent
+    /// characters counts as a single edit, plus the boost given by
+    /// `usage_tracker`, if any.
+    pub async fn fuzzy_search_bookmarks(
+        &self,
+        query: &str,
+        limit: usize,
+        usage_tracker: Option<&dyn BookmarkUsageTracker>,
+    ) -> Result<Vec<(String, f64)>, MononokeError> {
+        let bookmarks = self
+            .warm_bookmarks_cache()
+            .list(
+                self.ctx(),
+                &BookmarkPrefix::empty(),
+                &BookmarkPagination::FromStart,
+                None,
+            )
+            .await?;
+        Ok(rank_bookmarks(
+            query,
+            bookmarks
+                .into_iter()
+                .map(|(bookmark, _)| bookmark.into_string()),
+            limit,
+            usage_tracker,
+        ))
+    }
+}
+
+fn rank_bookmarks(
+    query: &str,
+    names: impl IntoIterator<Item = String>,
+    limit: usize,
+    usage_tracker: Option<&dyn BookmarkUsageTracker>,
+) -> Vec<(String, f64)> {
+    let mut ranked = names
+        .into_iter()
+        .map(|name| {
+            let mut score = strsim::normalized_damerau_levenshtein(query, &name);
+            if let Some(usage_tracker) = usage_tracker {
+                score += usage_tracker.usage_boost(&name);
+            }
+            (name, score)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(name_a, score_a), (name_b, score_b)| {
+        score_b.total_cmp(score_a).then_with(|| name_a.cmp(name_b))
+    });
+    ranked.truncate(limit);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        ["feature-main-v2", "mainn", "main", "release", "trunk"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    fn ranked_names(ranked: Vec<(String, f64)>) -> Vec<String> {
+        ranked.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn test_rank_bookmarks() {
+        let ranked = rank_bookmarks("mian", names(), 5, None);
+        assert_eq!(ranked[0], ("main".to_string(), 0.75));
+        let ranked = ranked_names(ranked);
+        let position = |name| ranked.iter().position(|n| n == name).unwrap();
+        assert!(position("mainn") < position("feature-main-v2"));
+
+        // Only the best matches are returned.
+        let ranked = rank_bookmarks("mian", names(), 2, None);
+        assert_eq!(ranked_names(ranked), vec!["main", "mainn"]);
+
+        // An exact match has a perfect score.
+        let ranked = rank_bookmarks("trunk", names(), 1, None);
+        assert_eq!(ranked, vec![("trunk".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn test_rank_bookmarks_usage_boost() {
+        struct ReleaseUsed;
+
+        impl BookmarkUsageTracker for ReleaseUsed {
+            fn usage_boost(&self, bookmark: &str) -> f64 {
+                if bookmark == "release" { 1.0 } else { 0.0 }
+            }
+        }
+
+        let ranked = rank_bookmarks("mian", names(), 2, Some(&ReleaseUsed));
+        assert_eq!(ranked_names(ranked), vec!["release", "main"]);
+    }
+}
@@ -123,7 +123,7 @@ async fn list_bookmarks(fb: FacebookInit) -> Result<()> {
     let (repo, changesets) = init_repo(&ctx).await?;
 
     assert_eq!(
-        repo.list_bookmarks(false, None, None, None)
+        repo.list_bookmarks(false, None, None, None, None)
             .await?
             .try_collect::<Vec<_>>()
             .await?,
@@ -131,7 +131,7 @@ async fn list_bookmarks(fb: FacebookInit) -> Result<()> {
     );
 
     assert_eq!(
-        repo.list_bookmarks(true, Some("scratch/"), None, Some(3))
+        repo.list_bookmarks(true, Some("scratch/"), None, None, Some(3))
             .await?
             .try_collect::<Vec<_>>()
             .await?,
@@ -142,10 +142,16 @@ async fn list_bookmarks(fb: FacebookInit) -> Result<()> {
     );
 
     assert_eq!(
-        repo.list_bookmarks(true, Some("scratch/"), Some("scratch/branch"), Some(3))
-            .await?
-            .try_collect::<Vec<_>>()
-            .await?,
+        repo.list_bookmarks(
+            true,
+            Some("scratch/"),
+            None,
+            Some("scratch/branch"),
+            Some(3)
+        )
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?,
         vec![(String::from("scratch/branchpoint"), changesets["B"])]
     );
     Ok(())
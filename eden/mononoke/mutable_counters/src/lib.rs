@@ -20,7 +20,9 @@ use mononoke_types::RepositoryId;
 use sql::Transaction as SqlTransaction;
 use sql_construct::SqlConstruct;
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::facebook::DeadlockRetryOptions;
 use sql_ext::mononoke_queries;
+use sql_ext::with_deadlock_retry;
 use sql_ext::SqlConnections;
 use sql_ext::TransactionResult;
 use stats::prelude::*;
@@ -96,10 +98,12 @@ mononoke_queries! {
 pub struct SqlMutableCounters {
     repo_id: RepositoryId,
     connections: SqlConnections,
+    deadlock_retry: DeadlockRetryOptions,
 }
 
 pub struct SqlMutableCountersBuilder {
     connections: SqlConnections,
+    deadlock_retry: DeadlockRetryOptions,
 }
 
 impl SqlConstruct for SqlMutableCountersBuilder {
@@ -108,17 +112,28 @@ impl SqlConstruct for SqlMutableCountersBuilder {
     const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-mutable-counters.sql");
 
     fn from_sql_connections(connections: SqlConnections) -> Self {
-        Self { connections }
+        Self {
+            connections,
+            deadlock_retry: DeadlockRetryOptions::default(),
+        }
     }
 }
 
 impl SqlConstructFromMetadataDatabaseConfig for SqlMutableCountersBuilder {}
 
 impl SqlMutableCountersBuilder {
+    pub fn with_deadlock_retry(self, deadlock_retry: DeadlockRetryOptions) -> Self {
+        Self {
+            deadlock_retry,
+            ..self
+        }
+    }
+
     pub fn build(self, repo_id: RepositoryId) -> SqlMutableCounters {
         SqlMutableCounters {
             repo_id,
             connections: self.connections,
+            deadlock_retry: self.deadlock_retry,
         }
     }
 }
@@ -149,17 +164,20 @@ impl MutableCounters for SqlMutableCounters {
         prev_value: Option<i64>,
     ) -> Result<bool> {
         let conn = &self.connections.write_connection;
-        let txn = conn.start_transaction().await?;
-        let txn_result =
-            Self::set_counter_on_txn(ctx, self.repo_id, name, value, prev_value, txn).await?;
-        match txn_result {
-            TransactionResult::Succeeded(txn) => {
-                txn.commit().await?;
-                STATS::cur_value.set_value(ctx.fb, value, (name.to_owned(),));
-                Ok(true)
+        with_deadlock_retry(&self.deadlock_retry, |_| async move {
+            let txn = conn.start_transaction().await?;
+            let txn_result =
+                Self::set_counter_on_txn(ctx, self.repo_id, name, value, prev_value, txn).await?;
+            match txn_result {
+                TransactionResult::Succeeded(txn) => {
+                    txn.commit().await?;
+                    STATS::cur_value.set_value(ctx.fb, value, (name.to_owned(),));
+                    Ok(true)
+                }
+                TransactionResult::Failed => Ok(false),
             }
-            TransactionResult::Failed => Ok(false),
-        }
+        })
+        .await
     }
 
     async fn get_all_counters(&self, ctx: &CoreContext) -> Result<Vec<(String, i64)>> {
@@ -5,9 +5,15 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use anyhow::bail;
 use anyhow::Error;
 use cached_config::ConfigHandle;
 use cached_config::ConfigStore;
@@ -20,6 +26,12 @@ use crate::scuba::ScubaLoggingDecisionFields;
 
 const CONFIGERATOR_OBSERVABILITY_CONFIG: &str = "scm/mononoke/observability/observability_config";
 
+/// Resolution of sampling rates.
+const SAMPLING_DENOMINATOR: u64 = 1_000_000;
+
+/// Version of the W3C Trace Context `traceparent` header that is generated.
+const TRACE_PARENT_VERSION: &str = "00";
+
 struct CfgrObservabilityContextInner {
     config_handle: ConfigHandle<ObservabilityConfig>,
 }
@@ -151,30 +163,181 @@ impl ObservabilityContextInner {
     }
 }
 
+/// Rate at which operations whose name starts with `operation_prefix` are
+/// sampled, between 0.0 (never) and 1.0 (always).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingRule {
+    pub operation_prefix: String,
+    pub rate: f64,
+}
+
+impl SamplingRule {
+    pub fn new(operation_prefix: impl Into<String>, rate: f64) -> Self {
+        Self {
+            operation_prefix: operation_prefix.into(),
+            rate,
+        }
+    }
+}
+
+/// A trace continued from a W3C Trace Context `traceparent` header.
+#[derive(Debug, Clone, PartialEq)]
+struct TraceParent {
+    /// 32 lowercase hex digits.
+    trace_id: String,
+    /// 16 lowercase hex digits.
+    parent_id: String,
+    flags: u8,
+}
+
+impl TraceParent {
+    /// Parse a header like `00-{trace_id}-{parent_id}-{flags}`.
+    fn parse(traceparent: &str) -> Result<Self, Error> {
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+        let version = parts[0];
+        if !is_hex(version, 2) || version == "ff" {
+            bail!("invalid traceparent version: {:?}", traceparent);
+        }
+        // Later versions may add fields after the known ones.
+        let valid_len = if version == TRACE_PARENT_VERSION {
+            parts.len() == 4
+        } else {
+            parts.len() >= 4
+        };
+        if !valid_len {
+            bail!("invalid traceparent: {:?}", traceparent);
+        }
+        let (trace_id, parent_id, flags) = (parts[1], parts[2], parts[3]);
+        if !is_hex(trace_id, 32) || is_zero(trace_id) {
+            bail!("invalid trace id in traceparent: {:?}", traceparent);
+        }
+        if !is_hex(parent_id, 16) || is_zero(parent_id) {
+            bail!("invalid parent id in traceparent: {:?}", traceparent);
+        }
+        if !is_hex(flags, 2) {
+            bail!("invalid flags in traceparent: {:?}", traceparent);
+        }
+        Ok(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            flags: u8::from_str_radix(flags, 16)?,
+        })
+    }
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn is_zero(s: &str) -> bool {
+    s.bytes().all(|b| b == b'0')
+}
+
 #[derive(Clone)]
 pub struct ObservabilityContext {
     inner: ObservabilityContextInner,
+    sampling_rules: Arc<Vec<SamplingRule>>,
+    // All sampling decisions of a request are derived from this, so that
+    // operations of the same request are sampled together.
+    sampling_seed: u64,
+    trace_parent: Option<Arc<TraceParent>>,
 }
 
 impl ObservabilityContext {
+    fn from_inner(inner: ObservabilityContextInner) -> Self {
+        Self {
+            inner,
+            sampling_rules: Arc::new(Vec::new()),
+            sampling_seed: RandomState::new().build_hasher().finish(),
+            trace_parent: None,
+        }
+    }
+
     pub fn new(config_store: &ConfigStore) -> Result<Self, Error> {
-        Ok(Self {
-            inner: ObservabilityContextInner::new(config_store)?,
-        })
+        Ok(Self::from_inner(ObservabilityContextInner::new(
+            config_store,
+        )?))
     }
 
     pub fn new_test(inner: Arc<Mutex<TestObservabilityContextInner>>) -> Self {
+        Self::from_inner(ObservabilityContextInner::new_test(inner))
+    }
+
+    pub fn new_static(level: Level) -> Self {
+        Self::from_inner(ObservabilityContextInner::new_static(level))
+    }
+
+    /// Sample operations according to `rules`. Operations that match no
+    /// rule are always sampled. If several rules match, the one with the
+    /// longest prefix is used.
+    pub fn with_sampling(self, rules: Vec<SamplingRule>) -> Self {
         Self {
-            inner: ObservabilityContextInner::new_test(inner),
+            sampling_rules: Arc::new(rules),
+            ..self
         }
     }
 
-    pub fn new_static(level: Level) -> Self {
+    /// Base the sampling decisions on `request_id`, so that they are the
+    /// same for every context of the request.
+    ///
+    /// Without a request ID, the decisions are based on a random seed picked
+    /// when the context is created, and are shared with its clones.
+    pub fn with_request_id(self, request_id: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        request_id.hash(&mut hasher);
         Self {
-            inner: ObservabilityContextInner::new_static(level),
+            sampling_seed: hasher.finish(),
+            ..self
         }
     }
 
+    /// Continue the trace of the caller, from the value of its W3C
+    /// `traceparent` header, instead of starting a new one.
+    pub fn with_trace_parent(self, traceparent: &str) -> Result<Self, Error> {
+        Ok(Self {
+            trace_parent: Some(Arc::new(TraceParent::parse(traceparent)?)),
+            ..self
+        })
+    }
+
+    /// ID of the trace continued by this context, as 32 hex digits.
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace_parent
+            .as_ref()
+            .map(|trace_parent| trace_parent.trace_id.as_str())
+    }
+
+    /// A `traceparent` header for a new span of the trace continued by this
+    /// context, to send with requests to other services.
+    pub fn child_trace_parent(&self) -> Option<String> {
+        let trace_parent = self.trace_parent.as_ref()?;
+        let span_id = loop {
+            let span_id = RandomState::new().build_hasher().finish();
+            if span_id != 0 {
+                break span_id;
+            }
+        };
+        Some(format!(
+            "{}-{}-{:016x}-{:02x}",
+            TRACE_PARENT_VERSION, trace_parent.trace_id, span_id, trace_parent.flags
+        ))
+    }
+
+    /// Whether `operation` should be sampled in this context.
+    pub fn should_sample(&self, operation: &str) -> bool {
+        let rule = self
+            .sampling_rules
+            .iter()
+            .filter(|rule| operation.starts_with(&rule.operation_prefix))
+            .max_by_key(|rule| rule.operation_prefix.len());
+        let rate = match rule {
+            Some(rule) => rule.rate,
+            None => return true,
+        };
+        let threshold = (rate.clamp(0.0, 1.0) * SAMPLING_DENOMINATOR as f64) as u64;
+        self.sampling_seed % SAMPLING_DENOMINATOR < threshold
+    }
+
     pub fn get_logging_level(&self) -> Level {
         self.inner.get_logging_level()
     }
@@ -188,3 +351,109 @@ impl ObservabilityContext {
             .should_log_scuba_sample(verbosity_level, logging_decision_fields)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context(rules: Vec<SamplingRule>, request_id: &str) -> ObservabilityContext {
+        ObservabilityContext::new_static(Level::Info)
+            .with_sampling(rules)
+            .with_request_id(request_id)
+    }
+
+    #[test]
+    fn test_sampling_rate() {
+        let rules = vec![SamplingRule::new("getpack", 0.1)];
+        let sampled = (0..1000)
+            .filter(|i| context(rules.clone(), &format!("request{}", i)).should_sample("getpack"))
+            .count();
+        assert!((50..150).contains(&sampled), "sampled {}", sampled);
+    }
+
+    #[test]
+    fn test_sampling_rules() {
+        let rules = vec![
+            SamplingRule::new("get", 0.0),
+            SamplingRule::new("getbundle", 1.0),
+        ];
+        for i in 0..100 {
+            let ctx = context(rules.clone(), &format!("request{}", i));
+            assert!(!ctx.should_sample("getpack"));
+            assert!(ctx.should_sample("getbundle"));
+            // No rule matches.
+            assert!(ctx.should_sample("unbundle"));
+        }
+    }
+
+    #[test]
+    fn test_sampling_consistent_within_request() {
+        let rules = vec![
+            SamplingRule::new("getpack", 0.5),
+            SamplingRule::new("gettreepack", 0.5),
+        ];
+        for i in 0..100 {
+            let request_id = format!("request{}", i);
+            let ctx = context(rules.clone(), &request_id);
+            let sampled = ctx.should_sample("getpack");
+            assert_eq!(ctx.should_sample("gettreepack"), sampled);
+            assert_eq!(ctx.clone().should_sample("getpack"), sampled);
+            assert_eq!(
+                context(rules.clone(), &request_id).should_sample("getpack"),
+                sampled
+            );
+        }
+    }
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[test]
+    fn test_trace_parent() -> Result<(), Error> {
+        let ctx = ObservabilityContext::new_static(Level::Info);
+        assert_eq!(ctx.trace_id(), None);
+        assert_eq!(ctx.child_trace_parent(), None);
+
+        let ctx = ctx.with_trace_parent(&format!("00-{}-00f067aa0ba902b7-01", TRACE_ID))?;
+        assert_eq!(ctx.trace_id(), Some(TRACE_ID));
+        assert_eq!(ctx.clone().trace_id(), Some(TRACE_ID));
+
+        let child = ctx.child_trace_parent().unwrap();
+        assert!(child.starts_with(&format!("00-{}-", TRACE_ID)));
+        assert!(child.ends_with("-01"));
+        let child = TraceParent::parse(&child)?;
+        assert_eq!(child.trace_id, TRACE_ID);
+        assert_ne!(child.parent_id, "00f067aa0ba902b7");
+
+        // Fields added by later versions are ignored.
+        let ctx = ObservabilityContext::new_static(Level::Info)
+            .with_trace_parent(&format!("01-{}-00f067aa0ba902b7-00-extra", TRACE_ID))?;
+        assert_eq!(ctx.trace_id(), Some(TRACE_ID));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_parent_invalid() {
+        for traceparent in [
+            "",
+            "00",
+            "garbage",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+        ] {
+            assert!(
+                ObservabilityContext::new_static(Level::Info)
+                    .with_trace_parent(traceparent)
+                    .is_err(),
+                "{:?} should be invalid",
+                traceparent
+            );
+        }
+    }
+}
@@ -5,11 +5,14 @@
  * GNU General Public License version 2.
  */
 
+use slog::BorrowedKV;
 use slog::Drain;
 use slog::Level;
 use slog::Never;
 use slog::OwnedKVList;
 use slog::Record;
+use slog::RecordStatic;
+use slog::SingleKV;
 
 use crate::context::ObservabilityContext;
 
@@ -36,10 +39,21 @@ impl<D: Drain<Ok = (), Err = Never>> Drain for DynamicLevelDrain<D> {
     type Err = Never;
 
     fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
-        if record.level().is_at_least(self.current_level()) {
-            self.inner.log(record, values)
-        } else {
-            Ok(())
+        if !record.level().is_at_least(self.current_level()) {
+            return Ok(());
+        }
+        match self.observability_context.trace_id() {
+            Some(trace_id) => {
+                let rstatic = RecordStatic {
+                    location: record.location(),
+                    tag: record.tag(),
+                    level: record.level(),
+                };
+                let kv = (SingleKV::from(("trace_id", trace_id)), record.kv());
+                let record = Record::new(&rstatic, record.msg(), BorrowedKV(&kv));
+                self.inner.log(&record, values)
+            }
+            None => self.inner.log(record, values),
         }
     }
 }
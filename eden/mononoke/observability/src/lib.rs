@@ -11,6 +11,7 @@ mod drain;
 mod scuba;
 
 pub use context::ObservabilityContext;
+pub use context::SamplingRule;
 pub use drain::DynamicLevelDrain;
 
 pub use crate::config::ScubaVerbosityLevel;
@@ -0,0 +1,331 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use slog::warn;
+use slog::Logger;
+
+use crate::pretty_print;
+use crate::AclProvider;
+use crate::AclResource;
+use crate::BoxMembershipChecker;
+use crate::BoxPermissionChecker;
+use crate::MembershipChecker;
+use crate::MononokeIdentitySet;
+use crate::PermissionCheck;
+use crate::PermissionChecker;
+
+/// Number of denials kept by [`AclProvider::with_audit_log`].
+pub const DEFAULT_MAX_RECENT_DENIALS: usize = 1000;
+
+/// A denied access attempt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub principal: MononokeIdentitySet,
+    /// The action that was denied. If the check allowed any of several
+    /// actions, they are comma-separated. Empty for group membership checks.
+    pub action: String,
+    pub resource: AclResource,
+    /// Name of the provider that denied the access.
+    pub provider: &'static str,
+    pub timestamp: SystemTime,
+}
+
+/// Shared between the provider and the checkers it returns.
+struct AuditLog {
+    logger: Logger,
+    provider: &'static str,
+    max_recent_denials: usize,
+    recent_denials: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    fn record(&self, principal: &MononokeIdentitySet, action: String, resource: &AclResource) {
+        let entry = AuditEntry {
+            principal: principal.clone(),
+            action,
+            resource: resource.clone(),
+            provider: self.provider,
+            timestamp: SystemTime::now(),
+        };
+        warn!(
+            self.logger,
+            "Access denied";
+            "principal" => pretty_print(&entry.principal),
+            "action" => &entry.action,
+            "resource" => entry.resource.to_string(),
+            "provider" => entry.provider,
+            "timestamp" => entry
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |t| t.as_secs()),
+        );
+
+        if self.max_recent_denials == 0 {
+            return;
+        }
+        let mut recent_denials = self.recent_denials.lock().expect("lock poisoned");
+        if recent_denials.len() == self.max_recent_denials {
+            recent_denials.pop_front();
+        }
+        recent_denials.push_back(entry);
+    }
+}
+
+/// An `AclProvider` that logs every access denied by another provider, and
+/// keeps the most recent denials in memory.
+pub struct AuditingAclProvider<A> {
+    inner: A,
+    log: Arc<AuditLog>,
+}
+
+impl<A: AclProvider> AuditingAclProvider<A> {
+    pub fn new(inner: A, logger: Logger, max_recent_denials: usize) -> Self {
+        let log = Arc::new(AuditLog {
+            logger,
+            provider: inner.provider_name(),
+            max_recent_denials,
+            recent_denials: Mutex::new(VecDeque::with_capacity(max_recent_denials)),
+        });
+        Self { inner, log }
+    }
+
+    /// Returns up to `n` of the most recent denials, oldest first.
+    pub fn recent_denials(&self, n: usize) -> Vec<AuditEntry> {
+        let recent_denials = self.log.recent_denials.lock().expect("lock poisoned");
+        let skip = recent_denials.len().saturating_sub(n);
+        recent_denials.iter().skip(skip).cloned().collect()
+    }
+
+    fn permission_checker(
+        &self,
+        resource: AclResource,
+        inner: BoxPermissionChecker,
+    ) -> BoxPermissionChecker {
+        Box::new(AuditingPermissionChecker {
+            inner,
+            resource,
+            log: self.log.clone(),
+        })
+    }
+
+    fn membership_checker(
+        &self,
+        resource: AclResource,
+        inner: BoxMembershipChecker,
+    ) -> BoxMembershipChecker {
+        Box::new(AuditingMembershipChecker {
+            inner,
+            resource,
+            log: self.log.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl<A: AclProvider> AclProvider for AuditingAclProvider<A> {
+    async fn repo_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        let inner = self.inner.repo_acl(name).await?;
+        Ok(self.permission_checker(AclResource::Repo(name.to_string()), inner))
+    }
+
+    async fn repo_region_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        let inner = self.inner.repo_region_acl(name).await?;
+        Ok(self.permission_checker(AclResource::RepoRegion(name.to_string()), inner))
+    }
+
+    async fn tier_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        let inner = self.inner.tier_acl(name).await?;
+        Ok(self.permission_checker(AclResource::Tier(name.to_string()), inner))
+    }
+
+    async fn group(&self, name: &str) -> Result<BoxMembershipChecker> {
+        let inner = self.inner.group(name).await?;
+        Ok(self.membership_checker(AclResource::Group(name.to_string()), inner))
+    }
+
+    async fn admin_group(&self) -> Result<BoxMembershipChecker> {
+        let inner = self.inner.admin_group().await?;
+        Ok(self.membership_checker(AclResource::AdminGroup, inner))
+    }
+
+    async fn reviewers_group(&self) -> Result<BoxMembershipChecker> {
+        let inner = self.inner.reviewers_group().await?;
+        Ok(self.membership_checker(AclResource::ReviewersGroup, inner))
+    }
+
+    async fn check_permissions_batch(
+        &self,
+        checks: &[PermissionCheck],
+    ) -> Result<HashMap<PermissionCheck, bool>> {
+        let results = self.inner.check_permissions_batch(checks).await?;
+        for (check, allowed) in &results {
+            if !allowed {
+                let (principal, action, resource) = check;
+                let action = if resource.is_group() {
+                    String::new()
+                } else {
+                    action.clone()
+                };
+                self.log.record(principal, action, resource);
+            }
+        }
+        Ok(results)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+}
+
+struct AuditingPermissionChecker {
+    inner: BoxPermissionChecker,
+    resource: AclResource,
+    log: Arc<AuditLog>,
+}
+
+#[async_trait]
+impl PermissionChecker for AuditingPermissionChecker {
+    async fn check_set(&self, accessors: &MononokeIdentitySet, actions: &[&str]) -> bool {
+        let allowed = self.inner.check_set(accessors, actions).await;
+        if !allowed {
+            self.log
+                .record(accessors, actions.join(","), &self.resource);
+        }
+        allowed
+    }
+}
+
+struct AuditingMembershipChecker {
+    inner: BoxMembershipChecker,
+    resource: AclResource,
+    log: Arc<AuditLog>,
+}
+
+#[async_trait]
+impl MembershipChecker for AuditingMembershipChecker {
+    async fn is_member(&self, identities: &MononokeIdentitySet) -> bool {
+        let member = self.inner.is_member(identities).await;
+        if !member {
+            self.log.record(identities, String::new(), &self.resource);
+        }
+        member
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use slog::o;
+    use slog::Drain;
+    use slog::Never;
+    use slog::OwnedKVList;
+    use slog::Record;
+    use slog::KV;
+
+    use super::*;
+    use crate::InternalAclProvider;
+
+    fn ids(ids: &[&str]) -> Result<MononokeIdentitySet> {
+        let mut set = MononokeIdentitySet::new();
+        for id in ids {
+            set.insert(id.parse()?);
+        }
+        Ok(set)
+    }
+
+    /// Drain that keeps the key-value pairs of every record.
+    #[derive(Clone, Default)]
+    struct RecordingDrain {
+        records: Arc<Mutex<Vec<HashMap<String, String>>>>,
+    }
+
+    struct KVCollector(HashMap<String, String>);
+
+    impl slog::Serializer for KVCollector {
+        fn emit_arguments(&mut self, key: slog::Key, val: &std::fmt::Arguments) -> slog::Result {
+            self.0.insert(key.to_string(), val.to_string());
+            Ok(())
+        }
+    }
+
+    impl Drain for RecordingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<(), Never> {
+            let mut collector = KVCollector(HashMap::new());
+            record.kv().serialize(record, &mut collector).unwrap();
+            self.records.lock().unwrap().push(collector.0);
+            Ok(())
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_audit_denials(_fb: FacebookInit) -> Result<()> {
+        let json = r##"
+            {
+                "repos": {
+                    "repo1": {
+                        "actions": {
+                            "read": ["USER:user1"]
+                        }
+                    }
+                },
+                "groups": {
+                    "admin": ["USER:user2"]
+                }
+            }
+        "##;
+        let drain = RecordingDrain::default();
+        let logger = Logger::root(drain.clone().fuse(), o!());
+        let prov = InternalAclProvider::new(serde_json::from_str(json)?);
+        let prov = AuditingAclProvider::new(prov, logger, 2);
+
+        let repo1 = prov.repo_acl("repo1").await?;
+        assert!(repo1.check_set(&ids(&["USER:user1"])?, &["read"]).await);
+        assert!(drain.records.lock().unwrap().is_empty());
+        assert!(prov.recent_denials(10).is_empty());
+
+        assert!(!repo1.check_set(&ids(&["USER:user2"])?, &["read"]).await);
+        assert!(!repo1.check_set(&ids(&["USER:user1"])?, &["write"]).await);
+        assert!(
+            !prov
+                .admin_group()
+                .await?
+                .is_member(&ids(&["USER:user1"])?)
+                .await
+        );
+
+        let records = drain.records.lock().unwrap().clone();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["principal"], "USER:user2");
+        assert_eq!(records[0]["action"], "read");
+        assert_eq!(records[0]["resource"], "repo:repo1");
+        assert!(records[0]["provider"].contains("InternalAclProvider"));
+        assert!(records[0].contains_key("timestamp"));
+
+        // Only the last two denials are kept.
+        let denials = prov.recent_denials(10);
+        assert_eq!(denials.len(), 2);
+        assert_eq!(denials[0].principal, ids(&["USER:user1"])?);
+        assert_eq!(denials[0].action, "write");
+        assert_eq!(denials[0].resource, AclResource::Repo("repo1".to_string()));
+        assert_eq!(denials[1].resource, AclResource::AdminGroup);
+        assert_eq!(prov.recent_denials(1), denials[1..]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use moka::sync::Cache;
+
+use crate::AclProvider;
+use crate::AclResource;
+use crate::ArcMembershipChecker;
+use crate::ArcPermissionChecker;
+use crate::BoxMembershipChecker;
+use crate::BoxPermissionChecker;
+use crate::MembershipChecker;
+use crate::MononokeIdentitySet;
+use crate::PermissionCheck;
+use crate::PermissionChecker;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CachingAclOptions {
+    /// Maximum number of entries in each of the caches.
+    pub max_capacity: u64,
+    /// How long an entry is used for before it is fetched again.
+    pub ttl: Duration,
+}
+
+impl CachingAclOptions {
+    pub fn new(max_capacity: u64, ttl: Duration) -> Self {
+        Self { max_capacity, ttl }
+    }
+
+    fn build_cache<K, V>(&self) -> Cache<K, V>
+    where
+        K: std::hash::Hash + Eq + Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static,
+    {
+        Cache::builder()
+            .max_capacity(self.max_capacity)
+            .time_to_live(self.ttl)
+            .build()
+    }
+}
+
+/// The identities and actions of a check, and the resource it is against.
+/// Membership checks have no actions.
+type CheckKey = (MononokeIdentitySet, Vec<String>, AclResource);
+
+fn check_key(check: &PermissionCheck) -> CheckKey {
+    let (principal, action, resource) = check;
+    let actions = if resource.is_group() {
+        Vec::new()
+    } else {
+        vec![action.clone()]
+    };
+    (principal.clone(), actions, resource.clone())
+}
+
+/// An `AclProvider` that caches the checkers returned by another provider,
+/// and the results of the checks made with them.
+pub struct CachedAclProvider<A> {
+    inner: A,
+    permission_checkers: Cache<AclResource, ArcPermissionChecker>,
+    membership_checkers: Cache<AclResource, ArcMembershipChecker>,
+    checks: Cache<CheckKey, bool>,
+}
+
+impl<A: AclProvider> CachedAclProvider<A> {
+    pub fn new(inner: A, options: CachingAclOptions) -> Self {
+        Self {
+            inner,
+            permission_checkers: options.build_cache(),
+            membership_checkers: options.build_cache(),
+            checks: options.build_cache(),
+        }
+    }
+
+    async fn permission_checker<Fut>(
+        &self,
+        resource: AclResource,
+        fetch: Fut,
+    ) -> Result<BoxPermissionChecker>
+    where
+        Fut: Future<Output = Result<BoxPermissionChecker>>,
+    {
+        let inner = match self.permission_checkers.get(&resource) {
+            Some(checker) => checker,
+            None => {
+                let checker = ArcPermissionChecker::from(fetch.await?);
+                self.permission_checkers
+                    .insert(resource.clone(), checker.clone());
+                checker
+            }
+        };
+        Ok(Box::new(CachedPermissionChecker {
+            inner,
+            resource,
+            checks: AssertUnwindSafe(self.checks.clone()),
+        }))
+    }
+
+    async fn membership_checker<Fut>(
+        &self,
+        resource: AclResource,
+        fetch: Fut,
+    ) -> Result<BoxMembershipChecker>
+    where
+        Fut: Future<Output = Result<BoxMembershipChecker>>,
+    {
+        let inner = match self.membership_checkers.get(&resource) {
+            Some(checker) => checker,
+            None => {
+                let checker = ArcMembershipChecker::from(fetch.await?);
+                self.membership_checkers
+                    .insert(resource.clone(), checker.clone());
+                checker
+            }
+        };
+        Ok(Box::new(CachedMembershipChecker {
+            inner,
+            resource,
+            checks: AssertUnwindSafe(self.checks.clone()),
+        }))
+    }
+}
+
+#[async_trait]
+impl<A: AclProvider> AclProvider for CachedAclProvider<A> {
+    async fn repo_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        self.permission_checker(
+            AclResource::Repo(name.to_string()),
+            self.inner.repo_acl(name),
+        )
+        .await
+    }
+
+    async fn repo_region_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        self.permission_checker(
+            AclResource::RepoRegion(name.to_string()),
+            self.inner.repo_region_acl(name),
+        )
+        .await
+    }
+
+    async fn tier_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        self.permission_checker(
+            AclResource::Tier(name.to_string()),
+            self.inner.tier_acl(name),
+        )
+        .await
+    }
+
+    async fn group(&self, name: &str) -> Result<BoxMembershipChecker> {
+        self.membership_checker(AclResource::Group(name.to_string()), self.inner.group(name))
+            .await
+    }
+
+    async fn admin_group(&self) -> Result<BoxMembershipChecker> {
+        self.membership_checker(AclResource::AdminGroup, self.inner.admin_group())
+            .await
+    }
+
+    async fn reviewers_group(&self) -> Result<BoxMembershipChecker> {
+        self.membership_checker(AclResource::ReviewersGroup, self.inner.reviewers_group())
+            .await
+    }
+
+    async fn check_permissions_batch(
+        &self,
+        checks: &[PermissionCheck],
+    ) -> Result<HashMap<PermissionCheck, bool>> {
+        let mut results = HashMap::with_capacity(checks.len());
+        let mut misses = Vec::new();
+        for check in checks {
+            match self.checks.get(&check_key(check)) {
+                Some(allowed) => {
+                    results.insert(check.clone(), allowed);
+                }
+                None => misses.push(check.clone()),
+            }
+        }
+        if !misses.is_empty() {
+            for (check, allowed) in self.inner.check_permissions_batch(&misses).await? {
+                self.checks.insert(check_key(&check), allowed);
+                results.insert(check, allowed);
+            }
+        }
+        Ok(results)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+}
+
+// The check caches are wrapped in `AssertUnwindSafe` as the checkers must be
+// `RefUnwindSafe`. A panic can at worst lose a cached result.
+
+struct CachedPermissionChecker {
+    inner: ArcPermissionChecker,
+    resource: AclResource,
+    checks: AssertUnwindSafe<Cache<CheckKey, bool>>,
+}
+
+#[async_trait]
+impl PermissionChecker for CachedPermissionChecker {
+    async fn check_set(&self, accessors: &MononokeIdentitySet, actions: &[&str]) -> bool {
+        let key = (
+            accessors.clone(),
+            actions.iter().map(|action| action.to_string()).collect(),
+            self.resource.clone(),
+        );
+        if let Some(result) = self.checks.get(&key) {
+            return result;
+        }
+        let result = self.inner.check_set(accessors, actions).await;
+        self.checks.insert(key, result);
+        result
+    }
+}
+
+struct CachedMembershipChecker {
+    inner: ArcMembershipChecker,
+    resource: AclResource,
+    checks: AssertUnwindSafe<Cache<CheckKey, bool>>,
+}
+
+#[async_trait]
+impl MembershipChecker for CachedMembershipChecker {
+    async fn is_member(&self, identities: &MononokeIdentitySet) -> bool {
+        let key = (identities.clone(), Vec::new(), self.resource.clone());
+        if let Some(result) = self.checks.get(&key) {
+            return result;
+        }
+        let result = self.inner.is_member(identities).await;
+        self.checks.insert(key, result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use fbinit::FacebookInit;
+
+    use super::*;
+    use crate::MemberAllowlist;
+
+    fn ids(ids: &[&str]) -> Result<MononokeIdentitySet> {
+        let mut set = MononokeIdentitySet::new();
+        for id in ids {
+            set.insert(id.parse()?);
+        }
+        Ok(set)
+    }
+
+    /// Provider that allows `USER:user1` everything, and counts how many
+    /// times it is asked for a checker, how many checks are made and how many
+    /// batches of checks are made.
+    #[derive(Default)]
+    struct CountingAclProvider {
+        fetches: Arc<AtomicUsize>,
+        checks: Arc<AtomicUsize>,
+        batches: Arc<AtomicUsize>,
+    }
+
+    struct CountingChecker {
+        checks: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl PermissionChecker for CountingChecker {
+        async fn check_set(&self, accessors: &MononokeIdentitySet, _actions: &[&str]) -> bool {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            accessors.contains(&"USER:user1".parse().unwrap())
+        }
+    }
+
+    impl CountingAclProvider {
+        fn checker(&self) -> Result<BoxPermissionChecker> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(CountingChecker {
+                checks: self.checks.clone(),
+            }))
+        }
+    }
+
+    #[async_trait]
+    impl AclProvider for CountingAclProvider {
+        async fn repo_acl(&self, _name: &str) -> Result<BoxPermissionChecker> {
+            self.checker()
+        }
+
+        async fn repo_region_acl(&self, _name: &str) -> Result<BoxPermissionChecker> {
+            self.checker()
+        }
+
+        async fn tier_acl(&self, _name: &str) -> Result<BoxPermissionChecker> {
+            self.checker()
+        }
+
+        async fn group(&self, _name: &str) -> Result<BoxMembershipChecker> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(MemberAllowlist::new(ids(&["USER:user1"])?))
+        }
+
+        async fn admin_group(&self) -> Result<BoxMembershipChecker> {
+            self.group("admin").await
+        }
+
+        async fn reviewers_group(&self) -> Result<BoxMembershipChecker> {
+            self.group("reviewers").await
+        }
+
+        async fn check_permissions_batch(
+            &self,
+            checks: &[PermissionCheck],
+        ) -> Result<HashMap<PermissionCheck, bool>> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            let user1 = "USER:user1".parse()?;
+            Ok(checks
+                .iter()
+                .map(|check| (check.clone(), check.0.contains(&user1)))
+                .collect())
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_cached_checks(_fb: FacebookInit) -> Result<()> {
+        let inner = CountingAclProvider::default();
+        let fetches = inner.fetches.clone();
+        let checks = inner.checks.clone();
+        let prov = CachedAclProvider::new(
+            inner,
+            CachingAclOptions::new(1000, Duration::from_secs(3600)),
+        );
+
+        for _ in 0..2 {
+            let repo1 = prov.repo_acl("repo1").await?;
+            assert!(repo1.check_set(&ids(&["USER:user1"])?, &["read"]).await);
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+        // Different identities, actions and resources are cached separately.
+        let repo1 = prov.repo_acl("repo1").await?;
+        assert!(!repo1.check_set(&ids(&["USER:impostor"])?, &["read"]).await);
+        assert!(repo1.check_set(&ids(&["USER:user1"])?, &["write"]).await);
+        let repo2 = prov.repo_acl("repo2").await?;
+        assert!(repo2.check_set(&ids(&["USER:user1"])?, &["read"]).await);
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert_eq!(checks.load(Ordering::SeqCst), 4);
+
+        for _ in 0..2 {
+            let admins = prov.admin_group().await?;
+            assert!(admins.is_member(&ids(&["USER:user1"])?).await);
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_batch_checks(_fb: FacebookInit) -> Result<()> {
+        let inner = CountingAclProvider::default();
+        let batches = inner.batches.clone();
+        let checks = inner.checks.clone();
+        let prov = CachedAclProvider::new(
+            inner,
+            CachingAclOptions::new(1000, Duration::from_secs(3600)),
+        );
+
+        let batch: Vec<PermissionCheck> = (0..100)
+            .map(|i| {
+                let user = if i % 2 == 0 {
+                    "USER:user1"
+                } else {
+                    "USER:user2"
+                };
+                (
+                    ids(&[user]).unwrap(),
+                    "read".to_string(),
+                    AclResource::Repo(format!("repo{}", i)),
+                )
+            })
+            .collect();
+        let results = prov.check_permissions_batch(&batch).await?;
+        assert_eq!(results.len(), 100);
+        for (i, check) in batch.iter().enumerate() {
+            assert_eq!(results[check], i % 2 == 0);
+        }
+        assert_eq!(batches.load(Ordering::SeqCst), 1);
+
+        // The batch results are cached, both for further batches and for
+        // individual checks.
+        assert_eq!(prov.check_permissions_batch(&batch).await?, results);
+        assert_eq!(batches.load(Ordering::SeqCst), 1);
+        let repo0 = prov.repo_acl("repo0").await?;
+        assert!(repo0.check_set(&ids(&["USER:user1"])?, &["read"]).await);
+        assert_eq!(checks.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_cache_ttl(_fb: FacebookInit) -> Result<()> {
+        let inner = CountingAclProvider::default();
+        let fetches = inner.fetches.clone();
+        let checks = inner.checks.clone();
+        let prov = CachedAclProvider::new(
+            inner,
+            CachingAclOptions::new(1000, Duration::from_millis(50)),
+        );
+
+        let repo1 = prov.repo_acl("repo1").await?;
+        assert!(repo1.check_set(&ids(&["USER:user1"])?, &["read"]).await);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let repo1 = prov.repo_acl("repo1").await?;
+        assert!(repo1.check_set(&ids(&["USER:user1"])?, &["read"]).await);
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+}
@@ -134,6 +134,7 @@ mod test {
     use fbinit::FacebookInit;
 
     use super::*;
+    use crate::AclResource;
 
     fn ids(ids: &[&str]) -> Result<MononokeIdentitySet> {
         let mut set = MononokeIdentitySet::new();
@@ -185,4 +186,39 @@ mod test {
         );
         Ok(())
     }
+
+    #[fbinit::test]
+    async fn batch_checks(_fb: FacebookInit) -> Result<()> {
+        let json = r##"
+            {
+                "repos": {
+                    "repo1": {
+                        "actions": {
+                            "read": ["USER:user1"]
+                        }
+                    }
+                },
+                "groups": {
+                    "admin": ["USER:user2"]
+                }
+            }
+        "##;
+        let acls = serde_json::from_str(json)?;
+        let prov = InternalAclProvider::new(acls);
+        let repo1 = AclResource::Repo("repo1".to_string());
+        let checks = vec![
+            (ids(&["USER:user1"])?, "read".to_string(), repo1.clone()),
+            (ids(&["USER:user1"])?, "write".to_string(), repo1.clone()),
+            (ids(&["USER:user2"])?, "read".to_string(), repo1),
+            (
+                ids(&["USER:user2"])?,
+                "anything".to_string(),
+                AclResource::AdminGroup,
+            ),
+        ];
+        let results = prov.check_permissions_batch(&checks).await?;
+        let allowed: Vec<bool> = checks.iter().map(|check| results[check]).collect();
+        assert_eq!(allowed, [true, false, false, true]);
+        Ok(())
+    }
 }
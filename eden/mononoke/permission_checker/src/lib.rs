@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+mod audit;
+mod caching;
 mod checker;
 #[cfg(fbcode_build)]
 mod facebook;
@@ -15,6 +17,10 @@ mod membership;
 mod oss;
 mod provider;
 
+pub use audit::AuditEntry;
+pub use audit::AuditingAclProvider;
+pub use caching::CachedAclProvider;
+pub use caching::CachingAclOptions;
 pub use checker::ArcPermissionChecker;
 pub use checker::BoxPermissionChecker;
 pub use checker::PermissionChecker;
@@ -23,6 +29,8 @@ pub use identity::pretty_print;
 pub use identity::MononokeIdentity;
 pub use identity::MononokeIdentitySet;
 pub use identity::MononokeIdentitySetExt;
+pub use internal::Acl;
+pub use internal::Acls;
 pub use internal::InternalAclProvider;
 pub use membership::AlwaysMember;
 pub use membership::ArcMembershipChecker;
@@ -31,6 +39,8 @@ pub use membership::MemberAllowlist;
 pub use membership::MembershipChecker;
 pub use membership::NeverMember;
 pub use provider::AclProvider;
+pub use provider::AclResource;
+pub use provider::PermissionCheck;
 
 #[cfg(fbcode_build)]
 pub type DefaultAclProvider = facebook::HipsterAclProvider;
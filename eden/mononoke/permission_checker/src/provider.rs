@@ -5,11 +5,58 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
 use anyhow::Result;
 use async_trait::async_trait;
+use slog::Logger;
 
+use crate::audit::AuditingAclProvider;
+use crate::audit::DEFAULT_MAX_RECENT_DENIALS;
 use crate::BoxMembershipChecker;
 use crate::BoxPermissionChecker;
+use crate::MononokeIdentitySet;
+
+/// Something that is controlled by an access control list or a group.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum AclResource {
+    Repo(String),
+    RepoRegion(String),
+    Tier(String),
+    Group(String),
+    AdminGroup,
+    ReviewersGroup,
+}
+
+impl AclResource {
+    /// Whether this resource is a group, which is checked for membership
+    /// regardless of the action.
+    pub fn is_group(&self) -> bool {
+        matches!(
+            self,
+            AclResource::Group(_) | AclResource::AdminGroup | AclResource::ReviewersGroup
+        )
+    }
+}
+
+impl fmt::Display for AclResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AclResource::Repo(name) => write!(f, "repo:{}", name),
+            AclResource::RepoRegion(name) => write!(f, "repo_region:{}", name),
+            AclResource::Tier(name) => write!(f, "tier:{}", name),
+            AclResource::Group(name) => write!(f, "group:{}", name),
+            AclResource::AdminGroup => write!(f, "admin_group"),
+            AclResource::ReviewersGroup => write!(f, "reviewers_group"),
+        }
+    }
+}
+
+/// A single permission check: whether the principal may perform the action
+/// on the resource.
+pub type PermissionCheck = (MononokeIdentitySet, String, AclResource);
 
 /// A provider of access control lists and groups.
 ///
@@ -38,4 +85,115 @@ pub trait AclProvider: Send + Sync {
 
     /// Returns a membership checker for the group that may review changes.
     async fn reviewers_group(&self) -> Result<BoxMembershipChecker>;
+
+    /// Checks whether `principal` may perform `action` on `resource`. For
+    /// groups, this checks whether `principal` is a member.
+    async fn check_permission(
+        &self,
+        principal: &MononokeIdentitySet,
+        action: &str,
+        resource: &AclResource,
+    ) -> Result<bool> {
+        match resource {
+            AclResource::Repo(name) => Ok(self
+                .repo_acl(name)
+                .await?
+                .check_set(principal, &[action])
+                .await),
+            AclResource::RepoRegion(name) => Ok(self
+                .repo_region_acl(name)
+                .await?
+                .check_set(principal, &[action])
+                .await),
+            AclResource::Tier(name) => Ok(self
+                .tier_acl(name)
+                .await?
+                .check_set(principal, &[action])
+                .await),
+            AclResource::Group(name) => Ok(self.group(name).await?.is_member(principal).await),
+            AclResource::AdminGroup => Ok(self.admin_group().await?.is_member(principal).await),
+            AclResource::ReviewersGroup => {
+                Ok(self.reviewers_group().await?.is_member(principal).await)
+            }
+        }
+    }
+
+    /// Makes many permission checks at once.
+    ///
+    /// By default the checks are made one at a time with
+    /// [`AclProvider::check_permission`]. Providers backed by a service with
+    /// a batch API should override this to make a single request.
+    async fn check_permissions_batch(
+        &self,
+        checks: &[PermissionCheck],
+    ) -> Result<HashMap<PermissionCheck, bool>> {
+        let mut results = HashMap::with_capacity(checks.len());
+        for check in checks {
+            let (principal, action, resource) = check;
+            let allowed = self.check_permission(principal, action, resource).await?;
+            results.insert(check.clone(), allowed);
+        }
+        Ok(results)
+    }
+
+    /// Name of this provider, as reported in audit logs.
+    fn provider_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Wraps this provider so that every denied access is logged to
+    /// `logger`, and the most recent denials are kept in memory.
+    fn with_audit_log(self, logger: Logger) -> AuditingAclProvider<Self>
+    where
+        Self: Sized,
+    {
+        AuditingAclProvider::new(self, logger, DEFAULT_MAX_RECENT_DENIALS)
+    }
+}
+
+#[async_trait]
+impl<A: AclProvider + ?Sized> AclProvider for Arc<A> {
+    async fn repo_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        (**self).repo_acl(name).await
+    }
+
+    async fn repo_region_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        (**self).repo_region_acl(name).await
+    }
+
+    async fn tier_acl(&self, name: &str) -> Result<BoxPermissionChecker> {
+        (**self).tier_acl(name).await
+    }
+
+    async fn group(&self, name: &str) -> Result<BoxMembershipChecker> {
+        (**self).group(name).await
+    }
+
+    async fn admin_group(&self) -> Result<BoxMembershipChecker> {
+        (**self).admin_group().await
+    }
+
+    async fn reviewers_group(&self) -> Result<BoxMembershipChecker> {
+        (**self).reviewers_group().await
+    }
+
+    async fn check_permission(
+        &self,
+        principal: &MononokeIdentitySet,
+        action: &str,
+        resource: &AclResource,
+    ) -> Result<bool> {
+        (**self).check_permission(principal, action, resource).await
+    }
+
+    async fn check_permissions_batch(
+        &self,
+        checks: &[PermissionCheck],
+    ) -> Result<HashMap<PermissionCheck, bool>> {
+        (**self).check_permissions_batch(checks).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        (**self).provider_name()
+    }
 }
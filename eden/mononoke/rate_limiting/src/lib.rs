@@ -8,6 +8,7 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::anyhow;
@@ -40,6 +41,9 @@ pub mod config;
 
 pub type LoadCost = f64;
 pub type BoxRateLimiter = Box<dyn RateLimiter + Send + Sync + 'static>;
+/// Rate limiter shared between sessions, which can be boxed into a
+/// `BoxRateLimiter` for each of them.
+pub type ArcRateLimiter = Arc<dyn RateLimiter + Send + Sync + 'static>;
 
 #[async_trait]
 pub trait RateLimiter {
@@ -60,6 +64,37 @@ pub trait RateLimiter {
     fn total_file_changes_limit(&self) -> Option<RateLimitBody>;
 }
 
+#[async_trait]
+impl<T: RateLimiter + Send + Sync + ?Sized> RateLimiter for Arc<T> {
+    async fn check_rate_limit(
+        &self,
+        metric: Metric,
+        identities: &MononokeIdentitySet,
+    ) -> Result<Result<(), RateLimitReason>, Error> {
+        (**self).check_rate_limit(metric, identities).await
+    }
+
+    fn check_load_shed(&self, identities: &MononokeIdentitySet) -> Result<(), RateLimitReason> {
+        (**self).check_load_shed(identities)
+    }
+
+    fn bump_load(&self, metric: Metric, load: LoadCost) {
+        (**self).bump_load(metric, load)
+    }
+
+    fn category(&self) -> &str {
+        (**self).category()
+    }
+
+    fn commits_per_author_limit(&self) -> Option<RateLimitBody> {
+        (**self).commits_per_author_limit()
+    }
+
+    fn total_file_changes_limit(&self) -> Option<RateLimitBody> {
+        (**self).total_file_changes_limit()
+    }
+}
+
 define_stats! {
     load_shed_counter: dynamic_singleton_counter("{}", (key: String)),
 }
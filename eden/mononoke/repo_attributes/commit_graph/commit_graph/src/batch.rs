@@ -0,0 +1,140 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use commit_graph_types::storage::Prefetch;
+use context::CoreContext;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use mononoke_types::ChangesetId;
+
+use crate::CommitGraph;
+
+/// Options for traversing the commit graph from a batch of changesets.
+#[derive(Clone, Debug)]
+pub struct BatchTraversalOptions {
+    /// Maximum number of traversals running concurrently.
+    pub parallelism: usize,
+    /// Incremented each time the parents of a changeset are found in the
+    /// cache shared by the traversals of the batch, instead of being
+    /// fetched from storage.
+    pub cache_hits: Arc<AtomicU64>,
+}
+
+impl Default for BatchTraversalOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: 100,
+            cache_hits: Default::default(),
+        }
+    }
+}
+
+/// Parents of the changesets visited so far by the traversals of a batch.
+type ParentsCache = Mutex<HashMap<ChangesetId, Vec<ChangesetId>>>;
+
+impl CommitGraph {
+    /// Returns a stream of all ancestors of each of the given changesets that
+    /// are at most max_depth edges away from it, including the changeset
+    /// itself, in breadth-first order.
+    ///
+    /// The traversals run concurrently, up to options.parallelism at a time,
+    /// and the results are yielded in the order the traversals complete.
+    /// Parents fetched by one traversal are reused by the others, which is
+    /// useful when the changesets of the batch have ancestors in common.
+    pub fn ancestors_batch(
+        &self,
+        ctx: &CoreContext,
+        commits: Vec<ChangesetId>,
+        max_depth: Option<usize>,
+        options: BatchTraversalOptions,
+    ) -> BoxStream<'static, Result<(ChangesetId, Vec<ChangesetId>)>> {
+        let graph = Arc::new(self.clone());
+        let ctx = ctx.clone();
+        let cache: Arc<ParentsCache> = Default::default();
+        let cache_hits = options.cache_hits;
+
+        stream::iter(commits)
+            .map(move |cs_id| {
+                let graph = graph.clone();
+                let ctx = ctx.clone();
+                let cache = cache.clone();
+                let cache_hits = cache_hits.clone();
+                async move {
+                    let ancestors = graph
+                        .cached_ancestors(&ctx, cs_id, max_depth, &cache, &cache_hits)
+                        .await?;
+                    Ok((cs_id, ancestors))
+                }
+            })
+            .buffer_unordered(options.parallelism.max(1))
+            .boxed()
+    }
+
+    async fn cached_ancestors(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        max_depth: Option<usize>,
+        cache: &ParentsCache,
+        cache_hits: &AtomicU64,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut ancestors = vec![cs_id];
+        let mut visited = HashSet::from([cs_id]);
+        let mut current = vec![cs_id];
+        let mut depth = 0;
+
+        while !current.is_empty() {
+            if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                break;
+            }
+            let mut parents = vec![];
+            let mut missing = vec![];
+            {
+                let cache = cache.lock().expect("poisoned lock");
+                for cs_id in &current {
+                    match cache.get(cs_id) {
+                        Some(cs_parents) => parents.extend(cs_parents.iter().copied()),
+                        None => missing.push(*cs_id),
+                    }
+                }
+            }
+            cache_hits.fetch_add((current.len() - missing.len()) as u64, Ordering::Relaxed);
+
+            if !missing.is_empty() {
+                let all_edges = self
+                    .storage
+                    .fetch_many_edges_required(ctx, &missing, Prefetch::None)
+                    .await?;
+                let mut cache = cache.lock().expect("poisoned lock");
+                for (cs_id, edges) in all_edges {
+                    let cs_parents: Vec<_> =
+                        edges.parents.iter().map(|parent| parent.cs_id).collect();
+                    parents.extend(cs_parents.iter().copied());
+                    cache.insert(cs_id, cs_parents);
+                }
+            }
+
+            current = parents
+                .into_iter()
+                .filter(|parent| visited.insert(*parent))
+                .collect();
+            ancestors.extend(current.iter().copied());
+            depth += 1;
+        }
+
+        Ok(ancestors)
+    }
+}
@@ -236,8 +236,8 @@ impl CommitGraph {
         .await
     }
 
-    /// Returns the lowest common ancestor of two changesets.
-    async fn lowest_common_ancestor<F, G, H>(
+    /// Returns the lowest common ancestor of two changesets in a tree.
+    async fn tree_lowest_common_ancestor<F, G, H>(
         &self,
         ctx: &CoreContext,
         cs_id1: ChangesetId,
@@ -323,7 +323,7 @@ impl CommitGraph {
         cs_id1: ChangesetId,
         cs_id2: ChangesetId,
     ) -> Result<Option<ChangesetNode>> {
-        self.lowest_common_ancestor(
+        self.tree_lowest_common_ancestor(
             ctx,
             cs_id1,
             cs_id2,
@@ -341,7 +341,7 @@ impl CommitGraph {
         cs_id1: ChangesetId,
         cs_id2: ChangesetId,
     ) -> Result<Option<ChangesetNode>> {
-        self.lowest_common_ancestor(
+        self.tree_lowest_common_ancestor(
             ctx,
             cs_id1,
             cs_id2,
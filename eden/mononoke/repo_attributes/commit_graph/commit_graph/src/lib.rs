@@ -9,6 +9,9 @@
 //!
 //! The graph of all commits in the repository.
 
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
 
@@ -19,6 +22,7 @@ use commit_graph_types::edges::ChangesetParents;
 use commit_graph_types::storage::CommitGraphStorage;
 use commit_graph_types::storage::Prefetch;
 use context::CoreContext;
+use futures::future;
 use futures::stream;
 use futures::stream::BoxStream;
 use futures::Future;
@@ -30,8 +34,10 @@ use mononoke_types::ChangesetIdsResolvedFromPrefix;
 use mononoke_types::Generation;
 
 pub use crate::ancestors_stream::AncestorsStreamBuilder;
+pub use crate::batch::BatchTraversalOptions;
 
 mod ancestors_stream;
+mod batch;
 mod compat;
 mod core;
 mod frontier;
@@ -197,6 +203,39 @@ impl CommitGraph {
         Ok(frontier.highest_generation_contains(ancestor, target_gen))
     }
 
+    /// Returns all ancestors of a changeset that are at most limit edges
+    /// away from it, including the changeset itself.
+    ///
+    /// Unlike is_ancestor, this can't skip any part of the graph, so the
+    /// cost is proportional to the number of returned changesets.
+    pub async fn reachable_set(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        limit: usize,
+    ) -> Result<HashSet<ChangesetId>> {
+        let mut reachable = HashSet::from([cs_id]);
+        let mut current = vec![cs_id];
+
+        for _ in 0..limit {
+            if current.is_empty() {
+                break;
+            }
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &current, Prefetch::None)
+                .await?;
+            current = all_edges
+                .into_values()
+                .flat_map(|edges| edges.parents)
+                .map(|parent| parent.cs_id)
+                .filter(|parent| reachable.insert(*parent))
+                .collect();
+        }
+
+        Ok(reachable)
+    }
+
     /// Returns a stream of all ancestors of any changeset in heads,
     /// excluding any ancestor of any changeset in common, in reverse
     /// topological order.
@@ -245,6 +284,84 @@ impl CommitGraph {
         Ok(stream::iter(range.into_iter().rev()).boxed())
     }
 
+    /// Returns a stream of all descendants of any changeset in roots,
+    /// including the roots themselves, in topological order.
+    ///
+    /// The descendants are loaded lazily, one generation at a time, by
+    /// following the children of the lowest generation changesets yielded
+    /// so far. If max_depth is given, only descendants reachable from one
+    /// of the roots in at most max_depth steps are returned.
+    pub async fn topological_sort_stream(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        max_depth: Option<usize>,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        let frontier = self.frontier(ctx, roots).await?;
+        // Length of the shortest path from a root to each changeset of the
+        // frontier.
+        let depths: HashMap<ChangesetId, usize> = frontier
+            .changesets()
+            .into_iter()
+            .map(|cs_id| (cs_id, 0))
+            .collect();
+        let graph = self.clone();
+        let ctx = ctx.clone();
+
+        let stream = stream::try_unfold((frontier, depths), move |(mut frontier, mut depths)| {
+            let graph = graph.clone();
+            let ctx = ctx.clone();
+            async move {
+                // All parents of a changeset have lower generations, so
+                // any of them that descends from a root has been popped
+                // before the changeset itself.
+                let cs_ids = match frontier.pop_first() {
+                    Some((_, cs_ids)) => cs_ids,
+                    None => return anyhow::Ok(None),
+                };
+                let cs_ids = cs_ids
+                    .into_iter()
+                    .map(|cs_id| (cs_id, depths.remove(&cs_id).unwrap_or_default()))
+                    .collect::<Vec<_>>();
+
+                let all_children = future::try_join_all(
+                    cs_ids
+                        .iter()
+                        .filter(|(_, depth)| !max_depth.is_some_and(|max| *depth >= max))
+                        .map(|(cs_id, depth)| {
+                            borrowed!(graph, ctx);
+                            async move {
+                                let children = graph.changeset_children(ctx, *cs_id).await?;
+                                anyhow::Ok((children, depth + 1))
+                            }
+                        }),
+                )
+                .await?;
+
+                let mut new_children = vec![];
+                for (children, depth) in all_children {
+                    for child in children {
+                        depths
+                            .entry(child)
+                            .and_modify(|child_depth| *child_depth = depth.min(*child_depth))
+                            .or_insert_with(|| {
+                                new_children.push(child);
+                                depth
+                            });
+                    }
+                }
+                if !new_children.is_empty() {
+                    frontier.extend(graph.frontier(&ctx, new_children).await?.into_flat_iter());
+                }
+
+                let cs_ids = cs_ids.into_iter().map(|(cs_id, _)| cs_id);
+                Ok(Some((stream::iter(cs_ids.map(Ok)), (frontier, depths))))
+            }
+        });
+
+        Ok(stream.try_flatten().boxed())
+    }
+
     /// Returns all of the highest generation changesets that
     /// are ancestors of both u and v, sorted by changeset id.
     pub async fn common_base(
@@ -312,6 +429,96 @@ impl CommitGraph {
         }
     }
 
+    /// Returns the lowest common ancestor (merge base) of u and v, or None
+    /// if they have no common ancestors.
+    ///
+    /// If there are several lowest common ancestors, returns the one with
+    /// the highest generation, and the lowest changeset id amongst those.
+    pub async fn lowest_common_ancestor(
+        &self,
+        ctx: &CoreContext,
+        u: ChangesetId,
+        v: ChangesetId,
+    ) -> Result<Option<ChangesetId>> {
+        // A common ancestor with the highest generation can't be an ancestor
+        // of another common ancestor, so it's a lowest common ancestor.
+        Ok(self.common_base(ctx, u, v).await?.into_iter().next())
+    }
+
+    /// Returns all lowest common ancestors of u and v, which are the common
+    /// ancestors that aren't ancestors of another common ancestor, sorted by
+    /// decreasing generation and then by changeset id.
+    pub async fn all_common_ancestors(
+        &self,
+        ctx: &CoreContext,
+        u: ChangesetId,
+        v: ChangesetId,
+    ) -> Result<Vec<ChangesetId>> {
+        const U_ANCESTOR: u8 = 1;
+        const V_ANCESTOR: u8 = 2;
+        const COMMON_ANCESTOR: u8 = U_ANCESTOR | V_ANCESTOR;
+        // Set on ancestors of lowest common ancestors, which can't be lowest
+        // common ancestors themselves.
+        const STALE: u8 = 4;
+
+        let (u_generation, v_generation) = futures::try_join!(
+            self.changeset_generation_required(ctx, u),
+            self.changeset_generation_required(ctx, v),
+        )?;
+
+        let mut flags: HashMap<ChangesetId, u8> = HashMap::new();
+        *flags.entry(u).or_default() |= U_ANCESTOR;
+        *flags.entry(v).or_default() |= V_ANCESTOR;
+
+        // Changesets are visited from the highest generation, so that all
+        // descendants of a changeset that could flag it are visited before it.
+        let mut queue = BinaryHeap::new();
+        queue.push((u_generation, u));
+        if u != v {
+            queue.push((v_generation, v));
+        }
+
+        // Queued changesets that aren't stale. Once there are none left, no
+        // more lowest common ancestors can be found.
+        let mut queued_non_stale: HashSet<ChangesetId> =
+            queue.iter().map(|(_, cs_id)| *cs_id).collect();
+
+        let mut common_ancestors = vec![];
+        while !queued_non_stale.is_empty() {
+            let (generation, cs_id) = match queue.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            queued_non_stale.remove(&cs_id);
+
+            let mut cs_flags = flags[&cs_id];
+            if cs_flags & (COMMON_ANCESTOR | STALE) == COMMON_ANCESTOR {
+                common_ancestors.push((generation, cs_id));
+                cs_flags |= STALE;
+            }
+
+            let edges = self.storage.fetch_edges_required(ctx, cs_id).await?;
+            for parent in edges.parents {
+                let parent_flags = flags.entry(parent.cs_id).or_insert_with(|| {
+                    queue.push((parent.generation, parent.cs_id));
+                    0
+                });
+                *parent_flags |= cs_flags;
+                if *parent_flags & STALE == 0 {
+                    queued_non_stale.insert(parent.cs_id);
+                } else {
+                    queued_non_stale.remove(&parent.cs_id);
+                }
+            }
+        }
+
+        common_ancestors.sort_by_key(|(generation, cs_id)| (Reverse(*generation), *cs_id));
+        Ok(common_ancestors
+            .into_iter()
+            .map(|(_, cs_id)| cs_id)
+            .collect())
+    }
+
     /// Slices ancestors of heads into a sequence of slices for processing.
     ///
     /// Each slice contains a frontier of changesets within a generation range, returning
@@ -7,16 +7,20 @@
 
 use std::collections::HashSet;
 use std::str::FromStr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use anyhow::Result;
 use cloned::cloned;
+use commit_graph::BatchTraversalOptions;
 use commit_graph::CommitGraph;
 use commit_graph_types::storage::CommitGraphStorage;
 use context::CoreContext;
+use futures::TryStreamExt;
 use in_memory_commit_graph_storage::InMemoryCommitGraphStorage;
 use mononoke_types::ChangesetIdPrefix;
 use mononoke_types::ChangesetIdsResolvedFromPrefix;
+use mononoke_types::Generation;
 use mononoke_types::RepositoryId;
 use smallvec::smallvec;
 use vec1::vec1;
@@ -37,11 +41,16 @@ macro_rules! impl_commit_graph_tests {
             test_find_by_prefix,
             test_add_recursive,
             test_add_recursive_many_changesets,
+            test_generation_numbers,
             test_ancestors_frontier_with,
             test_range_stream,
+            test_topological_sort_stream,
             test_common_base,
+            test_lowest_common_ancestors,
             test_slice_ancestors,
             test_children,
+            test_reachable_set,
+            test_ancestors_batch,
             test_ancestors_difference_segments_1,
             test_ancestors_difference_segments_2,
         );
@@ -623,6 +632,73 @@ pub async fn test_add_recursive_many_changesets(
     Ok(())
 }
 
+pub async fn test_generation_numbers(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H
+          \     /
+           E---F
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Roots have generation 1, and other changesets are one generation
+    // above their highest generation parent.
+    for (name, generation) in [
+        ("A", 1),
+        ("B", 2),
+        ("C", 3),
+        ("D", 4),
+        ("E", 2),
+        ("F", 3),
+        ("G", 5),
+        ("H", 6),
+    ] {
+        assert_eq!(
+            graph.changeset_generation(&ctx, name_cs_id(name)).await?,
+            Some(Generation::new(generation)),
+            "generation of {}",
+            name
+        );
+    }
+    assert_eq!(
+        graph
+            .changeset_generation(&ctx, name_cs_id("missing"))
+            .await?,
+        None
+    );
+
+    // Generation numbers are assigned incrementally when adding changesets,
+    // without walking the rest of the graph.
+    let mut parent = name_cs_id("H");
+    let mut parent_generation = graph.changeset_generation_required(&ctx, parent).await?;
+    for i in 0..10_000 {
+        let cs_id = name_cs_id(&format!("linear{}", i));
+        graph.add(&ctx, cs_id, smallvec![parent]).await?;
+
+        let generation = graph.changeset_generation_required(&ctx, cs_id).await?;
+        assert_eq!(generation.value(), parent_generation.value() + 1);
+        parent = cs_id;
+        parent_generation = generation;
+    }
+    assert_eq!(parent_generation, Generation::new(10_006));
+
+    // Generation numbers are persisted in the storage, so a new commit
+    // graph over the same storage sees the same ones.
+    let graph = CommitGraph::new(storage);
+    assert_eq!(
+        graph.changeset_generation(&ctx, parent).await?,
+        Some(Generation::new(10_006))
+    );
+
+    Ok(())
+}
+
 pub async fn test_ancestors_frontier_with(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -757,6 +833,67 @@ pub async fn test_range_stream(
     Ok(())
 }
 
+pub async fn test_topological_sort_stream(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-O-P
+          \ /
+           N
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_topological_sort_stream(&graph, &ctx, vec!["L"], None, vec!["L", "M", "N", "O", "P"])
+        .await?;
+    assert_topological_sort_stream(&graph, &ctx, vec!["L"], Some(1), vec!["L", "M", "N"]).await?;
+    assert_topological_sort_stream(&graph, &ctx, vec!["M"], None, vec!["M", "O", "P"]).await?;
+    assert_topological_sort_stream(
+        &graph,
+        &ctx,
+        vec!["A"],
+        None,
+        vec!["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+    assert_topological_sort_stream(
+        &graph,
+        &ctx,
+        vec!["B"],
+        Some(2),
+        vec!["B", "C", "D", "E", "F"],
+    )
+    .await?;
+    assert_topological_sort_stream(
+        &graph,
+        &ctx,
+        vec!["C", "F"],
+        Some(1),
+        vec!["C", "D", "F", "G"],
+    )
+    .await?;
+    assert_topological_sort_stream(&graph, &ctx, vec!["A", "D"], Some(0), vec!["A", "D"]).await?;
+    assert_topological_sort_stream(
+        &graph,
+        &ctx,
+        vec!["A", "D"],
+        Some(1),
+        vec!["A", "B", "D", "G"],
+    )
+    .await?;
+    assert_topological_sort_stream(&graph, &ctx, vec!["K", "P"], None, vec!["K", "P"]).await?;
+
+    Ok(())
+}
+
 pub async fn test_common_base(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -790,6 +927,67 @@ pub async fn test_common_base(
     Ok(())
 }
 
+pub async fn test_lowest_common_ancestors(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+        A-B-C-D-E
+           \
+            F-G
+        "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Linear history, where one changeset is an ancestor of the other.
+    assert_lowest_common_ancestors(&graph, &ctx, "E", "C", Some("C"), vec!["C"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "C", "E", Some("C"), vec!["C"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "D", "D", Some("D"), vec!["D"]).await?;
+
+    // Divergent branches.
+    assert_lowest_common_ancestors(&graph, &ctx, "E", "G", Some("B"), vec!["B"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "F", "C", Some("B"), vec!["B"]).await?;
+
+    let graph = from_dag(
+        &ctx,
+        r##"
+          M
+         / \
+        |   |
+        | N |
+        |/ \|        Z
+        P   Q       /|\
+        |   |      W X Y
+        |   O      | | |
+         \ /       T U V
+          L         \|/
+                     S
+        "##,
+        storage,
+    )
+    .await?;
+
+    // Octopus merge.
+    assert_lowest_common_ancestors(&graph, &ctx, "W", "X", Some("S"), vec!["S"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "Z", "W", Some("W"), vec!["W"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "Y", "Z", Some("Y"), vec!["Y"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "Z", "S", Some("S"), vec!["S"]).await?;
+
+    // Criss-cross merges with multiple lowest common ancestors.
+    assert_lowest_common_ancestors(&graph, &ctx, "M", "N", Some("Q"), vec!["Q", "P"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "M", "O", Some("O"), vec!["O"]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "N", "P", Some("P"), vec!["P"]).await?;
+
+    // No common ancestors.
+    assert_lowest_common_ancestors(&graph, &ctx, "M", "Z", None, vec![]).await?;
+    assert_lowest_common_ancestors(&graph, &ctx, "E", "N", None, vec![]).await?;
+
+    Ok(())
+}
+
 pub async fn test_slice_ancestors(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -894,6 +1092,149 @@ pub async fn test_children(ctx: CoreContext, storage: Arc<dyn CommitGraphStorage
     Ok(())
 }
 
+pub async fn test_reachable_set(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N
+         "##,
+        storage,
+    )
+    .await?;
+
+    assert_reachable_set(&graph, &ctx, "K", 0, vec!["K"]).await?;
+    assert_reachable_set(&graph, &ctx, "K", 1, vec!["K", "J"]).await?;
+    assert_reachable_set(&graph, &ctx, "K", 2, vec!["K", "J", "H", "I"]).await?;
+    assert_reachable_set(&graph, &ctx, "K", 3, vec!["K", "J", "H", "I", "G"]).await?;
+    assert_reachable_set(
+        &graph,
+        &ctx,
+        "K",
+        4,
+        vec!["K", "J", "H", "I", "G", "D", "F"],
+    )
+    .await?;
+    assert_reachable_set(
+        &graph,
+        &ctx,
+        "K",
+        100,
+        vec!["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+    assert_reachable_set(&graph, &ctx, "G", 2, vec!["G", "D", "F", "C", "E"]).await?;
+    assert_reachable_set(&graph, &ctx, "N", 10, vec!["L", "M", "N"]).await?;
+    assert_reachable_set(&graph, &ctx, "A", 10, vec!["A"]).await?;
+
+    // Roots are reachable from all of their descendants, and commits from
+    // themselves, but nothing is reachable across disconnected histories.
+    assert!(
+        graph
+            .is_ancestor(&ctx, name_cs_id("A"), name_cs_id("K"))
+            .await?
+    );
+    assert!(
+        graph
+            .is_ancestor(&ctx, name_cs_id("L"), name_cs_id("N"))
+            .await?
+    );
+    assert!(
+        graph
+            .is_ancestor(&ctx, name_cs_id("K"), name_cs_id("K"))
+            .await?
+    );
+    assert!(
+        !graph
+            .is_ancestor(&ctx, name_cs_id("L"), name_cs_id("K"))
+            .await?
+    );
+    assert!(
+        !graph
+            .is_ancestor(&ctx, name_cs_id("A"), name_cs_id("N"))
+            .await?
+    );
+    assert!(
+        !graph
+            .is_ancestor(&ctx, name_cs_id("I"), name_cs_id("G"))
+            .await?
+    );
+
+    Ok(())
+}
+
+pub async fn test_ancestors_batch(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N
+         "##,
+        storage,
+    )
+    .await?;
+
+    assert_ancestors_batch(&graph, &ctx, vec![], None, vec![]).await?;
+    assert_ancestors_batch(
+        &graph,
+        &ctx,
+        vec!["K", "G", "N"],
+        Some(2),
+        vec![
+            ("K", vec!["K", "J", "H", "I"]),
+            ("G", vec!["G", "D", "F", "C", "E"]),
+            ("N", vec!["N", "M", "L"]),
+        ],
+    )
+    .await?;
+    assert_ancestors_batch(
+        &graph,
+        &ctx,
+        vec!["K", "F", "A"],
+        None,
+        vec![
+            (
+                "K",
+                vec!["K", "J", "H", "I", "G", "D", "F", "C", "E", "B", "A"],
+            ),
+            ("F", vec!["F", "E", "B", "A"]),
+            ("A", vec!["A"]),
+        ],
+    )
+    .await?;
+
+    // Traversals of the same batch share the parents they fetched.
+    let options = BatchTraversalOptions {
+        parallelism: 1,
+        ..Default::default()
+    };
+    let results: Vec<_> = graph
+        .ancestors_batch(
+            &ctx,
+            vec![name_cs_id("K"), name_cs_id("H")],
+            None,
+            options.clone(),
+        )
+        .try_collect()
+        .await?;
+    assert_eq!(results.len(), 2);
+    assert!(options.cache_hits.load(Ordering::Relaxed) > 0);
+
+    Ok(())
+}
+
 pub async fn test_ancestors_difference_segments_1(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -257,6 +257,38 @@ pub async fn assert_range_stream(
     Ok(())
 }
 
+pub async fn assert_topological_sort_stream(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    roots: Vec<&str>,
+    max_depth: Option<usize>,
+    descendants: Vec<&str>,
+) -> Result<()> {
+    let roots = roots.into_iter().map(name_cs_id).collect();
+
+    let topological_sort_cs_ids = graph
+        .topological_sort_stream(ctx, roots, max_depth)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    assert_topological_order(graph, ctx, &topological_sort_cs_ids).await?;
+
+    assert_eq!(
+        topological_sort_cs_ids.len(),
+        descendants.len(),
+        "changesets should be returned only once"
+    );
+    assert_eq!(
+        topological_sort_cs_ids.into_iter().collect::<HashSet<_>>(),
+        descendants
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
 pub async fn assert_ancestors_frontier_with<Property, Out>(
     graph: &CommitGraph,
     ctx: &CoreContext,
@@ -357,6 +389,32 @@ pub async fn assert_common_base(
     Ok(())
 }
 
+pub async fn assert_lowest_common_ancestors(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    u: &str,
+    v: &str,
+    lowest_common_ancestor: Option<&str>,
+    all_common_ancestors: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .lowest_common_ancestor(ctx, name_cs_id(u), name_cs_id(v))
+            .await?,
+        lowest_common_ancestor.map(name_cs_id),
+    );
+    assert_eq!(
+        graph
+            .all_common_ancestors(ctx, name_cs_id(u), name_cs_id(v))
+            .await?,
+        all_common_ancestors
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<Vec<_>>(),
+    );
+    Ok(())
+}
+
 pub async fn assert_slice_ancestors<NeedsProcessing, Out>(
     graph: &CommitGraph,
     ctx: &CoreContext,
@@ -388,6 +446,61 @@ where
     Ok(())
 }
 
+pub async fn assert_reachable_set(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_id: &str,
+    limit: usize,
+    reachable: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph.reachable_set(ctx, name_cs_id(cs_id), limit).await?,
+        reachable
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>(),
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_batch(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_ids: Vec<&str>,
+    max_depth: Option<usize>,
+    ancestors: Vec<(&str, Vec<&str>)>,
+) -> Result<()> {
+    let results: Vec<_> = graph
+        .ancestors_batch(
+            ctx,
+            cs_ids.into_iter().map(name_cs_id).collect(),
+            max_depth,
+            Default::default(),
+        )
+        .try_collect()
+        .await?;
+
+    // Each changeset comes first in its own ancestors, and the others are in
+    // breadth-first order, which isn't unique, so only compare them as sets.
+    for (cs_id, cs_ancestors) in &results {
+        assert_eq!(cs_ancestors.first(), Some(cs_id));
+    }
+    assert_eq!(
+        results
+            .into_iter()
+            .map(|(cs_id, cs_ancestors)| (cs_id, cs_ancestors.into_iter().collect()))
+            .collect::<BTreeMap<_, HashSet<_>>>(),
+        ancestors
+            .into_iter()
+            .map(|(cs_id, cs_ancestors)| (
+                name_cs_id(cs_id),
+                cs_ancestors.into_iter().map(name_cs_id).collect()
+            ))
+            .collect::<BTreeMap<_, HashSet<_>>>(),
+    );
+    Ok(())
+}
+
 pub async fn assert_children(
     graph: &CommitGraph,
     ctx: &CoreContext,
@@ -76,12 +76,12 @@ impl SqlCommitGraphStorageBuilder {
             read_connection: RendezVousConnection::new(
                 self.connections.read_connection,
                 "read",
-                opts,
+                &opts,
             ),
             read_master_connection: RendezVousConnection::new(
                 self.connections.read_master_connection,
                 "read_master",
-                opts,
+                &opts,
             ),
             write_connection: self.connections.write_connection,
         }
@@ -95,15 +95,14 @@ struct RendezVousConnection {
 }
 
 impl RendezVousConnection {
-    fn new(conn: Connection, name: &str, opts: RendezVousOptions) -> Self {
+    fn new(conn: Connection, name: &str, opts: &RendezVousOptions) -> Self {
+        let endpoint = format!("commit_graph.fetch_single.{}", name);
         Self {
             conn,
-            fetch_single: RendezVous::new(
-                TunablesRendezVousController::new(opts),
-                Arc::new(RendezVousStats::new(format!(
-                    "commit_graph.fetch_single.{}",
-                    name
-                ))),
+            fetch_single: RendezVous::new_with_endpoint_options(
+                TunablesRendezVousController::new(opts.clone()),
+                Arc::new(RendezVousStats::new(endpoint.clone())),
+                opts.for_endpoint(&endpoint),
             ),
         }
     }
@@ -9,6 +9,8 @@
 //!
 //! Stores configuration and permission checkers for bookmarks
 
+use std::sync::Arc;
+
 use anyhow::bail;
 use anyhow::Result;
 use bookmarks_types::BookmarkKey;
@@ -16,11 +18,13 @@ use context::CoreContext;
 use metaconfig_types::BookmarkParams;
 use permission_checker::AclProvider;
 use permission_checker::BoxMembershipChecker;
+use permission_checker::MononokeIdentitySet;
 
 /// Repository bookmark attributes.
 #[facet::facet]
 pub struct RepoBookmarkAttrs {
     bookmark_attrs: Vec<BookmarkAttr>,
+    namespaces: Vec<BookmarkNamespace>,
 }
 
 impl RepoBookmarkAttrs {
@@ -34,7 +38,10 @@ impl RepoBookmarkAttrs {
             let attr = BookmarkAttr::new(acl_provider, params).await?;
             bookmark_attrs.push(attr);
         }
-        Ok(RepoBookmarkAttrs { bookmark_attrs })
+        Ok(RepoBookmarkAttrs {
+            bookmark_attrs,
+            namespaces: Vec::new(),
+        })
     }
 
     /// Construct a new RepoBookmarkAttrs for testing.
@@ -46,7 +53,58 @@ impl RepoBookmarkAttrs {
             let attr = BookmarkAttr::new_test(params)?;
             bookmark_attrs.push(attr);
         }
-        Ok(RepoBookmarkAttrs { bookmark_attrs })
+        Ok(RepoBookmarkAttrs {
+            bookmark_attrs,
+            namespaces: Vec::new(),
+        })
+    }
+
+    /// Group bookmarks into namespaces, replacing any previous ones.
+    pub fn with_namespaces(mut self, namespaces: Vec<BookmarkNamespace>) -> Self {
+        self.namespaces = namespaces;
+        self
+    }
+
+    /// Select the namespace of the provided bookmark, which is the one with
+    /// the longest prefix of its name.
+    pub fn namespace(&self, bookmark: &BookmarkKey) -> Option<&BookmarkNamespace> {
+        self.namespaces
+            .iter()
+            .filter(|namespace| bookmark.as_str().starts_with(&namespace.prefix))
+            .max_by_key(|namespace| namespace.prefix.len())
+    }
+
+    /// Select the namespace with the provided prefix
+    pub fn namespace_by_prefix(&self, prefix: &str) -> Option<&BookmarkNamespace> {
+        self.namespaces
+            .iter()
+            .find(|namespace| namespace.prefix == prefix)
+    }
+
+    /// Check if the identities are allowed to write to the namespace of the
+    /// specified bookmark, according to the repo ACL named `repo_acl` from
+    /// the namespace's ACL provider.
+    ///
+    /// Returns None if the bookmark is not in a namespace with its own ACL,
+    /// in which case only the default ACL applies. Writes to a namespace
+    /// with its own ACL are denied if the repo has no ACL to look up.
+    pub async fn is_allowed_namespace_writer(
+        &self,
+        identities: &MononokeIdentitySet,
+        repo_acl: Option<&str>,
+        bookmark: &BookmarkKey,
+    ) -> Option<bool> {
+        let acl_provider = self.namespace(bookmark)?.acl.as_ref()?;
+        let repo_acl = match repo_acl {
+            Some(repo_acl) => repo_acl,
+            None => return Some(false),
+        };
+        let allowed = match acl_provider.repo_acl(repo_acl).await {
+            Ok(acl) => acl.check_set(identities, &["write"]).await,
+            // Writes are denied if the ACL can't be loaded.
+            Err(_) => false,
+        };
+        Some(allowed)
     }
 
     /// Select bookmark params matching provided bookmark
@@ -116,6 +174,17 @@ impl RepoBookmarkAttrs {
     }
 }
 
+/// Bookmarks whose names start with the same prefix, like `team-A/`, which
+/// may have their own ACL for writes.
+#[derive(Clone)]
+pub struct BookmarkNamespace {
+    /// Prefix of the names of all the bookmarks in the namespace
+    pub prefix: String,
+    /// Provider of the ACL that controls writes to the namespace, in
+    /// addition to the default ACL
+    pub acl: Option<Arc<dyn AclProvider>>,
+}
+
 /// Single set of attributes for a bookmark or bookmark pattern.
 pub struct BookmarkAttr {
     params: BookmarkParams,
@@ -151,3 +220,115 @@ impl BookmarkAttr {
         self.membership.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use maplit::btreeset;
+    use maplit::hashmap;
+    use permission_checker::Acl;
+    use permission_checker::Acls;
+    use permission_checker::InternalAclProvider;
+    use permission_checker::MononokeIdentity;
+
+    use super::*;
+
+    fn writers_acl(writers: MononokeIdentitySet) -> Arc<dyn AclProvider> {
+        InternalAclProvider::new(Acls {
+            repos: hashmap! {
+                "repo".to_string() => Arc::new(Acl {
+                    actions: hashmap! { "write".to_string() => writers },
+                }),
+            },
+            repo_regions: Default::default(),
+            tiers: Default::default(),
+            groups: Default::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_namespaces() -> Result<()> {
+        let alice = btreeset! { MononokeIdentity::new("USER", "alice") };
+        let bob = btreeset! { MononokeIdentity::new("USER", "bob") };
+        let attrs = RepoBookmarkAttrs::new_test([])?.with_namespaces(vec![
+            BookmarkNamespace {
+                prefix: "team-A/".to_string(),
+                acl: Some(writers_acl(alice.clone())),
+            },
+            BookmarkNamespace {
+                prefix: "team-B/".to_string(),
+                acl: Some(writers_acl(bob.clone())),
+            },
+            BookmarkNamespace {
+                prefix: "team-B/open/".to_string(),
+                acl: None,
+            },
+        ]);
+
+        let team_a = BookmarkKey::new("team-A/main")?;
+        let team_b = BookmarkKey::new("team-B/main")?;
+        let team_b_open = BookmarkKey::new("team-B/open/main")?;
+        let main = BookmarkKey::new("main")?;
+
+        assert_eq!(
+            attrs.namespace(&team_b_open).map(|ns| ns.prefix.as_str()),
+            Some("team-B/open/")
+        );
+        assert!(attrs.namespace(&main).is_none());
+        assert!(attrs.namespace_by_prefix("team-A/").is_some());
+        assert!(attrs.namespace_by_prefix("team-C/").is_none());
+
+        // Each principal may only write to its own team's namespace.
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&alice, Some("repo"), &team_a)
+                .await,
+            Some(true)
+        );
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&alice, Some("repo"), &team_b)
+                .await,
+            Some(false)
+        );
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&bob, Some("repo"), &team_a)
+                .await,
+            Some(false)
+        );
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&bob, Some("repo"), &team_b)
+                .await,
+            Some(true)
+        );
+
+        // Other bookmarks fall through to the default ACL.
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&alice, Some("repo"), &team_b_open)
+                .await,
+            None
+        );
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&bob, Some("repo"), &main)
+                .await,
+            None
+        );
+
+        // Without a repo ACL to look up, namespace writes are denied.
+        assert_eq!(
+            attrs
+                .is_allowed_namespace_writer(&alice, None, &team_a)
+                .await,
+            Some(false)
+        );
+        assert_eq!(
+            attrs.is_allowed_namespace_writer(&alice, None, &main).await,
+            None
+        );
+
+        Ok(())
+    }
+}
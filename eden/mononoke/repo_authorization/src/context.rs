@@ -392,11 +392,25 @@ impl AuthorizationContext {
             AuthorizationContext::FullAccess => true,
             AuthorizationContext::Identity | AuthorizationContext::DraftOnlyIdentity => {
                 let user = ctx.metadata().unix_name().unwrap_or("svcscm");
-                repo.repo_bookmark_attrs()
+                let allowed_user = repo
+                    .repo_bookmark_attrs()
                     .is_allowed_user(ctx, user, bookmark)
-                    .await
+                    .await;
 
                 // TODO: Check using ctx.identities, and deny if neither are provided.
+
+                // Bookmarks in a namespace with its own ACL also need write
+                // access to the namespace.
+                let allowed_namespace_writer = repo
+                    .repo_bookmark_attrs()
+                    .is_allowed_namespace_writer(
+                        ctx.metadata().identities(),
+                        repo.repo_config().hipster_acl.as_deref(),
+                        bookmark,
+                    )
+                    .await
+                    .unwrap_or(true);
+                allowed_user && allowed_namespace_writer
             }
             AuthorizationContext::Service(service_name) => {
                 // Check this service is permitted to modify this bookmark.
@@ -12,6 +12,7 @@ use std::collections::HashMap;
 use std::future::Future;
 use std::hash::Hash;
 use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use acl_regions::build_acl_regions;
@@ -80,6 +81,7 @@ use deletion_log::SqlDeletionLog;
 use derived_data_client_library::Client as DerivationServiceClient;
 use derived_data_remote::Address;
 use derived_data_remote::DerivationClient;
+use derived_data_remote::FallbackDerivationClient;
 use derived_data_remote::RemoteDerivationOptions;
 use environment::Caching;
 use environment::MononokeEnvironment;
@@ -119,10 +121,13 @@ use mutable_renames::SqlMutableRenamesStore;
 use newfilenodes::NewFilenodesBuilder;
 use parking_lot::Mutex;
 use permission_checker::AclProvider;
+use permission_checker::InternalAclProvider;
 use phases::ArcPhases;
 use preloaded_commit_graph_storage::PreloadedCommitGraphStorage;
 use pushrebase_mutation_mapping::ArcPushrebaseMutationMapping;
 use pushrebase_mutation_mapping::SqlPushrebaseMutationMappingConnection;
+use rate_limiting::ArcRateLimiter;
+use rate_limiting::BoxRateLimiter;
 use readonlyblob::ReadOnlyBlobstore;
 use redactedblobstore::ArcRedactionConfigBlobstore;
 use redactedblobstore::RedactedBlobs;
@@ -133,6 +138,7 @@ use repo_blobstore::ArcRepoBlobstoreUnlinkOps;
 use repo_blobstore::RepoBlobstore;
 use repo_blobstore::RepoBlobstoreUnlinkOps;
 use repo_bookmark_attrs::ArcRepoBookmarkAttrs;
+use repo_bookmark_attrs::BookmarkNamespace;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
 use repo_cross_repo::ArcRepoCrossRepo;
 use repo_cross_repo::RepoCrossRepo;
@@ -231,7 +237,7 @@ pub trait RepoFactoryOverride<T> = Fn(T) -> T + Send + Sync + 'static;
 pub struct RepoFactory {
     pub env: Arc<MononokeEnvironment>,
     sql_factories: RepoFactoryCache<MetadataDatabaseConfig, Arc<MetadataSqlFactory>>,
-    blobstores: RepoFactoryCache<BlobConfig, Arc<dyn Blobstore>>,
+    blobstores: RepoFactoryCache<(Option<String>, BlobConfig), Arc<dyn Blobstore>>,
     redacted_blobs: RepoFactoryCache<MetadataDatabaseConfig, Arc<RedactedBlobs>>,
     blobstore_override: Option<Arc<dyn RepoFactoryOverride<Arc<dyn Blobstore>>>>,
     scrub_handler: Arc<dyn ScrubHandler>,
@@ -306,13 +312,36 @@ impl RepoFactory {
         sql_factory.open::<T>().await
     }
 
-    async fn blobstore_no_cache(&self, config: &BlobConfig) -> Result<Arc<dyn Blobstore>> {
+    /// Returns `repo_name` only if that repo has its own blobstore options or
+    /// blobstore cache shards, so that other repos can share blobstores.
+    fn blobstore_options_override<'a>(&self, repo_name: Option<&'a str>) -> Option<&'a str> {
+        repo_name.filter(|repo_name| {
+            self.env.blobstore_options.has_override(repo_name)
+                || self
+                    .env
+                    .blobstore_cache_shard_overrides
+                    .contains_key(*repo_name)
+        })
+    }
+
+    fn blobstore_options(&self, repo_name: Option<&str>) -> &BlobstoreOptions {
+        match repo_name {
+            Some(repo_name) => self.env.blobstore_options.resolve(repo_name),
+            None => &self.env.blobstore_options.base,
+        }
+    }
+
+    async fn blobstore_no_cache(
+        &self,
+        config: &BlobConfig,
+        repo_name: Option<&str>,
+    ) -> Result<Arc<dyn Blobstore>> {
         make_blobstore(
             self.env.fb,
             config.clone(),
             &self.env.mysql_options,
             self.env.readonly_storage,
-            &self.env.blobstore_options,
+            self.blobstore_options(repo_name),
             &self.env.logger,
             &self.env.config_store,
             &self.scrub_handler,
@@ -401,23 +430,31 @@ impl RepoFactory {
     async fn blobstore_enumerable_with_unlink(
         &self,
         config: &BlobConfig,
+        repo_name: Option<&str>,
     ) -> Result<Arc<dyn BlobstoreEnumerableWithUnlink>> {
         make_blobstore_enumerable_with_unlink(
             self.env.fb,
             config.clone(),
-            &self.env.blobstore_options,
+            self.blobstore_options(repo_name),
             &self.env.logger,
         )
         .watched(&self.env.logger)
         .await
     }
 
-    async fn blobstore(&self, config: &BlobConfig) -> Result<Arc<dyn Blobstore>> {
+    async fn blobstore(
+        &self,
+        config: &BlobConfig,
+        repo_name: Option<&str>,
+    ) -> Result<Arc<dyn Blobstore>> {
+        let repo_name = self.blobstore_options_override(repo_name);
+        let key = (repo_name.map(str::to_string), config.clone());
         self.blobstores
-            .get_or_try_init(config, || async move {
-                let mut blobstore = self.blobstore_no_cache(config).await?;
+            .get_or_try_init(&key, || async move {
+                let blobstore_options = self.blobstore_options(repo_name);
+                let mut blobstore = self.blobstore_no_cache(config, repo_name).await?;
 
-                match self.env.caching {
+                match self.env.repo_caching(repo_name) {
                     Caching::Enabled(local_cache_config) => {
                         let fb = self.env.fb;
                         let memcache_blobstore = tokio::task::spawn_blocking(move || {
@@ -427,14 +464,14 @@ impl RepoFactory {
                         blobstore = cachelib_blobstore(
                             memcache_blobstore,
                             local_cache_config.blobstore_cache_shards,
-                            &self.env.blobstore_options.cachelib_options,
+                            &blobstore_options.cachelib_options,
                         )?
                     }
                     Caching::LocalOnly(local_cache_config) => {
                         blobstore = cachelib_blobstore(
                             blobstore,
                             local_cache_config.blobstore_cache_shards,
-                            &self.env.blobstore_options.cachelib_options,
+                            &blobstore_options.cachelib_options,
                         )?;
                     }
                     Caching::Disabled => {}
@@ -452,13 +489,21 @@ impl RepoFactory {
     pub async fn blobstore_unlink_ops_with_overriden_blob_config(
         &self,
         config: &BlobConfig,
+    ) -> Result<Arc<dyn BlobstoreUnlinkOps>> {
+        self.blobstore_unlink_ops(config, None).await
+    }
+
+    async fn blobstore_unlink_ops(
+        &self,
+        config: &BlobConfig,
+        repo_name: Option<&str>,
     ) -> Result<Arc<dyn BlobstoreUnlinkOps>> {
         make_blobstore_unlink_ops(
             self.env.fb,
             config.clone(),
             &self.env.mysql_options,
             self.env.readonly_storage,
-            &self.env.blobstore_options,
+            self.blobstore_options(repo_name),
             &self.env.logger,
             &self.env.config_store,
             &self.scrub_handler,
@@ -495,7 +540,7 @@ impl RepoFactory {
         &self,
         config: &BlobConfig,
     ) -> Result<ArcRedactionConfigBlobstore> {
-        let blobstore = self.blobstore(config).await?;
+        let blobstore = self.blobstore(config, None).await?;
         Ok(Arc::new(RedactionConfigBlobstore::new(blobstore)))
     }
 
@@ -507,7 +552,7 @@ impl RepoFactory {
                 self.env.logger.new(o!("repo" => repo_name))
             },
         );
-        let session = SessionContainer::new_with_defaults(self.env.fb);
+        let session = rate_limited_session(self.env.fb, self.env.rate_limiter());
         session.new_context(logger, self.env.scuba_sample_builder.clone())
     }
 
@@ -545,6 +590,13 @@ impl RepoFactory {
     pub fn acl_provider(&self) -> &dyn AclProvider {
         self.env.acl_provider.as_ref()
     }
+
+    /// Flag that lets writes through the blobstores of `repo_name` despite
+    /// read-only storage while set. Only meant for privileged admin
+    /// operations.
+    pub fn readonly_storage_bypass(&self, repo_name: &str) -> Arc<AtomicBool> {
+        self.blobstore_options(Some(repo_name)).readonly_bypass()
+    }
 }
 
 fn cache_pool(name: &str) -> Result<cachelib::LruCachePool> {
@@ -703,7 +755,7 @@ impl RepoFactory {
             .open_sql::<SqlChangesetsBuilder>(repo_config)
             .await
             .context(RepoFactoryError::Changesets)?;
-        let changesets = builder.build(self.env.rendezvous_options, repo_identity.id());
+        let changesets = builder.build(self.env.rendezvous_options.clone(), repo_identity.id());
 
         let possibly_cached_changesets: ArcChangesets =
             if let Some(cache_handler_factory) = self.cache_handler_factory("changesets")? {
@@ -796,7 +848,8 @@ impl RepoFactory {
             builder = builder.with_overwrite();
         }
 
-        let bonsai_hg_mapping = builder.build(repo_identity.id(), self.env.rendezvous_options);
+        let bonsai_hg_mapping =
+            builder.build(repo_identity.id(), self.env.rendezvous_options.clone());
 
         if let Some(cache_handler_factory) = self.cache_handler_factory("bonsai_hg_mapping")? {
             Ok(Arc::new(CachingBonsaiHgMapping::new(
@@ -968,6 +1021,7 @@ impl RepoFactory {
             .open_sql::<SqlHgMutationStoreBuilder>(repo_config)
             .await
             .context(RepoFactoryError::HgMutationStore)?
+            .with_deadlock_retry(self.env.mysql_options.deadlock_retry)
             .with_repo_id(repo_identity.id());
 
         if let Some(cache_handler_factory) = self.cache_handler_factory("hg_mutation_store")? {
@@ -1079,7 +1133,10 @@ impl RepoFactory {
         common_config: &ArcCommonConfig,
     ) -> Result<ArcRepoBlobstore> {
         let blobstore = self
-            .blobstore(&repo_config.storage_config.blobstore)
+            .blobstore(
+                &repo_config.storage_config.blobstore,
+                Some(repo_identity.name()),
+            )
             .await?;
         Ok(Arc::new(
             self.repo_blobstore_from_blobstore(
@@ -1099,7 +1156,10 @@ impl RepoFactory {
         common_config: &ArcCommonConfig,
     ) -> Result<ArcRepoBlobstoreUnlinkOps> {
         let blobstore = self
-            .blobstore_unlink_ops_with_overriden_blob_config(&repo_config.storage_config.blobstore)
+            .blobstore_unlink_ops(
+                &repo_config.storage_config.blobstore,
+                Some(repo_identity.name()),
+            )
             .await?;
         Ok(Arc::new(
             self.repo_blobstore_unlink_ops_from_blobstore_unlink_ops(
@@ -1139,7 +1199,10 @@ impl RepoFactory {
     ) -> Result<ArcRepoEphemeralStore> {
         if let Some(ephemeral_config) = &repo_config.storage_config.ephemeral_blobstore {
             let blobstore = self
-                .blobstore_enumerable_with_unlink(&ephemeral_config.blobstore)
+                .blobstore_enumerable_with_unlink(
+                    &ephemeral_config.blobstore,
+                    Some(repo_identity.name()),
+                )
                 .await?;
             let ephemeral_blobstore = RepoEphemeralStoreBuilder::with_database_config(
                 self.env.fb,
@@ -1255,6 +1318,7 @@ impl RepoFactory {
             self.open_sql::<SqlMutableCountersBuilder>(repo_config)
                 .await
                 .context(RepoFactoryError::MutableCounters)?
+                .with_deadlock_retry(self.env.mysql_options.deadlock_retry)
                 .build(repo_identity.id()),
         ))
     }
@@ -1375,12 +1439,34 @@ impl RepoFactory {
         &self,
         repo_config: &ArcRepoConfig,
     ) -> Result<ArcRepoBookmarkAttrs> {
+        let mut namespaces = Vec::new();
+        for params in &repo_config.bookmark_namespaces {
+            let acl = match &params.acl_file {
+                Some(acl_file) => Some(
+                    InternalAclProvider::from_file(acl_file)
+                        .with_context(|| {
+                            format!(
+                                "Failed to load ACLs of bookmark namespace '{}' from '{}'",
+                                params.prefix,
+                                acl_file.display()
+                            )
+                        })
+                        .context(RepoFactoryError::RepoBookmarkAttrs)?,
+                ),
+                None => None,
+            };
+            namespaces.push(BookmarkNamespace {
+                prefix: params.prefix.clone(),
+                acl,
+            });
+        }
         let repo_bookmark_attrs = RepoBookmarkAttrs::new(
             self.env.acl_provider.as_ref(),
             repo_config.bookmarks.clone(),
         )
         .await
-        .context(RepoFactoryError::RepoBookmarkAttrs)?;
+        .context(RepoFactoryError::RepoBookmarkAttrs)?
+        .with_namespaces(namespaces);
         Ok(Arc::new(repo_bookmark_attrs))
     }
 
@@ -1534,6 +1620,7 @@ impl RepoFactory {
             .build(
                 RendezVousOptions {
                     free_connections: 5,
+                    ..Default::default()
                 },
                 repo_identity.id(),
             );
@@ -1558,7 +1645,10 @@ impl RepoFactory {
                         repo_identity,
                         repo_config,
                         &self
-                            .blobstore_no_cache(&repo_config.storage_config.blobstore)
+                            .blobstore_no_cache(
+                                &repo_config.storage_config.blobstore,
+                                Some(repo_identity.name()),
+                            )
                             .await?,
                         common_config,
                     )
@@ -1611,7 +1701,7 @@ fn lease_init(
 ) -> Result<Arc<dyn LeaseOps>> {
     // Derived data leasing is performed through the cache, so is only
     // available if caching is enabled.
-    if let Caching::Enabled(_) = caching {
+    if caching.shared_enabled() {
         Ok(Arc::new(MemcacheOps::new(fb, lease_type, "")?))
     } else {
         Ok(Arc::new(InProcessLease::new()))
@@ -1656,5 +1746,107 @@ fn get_derivation_client(
         } else {
             None
         };
-    Ok(derivation_service_client)
+    Ok(match derivation_service_client {
+        Some(client) if remote_derivation_options.fallback_to_local => {
+            Some(Arc::new(FallbackDerivationClient::new(client)))
+        }
+        client => client,
+    })
+}
+
+/// Session for the work started by the factory, like blobstore accesses and
+/// derivation, limited by the rate limiter of the environment, if any.
+fn rate_limited_session(
+    fb: FacebookInit,
+    rate_limiter: Option<&ArcRateLimiter>,
+) -> SessionContainer {
+    SessionContainer::builder(fb)
+        .rate_limiter(rate_limiter.map(|limiter| Box::new(limiter.clone()) as BoxRateLimiter))
+        .build()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use anyhow::Error;
+    use async_trait::async_trait;
+    use permission_checker::MononokeIdentitySet;
+    use rate_limiting::LoadCost;
+    use rate_limiting::Metric;
+    use rate_limiting::RateLimitBody;
+    use rate_limiting::RateLimitReason;
+    use rate_limiting::RateLimiter;
+
+    use super::*;
+
+    /// Rate limiter that sheds all the load, and counts the load reported.
+    #[derive(Default)]
+    struct CountingRateLimiter {
+        load: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl RateLimiter for CountingRateLimiter {
+        async fn check_rate_limit(
+            &self,
+            metric: Metric,
+            _identities: &MononokeIdentitySet,
+        ) -> Result<Result<(), RateLimitReason>, Error> {
+            Ok(Err(RateLimitReason::RateLimitedMetric(
+                metric,
+                Duration::from_secs(1),
+            )))
+        }
+
+        fn check_load_shed(
+            &self,
+            _identities: &MononokeIdentitySet,
+        ) -> Result<(), RateLimitReason> {
+            Err(RateLimitReason::LoadShedMetric("test".to_string(), 1, 0))
+        }
+
+        fn bump_load(&self, _metric: Metric, load: LoadCost) {
+            self.load.fetch_add(load as usize, Ordering::Relaxed);
+        }
+
+        fn category(&self) -> &str {
+            "test"
+        }
+
+        fn commits_per_author_limit(&self) -> Option<RateLimitBody> {
+            None
+        }
+
+        fn total_file_changes_limit(&self) -> Option<RateLimitBody> {
+            None
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_rate_limited_session(fb: FacebookInit) {
+        let limiter = Arc::new(CountingRateLimiter::default());
+        let shared: ArcRateLimiter = limiter.clone();
+
+        // Sessions share the limiter.
+        let sessions = [
+            rate_limited_session(fb, Some(&shared)),
+            rate_limited_session(fb, Some(&shared)),
+        ];
+        for session in &sessions {
+            assert!(session.rate_limiter().is_some());
+            session.bump_load(Metric::Commits, 2.0);
+            assert!(session.check_load_shed().is_err());
+            assert!(session.check_rate_limit(Metric::Commits).await.is_err());
+        }
+        assert_eq!(limiter.load.load(Ordering::Relaxed), 4);
+
+        // Without a limiter, nothing is limited.
+        let session = rate_limited_session(fb, None);
+        assert!(session.rate_limiter().is_none());
+        assert!(session.check_load_shed().is_ok());
+        assert!(session.check_rate_limit(Metric::Commits).await.is_ok());
+    }
 }
@@ -239,6 +239,7 @@ impl SourceControlServiceImpl {
             .list_bookmarks(
                 params.include_scratch,
                 prefix.as_deref(),
+                None,
                 params.after.as_deref(),
                 limit,
             )
@@ -147,7 +147,8 @@ pub async fn connection_acceptor(
         None => None,
     };
 
-    // Now that we are listening and ready to accept connections, report that we are alive.
+    // Now that we are listening and ready to accept connections, report that we are alive,
+    // both to FB303 and to the rendezvous health check.
     service.set_ready();
 
     let bound_addr = listener.local_addr()?.to_string();
@@ -242,7 +242,10 @@ where
             .unwrap_or_default();
         let compression = match req.headers().get(HEADER_CLIENT_COMPRESSION) {
             Some(header_value) => match header_value.as_bytes() {
-                b"zstd=stdin" if zstd_level > 0 => Ok(Some(zstd_level)),
+                // The client detects whether the connection is compressed from
+                // the encoding header of the response, so fall back to no
+                // compression when it's disabled instead of rejecting it.
+                b"zstd=stdin" => Ok((zstd_level > 0).then_some(zstd_level)),
                 header_value_bytes => Err(anyhow!(
                     "'{}' is not a recognized compression value",
                     String::from_utf8_lossy(header_value_bytes),
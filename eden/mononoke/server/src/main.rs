@@ -269,7 +269,7 @@ fn main(fb: FacebookInit) -> Result<()> {
     let host_port = args.listening_host_port;
     let bound_addr_file = args.bound_address_file;
 
-    let service = ReadyFlagService::new();
+    let service = ReadyFlagService::new().with_health_check(app.environment().health_check.clone());
     let (terminate_sender, terminate_receiver) = oneshot::channel::<()>();
     let will_exit = Arc::new(AtomicBool::new(false));
 
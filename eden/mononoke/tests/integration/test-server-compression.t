@@ -46,3 +46,15 @@ without compression again, no zstd indicator that compression is used
   $ hgmn pull --debug 2>&1 | grep -P "(zstd|pulling|checking)"
   pulling from mononoke://* (glob)
   checking for updated bookmarks
+
+with compression disabled on the server, clients asking for it fall back to no compression
+  $ merge_tunables <<EOF
+  > {
+  >   "ints": {
+  >     "zstd_compression_level": 0
+  >   }
+  > }
+  > EOF
+  $ hgmn pull --debug --config mononokepeer.compression=true 2>&1 | grep -P "(zstd|pulling|checking)"
+  pulling from mononoke://* (glob)
+  checking for updated bookmarks
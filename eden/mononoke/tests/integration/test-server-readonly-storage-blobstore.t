@@ -38,4 +38,4 @@ blobimport normally, so sql is populated
 blobimport, check blobstore puts are blocked
   $ rm -rf "$TESTTMP/blobstore/blobs/"*content*
   $ blobimport repo-hg/.hg repo --with-readonly-storage=true | grep 'root cause:'
-  * root cause: ReadOnlyPut("*") (glob)
+  * root cause: ReadOnly("*") (glob)
@@ -187,6 +187,7 @@ pub(super) async fn backfill(
         .build(
             RendezVousOptions {
                 free_connections: 5,
+                ..Default::default()
             },
             repo.repo_identity().id(),
         );
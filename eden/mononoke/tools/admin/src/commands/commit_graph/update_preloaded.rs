@@ -103,6 +103,7 @@ pub(super) async fn update_preloaded(
         .build(
             RendezVousOptions {
                 free_connections: 5,
+                ..Default::default()
             },
             repo.repo_identity().id(),
         );
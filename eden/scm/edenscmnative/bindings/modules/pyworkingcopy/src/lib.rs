@@ -26,6 +26,7 @@ use pyconfigloader::config;
 use pypathmatcher::extract_matcher;
 use pypathmatcher::extract_option_matcher;
 use pytreestate::treestate;
+use rsworkingcopy::physicalfs::PhysicalFileSystemOptions;
 use rsworkingcopy::walker::WalkError;
 use rsworkingcopy::walker::Walker;
 use rsworkingcopy::workingcopy::WorkingCopy;
@@ -62,6 +63,7 @@ py_class!(class walker |py| {
             Vec::new(),
             matcher,
             include_directories,
+            PhysicalFileSystemOptions::default().parallelism,
         ).map_pyerr(py)?;
         walker::create_instance(py, RefCell::new(walker), RefCell::new(Vec::new()))
     }
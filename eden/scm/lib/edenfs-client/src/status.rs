@@ -14,6 +14,7 @@ use anyhow::Error;
 use anyhow::Result;
 use eden::GetScmStatusParams;
 use eden::GetScmStatusResult;
+use eden::ScmStatus;
 use fbthrift_socket::SocketTransport;
 use serde::Deserialize;
 use thrift_types::edenfs as eden;
@@ -33,6 +34,13 @@ pub fn get_status(repo_root: &Path, commit: HgId) -> Result<GetScmStatusResult>
     rt.block_on(get_status_internal(repo_root, commit))
 }
 
+/// Get the status between two commits, regardless of the working copy.
+pub fn get_status_between_revisions(repo_root: &Path, old: HgId, new: HgId) -> Result<ScmStatus> {
+    let rt = tokio::runtime::Runtime::new()?;
+
+    rt.block_on(get_status_between_revisions_internal(repo_root, old, new))
+}
+
 async fn get_status_internal(repo_root: &Path, commit: HgId) -> Result<GetScmStatusResult> {
     let eden_config = EdenConfig::from_root(repo_root)?;
 
@@ -42,6 +50,26 @@ async fn get_status_internal(repo_root: &Path, commit: HgId) -> Result<GetScmSta
     get_status_helper(&client, &eden_config.root, commit, false).await
 }
 
+async fn get_status_between_revisions_internal(
+    repo_root: &Path,
+    old: HgId,
+    new: HgId,
+) -> Result<ScmStatus> {
+    let eden_config = EdenConfig::from_root(repo_root)?;
+
+    let transport = get_socket_transport(&eden_config.socket).await?;
+    let client = <dyn EdenService>::new(BinaryProtocol, transport);
+
+    client
+        .getScmStatusBetweenRevisions(
+            &eden_config.root.as_bytes().to_vec(),
+            &old.into_byte_array().into(),
+            &new.into_byte_array().into(),
+        )
+        .await
+        .map_err(|err| err.into())
+}
+
 #[derive(Deserialize)]
 struct EdenConfig {
     root: String,
@@ -5,6 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::io;
 use std::str;
 
 use byteorder::NetworkEndian;
@@ -20,20 +21,54 @@ use serde::de::Visitor;
 use vlqencoding::VLQDecode;
 
 use crate::Error;
+use crate::Options;
 use crate::Result;
 
+/// Name of the variant that unknown variants are deserialized as, when
+/// variants are length-prefixed.
+const UNKNOWN_VARIANT: &str = "Unknown";
+
 pub struct Deserializer<'de> {
     bytes: &'de [u8],
+    options: Options,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn new(bytes: &'de [u8]) -> Self {
-        Deserializer { bytes }
+        Self::with_options(bytes, Options::default())
+    }
+
+    pub fn with_options(bytes: &'de [u8], options: Options) -> Self {
+        Deserializer { bytes, options }
+    }
+
+    /// Input that has not been consumed yet.
+    pub fn remaining(&self) -> &'de [u8] {
+        self.bytes
+    }
+
+    #[inline]
+    fn read_varint<T>(&mut self) -> Result<T>
+    where
+        &'de [u8]: VLQDecode<T>,
+    {
+        let start = self.bytes;
+        let value = self.bytes.read_vlq()?;
+        let len = start.len() - self.bytes.len();
+        // The last byte of a varint holds its most significant bits, so it
+        // can only be 0 if the value would fit in fewer bytes.
+        if self.options.reject_overlong_varints && len > 1 && start[len - 1] == 0 {
+            return Err(Error::new(format!("overlong varint of {} bytes", len)));
+        }
+        Ok(value)
     }
 
     #[inline]
     fn read_slice(&mut self) -> Result<&'de [u8]> {
         let len = Deserialize::deserialize(&mut *self)?;
+        if len > self.bytes.len() {
+            return Err(unexpected_eof());
+        }
         let (slice, rest) = self.bytes.split_at(len);
         self.bytes = rest;
         Ok(slice)
@@ -57,6 +92,19 @@ macro_rules! impl_nums {
     };
 }
 
+macro_rules! impl_varints {
+    ($ty:ty, $dser_method:ident, $visitor_method:ident) => {
+        #[inline]
+        fn $dser_method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            let value: $ty = self.read_varint()?;
+            visitor.$visitor_method(value)
+        }
+    };
+}
+
 impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     type Error = Error;
 
@@ -80,13 +128,13 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 
     impl_nums!(u8, deserialize_u8, visit_u8, read_u8);
-    impl_nums!(u16, deserialize_u16, visit_u16, read_vlq);
-    impl_nums!(u32, deserialize_u32, visit_u32, read_vlq);
-    impl_nums!(u64, deserialize_u64, visit_u64, read_vlq);
+    impl_varints!(u16, deserialize_u16, visit_u16);
+    impl_varints!(u32, deserialize_u32, visit_u32);
+    impl_varints!(u64, deserialize_u64, visit_u64);
     impl_nums!(i8, deserialize_i8, visit_i8, read_i8);
-    impl_nums!(i16, deserialize_i16, visit_i16, read_vlq);
-    impl_nums!(i32, deserialize_i32, visit_i32, read_vlq);
-    impl_nums!(i64, deserialize_i64, visit_i64, read_vlq);
+    impl_varints!(i16, deserialize_i16, visit_i16);
+    impl_varints!(i32, deserialize_i32, visit_i32);
+    impl_varints!(i64, deserialize_i64, visit_i64);
     impl_nums!(f32, deserialize_f32, visit_f32, read_f32::<NetworkEndian>);
     impl_nums!(f64, deserialize_f64, visit_f64, read_f64::<NetworkEndian>);
 
@@ -103,13 +151,17 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let width = utf8_char_width(self.bytes[0]);
+        let first = *self.bytes.first().ok_or_else(unexpected_eof)?;
+        let width = utf8_char_width(first);
         if width == 1 {
-            return visitor.visit_char(self.bytes[0] as char);
+            return visitor.visit_char(first as char);
         }
         if width == 0 {
             return Err(Error::new("invalid char"));
         }
+        if width > self.bytes.len() {
+            return Err(unexpected_eof());
+        }
         let res = match str::from_utf8(&self.bytes[..width]) {
             Ok(s) => s.chars().next().unwrap(),
             Err(err) => {
@@ -155,14 +207,54 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     #[inline]
     fn deserialize_enum<V>(
         self,
-        _enum: &'static str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(self)
+        if !self.options.length_prefixed_variants {
+            return visitor.visit_enum(self);
+        }
+
+        let mut index: u32 = Deserialize::deserialize(&mut *self)?;
+        let len = Deserialize::deserialize(&mut *self)?;
+        if len > self.bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "variant payload of {} bytes is longer than the remaining {} bytes",
+                    len,
+                    self.bytes.len()
+                ),
+            )
+            .into());
+        }
+        let (mut payload, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        if index as usize >= variants.len() {
+            // Written by a newer version: skip the payload, which has been
+            // consumed above, and fall back to the `Unknown` variant.
+            match variants.iter().position(|v| *v == UNKNOWN_VARIANT) {
+                Some(unknown) => {
+                    index = unknown as u32;
+                    payload = &[];
+                }
+                None => {
+                    return Err(Error::new(format!(
+                        "unknown variant index {} for enum {}",
+                        index, name
+                    )));
+                }
+            }
+        }
+
+        visitor.visit_enum(PrefixedVariant {
+            index,
+            payload: Deserializer::with_options(payload, self.options),
+        })
     }
 
     #[inline]
@@ -382,6 +474,66 @@ impl<'de, 'a> VariantAccess<'de> for &'a mut Deserializer<'de> {
     }
 }
 
+/// A length-prefixed enum variant, whose payload has already been split off
+/// the input.
+struct PrefixedVariant<'de> {
+    index: u32,
+    payload: Deserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for PrefixedVariant<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    #[inline]
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let deserializer = <u32 as IntoDeserializer<Error>>::into_deserializer(self.index);
+        let value = seed.deserialize(deserializer)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for PrefixedVariant<'de> {
+    type Error = Error;
+
+    #[inline]
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut self.payload)
+    }
+
+    #[inline]
+    fn tuple_variant<V>(mut self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(&mut self.payload)
+    }
+
+    #[inline]
+    fn struct_variant<V>(mut self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(&mut self.payload)
+    }
+}
+
+/// The error for input that ends in the middle of a value.
+fn unexpected_eof() -> Error {
+    io::Error::from(io::ErrorKind::UnexpectedEof).into()
+}
+
 #[rustfmt::skip]
 static UTF8_CHAR_WIDTH: [u8; 256] = [
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
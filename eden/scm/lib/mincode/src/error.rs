@@ -18,6 +18,7 @@ use serde::ser;
 #[derive(Debug)]
 pub struct Error {
     msg: String,
+    io: Option<io::Error>,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -26,14 +27,30 @@ impl Error {
     pub fn new<T: Display>(msg: T) -> Self {
         Error {
             msg: msg.to_string(),
+            io: None,
         }
     }
+
+    /// The IO error this error was caused by, if any, such as a failure of
+    /// the writer passed to [`serialize_into`](crate::serialize_into).
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.io.as_ref()
+    }
+
+    /// Whether the input ended in the middle of a value.
+    pub(crate) fn is_unexpected_eof(&self) -> bool {
+        matches!(&self.io, Some(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+    }
 }
 
 impl error::Error for Error {
     fn description(&self) -> &str {
         &self.msg
     }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.io.as_ref().map(|e| e as _)
+    }
 }
 
 impl Display for Error {
@@ -56,7 +73,10 @@ impl de::Error for Error {
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
-        Error::new(err)
+        Error {
+            msg: err.to_string(),
+            io: Some(err),
+        }
     }
 }
 
@@ -8,6 +8,7 @@
 mod de;
 mod error;
 mod ser;
+pub mod time;
 
 #[cfg(test)]
 mod tests;
@@ -16,27 +17,89 @@ use std::io;
 
 use serde::Deserialize;
 use serde::Serialize;
+use vlqencoding::VLQDecode;
 
 use self::de::Deserializer;
 pub use self::error::Error;
 pub use self::error::Result;
 use self::ser::Serializer;
 
+/// Encoding options.
+///
+/// Data must be deserialized with the same options it was serialized with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    length_prefixed_variants: bool,
+    reject_overlong_varints: bool,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the length of each enum variant's payload after its index.
+    ///
+    /// This lets readers skip variants they do not know about. On reading,
+    /// an unknown variant is turned into the `Unknown` unit variant of the
+    /// target enum if there is one, and is an error otherwise.
+    pub fn length_prefixed_variants(mut self, value: bool) -> Self {
+        self.length_prefixed_variants = value;
+        self
+    }
+
+    /// Fail to read integers and lengths encoded with more varint bytes than
+    /// needed, such as `0x80 0x00` for 0.
+    ///
+    /// Such encodings are never written, but are accepted by default. This
+    /// only affects reading.
+    pub fn reject_overlong_varints(mut self, value: bool) -> Self {
+        self.reject_overlong_varints = value;
+        self
+    }
+}
+
 pub fn serialize<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    serialize_with_options(value, Options::default())
+}
+
+pub fn serialize_with_options<T>(value: &T, options: Options) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
     let mut out = Vec::new();
-    serialize_into(&mut out, value)?;
+    serialize_into_with_options(&mut out, value, options)?;
     Ok(out)
 }
 
+/// Serialize `value` into `writer`.
+///
+/// The output is written as it is produced, without buffering all of it in
+/// memory, so large values can be written directly to a file. Consider
+/// wrapping unbuffered writers in a `BufWriter`, as there are many small
+/// writes. Errors of the writer are returned as errors with an
+/// [`io_error`](Error::io_error).
 pub fn serialize_into<W, T: ?Sized>(writer: W, value: &T) -> Result<()>
 where
     W: io::Write,
     T: Serialize,
 {
-    let mut ser = Serializer::new(writer);
+    serialize_into_with_options(writer, value, Options::default())
+}
+
+/// Same as [`serialize_into`], with encoding options.
+///
+/// With [`Options::length_prefixed_variants`], the payload of each enum
+/// variant is buffered to compute its length before being written.
+pub fn serialize_into_with_options<W, T>(writer: W, value: &T, options: Options) -> Result<()>
+where
+    W: io::Write,
+    T: Serialize + ?Sized,
+{
+    let mut ser = Serializer::with_options(writer, options);
     Serialize::serialize(value, &mut ser)
 }
 
@@ -44,6 +107,99 @@ pub fn deserialize<'de, T>(bytes: &'de [u8]) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    let mut de = Deserializer::new(bytes);
+    deserialize_with_options(bytes, Options::default())
+}
+
+pub fn deserialize_with_options<'de, T>(bytes: &'de [u8], options: Options) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let mut de = Deserializer::with_options(bytes, options);
     Deserialize::deserialize(&mut de)
 }
+
+/// Deserialize a value from the start of `bytes`, and return it together with
+/// the bytes following it.
+pub fn deserialize_prefix<'de, T>(bytes: &'de [u8]) -> Result<(T, &'de [u8])>
+where
+    T: Deserialize<'de>,
+{
+    let mut de = Deserializer::new(bytes);
+    let value = Deserialize::deserialize(&mut de)?;
+    Ok((value, de.remaining()))
+}
+
+/// Deserialize a value that must span all of `bytes`.
+///
+/// Unlike [`deserialize`], this errors if there are bytes left over after the
+/// value.
+pub fn deserialize_exact<'de, T>(bytes: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let (value, rest) = deserialize_prefix(bytes)?;
+    if !rest.is_empty() {
+        return Err(Error::new(format!(
+            "trailing bytes: {} bytes left after the value",
+            rest.len()
+        )));
+    }
+    Ok(value)
+}
+
+/// The encoded size of the length-prefixed value at the start of `bytes`,
+/// such as a string, a byte string or a `Vec<u8>`, without decoding it.
+///
+/// Only the length prefix is read, so the size is known as soon as the
+/// prefix is complete: compare it with `bytes.len()` to tell whether all of
+/// the value arrived. Returns `None` if the prefix itself is incomplete.
+///
+/// Other values have no prefix giving their size in bytes: collections are
+/// prefixed with their number of items, and fixed-size scalars, like `u8` or
+/// `f64`, have no prefix at all. Use [`peek_len_of`] for them.
+pub fn peek_len(bytes: &[u8]) -> Result<Option<usize>> {
+    let mut rest = bytes;
+    let len: usize = match rest.read_vlq() {
+        Ok(len) => len,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let prefix_len = bytes.len() - rest.len();
+    match prefix_len.checked_add(len) {
+        Some(total) => Ok(Some(total)),
+        None => Err(Error::new(format!("length {} is too large", len))),
+    }
+}
+
+/// The encoded size of the `T` at the start of `bytes`, or `None` if `bytes`
+/// ends before the end of the value. For fixed-size scalars, this is their
+/// width.
+///
+/// Unlike [`peek_len`], this decodes the value, and works for any type.
+pub fn peek_len_of<'de, T>(bytes: &'de [u8]) -> Result<Option<usize>>
+where
+    T: Deserialize<'de>,
+{
+    match deserialize_prefix::<T>(bytes) {
+        Ok((_, rest)) => Ok(Some(bytes.len() - rest.len())),
+        Err(err) if err.is_unexpected_eof() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether `bytes` is the canonical encoding of a `T`: the one produced by
+/// serializing the value it decodes to.
+///
+/// Some values can be decoded from several encodings, like integers from
+/// overlong varints, or sorted maps from entries in any order. Only the
+/// canonical one should be stored where bytes are hashed or compared.
+///
+/// Fails if `bytes` is not a valid encoding of a `T`, including if there are
+/// bytes left over after the value, like [`deserialize_exact`].
+pub fn is_canonical<'de, T>(bytes: &'de [u8]) -> Result<bool>
+where
+    T: Deserialize<'de> + Serialize,
+{
+    let value: T = deserialize_exact(bytes)?;
+    Ok(serialize(&value)? == bytes)
+}
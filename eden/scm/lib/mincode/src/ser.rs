@@ -19,10 +19,12 @@ use serde::ser::SerializeTupleVariant;
 use vlqencoding::VLQEncode;
 
 use crate::Error;
+use crate::Options;
 use crate::Result;
 
 pub struct Serializer<W> {
     writer: W,
+    options: Options,
 }
 
 impl<W> Serializer<W>
@@ -30,7 +32,73 @@ where
     W: Write,
 {
     pub fn new(w: W) -> Self {
-        Serializer { writer: w }
+        Self::with_options(w, Options::default())
+    }
+
+    pub fn with_options(w: W, options: Options) -> Self {
+        Serializer { writer: w, options }
+    }
+
+    #[inline]
+    fn serialize_variant_index(&mut self, variant_index: u32) -> Result<()> {
+        self.writer.write_vlq(variant_index).map_err(From::from)
+    }
+
+    /// Serializer for a variant's payload, if it needs to be length-prefixed.
+    #[inline]
+    fn payload_serializer(&self) -> Option<Serializer<Vec<u8>>> {
+        if self.options.length_prefixed_variants {
+            Some(Serializer::with_options(Vec::new(), self.options))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn write_payload(&mut self, payload: Serializer<Vec<u8>>) -> Result<()> {
+        let payload = payload.writer;
+        self.writer.write_vlq(payload.len())?;
+        self.writer.write_all(&payload).map_err(From::from)
+    }
+}
+
+/// Serializer for the fields of a tuple or struct variant.
+pub struct VariantSerializer<'a, W> {
+    serializer: &'a mut Serializer<W>,
+    payload: Option<Serializer<Vec<u8>>>,
+}
+
+impl<'a, W> VariantSerializer<'a, W>
+where
+    W: Write,
+{
+    #[inline]
+    fn new(serializer: &'a mut Serializer<W>, variant_index: u32) -> Result<Self> {
+        serializer.serialize_variant_index(variant_index)?;
+        let payload = serializer.payload_serializer();
+        Ok(VariantSerializer {
+            serializer,
+            payload,
+        })
+    }
+
+    #[inline]
+    fn serialize_field<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: serde::Serialize + ?Sized,
+    {
+        match self.payload {
+            Some(ref mut payload) => value.serialize(payload),
+            None => value.serialize(&mut *self.serializer),
+        }
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        match self.payload {
+            Some(payload) => self.serializer.write_payload(payload),
+            None => Ok(()),
+        }
     }
 }
 
@@ -44,10 +112,10 @@ where
     type SerializeSeq = Self;
     type SerializeTuple = Self;
     type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
+    type SerializeTupleVariant = VariantSerializer<'a, W>;
     type SerializeMap = Self;
     type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeStructVariant = VariantSerializer<'a, W>;
 
     #[inline]
     fn serialize_unit(self) -> Result<()> {
@@ -180,8 +248,7 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.serialize_u32(variant_index)?;
-        Ok(self)
+        VariantSerializer::new(self, variant_index)
     }
 
     #[inline]
@@ -204,8 +271,7 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.serialize_u32(variant_index)?;
-        Ok(self)
+        VariantSerializer::new(self, variant_index)
     }
 
     #[inline]
@@ -227,8 +293,14 @@ where
     where
         T: serde::ser::Serialize,
     {
-        self.serialize_u32(variant_index)?;
-        value.serialize(self)
+        self.serialize_variant_index(variant_index)?;
+        match self.payload_serializer() {
+            Some(mut payload) => {
+                value.serialize(&mut payload)?;
+                self.write_payload(payload)
+            }
+            None => value.serialize(self),
+        }
     }
 
     #[inline]
@@ -238,7 +310,11 @@ where
         variant_index: u32,
         _variant: &'static str,
     ) -> Result<()> {
-        self.serialize_u32(variant_index)
+        self.serialize_variant_index(variant_index)?;
+        match self.payload_serializer() {
+            Some(payload) => self.write_payload(payload),
+            None => Ok(()),
+        }
     }
 }
 
@@ -305,7 +381,7 @@ where
     }
 }
 
-impl<'a, W> SerializeTupleVariant for &'a mut Serializer<W>
+impl<'a, W> SerializeTupleVariant for VariantSerializer<'a, W>
 where
     W: Write,
 {
@@ -317,12 +393,12 @@ where
     where
         V: serde::Serialize,
     {
-        value.serialize(&mut **self)
+        VariantSerializer::serialize_field(self, value)
     }
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        VariantSerializer::end(self)
     }
 }
 
@@ -376,7 +452,7 @@ where
     }
 }
 
-impl<'a, W> SerializeStructVariant for &'a mut Serializer<W>
+impl<'a, W> SerializeStructVariant for VariantSerializer<'a, W>
 where
     W: Write,
 {
@@ -388,12 +464,12 @@ where
     where
         V: serde::Serialize,
     {
-        value.serialize(&mut **self)
+        VariantSerializer::serialize_field(self, value)
     }
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        VariantSerializer::end(self)
     }
 }
 
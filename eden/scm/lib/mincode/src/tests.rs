@@ -5,6 +5,11 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+use std::time::SystemTime;
+
 use quickcheck::quickcheck;
 use serde::Deserialize;
 use serde::Serialize;
@@ -37,3 +42,346 @@ quickcheck! {
         foo == foo_deserialized
     }
 }
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct Times {
+    #[serde(with = "crate::time::system_time")]
+    last_write: SystemTime,
+    #[serde(with = "crate::time::duration")]
+    elapsed: Duration,
+}
+
+#[test]
+fn test_time_roundtrip() {
+    // 9999-12-31T23:59:59.999999999Z
+    let far_future = Duration::new(253_402_300_799, 999_999_999);
+    for since_epoch in [Duration::ZERO, far_future] {
+        let times = Times {
+            last_write: SystemTime::UNIX_EPOCH + since_epoch,
+            elapsed: since_epoch,
+        };
+        let bytes = crate::serialize(&times).unwrap();
+        let times_deserialized: Times = crate::deserialize(&bytes).unwrap();
+        assert_eq!(times, times_deserialized);
+    }
+}
+
+#[test]
+fn test_time_pre_epoch() {
+    let times = Times {
+        last_write: SystemTime::UNIX_EPOCH - Duration::from_secs(1),
+        elapsed: Duration::ZERO,
+    };
+    assert!(crate::serialize(&times).is_err());
+}
+
+#[test]
+fn test_time_out_of_range() {
+    let bytes = crate::serialize(&((0u64, 1_000_000_000u32), (0u64, 0u32))).unwrap();
+    assert!(crate::deserialize::<Times>(&bytes).is_err());
+
+    let bytes = crate::serialize(&((0u64, 0u32), (0u64, 1_000_000_000u32))).unwrap();
+    assert!(crate::deserialize::<Times>(&bytes).is_err());
+
+    let bytes = crate::serialize(&((u64::MAX, 0u32), (0u64, 0u32))).unwrap();
+    assert!(crate::deserialize::<Times>(&bytes).is_err());
+}
+
+#[test]
+fn test_deserialize_exact() {
+    let foo = Foo {
+        bar: "bar".to_string(),
+        baz: Some(Wrap(1.0, -2, 3)),
+        derp: true,
+        list: vec![4, 5],
+    };
+    let mut bytes = crate::serialize(&foo).unwrap();
+    assert_eq!(crate::deserialize_exact::<Foo>(&bytes).unwrap(), foo);
+
+    bytes.push(0);
+    assert!(crate::deserialize_exact::<Foo>(&bytes).is_err());
+    assert_eq!(crate::deserialize::<Foo>(&bytes).unwrap(), foo);
+}
+
+#[test]
+fn test_is_canonical() {
+    let foo = Foo {
+        bar: "bar".to_string(),
+        baz: None,
+        derp: false,
+        list: vec![1, 300],
+    };
+    let mut bytes = crate::serialize(&foo).unwrap();
+    assert!(crate::is_canonical::<Foo>(&bytes).unwrap());
+
+    bytes.push(0);
+    assert!(crate::is_canonical::<Foo>(&bytes).is_err());
+
+    // Maps are serialized like sequences of key-value pairs, sorted by key
+    // for BTreeMap.
+    let entries = vec![(2u8, 20u8), (1, 10)];
+    let bytes = crate::serialize(&entries).unwrap();
+    assert!(crate::is_canonical::<Vec<(u8, u8)>>(&bytes).unwrap());
+    assert!(!crate::is_canonical::<BTreeMap<u8, u8>>(&bytes).unwrap());
+    let sorted: BTreeMap<u8, u8> = entries.into_iter().collect();
+    let bytes = crate::serialize(&sorted).unwrap();
+    assert!(crate::is_canonical::<BTreeMap<u8, u8>>(&bytes).unwrap());
+
+    // 1 as an overlong varint.
+    assert_eq!(crate::deserialize_exact::<u32>(&[0x81, 0]).unwrap(), 1);
+    assert!(!crate::is_canonical::<u32>(&[0x81, 0]).unwrap());
+    assert!(crate::is_canonical::<u32>(&[1]).unwrap());
+}
+
+#[test]
+fn test_varint_roundtrip() {
+    let options = crate::Options::new().reject_overlong_varints(true);
+    let unsigned = [
+        0u64,
+        1,
+        127,
+        128,
+        300,
+        u32::MAX as u64,
+        u64::MAX - 1,
+        u64::MAX,
+    ];
+    for value in unsigned {
+        let bytes = crate::serialize(&value).unwrap();
+        let decoded: u64 = crate::deserialize_with_options(&bytes, options).unwrap();
+        assert_eq!(decoded, value);
+    }
+    let signed = [0i64, -1, 1, -64, 64, i32::MIN as i64, i64::MIN, i64::MAX];
+    for value in signed {
+        let bytes = crate::serialize(&value).unwrap();
+        let decoded: i64 = crate::deserialize_with_options(&bytes, options).unwrap();
+        assert_eq!(decoded, value);
+    }
+    assert_eq!(crate::serialize(&5u64).unwrap(), [5]);
+    assert_eq!(crate::serialize(&-1i64).unwrap(), [1]);
+}
+
+#[test]
+fn test_reject_overlong_varints() {
+    let options = crate::Options::new().reject_overlong_varints(true);
+    for bytes in [&[0x80, 0][..], &[0x81, 0], &[0x81, 0x80, 0]] {
+        assert!(crate::deserialize::<u32>(bytes).is_ok());
+        assert!(crate::deserialize_with_options::<u32>(bytes, options).is_err());
+        assert!(crate::deserialize_with_options::<i64>(bytes, options).is_err());
+    }
+    assert_eq!(
+        crate::deserialize_with_options::<u32>(&[0x80, 1], options).unwrap(),
+        128
+    );
+
+    // Lengths are varints too.
+    let bytes = [0x81, 0, b'a'];
+    assert_eq!(crate::deserialize::<String>(&bytes).unwrap(), "a");
+    assert!(crate::deserialize_with_options::<String>(&bytes, options).is_err());
+}
+
+#[test]
+fn test_deserialize_prefix() {
+    let mut bytes = crate::serialize(&(1u32, "a")).unwrap();
+    bytes.extend(crate::serialize(&(2u32, "b")).unwrap());
+
+    let (first, rest): ((u32, String), _) = crate::deserialize_prefix(&bytes).unwrap();
+    assert_eq!(first, (1, "a".to_string()));
+    let (second, rest): ((u32, String), _) = crate::deserialize_prefix(rest).unwrap();
+    assert_eq!(second, (2, "b".to_string()));
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn test_peek_len() {
+    let long = "x".repeat(200);
+    let mut bytes = crate::serialize(&long).unwrap();
+    bytes.extend(crate::serialize(&"next").unwrap());
+    let total = crate::serialize(&long).unwrap().len();
+    assert_eq!(total, 202);
+
+    // A partial buffer: the prefix, or only some of the value.
+    assert_eq!(crate::peek_len(&[]).unwrap(), None);
+    assert_eq!(crate::peek_len(&bytes[..1]).unwrap(), None);
+    assert_eq!(crate::peek_len(&bytes[..2]).unwrap(), Some(total));
+    assert_eq!(crate::peek_len(&bytes[..100]).unwrap(), Some(total));
+
+    // A complete buffer, with another value following.
+    assert_eq!(crate::peek_len(&bytes).unwrap(), Some(total));
+    assert_eq!(crate::peek_len(&bytes[total..]).unwrap(), Some(5));
+
+    let bytes = crate::serialize(&vec![1u8, 2, 3]).unwrap();
+    assert_eq!(crate::peek_len(&bytes).unwrap(), Some(4));
+
+    // A length prefix overflowing usize.
+    assert!(crate::peek_len(&[0xff; 11]).is_err());
+}
+
+#[test]
+fn test_peek_len_of() {
+    // Fixed-size scalars have their width.
+    assert_eq!(crate::peek_len_of::<u8>(&[7, 8]).unwrap(), Some(1));
+    assert_eq!(crate::peek_len_of::<bool>(&[1]).unwrap(), Some(1));
+    let bytes = crate::serialize(&1.5f64).unwrap();
+    assert_eq!(crate::peek_len_of::<f64>(&bytes).unwrap(), Some(8));
+    assert_eq!(crate::peek_len_of::<f64>(&bytes[..7]).unwrap(), None);
+    assert_eq!(crate::peek_len_of::<u8>(&[]).unwrap(), None);
+
+    let bytes = crate::serialize(&'é').unwrap();
+    assert_eq!(crate::peek_len_of::<char>(&bytes).unwrap(), Some(2));
+    assert_eq!(crate::peek_len_of::<char>(&bytes[..1]).unwrap(), None);
+
+    // Varints and values made of several parts.
+    let value = (300u32, "abc".to_string(), vec![1u64, 1000]);
+    let bytes = crate::serialize(&value).unwrap();
+    for end in 0..bytes.len() {
+        assert_eq!(
+            crate::peek_len_of::<(u32, String, Vec<u64>)>(&bytes[..end]).unwrap(),
+            None
+        );
+    }
+    assert_eq!(
+        crate::peek_len_of::<(u32, String, Vec<u64>)>(&bytes).unwrap(),
+        Some(bytes.len())
+    );
+
+    // Invalid values are still errors.
+    assert!(crate::peek_len_of::<bool>(&[2]).is_err());
+    assert!(crate::peek_len_of::<String>(&[1, 0xff]).is_err());
+}
+
+mod v1 {
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    pub enum Entry {
+        File(String),
+        Dir { name: String, children: u32 },
+        Unknown,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    pub enum Strict {
+        File(String),
+    }
+}
+
+mod v2 {
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    pub enum Entry {
+        File(String),
+        Dir { name: String, children: u32 },
+        Unknown,
+        Symlink(String, String),
+        Removed,
+    }
+}
+
+#[test]
+fn test_length_prefixed_variants_roundtrip() {
+    let options = crate::Options::new().length_prefixed_variants(true);
+    let entries = vec![
+        v2::Entry::File("a".to_string()),
+        v2::Entry::Dir {
+            name: "b".to_string(),
+            children: 3,
+        },
+        v2::Entry::Symlink("c".to_string(), "a".to_string()),
+        v2::Entry::Removed,
+    ];
+    let bytes = crate::serialize_with_options(&entries, options).unwrap();
+    assert_ne!(bytes, crate::serialize(&entries).unwrap());
+    let decoded: Vec<v2::Entry> = crate::deserialize_with_options(&bytes, options).unwrap();
+    assert_eq!(decoded, entries);
+}
+
+#[test]
+fn test_skip_unknown_variant() {
+    let options = crate::Options::new().length_prefixed_variants(true);
+    let entries = (
+        vec![
+            v2::Entry::Symlink("c".to_string(), "a".to_string()),
+            v2::Entry::File("a".to_string()),
+            v2::Entry::Removed,
+            v2::Entry::Dir {
+                name: "b".to_string(),
+                children: 3,
+            },
+        ],
+        42u32,
+    );
+    let bytes = crate::serialize_with_options(&entries, options).unwrap();
+
+    let decoded: (Vec<v1::Entry>, u32) = crate::deserialize_with_options(&bytes, options).unwrap();
+    assert_eq!(
+        decoded,
+        (
+            vec![
+                v1::Entry::Unknown,
+                v1::Entry::File("a".to_string()),
+                v1::Entry::Unknown,
+                v1::Entry::Dir {
+                    name: "b".to_string(),
+                    children: 3,
+                },
+            ],
+            42
+        )
+    );
+
+    // Without an `Unknown` variant to fall back to, this is still an error.
+    let bytes = crate::serialize_with_options(
+        &v2::Entry::Symlink("c".to_string(), "a".to_string()),
+        options,
+    )
+    .unwrap();
+    let err = crate::deserialize_with_options::<v1::Strict>(&bytes, options).unwrap_err();
+    assert_eq!(err.to_string(), "unknown variant index 3 for enum Strict");
+}
+
+/// A writer that fails once `limit` bytes were written to it.
+struct FailingWriter {
+    written: Vec<u8>,
+    limit: usize,
+}
+
+impl io::Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.limit - self.written.len());
+        if len == 0 && !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "writer is full"));
+        }
+        self.written.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_serialize_into() {
+    let value: Vec<String> = (0..1000).map(|i| format!("item{}", i)).collect();
+    let bytes = crate::serialize(&value).unwrap();
+
+    let mut out = Vec::new();
+    crate::serialize_into(&mut out, &value).unwrap();
+    assert_eq!(out, bytes);
+
+    // The output is written incrementally, up to the failure.
+    let mut writer = FailingWriter {
+        written: Vec::new(),
+        limit: 100,
+    };
+    let err = crate::serialize_into(&mut writer, &value).unwrap_err();
+    assert_eq!(
+        err.io_error().map(|e| e.kind()),
+        Some(io::ErrorKind::WriteZero)
+    );
+    assert_eq!(writer.written, bytes[..100]);
+}
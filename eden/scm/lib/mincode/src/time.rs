@@ -0,0 +1,73 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Stable encodings for `std::time` types.
+//!
+//! Use them as `#[serde(with = "mincode::time::system_time")]` or
+//! `#[serde(with = "mincode::time::duration")]` on struct fields.
+//!
+//! Both types are encoded as a `(u64, u32)` tuple of whole seconds and
+//! nanoseconds. `SystemTime` is measured from the Unix epoch.
+
+/// Encode a `Duration` as seconds and nanoseconds.
+pub mod duration {
+    use std::time::Duration;
+
+    use serde::de;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        (value.as_secs(), value.subsec_nanos()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let (secs, nanos) = <(u64, u32)>::deserialize(deserializer)?;
+        if nanos >= NANOS_PER_SEC {
+            return Err(de::Error::custom(format!(
+                "invalid duration: {} nanoseconds is out of range",
+                nanos
+            )));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+/// Encode a `SystemTime` as seconds and nanoseconds since the Unix epoch.
+///
+/// Times before the epoch cannot be encoded.
+pub mod system_time {
+    use std::time::SystemTime;
+
+    use serde::de;
+    use serde::ser;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(value: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        let since_epoch = value
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|e| ser::Error::custom(format!("time is before the Unix epoch: {:?}", e)))?;
+        super::duration::serialize(&since_epoch, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let since_epoch = super::duration::deserialize(deserializer)?;
+        SystemTime::UNIX_EPOCH
+            .checked_add(since_epoch)
+            .ok_or_else(|| {
+                de::Error::custom(format!(
+                    "invalid time: {:?} since the Unix epoch is out of range",
+                    since_epoch
+                ))
+            })
+    }
+}
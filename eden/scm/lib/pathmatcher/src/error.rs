@@ -10,6 +10,30 @@ pub enum Error {
     #[error("unsuppported pattern kind {0}")]
     UnsupportedPatternKind(String),
 
+    #[error("unknown pattern kind in {input:?} (did you mean \"{}:\"?)", .suggestion.name())]
+    UnknownPatternKind {
+        input: String,
+        suggestion: crate::PatternKind,
+    },
+
+    #[error("path {0} is not under root {1}")]
+    PathOutsideRoot(String, String),
+
+    #[error("invalid regex {0:?}: {1}")]
+    InvalidRegex(String, String),
+
+    #[error("regex {pattern:.100} is too large (exceeds the size limit of {limit} bytes)")]
+    RegexTooLarge { pattern: String, limit: usize },
+
+    #[error("invalid glob {0:?}: {1}")]
+    InvalidGlob(String, String),
+
+    #[error("unknown file type {0:?} (expected \"regular\", \"symlink\" or \"dir\")")]
+    UnknownFileType(String),
+
+    #[error("invalid JSON listfile {0}: {1}")]
+    InvalidListFileJson(String, String),
+
     #[error(transparent)]
     IOError(#[from] util::errors::IOError),
 }
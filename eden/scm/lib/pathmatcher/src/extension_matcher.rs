@@ -0,0 +1,140 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Pattern matcher that matches files by extension.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+use types::RepoPath;
+
+use crate::DirectoryMatch;
+use crate::DynMatcher;
+use crate::Matcher;
+
+/// A [Matcher] that matches files with one of a set of extensions, in any
+/// directory.
+///
+/// The extension of a file is anything after a dot in its name, so that
+/// `foo.tar.gz` matches both `gz` and `tar.gz`. Dotfiles like `.gitignore`
+/// have no extension unless they have another dot: `.eslintrc.json` matches
+/// `json`, but not `eslintrc.json`.
+#[derive(Clone, Debug)]
+pub struct ExtensionMatcher {
+    extensions: HashSet<String>,
+}
+
+impl ExtensionMatcher {
+    /// Create [ExtensionMatcher] from a list of extensions, with or without
+    /// their leading dot (`rs` and `.rs` are the same).
+    pub fn new(extensions: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let extensions = extensions
+            .into_iter()
+            .map(|ext| {
+                let ext = ext.as_ref();
+                ext.strip_prefix('.').unwrap_or(ext).to_string()
+            })
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        ExtensionMatcher { extensions }
+    }
+}
+
+/// Build a matcher for files with any of `exts` as extension. See
+/// [ExtensionMatcher] for details.
+pub fn extensions_matcher(exts: &[&str]) -> DynMatcher {
+    Arc::new(ExtensionMatcher::new(exts))
+}
+
+impl Matcher for ExtensionMatcher {
+    fn matches_directory(&self, _path: &RepoPath) -> Result<DirectoryMatch> {
+        if self.extensions.is_empty() {
+            Ok(DirectoryMatch::Nothing)
+        } else {
+            Ok(DirectoryMatch::ShouldTraverse)
+        }
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> Result<bool> {
+        let name = match path.last_component() {
+            Some(name) => name.as_str(),
+            None => return Ok(false),
+        };
+        // Skip the first character, so that the leading dot of dotfiles
+        // doesn't start an extension.
+        Ok(name
+            .char_indices()
+            .skip(1)
+            .any(|(i, c)| c == '.' && self.extensions.contains(&name[i + 1..])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(m: &impl Matcher, path: &str) -> bool {
+        m.matches_file(RepoPath::from_str(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_single_extensions() {
+        let m = ExtensionMatcher::new(["rs", ".py"]);
+        assert!(matches(&m, "a.rs"));
+        assert!(matches(&m, "a/b/c.rs"));
+        assert!(matches(&m, "a/b.py"));
+        assert!(matches(&m, "a.b.rs"));
+        assert!(!matches(&m, "a.rsx"));
+        assert!(!matches(&m, "a.c"));
+        assert!(!matches(&m, "rs"));
+        assert!(!matches(&m, "a.rs/b"));
+        assert!(!matches(&m, "a.rs."));
+
+        let root = RepoPath::empty();
+        let dir = RepoPath::from_str("a.rs").unwrap();
+        assert_eq!(
+            m.matches_directory(root).unwrap(),
+            DirectoryMatch::ShouldTraverse
+        );
+        assert_eq!(
+            m.matches_directory(dir).unwrap(),
+            DirectoryMatch::ShouldTraverse
+        );
+        assert_eq!(
+            ExtensionMatcher::new([""]).matches_directory(root).unwrap(),
+            DirectoryMatch::Nothing
+        );
+    }
+
+    #[test]
+    fn test_compound_extensions() {
+        let m = extensions_matcher(&["tar.gz"]);
+        assert!(matches(&m, "a.tar.gz"));
+        assert!(matches(&m, "a.b.tar.gz"));
+        assert!(!matches(&m, "a.gz"));
+        assert!(!matches(&m, "a.tar"));
+
+        let m = extensions_matcher(&["gz"]);
+        assert!(matches(&m, "a.tar.gz"));
+        assert!(matches(&m, "a.gz"));
+    }
+
+    #[test]
+    fn test_dotfiles() {
+        let m = extensions_matcher(&["gitignore", "json", "eslintrc.json"]);
+        assert!(!matches(&m, ".gitignore"));
+        assert!(!matches(&m, "a/.gitignore"));
+        assert!(matches(&m, "a.gitignore"));
+        assert!(matches(&m, ".eslintrc.json"));
+        assert!(matches(&m, "a/.eslintrc.json"));
+
+        let m = extensions_matcher(&["eslintrc.json"]);
+        assert!(!matches(&m, ".eslintrc.json"));
+        assert!(matches(&m, "a.eslintrc.json"));
+    }
+}
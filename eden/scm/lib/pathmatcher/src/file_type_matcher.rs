@@ -0,0 +1,230 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Matcher that restricts another matcher to files of some types.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use types::RepoPath;
+
+use crate::DirectoryMatch;
+use crate::Error;
+use crate::Matcher;
+
+/// Prefix of the patterns selecting a file type, as in `type:symlink`.
+const TYPE_PREFIX: &str = "type:";
+
+/// Type of a file, as reported by `lstat`: symlinks are not followed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileType {
+    Regular,
+    Symlink,
+    Directory,
+}
+
+impl FileType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileType::Regular => "regular",
+            FileType::Symlink => "symlink",
+            FileType::Directory => "dir",
+        }
+    }
+}
+
+impl FromStr for FileType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regular" => Ok(FileType::Regular),
+            "symlink" => Ok(FileType::Symlink),
+            "dir" => Ok(FileType::Directory),
+            _ => Err(Error::UnknownFileType(s.to_string())),
+        }
+    }
+}
+
+/// Returns the type of a file, or `None` if it does not exist.
+///
+/// Pathmatcher has no access to the file system, so callers provide this.
+/// It must not follow symlinks: a broken symlink is a symlink, not a
+/// missing file.
+pub type FileTypeResolver = Arc<dyn Fn(&RepoPath) -> Result<Option<FileType>> + Send + Sync>;
+
+/// Split `type:` patterns, such as `type:symlink`, from the other patterns,
+/// keeping the order of both. Returns the parsed types and the other
+/// patterns, unchanged. Callers can build a matcher from the other patterns
+/// and pass both to [FileTypeMatcher::new].
+///
+/// Fails with [Error::UnknownFileType] for a `type:` pattern naming an
+/// unknown type.
+pub fn partition_file_types(
+    patterns: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<(Vec<FileType>, Vec<String>), Error> {
+    let mut types = Vec::new();
+    let mut others = Vec::new();
+    for pattern in patterns {
+        let pattern = pattern.as_ref();
+        match pattern.strip_prefix(TYPE_PREFIX) {
+            Some(name) => types.push(name.parse()?),
+            None => others.push(pattern.to_string()),
+        }
+    }
+    Ok((types, others))
+}
+
+/// A [Matcher] that matches the files of another matcher that have one of
+/// a set of types. Files that no longer exist never match.
+pub struct FileTypeMatcher<M> {
+    matcher: M,
+    types: Vec<FileType>,
+    resolver: FileTypeResolver,
+}
+
+impl<M> FileTypeMatcher<M> {
+    pub fn new(
+        matcher: M,
+        types: impl IntoIterator<Item = FileType>,
+        resolver: FileTypeResolver,
+    ) -> Self {
+        let mut unique = Vec::new();
+        for file_type in types {
+            if !unique.contains(&file_type) {
+                unique.push(file_type);
+            }
+        }
+        Self {
+            matcher,
+            types: unique,
+            resolver,
+        }
+    }
+}
+
+impl<M: Matcher> Matcher for FileTypeMatcher<M> {
+    fn matches_directory(&self, path: &RepoPath) -> Result<DirectoryMatch> {
+        if self.types.is_empty() {
+            return Ok(DirectoryMatch::Nothing);
+        }
+        // Not every file under a directory has the right type.
+        Ok(match self.matcher.matches_directory(path)? {
+            DirectoryMatch::Everything => DirectoryMatch::ShouldTraverse,
+            m => m,
+        })
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> Result<bool> {
+        // Check the path first, as resolving the type can hit the disk.
+        if self.types.is_empty() || !self.matcher.matches_file(path)? {
+            return Ok(false);
+        }
+        Ok(match (self.resolver)(path)? {
+            Some(file_type) => self.types.contains(&file_type),
+            None => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::AlwaysMatcher;
+    use crate::TreeMatcher;
+
+    fn resolver(files: &[(&str, FileType)]) -> FileTypeResolver {
+        let files: HashMap<String, FileType> =
+            files.iter().map(|(p, t)| (p.to_string(), *t)).collect();
+        Arc::new(move |path: &RepoPath| Ok(files.get(path.as_str()).copied()))
+    }
+
+    fn matches(m: &impl Matcher, path: &str) -> bool {
+        m.matches_file(RepoPath::from_str(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_symlinks() {
+        let files = resolver(&[
+            ("a/file", FileType::Regular),
+            ("a/link", FileType::Symlink),
+            // A broken symlink is still resolved as a symlink.
+            ("a/broken", FileType::Symlink),
+            ("a/dir", FileType::Directory),
+        ]);
+
+        let m = FileTypeMatcher::new(AlwaysMatcher::new(), [FileType::Symlink], files.clone());
+        assert!(matches(&m, "a/link"));
+        assert!(matches(&m, "a/broken"));
+        assert!(!matches(&m, "a/file"));
+        assert!(!matches(&m, "a/dir"));
+        assert!(!matches(&m, "a/missing"));
+        assert_eq!(
+            m.matches_directory(RepoPath::from_str("a").unwrap())
+                .unwrap(),
+            DirectoryMatch::ShouldTraverse
+        );
+
+        let m = FileTypeMatcher::new(AlwaysMatcher::new(), [FileType::Regular], files);
+        assert!(matches(&m, "a/file"));
+        assert!(!matches(&m, "a/link"));
+        assert!(!matches(&m, "a/broken"));
+    }
+
+    #[test]
+    fn test_compose_with_patterns() -> Result<()> {
+        let (types, patterns) = partition_file_types(["type:symlink", "a/**"])?;
+        assert_eq!(types, vec![FileType::Symlink]);
+        assert_eq!(patterns, vec!["a/**".to_string()]);
+
+        let files = resolver(&[
+            ("a/link", FileType::Symlink),
+            ("a/file", FileType::Regular),
+            ("b/link", FileType::Symlink),
+        ]);
+        let m = FileTypeMatcher::new(
+            TreeMatcher::from_rules(patterns.iter(), true)?,
+            types,
+            files,
+        );
+        assert!(matches(&m, "a/link"));
+        assert!(!matches(&m, "a/file"));
+        assert!(!matches(&m, "b/link"));
+        assert_eq!(
+            m.matches_directory(RepoPath::from_str("b")?)?,
+            DirectoryMatch::Nothing
+        );
+
+        // Ignoring all symlinks.
+        let symlinks = FileTypeMatcher::new(
+            AlwaysMatcher::new(),
+            [FileType::Symlink],
+            resolver(&[("link", FileType::Symlink), ("file", FileType::Regular)]),
+        );
+        let m = crate::DifferenceMatcher::new(AlwaysMatcher::new(), symlinks);
+        assert!(matches(&m, "file"));
+        assert!(!matches(&m, "link"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_file_types() {
+        let (types, others) =
+            partition_file_types(["type:regular", "glob:*.c", "type:dir"]).unwrap();
+        assert_eq!(types, vec![FileType::Regular, FileType::Directory]);
+        assert_eq!(others, vec!["glob:*.c".to_string()]);
+
+        assert!(matches!(
+            partition_file_types(["type:fifo"]),
+            Err(Error::UnknownFileType(t)) if t == "fifo"
+        ));
+    }
+}
@@ -7,9 +7,13 @@
 
 mod error;
 mod exact_matcher;
+mod extension_matcher;
+mod file_type_matcher;
 mod gitignore_matcher;
 mod matcher;
+mod matcher_cache;
 mod pattern;
+mod re2;
 mod regex_matcher;
 mod tree_matcher;
 mod utils;
@@ -22,14 +26,42 @@ use types::RepoPath;
 
 pub use crate::error::Error;
 pub use crate::exact_matcher::ExactMatcher;
+pub use crate::extension_matcher::extensions_matcher;
+pub use crate::extension_matcher::ExtensionMatcher;
+pub use crate::file_type_matcher::partition_file_types;
+pub use crate::file_type_matcher::FileType;
+pub use crate::file_type_matcher::FileTypeMatcher;
+pub use crate::file_type_matcher::FileTypeResolver;
 pub use crate::gitignore_matcher::GitignoreMatcher;
 pub use crate::matcher::build_matcher;
+pub use crate::matcher_cache::build_matcher_cached;
+pub use crate::matcher_cache::MatcherCache;
 pub use crate::pattern::build_patterns;
+pub use crate::pattern::escape_literal;
+pub use crate::pattern::minimize_patterns;
+pub use crate::pattern::normalize_path_pattern_with_separator;
+pub use crate::pattern::normalize_patterns_report;
+pub use crate::pattern::normalize_patterns_with_warnings;
+pub use crate::pattern::partition_rooted;
+pub use crate::pattern::pattern_diff;
+pub use crate::pattern::patterns_equivalent;
+pub use crate::pattern::reroot_patterns;
 pub use crate::pattern::split_pattern;
+pub use crate::pattern::split_pattern_strict;
+pub use crate::pattern::try_build_patterns;
+pub use crate::pattern::NormalizeOptions;
+pub use crate::pattern::NormalizeReport;
+pub use crate::pattern::NormalizeReportEntry;
 pub use crate::pattern::PatternKind;
+pub use crate::pattern::PatternWarning;
+pub use crate::pattern::PatternWarningKind;
+pub use crate::pattern::ReportedPattern;
 pub use crate::regex_matcher::RegexMatcher;
+pub use crate::regex_matcher::LISTFILE_REGEX_SIZE_LIMIT;
+pub use crate::regex_matcher::REGEX_SIZE_LIMIT;
 pub use crate::tree_matcher::TreeMatcher;
 pub use crate::utils::expand_curly_brackets;
+pub use crate::utils::glob_to_regex;
 pub use crate::utils::normalize_glob;
 pub use crate::utils::plain_to_glob;
 
@@ -11,6 +11,8 @@ use std::sync::Arc;
 use anyhow::Result;
 
 use crate::pattern::Pattern;
+use crate::regex_matcher::LISTFILE_REGEX_SIZE_LIMIT;
+use crate::regex_matcher::REGEX_SIZE_LIMIT;
 use crate::AlwaysMatcher;
 use crate::DifferenceMatcher;
 use crate::DynMatcher;
@@ -57,14 +59,35 @@ pub fn build_matcher(
 
 fn build_matcher_from_patterns(patterns: &[Pattern], case_sensitive: bool) -> Result<DynMatcher> {
     assert!(!patterns.is_empty(), "patterns should not be empty");
+    let (compound_patterns, patterns): (Vec<&Pattern>, Vec<&Pattern>) =
+        patterns.iter().partition(|p| !p.excludes.is_empty());
+    // Regexes read from listfiles end up in one large alternation.
+    let regex_size_limit = if patterns
+        .iter()
+        .any(|p| p.kind == PatternKind::RE && p.source.is_some())
+    {
+        LISTFILE_REGEX_SIZE_LIMIT
+    } else {
+        REGEX_SIZE_LIMIT
+    };
     let grouped_patterns = group_by_pattern_kind(patterns);
     let mut matchers: Vec<DynMatcher> = Vec::new();
+    for p in compound_patterns {
+        // Only globs can have exclusions.
+        let include = TreeMatcher::from_rules([&p.pattern].iter(), case_sensitive)?;
+        let exclude = TreeMatcher::from_rules(p.excludes.iter(), case_sensitive)?;
+        matchers.push(Arc::new(DifferenceMatcher::new(include, exclude)));
+    }
     for (kind, pats) in &grouped_patterns {
         let m: DynMatcher = match kind {
             PatternKind::Glob => Arc::new(TreeMatcher::from_rules(pats.iter(), case_sensitive)?),
             PatternKind::RE => {
                 let regex_pat = format!("(?:{})", pats.join("|"));
-                Arc::new(RegexMatcher::new(&regex_pat, case_sensitive)?)
+                Arc::new(RegexMatcher::with_size_limit(
+                    &regex_pat,
+                    case_sensitive,
+                    regex_size_limit,
+                )?)
             }
             _ => {
                 return Err(Error::UnsupportedPatternKind(kind.name().to_string()).into());
@@ -80,9 +103,9 @@ fn build_matcher_from_patterns(patterns: &[Pattern], case_sensitive: bool) -> Re
     }
 }
 
-fn group_by_pattern_kind(patterns: &[Pattern]) -> HashMap<PatternKind, Vec<String>> {
+fn group_by_pattern_kind(patterns: Vec<&Pattern>) -> HashMap<PatternKind, Vec<String>> {
     let mut res = HashMap::new();
-    for p in patterns.iter() {
+    for p in patterns {
         res.entry(p.kind)
             .or_insert_with(Vec::new)
             .push(p.pattern.clone())
@@ -199,4 +222,19 @@ mod tests {
             DirectoryMatch::ShouldTraverse
         );
     }
+
+    #[test]
+    fn test_build_matcher_with_pathological_regex() {
+        let patterns = &[Pattern::new(
+            PatternKind::RE,
+            r"(?:a|b)*a(?:a|b){30}".to_string(),
+        )];
+
+        let err = build_matcher(patterns, &[], &[], true).err().unwrap();
+
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RegexTooLarge { .. })
+        ));
+    }
 }
@@ -0,0 +1,244 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use lru_cache::LruCache;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::build_matcher;
+use crate::pattern::Pattern;
+use crate::DynMatcher;
+use crate::PatternKind;
+
+/// Number of matchers kept by [`MatcherCache::global`] unless changed with
+/// [`MatcherCache::set_capacity`].
+const DEFAULT_CAPACITY: usize = 64;
+
+static GLOBAL: Lazy<MatcherCache> = Lazy::new(|| MatcherCache::new(DEFAULT_CAPACITY));
+
+/// Everything `build_matcher` depends on.
+///
+/// Patterns are keyed after normalization, so the same patterns written
+/// relative to different roots or cwds can share a matcher, while the same
+/// input resolving to different patterns cannot.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct MatcherKey {
+    patterns: Vec<KeyPattern>,
+    include: Vec<KeyPattern>,
+    exclude: Vec<KeyPattern>,
+    case_sensitive: bool,
+}
+
+/// Kind, pattern, excludes, and whether the pattern was read from a
+/// listfile, which allows larger regexes.
+type KeyPattern = (PatternKind, String, Vec<String>, bool);
+
+fn key_patterns(patterns: &[Pattern]) -> Vec<KeyPattern> {
+    patterns
+        .iter()
+        .map(|p| {
+            (
+                p.kind,
+                p.pattern.clone(),
+                p.excludes.clone(),
+                p.source.is_some(),
+            )
+        })
+        .collect()
+}
+
+/// LRU cache of matchers built by [`build_matcher`], to avoid compiling the
+/// same pattern set again.
+pub struct MatcherCache {
+    matchers: Mutex<LruCache<MatcherKey, DynMatcher>>,
+}
+
+impl MatcherCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            matchers: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// The process-wide cache used by [`build_matcher_cached`].
+    pub fn global() -> &'static MatcherCache {
+        &GLOBAL
+    }
+
+    /// Same as [`build_matcher`], but return a shared matcher if one was
+    /// already built for the same normalized patterns.
+    pub fn build_matcher(
+        &self,
+        patterns: &[Pattern],
+        include: &[Pattern],
+        exclude: &[Pattern],
+        case_sensitive: bool,
+    ) -> Result<DynMatcher> {
+        let key = MatcherKey {
+            patterns: key_patterns(patterns),
+            include: key_patterns(include),
+            exclude: key_patterns(exclude),
+            case_sensitive,
+        };
+        if let Some(matcher) = self.matchers.lock().get_mut(&key) {
+            return Ok(matcher.clone());
+        }
+
+        // Build outside of the lock, as it can be slow. Concurrent builds of
+        // the same patterns are harmless: the last one wins.
+        let matcher = build_matcher(patterns, include, exclude, case_sensitive)?;
+        self.matchers.lock().insert(key, matcher.clone());
+        Ok(matcher)
+    }
+
+    /// Change the maximum number of matchers kept, evicting the least
+    /// recently used ones if there are more.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.matchers.lock().set_capacity(capacity);
+    }
+
+    pub fn clear(&self) {
+        self.matchers.lock().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.matchers.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Same as [`build_matcher`], using the process-wide [`MatcherCache`].
+pub fn build_matcher_cached(
+    patterns: &[Pattern],
+    include: &[Pattern],
+    exclude: &[Pattern],
+    case_sensitive: bool,
+) -> Result<DynMatcher> {
+    MatcherCache::global().build_matcher(patterns, include, exclude, case_sensitive)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::pattern::normalize_patterns;
+
+    fn glob(pattern: &str) -> Pattern {
+        Pattern::new(PatternKind::Glob, pattern.to_string())
+    }
+
+    #[test]
+    fn test_same_patterns_share_matcher() {
+        let cache = MatcherCache::new(10);
+        let m1 = cache
+            .build_matcher(&[glob("a/**")], &[], &[glob("a/b/**")], true)
+            .unwrap();
+        let m2 = cache
+            .build_matcher(&[glob("a/**")], &[], &[glob("a/b/**")], true)
+            .unwrap();
+        assert!(Arc::ptr_eq(&m1, &m2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_different_patterns_dont_share_matcher() {
+        let cache = MatcherCache::new(10);
+        let m = cache
+            .build_matcher(&[glob("a/**")], &[], &[], true)
+            .unwrap();
+        let others = [
+            cache
+                .build_matcher(&[glob("b/**")], &[], &[], true)
+                .unwrap(),
+            cache
+                .build_matcher(&[glob("a/**")], &[], &[], false)
+                .unwrap(),
+            cache
+                .build_matcher(&[], &[glob("a/**")], &[], true)
+                .unwrap(),
+            cache
+                .build_matcher(
+                    &[Pattern::new(PatternKind::RE, "a/.*".to_string())],
+                    &[],
+                    &[],
+                    true,
+                )
+                .unwrap(),
+        ];
+        for other in &others {
+            assert!(!Arc::ptr_eq(&m, other));
+        }
+        assert_eq!(cache.len(), 5);
+    }
+
+    #[test]
+    fn test_listfile_patterns_dont_share_matcher() {
+        // Too large for a regex given directly, but not for regexes read
+        // from a listfile.
+        let paths: Vec<String> = (0..20000)
+            .map(|i| format!("dir{}/file{}\\.txt", i % 50, i))
+            .collect();
+        let re = Pattern::new(PatternKind::RE, format!("(?:{})$", paths.join("|")));
+        let cache = MatcherCache::new(10);
+        cache
+            .build_matcher(
+                &[re.clone().with_source("list".to_string())],
+                &[],
+                &[],
+                true,
+            )
+            .unwrap();
+        assert!(cache.build_matcher(&[re], &[], &[], true).is_err());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_key_is_normalized_patterns() {
+        let cache = MatcherCache::new(10);
+        let root = if cfg!(windows) {
+            Path::new(r"C:\repo")
+        } else {
+            Path::new("/repo")
+        };
+        let build = |pattern: &str, cwd: &Path| {
+            let patterns = normalize_patterns([pattern], PatternKind::Glob, root, cwd).unwrap();
+            cache.build_matcher(&patterns, &[], &[], true).unwrap()
+        };
+
+        let from_root = build("foo/*.c", root);
+        let from_foo = build("*.c", &root.join("foo"));
+        let from_bar = build("*.c", &root.join("bar"));
+        assert!(Arc::ptr_eq(&from_root, &from_foo));
+        assert!(!Arc::ptr_eq(&from_foo, &from_bar));
+    }
+
+    #[test]
+    fn test_capacity_and_clear() {
+        let cache = MatcherCache::new(2);
+        let a = cache.build_matcher(&[glob("a")], &[], &[], true).unwrap();
+        cache.build_matcher(&[glob("b")], &[], &[], true).unwrap();
+        cache.build_matcher(&[glob("c")], &[], &[], true).unwrap();
+        assert_eq!(cache.len(), 2);
+        // "a" was evicted.
+        let a2 = cache.build_matcher(&[glob("a")], &[], &[], true).unwrap();
+        assert!(!Arc::ptr_eq(&a, &a2));
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        let a3 = cache.build_matcher(&[glob("a")], &[], &[], true).unwrap();
+        assert!(!Arc::ptr_eq(&a2, &a3));
+    }
+}
@@ -5,11 +5,33 @@
  * GNU General Public License version 2.
  */
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use serde::Deserialize;
+use serde::Serialize;
+use types::RepoPath;
+use util::errors::IOContext;
+
 use crate::error::Error;
+use crate::utils::expand_bounded_globstar;
+use crate::utils::expand_curly_brackets;
+use crate::utils::plain_to_glob;
+use crate::DirectoryMatch;
+use crate::Matcher;
+use crate::TreeMatcher;
 
-#[derive(Debug, PartialEq, Copy, Clone, Hash, Eq)]
+/// Serialized as its [`PatternKind::name`], which is stable across versions.
+#[derive(Debug, PartialEq, Copy, Clone, Hash, Eq, Serialize, Deserialize)]
+#[serde(into = "&'static str", try_from = "String")]
 pub enum PatternKind {
     /// a regular expression relative to repository root, check [RegexMatcher]
     /// for supported RE syntax
@@ -22,7 +44,8 @@ pub enum PatternKind {
     /// directory, it is matched recursively
     Path,
 
-    /// an unrooted glob (e.g.: *.c matches C files in all dirs)
+    /// an unrooted glob (e.g.: *.c matches C files in all dirs), unless it
+    /// starts with a slash (e.g.: /*.c only matches C files in the root)
     RelGlob,
 
     /// a path relative to cwd
@@ -37,6 +60,13 @@ pub enum PatternKind {
     /// read file patterns with null byte delimiters from a file
     ListFile0,
 
+    /// read file patterns from a file containing a JSON array of strings
+    ListFileJson,
+
+    /// read patterns from a file using the .gitignore syntax, relative to
+    /// the directory of the file
+    GitIgnore,
+
     /// a fileset expression
     Set,
 
@@ -62,12 +92,75 @@ impl PatternKind {
             PatternKind::RelRE => "relre",
             PatternKind::ListFile => "listfile",
             PatternKind::ListFile0 => "listfile0",
+            PatternKind::ListFileJson => "listfilejson",
+            PatternKind::GitIgnore => "gitignore",
             PatternKind::Set => "set",
             PatternKind::Include => "include",
             PatternKind::SubInclude => "subinclude",
             PatternKind::RootFilesIn => "rootfilesin",
         }
     }
+
+    const ALL: [PatternKind; 14] = [
+        PatternKind::RE,
+        PatternKind::Glob,
+        PatternKind::Path,
+        PatternKind::RelGlob,
+        PatternKind::RelPath,
+        PatternKind::RelRE,
+        PatternKind::ListFile,
+        PatternKind::ListFile0,
+        PatternKind::ListFileJson,
+        PatternKind::GitIgnore,
+        PatternKind::Set,
+        PatternKind::Include,
+        PatternKind::SubInclude,
+        PatternKind::RootFilesIn,
+    ];
+
+    /// Whether patterns of this kind can match at any depth, instead of only
+    /// under the directory they name, like `relglob:*.c`.
+    pub fn is_free(&self) -> bool {
+        matches!(self, PatternKind::RelGlob | PatternKind::RelRE)
+    }
+
+    /// The kind whose name is the closest to `name`, if it's close enough
+    /// for `name` to be a typo of it.
+    ///
+    /// Up to one edit per two characters of the kind name is allowed, and
+    /// at most two, so that short directory names like `src` aren't taken
+    /// for typos of short kinds like `re` or `set`.
+    pub(crate) fn suggest(name: &str) -> Option<PatternKind> {
+        PatternKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let distance = edit_distance(name, kind.name());
+                let max_distance = ((kind.name().len() - 1) / 2).min(2);
+                (distance <= max_distance).then_some((distance, kind))
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, kind)| kind)
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, counting characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances between the prefix of `a` processed so far and each prefix
+    // of `b`.
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous_diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_diagonal + usize::from(a_char != *b_char);
+            previous_diagonal = distances[j + 1];
+            distances[j + 1] = substitution
+                .min(distances[j] + 1)
+                .min(previous_diagonal + 1);
+        }
+    }
+    distances[b.len()]
 }
 
 impl std::str::FromStr for PatternKind {
@@ -83,6 +176,8 @@ impl std::str::FromStr for PatternKind {
             "relre" => Ok(PatternKind::RelRE),
             "listfile" => Ok(PatternKind::ListFile),
             "listfile0" => Ok(PatternKind::ListFile0),
+            "listfilejson" => Ok(PatternKind::ListFileJson),
+            "gitignore" => Ok(PatternKind::GitIgnore),
             "set" => Ok(PatternKind::Set),
             "include" => Ok(PatternKind::Include),
             "subinclude" => Ok(PatternKind::SubInclude),
@@ -92,11 +187,30 @@ impl std::str::FromStr for PatternKind {
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl From<PatternKind> for &'static str {
+    fn from(kind: PatternKind) -> Self {
+        kind.name()
+    }
+}
+
+impl TryFrom<String> for PatternKind {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Pattern {
     pub(crate) kind: PatternKind,
     pub(crate) pattern: String,
     pub(crate) source: Option<String>,
+    /// Globs of the same kind excluded from the matches of `pattern`, from
+    /// the `include~exclude` syntax enabled by
+    /// [`NormalizeOptions::glob_excludes`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) excludes: Vec<String>,
 }
 
 impl Pattern {
@@ -105,6 +219,7 @@ impl Pattern {
             kind,
             pattern,
             source: None,
+            excludes: Vec::new(),
         }
     }
 
@@ -113,16 +228,53 @@ impl Pattern {
         self
     }
 
+    pub(crate) fn with_excludes(mut self, excludes: Vec<String>) -> Self {
+        self.excludes = excludes;
+        self
+    }
+
     /// Build `Pattern` from str.
     ///
     /// * If the str doesn't have pattern kind prefix, we will use `default_kind`.
     /// * `source` is set to None.
     pub(crate) fn from_str(pattern: &str, default_kind: PatternKind) -> Self {
         let (kind, pat) = split_pattern(pattern, default_kind);
-        Self {
-            kind,
-            pattern: pat.to_string(),
-            source: None,
+        Self::new(kind, pat.to_string())
+    }
+
+    /// Whether this normalized pattern could match a file under `dir`, so
+    /// that directories for which this is false don't need to be visited.
+    ///
+    /// This is conservative: patterns that can match anywhere, like
+    /// `relglob:`, or that are too costly to check, like regular
+    /// expressions, could match under any directory.
+    pub fn matches_directory_prefix(&self, dir: &RepoPath) -> bool {
+        let path_components = || {
+            self.pattern
+                .split('/')
+                .filter(|c| !c.is_empty() && *c != ".")
+        };
+        match self.kind {
+            // Both the ancestors of the path and the directories inside it.
+            PatternKind::Path | PatternKind::RelPath => dir
+                .components()
+                .zip(path_components())
+                .all(|(d, p)| d.as_str() == p),
+            // Only the path and its ancestors.
+            PatternKind::RootFilesIn => {
+                let mut path = path_components();
+                dir.components().all(|d| path.next() == Some(d.as_str()))
+            }
+            // TreeMatcher takes curly brackets literally.
+            PatternKind::Glob => {
+                match TreeMatcher::from_rules(expand_curly_brackets(&self.pattern).iter(), true) {
+                    Ok(matcher) => {
+                        !matches!(matcher.matches_directory(dir), Ok(DirectoryMatch::Nothing))
+                    }
+                    Err(_) => true,
+                }
+            }
+            _ => true,
         }
     }
 }
@@ -135,8 +287,321 @@ pub fn build_patterns(patterns: &[String], default_kind: PatternKind) -> Vec<Pat
         .collect()
 }
 
+/// Build normalized `Pattern`s from strings, validating them.
+///
+/// Unlike [`build_patterns`], which only splits the kind off each pattern,
+/// this reports every problem with the input: kinds that look like typos,
+/// patterns outside of `root`, listfiles that can't be read and invalid
+/// regular expressions. See [`normalize_patterns`] for how `root` and `cwd`
+/// are used.
+pub fn try_build_patterns(
+    patterns: &[String],
+    default_kind: PatternKind,
+    root: &Path,
+    cwd: &Path,
+) -> Result<Vec<Pattern>, Error> {
+    for pattern in patterns {
+        split_pattern_strict(pattern, default_kind)?;
+    }
+    let patterns = normalize_patterns(patterns, default_kind, root, cwd)?;
+    for p in &patterns {
+        if matches!(p.kind, PatternKind::RE | PatternKind::RelRE) {
+            regex_syntax::Parser::new()
+                .parse(&p.pattern)
+                .map_err(|e| Error::InvalidRegex(p.pattern.clone(), e.to_string()))?;
+        }
+    }
+    Ok(patterns)
+}
+
+/// Drop the normalized patterns that only match paths already matched by a
+/// broader recursive `path:` or `relpath:` pattern.
+///
+/// For example, `path:src/a` and `rootfilesin:src/b` are redundant with
+/// `path:src`. Only path-like patterns, and globs of the form `dir/**`, are
+/// ever dropped: other globs and regular expressions are kept as they are.
+/// The surviving patterns keep their order and `source`.
+pub fn minimize_patterns(patterns: &[Pattern]) -> Vec<Pattern> {
+    // Directory matched recursively -> index of the first pattern matching it.
+    let mut recursive_dirs: HashMap<&str, usize> = HashMap::new();
+    for (i, p) in patterns.iter().enumerate() {
+        if matches!(p.kind, PatternKind::Path | PatternKind::RelPath) {
+            recursive_dirs.entry(p.pattern.as_str()).or_insert(i);
+        }
+    }
+
+    let is_subsumed = |i: usize, p: &Pattern| -> bool {
+        let dir = match p.kind {
+            PatternKind::Path | PatternKind::RelPath | PatternKind::RootFilesIn => {
+                p.pattern.as_str()
+            }
+            PatternKind::Glob => match literal_recursive_glob_dir(&p.pattern) {
+                Some(dir) => dir,
+                None => return false,
+            },
+            _ => return false,
+        };
+        // `dir` itself is only redundant with an earlier pattern, so that one
+        // of duplicated patterns is kept.
+        if let Some(&j) = recursive_dirs.get(dir) {
+            if j != i {
+                return true;
+            }
+        }
+        let mut ancestor = dir;
+        while !ancestor.is_empty() {
+            ancestor = match ancestor.rfind('/') {
+                Some(pos) => &ancestor[..pos],
+                None => "",
+            };
+            if recursive_dirs.contains_key(ancestor) {
+                return true;
+            }
+        }
+        false
+    };
+
+    patterns
+        .iter()
+        .enumerate()
+        .filter(|(i, p)| !is_subsumed(*i, p))
+        .map(|(_, p)| p.clone())
+        .collect()
+}
+
+/// Whether the normalized patterns `a` and `b` match the same paths, as far
+/// as can be told from the patterns themselves: they must have the same
+/// kinds and patterns, in any order. `source` is ignored.
+///
+/// Patterns with excludes, from the `include~exclude` syntax or negated
+/// .gitignore lines, are compared in order: if either side has some, `a`
+/// and `b` must list the same patterns, with the same excludes, in the same
+/// order.
+pub fn patterns_equivalent(a: &[Pattern], b: &[Pattern]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    fn key(p: &Pattern) -> (PatternKind, &str) {
+        (p.kind, &p.pattern)
+    }
+    if a.iter().chain(b).any(|p| !p.excludes.is_empty()) {
+        return a
+            .iter()
+            .zip(b)
+            .all(|(x, y)| key(x) == key(y) && x.excludes == y.excludes);
+    }
+    let mut counts: HashMap<(PatternKind, &str), isize> = HashMap::new();
+    for p in a {
+        *counts.entry(key(p)).or_default() += 1;
+    }
+    for p in b {
+        *counts.entry(key(p)).or_default() -= 1;
+    }
+    counts.values().all(|c| *c == 0)
+}
+
+/// The normalized patterns of `new` that are not in `old`, and those of `old`
+/// that are not in `new`, as `(added, removed)`.
+///
+/// Patterns are identified by their kind, pattern and excludes: a pattern
+/// that only moved to another `source` is unchanged. Both lists keep the
+/// order of their side, and list duplicated patterns once.
+pub fn pattern_diff(old: &[Pattern], new: &[Pattern]) -> (Vec<Pattern>, Vec<Pattern>) {
+    fn key(p: &Pattern) -> (PatternKind, &str, &[String]) {
+        (p.kind, &p.pattern, &p.excludes)
+    }
+    fn missing(from: &[Pattern], other: &[Pattern]) -> Vec<Pattern> {
+        let other: HashSet<_> = other.iter().map(key).collect();
+        let mut seen = HashSet::new();
+        from.iter()
+            .filter(|p| !other.contains(&key(p)) && seen.insert(key(p)))
+            .cloned()
+            .collect()
+    }
+    (missing(new, old), missing(old, new))
+}
+
+/// Split normalized patterns into `(rooted, unrooted)`, keeping their order.
+///
+/// Rooted patterns only match under the directory they start with, so
+/// matchers can prune the directories they don't lead to. Unrooted ones are
+/// the free kinds, see [`PatternKind::is_free`], which can match anywhere.
+/// Patterns relative to cwd are relative to the root once normalized, and
+/// are rooted.
+pub fn partition_rooted(patterns: Vec<Pattern>) -> (Vec<Pattern>, Vec<Pattern>) {
+    patterns.into_iter().partition(|p| !p.kind.is_free())
+}
+
+/// Re-root normalized patterns to `nested_root`, a directory relative to the
+/// repository root, so that they match the same paths under it, relative
+/// to it. Patterns that can't match anything under `nested_root` are dropped.
+///
+/// Patterns straddling the boundary are trimmed to what they match under
+/// `nested_root`: with `src/sub` as `nested_root`, `path:src` becomes
+/// `path:` and `glob:src/**/*.c` becomes `glob:**/*.c`. Unrooted patterns
+/// are kept as they are. Rooted regular expressions can't be re-rooted and
+/// are rejected.
+pub fn reroot_patterns(patterns: &[Pattern], nested_root: &str) -> Result<Vec<Pattern>, Error> {
+    let root: Vec<&str> = nested_root
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect();
+    let mut result = Vec::new();
+    for p in patterns {
+        let rerooted = match p.kind {
+            kind if kind.is_free() => vec![p.pattern.clone()],
+            PatternKind::Path | PatternKind::RelPath => {
+                let path: Vec<&str> = p.pattern.split('/').filter(|c| !c.is_empty()).collect();
+                if path.starts_with(&root) {
+                    vec![path[root.len()..].join("/")]
+                } else if root.starts_with(&path) {
+                    // The whole nested root is under the path.
+                    vec![String::new()]
+                } else {
+                    Vec::new()
+                }
+            }
+            PatternKind::RootFilesIn => {
+                let path: Vec<&str> = p.pattern.split('/').filter(|c| !c.is_empty()).collect();
+                if path.starts_with(&root) {
+                    vec![path[root.len()..].join("/")]
+                } else {
+                    Vec::new()
+                }
+            }
+            PatternKind::Glob => reroot_glob(&p.pattern, &root),
+            kind => return Err(Error::UnsupportedPatternKind(kind.name().to_string())),
+        };
+        let excludes: Vec<String> = p
+            .excludes
+            .iter()
+            .flat_map(|e| reroot_glob(e, &root))
+            .collect();
+        for pattern in rerooted {
+            result.push(Pattern {
+                kind: p.kind,
+                pattern,
+                source: p.source.clone(),
+                excludes: excludes.clone(),
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// The globs matching, relative to `root`, the paths under `root` that are
+/// matched by `glob`.
+fn reroot_glob(glob: &str, root: &[&str]) -> Vec<String> {
+    let mut rerooted = Vec::new();
+    for glob in expand_curly_brackets(glob) {
+        let components: Vec<&str> = glob.split('/').collect();
+        reroot_glob_components(&components, root, &mut rerooted);
+    }
+    let mut seen = HashSet::new();
+    rerooted.retain(|g| seen.insert(g.clone()));
+    rerooted
+}
+
+fn reroot_glob_components(glob: &[&str], root: &[&str], rerooted: &mut Vec<String>) {
+    match (glob.split_first(), root.split_first()) {
+        // Globs matching `root` itself don't match anything under it.
+        (_, None) => {
+            if !glob.is_empty() {
+                rerooted.push(glob.join("/"));
+            }
+        }
+        (None, Some(_)) => {}
+        // `**` can stop before or inside `root`, or go past it.
+        (Some((&"**", rest)), Some((_, root_rest))) => {
+            reroot_glob_components(rest, root, rerooted);
+            reroot_glob_components(glob, root_rest, rerooted);
+        }
+        (Some((component, rest)), Some((root_component, root_rest))) => {
+            if glob_component_matches(component, root_component) {
+                reroot_glob_components(rest, root_rest, rerooted);
+            }
+        }
+    }
+}
+
+/// Whether the glob `component`, without separators, matches `name`.
+fn glob_component_matches(component: &str, name: &str) -> bool {
+    if !component.contains(['*', '?', '[', '\\']) {
+        return component == name;
+    }
+    match globset::GlobBuilder::new(component)
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()
+    {
+        Ok(glob) => glob.compile_matcher().is_match(name),
+        Err(_) => false,
+    }
+}
+
+/// The directory matched by a glob like `dir/**`, if `dir` has no special
+/// characters.
+fn literal_recursive_glob_dir(pattern: &str) -> Option<&str> {
+    if pattern == "**" {
+        return Some("");
+    }
+    let dir = pattern.strip_suffix("/**")?;
+    if dir.contains(['*', '?', '[', ']', '{', '}', '\\', '!']) {
+        None
+    } else {
+        Some(dir)
+    }
+}
+
+/// Escape `literal` so that, used as a pattern of `kind`, it stands for
+/// itself rather than being interpreted.
+///
+/// Glob kinds escape glob special characters, like [`plain_to_glob`], as
+/// well as `?`, `~` and a leading `norec:` marker. Regex kinds escape regex
+/// special characters. Path kinds, and kinds that read patterns from a
+/// file, take `literal` as is. Regexes still match any path starting with
+/// `literal`, as they are only anchored at the start.
+///
+/// Fails with [`Error::UnsupportedPatternKind`] for [`PatternKind::Set`], as
+/// a fileset expression has no way to quote a literal.
+pub fn escape_literal(literal: &str, kind: PatternKind) -> Result<String, Error> {
+    match kind {
+        PatternKind::Glob | PatternKind::RelGlob => {
+            let mut glob = String::with_capacity(literal.len());
+            for ch in plain_to_glob(literal).chars() {
+                // `?` matches any character, and `~` starts an exclusion.
+                if matches!(ch, '?' | '~') {
+                    glob.push('\\');
+                }
+                glob.push(ch);
+            }
+            // Not to be taken for the non-recursive marker.
+            Ok(match glob.strip_prefix(NON_RECURSIVE_GLOB_MARKER) {
+                Some(rest) => format!("\\{}{}", NON_RECURSIVE_GLOB_MARKER, rest),
+                None => glob,
+            })
+        }
+        PatternKind::RE | PatternKind::RelRE => Ok(regex_syntax::escape(literal)),
+        PatternKind::Path
+        | PatternKind::RelPath
+        | PatternKind::RootFilesIn
+        | PatternKind::ListFile
+        | PatternKind::ListFile0
+        | PatternKind::ListFileJson
+        | PatternKind::GitIgnore
+        | PatternKind::Include
+        | PatternKind::SubInclude => Ok(literal.to_string()),
+        PatternKind::Set => Err(Error::UnsupportedPatternKind(kind.name().to_string())),
+    }
+}
+
+/// Split the kind prefix off `pattern`, using `default_kind` if there is none.
+///
+/// A pattern starting with a Windows drive, like `C:\foo` or `C:/foo`, has no
+/// kind prefix.
 pub fn split_pattern<'a>(pattern: &'a str, default_kind: PatternKind) -> (PatternKind, &'a str) {
     match pattern.split_once(':') {
+        Some((k, p)) if is_drive_letter(k, p) => (default_kind, pattern),
         Some((k, p)) => {
             if let Ok(kind) = PatternKind::from_str(k) {
                 (kind, p)
@@ -148,83 +613,804 @@ pub fn split_pattern<'a>(pattern: &'a str, default_kind: PatternKind) -> (Patter
     }
 }
 
+/// Like [`split_pattern`], but fail with [`Error::UnknownPatternKind`] if
+/// the prefix of `pattern` looks like a typo of a pattern kind, like
+/// `globb:*.c`, rather than silently using `default_kind`.
+///
+/// Prefixes that don't look like any kind, like `src:foo`, are still treated
+/// as part of a pattern of `default_kind`.
+pub fn split_pattern_strict(
+    pattern: &str,
+    default_kind: PatternKind,
+) -> Result<(PatternKind, &str), Error> {
+    let (kind, pat) = split_pattern(pattern, default_kind);
+    if let Some((prefix, rest)) = pattern.split_once(':') {
+        if pat == pattern && !is_drive_letter(prefix, rest) {
+            if let Some(suggestion) = PatternKind::suggest(prefix) {
+                return Err(Error::UnknownPatternKind {
+                    input: pattern.to_string(),
+                    suggestion,
+                });
+            }
+        }
+    }
+    Ok((kind, pat))
+}
+
+/// Options for normalizing patterns.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NormalizeOptions<'a> {
+    /// Reject `re:` and `relre:` patterns that would not be accepted by RE2,
+    /// which is used to match them on the server.
+    pub re2_syntax: bool,
+
+    /// Directory, relative to the repository root, that `relglob:` patterns
+    /// are unrooted under, instead of the whole repository. For example,
+    /// with `src`, `relglob:*.c` normalizes to `glob:src/**/*.c`.
+    /// `relglob:` patterns anchored by a leading slash stay rooted at the
+    /// repository root.
+    pub relglob_base: Option<&'a str>,
+
+    /// Make `glob:` and `relglob:` patterns also match everything under the
+    /// directories they match, like `-I` and `-X` patterns. A pattern can
+    /// opt out with a `norec:` marker after its kind, like `glob:norec:*.c`.
+    /// Without this option, `norec:` is part of the pattern.
+    pub force_recursive_glob: bool,
+
+    /// Parse `glob:` patterns of the form `include~exclude`, which match
+    /// what `include` matches except what `exclude` matches. `\~` is a
+    /// literal `~`. Without this, `~` has no special meaning.
+    pub glob_excludes: bool,
+}
+
+/// Marker, after the kind, of a glob that is not made recursive by
+/// [`NormalizeOptions::force_recursive_glob`].
+const NON_RECURSIVE_GLOB_MARKER: &str = "norec:";
+
+/// Whether `prefix` and `rest`, split at a colon, are the drive letter and
+/// path of an absolute Windows path.
+fn is_drive_letter(prefix: &str, rest: &str) -> bool {
+    let mut chars = prefix.chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if c.is_ascii_alphabetic())
+        && (rest.starts_with('\\') || rest.starts_with('/'))
+}
+
+/// Normalize `patterns` so they are relative to the repository `root`.
+///
+/// Patterns of cwd-relative kinds are resolved against `cwd`, which must be
+/// inside `root`. The position of `cwd` relative to `root` is computed once
+/// and shared by every pattern, including the ones read from listfiles.
 // TODO: refactor this code to avoid the overhead of monomorphization by
 // using a wrapper function.
-#[allow(dead_code)]
 pub(crate) fn normalize_patterns<I>(
     patterns: I,
     default_kind: PatternKind,
+    root: &Path,
+    cwd: &Path,
+) -> Result<Vec<Pattern>, Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    normalize_patterns_with_options(
+        patterns,
+        default_kind,
+        root,
+        cwd,
+        NormalizeOptions::default(),
+    )
+}
+
+/// Same as [`normalize_patterns`], with non-default `options`.
+///
+/// Apart from `relglob_base`, `options` only affect validation: patterns
+/// that are accepted are normalized the same way.
+pub(crate) fn normalize_patterns_with_options<I>(
+    patterns: I,
+    default_kind: PatternKind,
+    root: &Path,
+    cwd: &Path,
+    options: NormalizeOptions,
+) -> Result<Vec<Pattern>, Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let resolver = CwdResolver::new(root, cwd)?;
+    normalize_patterns_impl(patterns, default_kind, root, &resolver, &options, None)
+}
+
+/// Normalize `patterns` so they are relative to the repository `root`, and
+/// also return warnings for patterns that are valid but are unlikely to
+/// match anything.
+///
+/// Patterns of cwd-relative kinds are resolved against `cwd`, which must be
+/// inside `root`. Checking for warnings looks up paths under `root` in the
+/// filesystem.
+pub fn normalize_patterns_with_warnings<I>(
+    patterns: I,
+    default_kind: PatternKind,
+    root: &Path,
+    cwd: &Path,
+    options: NormalizeOptions,
+) -> Result<(Vec<Pattern>, Vec<PatternWarning>), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let resolver = CwdResolver::new(root, cwd)?;
+    let mut warnings = Vec::new();
+    let patterns = normalize_patterns_impl(
+        patterns,
+        default_kind,
+        root,
+        &resolver,
+        &options,
+        Some(&mut warnings),
+    )?;
+    Ok((patterns, warnings))
+}
+
+/// Normalize `patterns` like [`normalize_patterns_with_warnings`], but
+/// report what each of them normalized to instead of only returning the
+/// result.
+///
+/// This is meant to preview the effect of patterns without matching anything.
+/// Patterns read from a listfile are reported under the `listfile:` pattern.
+pub fn normalize_patterns_report<I>(
+    patterns: I,
+    default_kind: PatternKind,
+    root: &Path,
+    cwd: &Path,
+    options: NormalizeOptions,
+) -> Result<NormalizeReport, Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let resolver = CwdResolver::new(root, cwd)?;
+    let mut entries = Vec::new();
+    for input in patterns {
+        let input = input.as_ref();
+        let patterns =
+            normalize_patterns_impl([input], default_kind, root, &resolver, &options, None)?
+                .into_iter()
+                .map(ReportedPattern::new)
+                .collect();
+        entries.push(NormalizeReportEntry {
+            input: input.to_string(),
+            patterns,
+        });
+    }
+    Ok(NormalizeReport { entries })
+}
+
+/// What each pattern given to [`normalize_patterns_report`] normalized to,
+/// in the order they were given.
+#[derive(Debug, PartialEq)]
+pub struct NormalizeReport {
+    pub entries: Vec<NormalizeReportEntry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NormalizeReportEntry {
+    /// The pattern as it was given, before normalization.
+    pub input: String,
+    /// The normalized patterns. For listfiles, the patterns read from the
+    /// file, with their `source` set to it.
+    pub patterns: Vec<ReportedPattern>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReportedPattern {
+    pub pattern: Pattern,
+    /// The globs that a glob with curly brackets expands to when matching.
+    /// Empty if there is nothing to expand.
+    pub brace_expansions: Vec<String>,
+}
+
+impl ReportedPattern {
+    fn new(pattern: Pattern) -> Self {
+        let brace_expansions = match pattern.kind {
+            PatternKind::Glob | PatternKind::RelGlob => {
+                let expanded = expand_curly_brackets(&pattern.pattern);
+                if expanded == [pattern.pattern.as_str()] {
+                    Vec::new()
+                } else {
+                    expanded
+                }
+            }
+            _ => Vec::new(),
+        };
+        Self {
+            pattern,
+            brace_expansions,
+        }
+    }
+}
+
+impl std::fmt::Display for NormalizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.input)?;
+            for p in &entry.patterns {
+                write!(f, "  {}:{}", p.pattern.kind.name(), p.pattern.pattern)?;
+                for exclude in &p.pattern.excludes {
+                    write!(f, "~{}", exclude)?;
+                }
+                if let Some(source) = &p.pattern.source {
+                    write!(f, " (from {})", source)?;
+                }
+                writeln!(f)?;
+                for expansion in &p.brace_expansions {
+                    writeln!(f, "    {}", expansion)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A pattern that was accepted by [`normalize_patterns_with_warnings`], but
+/// is unlikely to do what the user expects.
+#[derive(Debug, PartialEq)]
+pub struct PatternWarning {
+    /// The pattern as it was given, before normalization.
+    pub pattern: String,
+    pub kind: PatternWarningKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PatternWarningKind {
+    /// The pattern normalized to an empty glob, which matches nothing.
+    Empty,
+    /// The pattern is rooted at a path that doesn't exist under the
+    /// repository root.
+    MissingPath(String),
+}
+
+impl std::fmt::Display for PatternWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.kind {
+            PatternWarningKind::Empty => {
+                write!(f, "pattern {:?} is empty and matches nothing", self.pattern)
+            }
+            PatternWarningKind::MissingPath(path) => write!(
+                f,
+                "pattern {:?} refers to {:?}, which does not exist",
+                self.pattern, path
+            ),
+        }
+    }
+}
+
+/// Return why `pattern`, normalized from a pattern of the same kind, is
+/// unlikely to match anything, if it is.
+fn check_pattern(root: &Path, pattern: &Pattern) -> Option<PatternWarningKind> {
+    let prefix = match pattern.kind {
+        PatternKind::Glob | PatternKind::RelGlob if pattern.pattern.is_empty() => {
+            return Some(PatternWarningKind::Empty);
+        }
+        // The literal directories before the first special character.
+        PatternKind::Glob => {
+            let end = pattern
+                .pattern
+                .find(['*', '?', '[', '{', '\\'])
+                .map_or(pattern.pattern.len(), |i| {
+                    pattern.pattern[..i].rfind('/').unwrap_or(0)
+                });
+            &pattern.pattern[..end]
+        }
+        PatternKind::Path | PatternKind::RelPath | PatternKind::RootFilesIn => {
+            pattern.pattern.as_str()
+        }
+        _ => return None,
+    };
+    if prefix.is_empty() || root.join(prefix).exists() {
+        None
+    } else {
+        Some(PatternWarningKind::MissingPath(prefix.to_string()))
+    }
+}
+
+fn normalize_patterns_impl<I>(
+    patterns: I,
+    default_kind: PatternKind,
+    root: &Path,
+    resolver: &CwdResolver,
+    options: &NormalizeOptions,
+    mut warnings: Option<&mut Vec<PatternWarning>>,
 ) -> Result<Vec<Pattern>, Error>
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    let relglob_base = options
+        .relglob_base
+        .map(|base| plain_to_glob(&normalize_path_pattern(base)))
+        .filter(|base| base != ".");
     let mut result: Vec<Pattern> = Vec::new();
     for pattern in patterns {
         let pattern = pattern.as_ref();
         let (kind, pat) = split_pattern(pattern, default_kind);
+        // The marker only has a meaning when globs are made recursive:
+        // otherwise, it is part of the pattern.
+        let (pat, recursive_glob) = match kind {
+            PatternKind::Glob | PatternKind::RelGlob if options.force_recursive_glob => {
+                match pat.strip_prefix(NON_RECURSIVE_GLOB_MARKER) {
+                    Some(pat) => (pat, false),
+                    None => (pat, true),
+                }
+            }
+            _ => (pat, false),
+        };
+        let start = result.len();
         match kind {
-            PatternKind::RelPath | PatternKind::Glob => {
-                // TODO: need to implement pathutil.pathauditor and pathutil.canonpath
+            PatternKind::RelPath => {
+                // TODO: need to implement pathutil.pathauditor
                 // https://fburl.com/code/0q9sgvbj
-                result.push(Pattern::new(kind, pat.to_string()));
+                let root_relative_pat = resolver.resolve(pat)?;
+                result.push(Pattern::new(kind, root_relative_pat));
+            }
+            PatternKind::Glob => {
+                let (include, excludes) = if options.glob_excludes {
+                    split_glob_excludes(pat)
+                } else {
+                    (pat.to_string(), Vec::new())
+                };
+                let mut root_relative_excludes = Vec::new();
+                for exclude in excludes.iter().flat_map(|e| expand_bounded_globstar(e)) {
+                    root_relative_excludes.push(resolver.resolve_glob(&exclude)?);
+                }
+                for pat in expand_bounded_globstar(&include) {
+                    let root_relative_pat = resolver.resolve_glob(&pat)?;
+                    result.push(
+                        Pattern::new(kind, root_relative_pat)
+                            .with_excludes(root_relative_excludes.clone()),
+                    );
+                }
+            }
+            PatternKind::RelGlob if pat.starts_with('/') => {
+                // A leading slash anchors the glob to the repository root.
+                for pat in expand_bounded_globstar(&pat[1..]) {
+                    let normalized_pat = normalize_path_pattern(&pat);
+                    result.push(Pattern::new(
+                        PatternKind::Glob,
+                        anchored_relglob_pattern(&normalized_pat),
+                    ));
+                }
+            }
+            PatternKind::RelGlob => {
+                for pat in expand_bounded_globstar(pat) {
+                    let normalized_pat = normalize_path_pattern(&pat);
+                    result.push(match &relglob_base {
+                        Some(base) => Pattern::new(
+                            PatternKind::Glob,
+                            based_relglob_pattern(base, &normalized_pat),
+                        ),
+                        None => Pattern::new(kind, normalized_pat),
+                    });
+                }
             }
-            PatternKind::RelGlob | PatternKind::Path | PatternKind::RootFilesIn => {
+            PatternKind::Path | PatternKind::RootFilesIn => {
                 let normalized_pat = normalize_path_pattern(pat);
                 result.push(Pattern::new(kind, normalized_pat));
             }
             PatternKind::ListFile | PatternKind::ListFile0 => {
-                let contents = util::file::read_to_string(pat)?;
                 let sep = if kind == PatternKind::ListFile {
-                    '\n'
+                    b'\n'
                 } else {
-                    '\0'
+                    b'\0'
                 };
-                let lines = contents.split(sep);
-                for p in normalize_patterns(lines, default_kind)? {
+                for_each_listfile_line(pat, sep, |line| {
+                    for p in normalize_patterns_impl(
+                        [line],
+                        default_kind,
+                        root,
+                        resolver,
+                        options,
+                        warnings.as_deref_mut(),
+                    )? {
+                        result.push(p.with_source(pat.to_string()));
+                    }
+                    Ok(())
+                })?;
+            }
+            PatternKind::ListFileJson => {
+                let contents = util::file::read_to_string(pat)?;
+                let lines: Vec<String> = serde_json::from_str(&contents)
+                    .map_err(|e| Error::InvalidListFileJson(pat.to_string(), e.to_string()))?;
+                for p in normalize_patterns_impl(
+                    lines,
+                    default_kind,
+                    root,
+                    resolver,
+                    options,
+                    warnings.as_deref_mut(),
+                )? {
                     let p = p.with_source(pat.to_string());
                     result.push(p);
                 }
             }
+            PatternKind::GitIgnore => {
+                let contents = util::file::read_to_string(pat)?;
+                let dir = Path::new(pat).parent().unwrap_or(Path::new(""));
+                // A file outside of the repository, like git's
+                // core.excludesFile, applies to the whole repository.
+                let base = resolver
+                    .root_relative_path(dir)
+                    .map(|dir| plain_to_glob(&normalize_path_pattern(&dir.to_string_lossy())))
+                    .filter(|base| base != ".")
+                    .unwrap_or_default();
+                for p in gitignore_patterns(&contents, &base) {
+                    result.push(p.with_source(pat.to_string()));
+                }
+            }
             PatternKind::Set | PatternKind::Include | PatternKind::SubInclude => {
                 return Err(Error::UnsupportedPatternKind(kind.name().to_string()));
             }
+            PatternKind::RE | PatternKind::RelRE if options.re2_syntax => {
+                crate::re2::check_syntax(pat)
+                    .map_err(|reason| Error::InvalidRegex(pat.to_string(), reason))?;
+                result.push(Pattern::new(kind, pat.to_string()));
+            }
             _ => result.push(Pattern::new(kind, pat.to_string())),
         }
+        if recursive_glob {
+            for p in &mut result[start..] {
+                p.pattern = recursive_glob_pattern(&p.pattern);
+                for exclude in p.excludes.iter_mut() {
+                    *exclude = recursive_glob_pattern(exclude);
+                }
+            }
+        }
+        if let Some(warnings) = warnings.as_deref_mut() {
+            for p in &result[start..] {
+                // Patterns read from listfiles were checked when reading them.
+                if p.source.is_none() {
+                    if let Some(kind) = check_pattern(root, p) {
+                        warnings.push(PatternWarning {
+                            pattern: pattern.to_string(),
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
     }
     Ok(result)
 }
 
-/// A wrapper of `util::path::normalize` function by adding path separator convertion,
-/// yields normalized [String] if the pattern is valid unicode.
+/// Call `f` with each line of the listfile at `path`, split by `sep`, without
+/// reading the whole file in memory.
 ///
-/// This function normalize the path difference on Windows by converting
-/// path separator from `\` to `/`. This is need because our `RepoPathBuf`
-/// is a path separated by `/`.
-fn normalize_path_pattern(pattern: &str) -> String {
-    let pattern = util::path::normalize(pattern.as_ref());
-    // SAFTEY: In Rust, values of type String are always valid UTF-8.
-    // Our input pattern is a &str, and we don't add invalid chars in
-    // out `util::path::normalize` function, so it should be safe here.
-    let pattern_str = pattern.to_string_lossy();
-    if cfg!(windows) {
-        pattern_str.replace(
-            std::path::MAIN_SEPARATOR,
-            &types::path::SEPARATOR.to_string(),
-        )
+/// Lines are the same as `str::split` would return: a file ending with `sep`
+/// has an empty last line.
+fn for_each_listfile_line(
+    path: &str,
+    sep: u8,
+    mut f: impl FnMut(&str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut reader = BufReader::new(util::file::open(path, "r")?);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        reader
+            .read_until(sep, &mut line)
+            .path_context("error reading file", path)?;
+        let is_last = line.last() != Some(&sep);
+        if !is_last {
+            line.pop();
+        }
+        let line = std::str::from_utf8(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .path_context("error reading file", path)?;
+        f(line)?;
+        if is_last {
+            return Ok(());
+        }
+    }
+}
+
+/// The glob matching what the normalized glob `pat` matches, and everything
+/// under it.
+fn recursive_glob_pattern(pat: &str) -> String {
+    if pat.is_empty() || pat == "." || pat == "**" {
+        "**".to_string()
+    } else if pat.ends_with("/**") {
+        pat.to_string()
     } else {
-        pattern_str.to_string()
+        format!("{}/**", pat)
     }
 }
 
-#[cfg(test)]
-mod tests {
+/// The glob matching the normalized relglob `pat`, with its leading slash
+/// removed, from the repository root.
+fn anchored_relglob_pattern(pat: &str) -> String {
+    // `relglob:/` matches everything, like an empty relglob.
+    if pat == "." {
+        "**".to_string()
+    } else {
+        pat.to_string()
+    }
+}
+
+/// The glob matching the normalized relglob `pat` anywhere under `base`.
+fn based_relglob_pattern(base: &str, pat: &str) -> String {
+    // An empty relglob normalizes to ".".
+    if pat == "." {
+        format!("{}/**", base)
+    } else {
+        format!("{}/**/{}", base, pat)
+    }
+}
+
+/// Split a glob of the form `include~exclude1~exclude2` into the glob to
+/// include and the globs to exclude from it.
+///
+/// `\~` is a literal `~`. If any of the parts is empty, like in `*~`, every
+/// `~` is literal, and the glob has no exclusions.
+fn split_glob_excludes(pat: &str) -> (String, Vec<String>) {
+    let mut parts = vec![String::new()];
+    let mut chars = pat.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('~') => parts.last_mut().unwrap().push('~'),
+                Some(c) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(c);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            },
+            '~' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    if parts.len() > 1 && parts.iter().any(|p| p.is_empty()) {
+        return (pat.to_string(), Vec::new());
+    }
+    let include = parts.remove(0);
+    (include, parts)
+}
+
+/// Prefix of a cwd-relative pattern that anchors it at the repository root
+/// instead, e.g. `glob://foo/*.c` matches `foo/*.c` regardless of cwd.
+///
+/// On Windows, `//server/share` would otherwise be a UNC path. Such paths
+/// must be spelled with backslashes (`\\server\share`) to be treated as
+/// absolute.
+const ROOT_ANCHOR: &str = "//";
+
+/// Resolves cwd-relative patterns to patterns relative to the repository
+/// root.
+///
+/// The root and the cwd are normalized once, when the resolver is created,
+/// and reused for every pattern.
+struct CwdResolver {
+    /// The normalized repository root.
+    root: PathBuf,
+    /// The normalized cwd, under `root`.
+    cwd: PathBuf,
+}
+
+impl CwdResolver {
+    /// Return an error if `cwd` is not under `root`.
+    fn new(root: &Path, cwd: &Path) -> Result<Self, Error> {
+        let normalized_root = normalize_base(root);
+        let normalized_cwd = normalize_base(cwd);
+        if !normalized_cwd.starts_with(&normalized_root) {
+            return Err(Error::PathOutsideRoot(
+                cwd.display().to_string(),
+                root.display().to_string(),
+            ));
+        }
+        Ok(Self {
+            root: normalized_root,
+            cwd: normalized_cwd,
+        })
+    }
+
+    /// Make a cwd-relative `pattern` relative to the root.
+    ///
+    /// An absolute `pattern` is made relative to the root directly, and so
+    /// is a pattern starting with [`ROOT_ANCHOR`]. An absolute `pattern`
+    /// outside of the root is returned as is.
+    fn resolve(&self, pattern: &str) -> Result<String, Error> {
+        let (base, path) = match pattern.strip_prefix(ROOT_ANCHOR) {
+            Some(rest) => (&self.root, rest.trim_start_matches('/')),
+            None => (&self.cwd, pattern),
+        };
+        let path = util::path::strip_extended_length_prefix(Path::new(path));
+        match self.relative_to_root(base, &path) {
+            Some(relative) if relative.as_os_str().is_empty() => Ok(String::new()),
+            Some(relative) => Ok(normalize_path_pattern(&relative.to_string_lossy())),
+            None if path.has_root() => Ok(pattern.to_string()),
+            None => Err(Error::PathOutsideRoot(
+                pattern.to_string(),
+                self.root.display().to_string(),
+            )),
+        }
+    }
+
+    /// Make a cwd-relative glob `pattern` relative to the root, like
+    /// [`CwdResolver::resolve`], but with the special characters of the cwd
+    /// escaped so that they only match themselves.
+    fn resolve_glob(&self, pattern: &str) -> Result<String, Error> {
+        let (base, path) = match pattern.strip_prefix(ROOT_ANCHOR) {
+            Some(rest) => (&self.root, rest.trim_start_matches('/')),
+            None => (&self.cwd, pattern),
+        };
+        let base = base
+            .strip_prefix(&self.root)
+            .expect("cwd is under the root");
+        let base = plain_to_glob(&normalize_path_pattern(&base.to_string_lossy()));
+        let mut components: Vec<Cow<str>> = base
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(Cow::Borrowed)
+            .collect();
+        let path = util::path::strip_extended_length_prefix(Path::new(path));
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if components.pop().is_none() {
+                        return Err(Error::PathOutsideRoot(
+                            pattern.to_string(),
+                            self.root.display().to_string(),
+                        ));
+                    }
+                }
+                Component::Normal(c) => components.push(c.to_string_lossy()),
+                // Absolute globs don't depend on the cwd.
+                Component::Prefix(_) | Component::RootDir => return self.resolve(pattern),
+            }
+        }
+        Ok(components.join("/"))
+    }
+
+    /// `path`, resolved against the cwd, relative to the root. `None` if it
+    /// is not under the root.
+    fn root_relative_path(&self, path: &Path) -> Option<PathBuf> {
+        let path = util::path::strip_extended_length_prefix(path);
+        self.relative_to_root(&self.cwd, &path)
+    }
+
+    fn relative_to_root(&self, base: &Path, path: &Path) -> Option<PathBuf> {
+        let path = util::path::normalize(&base.join(path));
+        path.strip_prefix(&self.root).ok().map(Path::to_path_buf)
+    }
+}
+
+/// Normalize the root or the cwd given to [`CwdResolver::new`].
+fn normalize_base(path: &Path) -> PathBuf {
+    util::path::normalize(&util::path::strip_extended_length_prefix(path))
+}
+
+/// A wrapper of `util::path::normalize` function by adding path separator convertion,
+/// yields normalized [String] if the pattern is valid unicode.
+///
+/// This function normalize the path difference on Windows by converting
+/// path separator from `\` to `/`. This is need because our `RepoPathBuf`
+/// is a path separated by `/`.
+fn normalize_path_pattern(pattern: &str) -> String {
+    normalize_path_pattern_with_separator(pattern, types::path::SEPARATOR)
+}
+
+/// Same as `normalize_path_pattern`, but separate the path components with
+/// `separator` instead of `/`, e.g. [`std::path::MAIN_SEPARATOR`] to keep
+/// native separators for display.
+///
+/// On Windows, both `\` and `/` in `pattern` are separators, and are all
+/// converted to `separator`. Extended-length paths, like `\\?\C:\foo`, are
+/// converted to their usual form, like `C:\foo`.
+pub fn normalize_path_pattern_with_separator(pattern: &str, separator: char) -> String {
+    let pattern = util::path::strip_extended_length_prefix(pattern.as_ref());
+    let pattern = util::path::normalize(&pattern);
+    // SAFTEY: In Rust, values of type String are always valid UTF-8.
+    // Our input pattern is a &str, and we don't add invalid chars in
+    // out `util::path::normalize` function, so it should be safe here.
+    let pattern_str = pattern.to_string_lossy();
+    if separator != std::path::MAIN_SEPARATOR {
+        pattern_str.replace(std::path::MAIN_SEPARATOR, &separator.to_string())
+    } else {
+        pattern_str.to_string()
+    }
+}
+
+/// Translate the lines of a .gitignore file into globs under `base`, which
+/// is the root relative glob of the directory of the file.
+///
+/// Each pattern matches the paths it names and everything under them,
+/// except for directory-only patterns like `build/`, which only match
+/// what's under them. A negated pattern like `!keep.log` is excluded from
+/// the patterns before it, so that the last matching line wins. Like in
+/// git, negating a file doesn't re-include it if a directory above it is
+/// ignored.
+fn gitignore_patterns(contents: &str, base: &str) -> Vec<Pattern> {
+    // Patterns, and whether they match what's under a directory.
+    let mut patterns: Vec<(Pattern, bool)> = Vec::new();
+    for line in contents.lines() {
+        let line = trim_gitignore_line(line);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest.trim_end_matches('/')),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            continue;
+        }
+        // Patterns with a slash are relative to the directory of the file,
+        // others match at any depth under it.
+        let glob = if line.contains('/') {
+            line.trim_start_matches('/').to_string()
+        } else {
+            format!("**/{}", line)
+        };
+        let glob = if base.is_empty() {
+            glob
+        } else {
+            format!("{}/{}", base, glob)
+        };
+        // Not `glob/**`, which also matches `glob` itself.
+        let recursive_glob = format!("{}/*/**", glob);
+
+        if negated {
+            for (p, recursive) in patterns.iter_mut() {
+                if *recursive {
+                    p.excludes.push(recursive_glob.clone());
+                } else if !dir_only {
+                    p.excludes.push(glob.clone());
+                }
+            }
+        } else {
+            if !dir_only {
+                patterns.push((Pattern::new(PatternKind::Glob, glob), false));
+            }
+            patterns.push((Pattern::new(PatternKind::Glob, recursive_glob), true));
+        }
+    }
+    patterns.into_iter().map(|(p, _)| p).collect()
+}
+
+/// Remove the trailing spaces of a .gitignore line, unless they are escaped
+/// with a backslash.
+fn trim_gitignore_line(line: &str) -> &str {
+    let mut end = line.len();
+    while line[..end].ends_with(' ') {
+        let backslashes = line[..end - 1].len() - line[..end - 1].trim_end_matches('\\').len();
+        if backslashes % 2 == 1 {
+            break;
+        }
+        end -= 1;
+    }
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
 
     use std::fs;
 
     use tempfile::TempDir;
+    use types::RepoPath;
 
     use super::*;
+    use crate::Matcher;
+    use crate::RegexMatcher;
+
+    #[cfg(windows)]
+    const ROOT: &str = r"C:\repo";
+    #[cfg(not(windows))]
+    const ROOT: &str = "/repo";
 
     #[test]
     fn test_split_pattern() {
@@ -236,6 +1422,141 @@ mod tests {
 
         let v = split_pattern("a.*py", PatternKind::RE);
         assert_eq!(v, (PatternKind::RE, "a.*py"));
+
+        let v = split_pattern(r"C:\proj\foo.c", PatternKind::Glob);
+        assert_eq!(v, (PatternKind::Glob, r"C:\proj\foo.c"));
+
+        let v = split_pattern("c:/proj/foo.c", PatternKind::Path);
+        assert_eq!(v, (PatternKind::Path, "c:/proj/foo.c"));
+
+        let v = split_pattern(r"glob:C:\proj\*.c", PatternKind::Path);
+        assert_eq!(v, (PatternKind::Glob, r"C:\proj\*.c"));
+    }
+
+    #[test]
+    fn test_split_pattern_strict() {
+        let v = split_pattern_strict("glob:*.c", PatternKind::Path).unwrap();
+        assert_eq!(v, (PatternKind::Glob, "*.c"));
+
+        let v = split_pattern_strict("src:foo", PatternKind::Path).unwrap();
+        assert_eq!(v, (PatternKind::Path, "src:foo"));
+
+        let v = split_pattern_strict("badkind:a.*py", PatternKind::Glob).unwrap();
+        assert_eq!(v, (PatternKind::Glob, "badkind:a.*py"));
+
+        let v = split_pattern_strict(r"C:\proj\foo.c", PatternKind::Glob).unwrap();
+        assert_eq!(v, (PatternKind::Glob, r"C:\proj\foo.c"));
+
+        let v = split_pattern_strict("a.*py", PatternKind::RE).unwrap();
+        assert_eq!(v, (PatternKind::RE, "a.*py"));
+
+        let err = split_pattern_strict("globb:*.c", PatternKind::Path).unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::UnknownPatternKind { input, suggestion: PatternKind::Glob } if input == "globb:*.c"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "unknown pattern kind in \"globb:*.c\" (did you mean \"glob:\"?)"
+        );
+
+        for (typo, kind) in [
+            ("relgolb", PatternKind::RelGlob),
+            ("pth", PatternKind::Path),
+            ("listfle0", PatternKind::ListFile0),
+            ("listfilejsn", PatternKind::ListFileJson),
+            ("gitingore", PatternKind::GitIgnore),
+            ("rootfilein", PatternKind::RootFilesIn),
+        ] {
+            let pattern = format!("{typo}:foo");
+            assert!(
+                matches!(
+                    split_pattern_strict(&pattern, PatternKind::Path),
+                    Err(Error::UnknownPatternKind { suggestion, .. }) if suggestion == kind
+                ),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_directory_prefix() {
+        let check = |kind: PatternKind, pattern: &str, dirs: &[(&str, bool)]| {
+            let pattern = Pattern::new(kind, pattern.to_string());
+            for (dir, expected) in dirs {
+                assert_eq!(
+                    pattern.matches_directory_prefix(RepoPath::from_str(dir).unwrap()),
+                    *expected,
+                    "{:?} under {:?}",
+                    pattern,
+                    dir
+                );
+            }
+        };
+
+        check(
+            PatternKind::Path,
+            "a/b",
+            &[
+                ("", true),
+                ("a", true),
+                ("a/b", true),
+                ("a/b/c", true),
+                ("a/c", false),
+                ("b", false),
+                ("a/bc", false),
+            ],
+        );
+        check(PatternKind::Path, ".", &[("", true), ("a/b", true)]);
+        check(
+            PatternKind::RootFilesIn,
+            "a/b",
+            &[
+                ("", true),
+                ("a", true),
+                ("a/b", true),
+                ("a/b/c", false),
+                ("b", false),
+            ],
+        );
+        check(
+            PatternKind::Glob,
+            "a/*/c.txt",
+            &[
+                ("", true),
+                ("a", true),
+                ("a/x", true),
+                ("a/y", true),
+                ("a/x/y", false),
+                ("b", false),
+            ],
+        );
+        check(
+            PatternKind::Glob,
+            "a/**/c.txt",
+            &[("a", true), ("a/x/y/z", true), ("b", false)],
+        );
+        check(
+            PatternKind::Glob,
+            "{a,b}/[cd]/*.txt",
+            &[
+                ("a", true),
+                ("b/c", true),
+                ("b/d", true),
+                ("b/e", false),
+                ("c", false),
+            ],
+        );
+
+        // Unrooted patterns could match anywhere.
+        for (kind, pattern) in [
+            (PatternKind::RelGlob, "*.c"),
+            (PatternKind::RelGlob, "a/*.c"),
+            (PatternKind::RelRE, "a.*"),
+            (PatternKind::RE, "a/.*"),
+        ] {
+            check(kind, pattern, &[("", true), ("a", true), ("b/c", true)]);
+        }
     }
 
     #[test]
@@ -254,12 +1575,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_normalize_path_pattern_with_separator() {
+        let pattern = r"foo\bar/../baz/qux";
+        #[cfg(windows)]
+        {
+            assert_eq!(
+                normalize_path_pattern_with_separator(pattern, '/'),
+                "foo/baz/qux"
+            );
+            assert_eq!(
+                normalize_path_pattern_with_separator(pattern, '\\'),
+                r"foo\baz\qux"
+            );
+        }
+        // `\` is not a separator on Unix.
+        #[cfg(not(windows))]
+        {
+            assert_eq!(
+                normalize_path_pattern_with_separator(pattern, '/'),
+                "baz/qux"
+            );
+            assert_eq!(
+                normalize_path_pattern_with_separator(pattern, '\\'),
+                r"baz\qux"
+            );
+        }
+        assert_eq!(
+            normalize_path_pattern_with_separator("foo/bar/../baz/", '\\'),
+            r"foo\baz"
+        );
+    }
+
     #[test]
     fn test_normalize_patterns() {
+        let root = Path::new(ROOT);
         assert_eq!(
             normalize_patterns(
                 vec!["glob:/a/*", r"re:a.*\.py", "path:foo/bar/../baz/"],
-                PatternKind::Glob
+                PatternKind::Glob,
+                root,
+                root,
             )
             .unwrap(),
             [
@@ -269,23 +1625,818 @@ mod tests {
             ]
         );
         assert_eq!(
-            normalize_patterns(vec!["/a/*", r"re:a.*\.py"], PatternKind::Glob).unwrap(),
+            normalize_patterns(vec!["/a/*", r"re:a.*\.py"], PatternKind::Glob, root, root).unwrap(),
             [
                 Pattern::new(PatternKind::Glob, "/a/*".to_string()),
                 Pattern::new(PatternKind::RE, r"a.*\.py".to_string()),
             ]
         );
+        // Absolute paths under the root are made relative to it.
         assert_eq!(
-            normalize_patterns(vec!["relglob:*.c"], PatternKind::Glob).unwrap(),
+            normalize_patterns(
+                vec![format!("glob:{}/a/*", ROOT)],
+                PatternKind::Glob,
+                root,
+                root
+            )
+            .unwrap(),
+            [Pattern::new(PatternKind::Glob, "a/*".to_string())]
+        );
+        assert_eq!(
+            normalize_patterns(vec!["relglob:*.c"], PatternKind::Glob, root, root).unwrap(),
             [Pattern::new(PatternKind::RelGlob, "*.c".to_string()),]
         );
     }
 
+    #[test]
+    fn test_normalize_patterns_cwd() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("foo");
+        assert_eq!(
+            normalize_patterns(
+                vec![
+                    "glob:*.c",
+                    "relpath:bar",
+                    "glob:../*.c",
+                    "relpath:",
+                    "path:bar"
+                ],
+                PatternKind::Glob,
+                root,
+                &cwd,
+            )
+            .unwrap(),
+            [
+                Pattern::new(PatternKind::Glob, "foo/*.c".to_string()),
+                Pattern::new(PatternKind::RelPath, "foo/bar".to_string()),
+                Pattern::new(PatternKind::Glob, "*.c".to_string()),
+                Pattern::new(PatternKind::RelPath, "foo".to_string()),
+                Pattern::new(PatternKind::Path, "bar".to_string()),
+            ]
+        );
+
+        assert!(normalize_patterns(vec!["glob:../../*.c"], PatternKind::Glob, root, &cwd).is_err());
+        assert!(
+            normalize_patterns(
+                vec!["glob:*.c"],
+                PatternKind::Glob,
+                root,
+                Path::new("/elsewhere")
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_normalize_patterns_cwd_escaped_in_globs() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("dir[1]").join("sub");
+        let patterns = normalize_patterns(
+            vec!["glob:*.c", "glob:../*.c", "relpath:a.c"],
+            PatternKind::Glob,
+            root,
+            &cwd,
+        )
+        .unwrap();
+        assert_eq!(
+            patterns,
+            [
+                Pattern::new(PatternKind::Glob, r"dir\[1\]/sub/*.c".to_string()),
+                Pattern::new(PatternKind::Glob, r"dir\[1\]/*.c".to_string()),
+                Pattern::new(PatternKind::RelPath, "dir[1]/sub/a.c".to_string()),
+            ]
+        );
+
+        let matcher = TreeMatcher::from_rules([&patterns[1].pattern].iter(), true).unwrap();
+        assert!(
+            matcher
+                .matches_file(RepoPath::from_str("dir[1]/a.c").unwrap())
+                .unwrap()
+        );
+        assert!(
+            !matcher
+                .matches_file(RepoPath::from_str("dir1/a.c").unwrap())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_normalize_patterns_root_anchor() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("foo");
+        assert_eq!(
+            normalize_patterns(
+                vec![
+                    "glob://a/*.c",
+                    "//a/*.c",
+                    "relpath://bar",
+                    "glob:///a/*.c",
+                    "glob://",
+                    "glob://foo/../a/*.c",
+                ],
+                PatternKind::Glob,
+                root,
+                &cwd,
+            )
+            .unwrap(),
+            [
+                Pattern::new(PatternKind::Glob, "a/*.c".to_string()),
+                Pattern::new(PatternKind::Glob, "a/*.c".to_string()),
+                Pattern::new(PatternKind::RelPath, "bar".to_string()),
+                Pattern::new(PatternKind::Glob, "a/*.c".to_string()),
+                Pattern::new(PatternKind::Glob, "".to_string()),
+                Pattern::new(PatternKind::Glob, "a/*.c".to_string()),
+            ]
+        );
+
+        // Same result as the absolute path spelling.
+        assert_eq!(
+            normalize_patterns(vec!["glob://a/*"], PatternKind::Glob, root, &cwd).unwrap(),
+            normalize_patterns(
+                vec![format!("glob:{}/a/*", ROOT)],
+                PatternKind::Glob,
+                root,
+                &cwd
+            )
+            .unwrap(),
+        );
+
+        assert!(normalize_patterns(vec!["glob://../*.c"], PatternKind::Glob, root, &cwd).is_err());
+    }
+
+    #[test]
+    fn test_normalize_patterns_bounded_globstar() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("x");
+        assert_eq!(
+            normalize_patterns(vec!["glob:{**:2}/foo"], PatternKind::Glob, root, &cwd).unwrap(),
+            [
+                Pattern::new(PatternKind::Glob, "x/foo".to_string()),
+                Pattern::new(PatternKind::Glob, "x/*/foo".to_string()),
+                Pattern::new(PatternKind::Glob, "x/*/*/foo".to_string()),
+            ]
+        );
+
+        let matches = |pattern: &str, path: &str| {
+            let patterns = normalize_patterns([pattern], PatternKind::Glob, root, root).unwrap();
+            let matcher = crate::build_matcher(&patterns, &[], &[], true).unwrap();
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches("glob:{**:2}/foo", "foo"));
+        assert!(matches("glob:{**:2}/foo", "a/foo"));
+        assert!(matches("glob:{**:2}/foo", "a/b/foo"));
+        assert!(!matches("glob:{**:2}/foo", "a/b/c/foo"));
+
+        // Composes with a trailing recursive glob.
+        assert!(matches("glob:{**:1}/foo/**", "a/foo/b/c"));
+        assert!(!matches("glob:{**:1}/foo/**", "a/b/foo/c"));
+    }
+
+    #[test]
+    fn test_normalize_patterns_warnings() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("exists")).unwrap();
+        let cwd = root.join("exists");
+
+        let (patterns, warnings) = normalize_patterns_with_warnings(
+            vec![
+                "glob:",
+                "path:exists/../missing",
+                "glob:missing/*.c",
+                "relpath:sub",
+                "glob:*.c",
+                "glob:../exists/*.c",
+                "path:exists",
+                "relglob:*.c",
+                "re:.*",
+            ],
+            PatternKind::Glob,
+            root,
+            &cwd,
+            NormalizeOptions::default(),
+        )
+        .unwrap();
+
+        // Warnings don't change the result.
+        assert_eq!(patterns.len(), 9);
+        assert_eq!(
+            warnings,
+            [
+                PatternWarning {
+                    pattern: "path:exists/../missing".to_string(),
+                    kind: PatternWarningKind::MissingPath("missing".to_string()),
+                },
+                PatternWarning {
+                    pattern: "glob:missing/*.c".to_string(),
+                    kind: PatternWarningKind::MissingPath("exists/missing".to_string()),
+                },
+                PatternWarning {
+                    pattern: "relpath:sub".to_string(),
+                    kind: PatternWarningKind::MissingPath("exists/sub".to_string()),
+                },
+            ]
+        );
+
+        let (_, warnings) = normalize_patterns_with_warnings(
+            vec!["glob:"],
+            PatternKind::Glob,
+            root,
+            root,
+            NormalizeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            warnings,
+            [PatternWarning {
+                pattern: "glob:".to_string(),
+                kind: PatternWarningKind::Empty,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_partition_rooted() {
+        let patterns = normalize_patterns(
+            [
+                "relglob:*.c",
+                "glob:a/*.c",
+                "rootfilesin:b",
+                "re:c/.*",
+                "relre:d.*",
+                "path:e",
+                "relpath:f",
+            ],
+            PatternKind::Glob,
+            Path::new(ROOT),
+            &Path::new(ROOT).join("sub"),
+        )
+        .unwrap();
+        let (rooted, unrooted) = partition_rooted(patterns);
+        assert_eq!(
+            rooted,
+            [
+                Pattern::new(PatternKind::Glob, "sub/a/*.c".to_string()),
+                Pattern::new(PatternKind::RootFilesIn, "b".to_string()),
+                Pattern::new(PatternKind::RE, "c/.*".to_string()),
+                Pattern::new(PatternKind::Path, "e".to_string()),
+                Pattern::new(PatternKind::RelPath, "sub/f".to_string()),
+            ]
+        );
+        assert_eq!(
+            unrooted,
+            [
+                Pattern::new(PatternKind::RelGlob, "*.c".to_string()),
+                Pattern::new(PatternKind::RelRE, "d.*".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minimize_patterns() {
+        let patterns = vec![
+            Pattern::new(PatternKind::Path, "src/a".to_string()),
+            Pattern::new(PatternKind::Path, "src".to_string()).with_source("list".to_string()),
+            Pattern::new(PatternKind::Path, "src/a/b".to_string()),
+            Pattern::new(PatternKind::RelPath, "src/c".to_string()),
+            Pattern::new(PatternKind::RootFilesIn, "src".to_string()),
+            Pattern::new(PatternKind::Glob, "src/d/**".to_string()),
+            Pattern::new(PatternKind::Path, "src".to_string()),
+            Pattern::new(PatternKind::Path, "srcfoo".to_string()),
+            Pattern::new(PatternKind::Glob, "src/*.c".to_string()),
+            Pattern::new(PatternKind::Glob, "src/*/**".to_string()),
+            Pattern::new(PatternKind::RE, "src/.*".to_string()),
+            Pattern::new(PatternKind::RelGlob, "*.c".to_string()),
+            Pattern::new(PatternKind::Path, "other/a".to_string()),
+            Pattern::new(PatternKind::RootFilesIn, "other".to_string()),
+        ];
+        assert_eq!(
+            minimize_patterns(&patterns),
+            [
+                Pattern::new(PatternKind::Path, "src".to_string()).with_source("list".to_string()),
+                Pattern::new(PatternKind::Path, "srcfoo".to_string()),
+                Pattern::new(PatternKind::Glob, "src/*.c".to_string()),
+                Pattern::new(PatternKind::Glob, "src/*/**".to_string()),
+                Pattern::new(PatternKind::RE, "src/.*".to_string()),
+                Pattern::new(PatternKind::RelGlob, "*.c".to_string()),
+                Pattern::new(PatternKind::Path, "other/a".to_string()),
+                Pattern::new(PatternKind::RootFilesIn, "other".to_string()),
+            ]
+        );
+
+        // The root directory matches everything path-like.
+        let patterns = vec![
+            Pattern::new(PatternKind::Path, "a".to_string()),
+            Pattern::new(PatternKind::Path, "".to_string()),
+            Pattern::new(PatternKind::Glob, "**".to_string()),
+        ];
+        assert_eq!(
+            minimize_patterns(&patterns),
+            [Pattern::new(PatternKind::Path, "".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_patterns_equivalent() {
+        let a = vec![
+            Pattern::new(PatternKind::Glob, "a/*.c".to_string()),
+            Pattern::new(PatternKind::Path, "b".to_string()).with_source("x".to_string()),
+            Pattern::new(PatternKind::Path, "b".to_string()),
+        ];
+        let reordered = vec![
+            Pattern::new(PatternKind::Path, "b".to_string()),
+            Pattern::new(PatternKind::Glob, "a/*.c".to_string()).with_source("y".to_string()),
+            Pattern::new(PatternKind::Path, "b".to_string()),
+        ];
+        assert!(patterns_equivalent(&a, &reordered));
+        assert!(patterns_equivalent(&[], &[]));
+
+        // Different kinds, patterns or number of duplicates.
+        let mut other_kind = reordered.clone();
+        other_kind[0].kind = PatternKind::RelPath;
+        assert!(!patterns_equivalent(&a, &other_kind));
+        let mut other_pattern = reordered.clone();
+        other_pattern[1].pattern = "a/*.h".to_string();
+        assert!(!patterns_equivalent(&a, &other_pattern));
+        assert!(!patterns_equivalent(&a, &reordered[..2]));
+        let mut other_duplicate = reordered;
+        other_duplicate[2] = Pattern::new(PatternKind::Glob, "a/*.c".to_string());
+        assert!(!patterns_equivalent(&a, &other_duplicate));
+
+        // Patterns with excludes are compared in order.
+        let excluded = vec![
+            Pattern::new(PatternKind::Glob, "a/**".to_string())
+                .with_excludes(vec!["a/b/**".to_string()]),
+            Pattern::new(PatternKind::Glob, "c/**".to_string()).with_source("x".to_string()),
+        ];
+        let mut same = excluded.clone();
+        same[1].source = None;
+        assert!(patterns_equivalent(&excluded, &same));
+        let reversed: Vec<Pattern> = excluded.iter().rev().cloned().collect();
+        assert!(!patterns_equivalent(&excluded, &reversed));
+        let mut other_excludes = excluded.clone();
+        other_excludes[0].excludes = vec!["a/c/**".to_string()];
+        assert!(!patterns_equivalent(&excluded, &other_excludes));
+    }
+
+    #[test]
+    fn test_pattern_diff() {
+        let glob = |p: &str| Pattern::new(PatternKind::Glob, p.to_string());
+        let path = |p: &str| Pattern::new(PatternKind::Path, p.to_string());
+
+        let old = vec![glob("a/**"), path("b"), path("c"), path("c")];
+        let new = vec![path("d"), glob("a/**"), glob("b"), path("e"), path("d")];
+        let (added, removed) = pattern_diff(&old, &new);
+        assert_eq!(added, vec![path("d"), glob("b"), path("e")]);
+        assert_eq!(removed, vec![path("b"), path("c")]);
+
+        // Unchanged patterns.
+        let (added, removed) = pattern_diff(&old, &old);
+        assert!(added.is_empty());
+        assert!(removed.is_empty());
+        assert_eq!(pattern_diff(&[], &[]), (vec![], vec![]));
+
+        // Patterns that only moved to another source are unchanged, while
+        // different excludes make a different pattern.
+        let old = vec![
+            path("a").with_source("x".to_string()),
+            glob("b/**").with_excludes(vec!["b/c/**".to_string()]),
+        ];
+        let new = vec![
+            path("a").with_source("y".to_string()),
+            glob("b/**").with_excludes(vec!["b/d/**".to_string()]),
+        ];
+        let (added, removed) = pattern_diff(&old, &new);
+        assert_eq!(added, vec![new[1].clone()]);
+        assert_eq!(removed, vec![old[1].clone()]);
+    }
+
+    #[test]
+    fn test_pattern_serde() {
+        let kinds = [
+            PatternKind::RE,
+            PatternKind::Glob,
+            PatternKind::Path,
+            PatternKind::RelGlob,
+            PatternKind::RelPath,
+            PatternKind::RelRE,
+            PatternKind::ListFile,
+            PatternKind::ListFile0,
+            PatternKind::ListFileJson,
+            PatternKind::GitIgnore,
+            PatternKind::Set,
+            PatternKind::Include,
+            PatternKind::SubInclude,
+            PatternKind::RootFilesIn,
+        ];
+        for kind in kinds {
+            let serialized = serde_json::to_string(&kind).unwrap();
+            assert_eq!(serialized, format!("\"{}\"", kind.name()));
+            assert_eq!(
+                serde_json::from_str::<PatternKind>(&serialized).unwrap(),
+                kind
+            );
+
+            for pattern in [
+                Pattern::new(kind, "a/b".to_string()),
+                Pattern::new(kind, "a/*".to_string()).with_source("x/y".to_string()),
+            ] {
+                let serialized = serde_json::to_string(&pattern).unwrap();
+                assert_eq!(
+                    serde_json::from_str::<Pattern>(&serialized).unwrap(),
+                    pattern
+                );
+            }
+        }
+
+        let pattern = Pattern::new(PatternKind::Glob, "a/**".to_string())
+            .with_source("x/y".to_string())
+            .with_excludes(vec!["a/b/**".to_string()]);
+        let serialized = serde_json::to_string(&pattern).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"kind":"glob","pattern":"a/**","source":"x/y","excludes":["a/b/**"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Pattern>(&serialized).unwrap(),
+            pattern
+        );
+        assert_eq!(
+            serde_json::to_string(&Pattern::new(PatternKind::Path, "a".to_string())).unwrap(),
+            r#"{"kind":"path","pattern":"a","source":null}"#
+        );
+
+        let err = serde_json::from_str::<PatternKind>(r#""foo""#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            Error::UnsupportedPatternKind("foo".to_string()).to_string()
+        );
+        assert!(
+            serde_json::from_str::<Pattern>(r#"{"kind":"foo","pattern":"a","source":null}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_split_glob_excludes() {
+        assert_eq!(split_glob_excludes("*.py"), ("*.py".to_string(), vec![]));
+        assert_eq!(
+            split_glob_excludes("**/*.py~**/test_*.py~a/**"),
+            (
+                "**/*.py".to_string(),
+                vec!["**/test_*.py".to_string(), "a/**".to_string()]
+            )
+        );
+        assert_eq!(
+            split_glob_excludes(r"a\~b~c\*"),
+            ("a~b".to_string(), vec![r"c\*".to_string()])
+        );
+        // Empty parts make "~" literal.
+        assert_eq!(split_glob_excludes("*~"), ("*~".to_string(), vec![]));
+        assert_eq!(split_glob_excludes("~/a"), ("~/a".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_normalize_patterns_glob_excludes() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("x");
+        let options = NormalizeOptions {
+            glob_excludes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_patterns_with_options(
+                vec![r"glob:**/*.py~**/test_*.py", r"glob:a\~b"],
+                PatternKind::Glob,
+                root,
+                &cwd,
+                options,
+            )
+            .unwrap(),
+            [
+                Pattern::new(PatternKind::Glob, "x/**/*.py".to_string())
+                    .with_excludes(vec!["x/**/test_*.py".to_string()]),
+                Pattern::new(PatternKind::Glob, "x/a~b".to_string()),
+            ]
+        );
+
+        // "~" is not special unless enabled.
+        assert_eq!(
+            normalize_patterns(vec!["glob:*.py~test_*.py"], PatternKind::Glob, root, &cwd).unwrap(),
+            [Pattern::new(
+                PatternKind::Glob,
+                "x/*.py~test_*.py".to_string()
+            )]
+        );
+
+        let patterns = normalize_patterns_with_options(
+            vec!["glob:**/*.py~**/test_*.py", "glob:test_bar.py"],
+            PatternKind::Glob,
+            root,
+            root,
+            options,
+        )
+        .unwrap();
+        let matcher = crate::build_matcher(&patterns, &[], &[], true).unwrap();
+        let matches = |path: &str| {
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches("foo.py"));
+        assert!(matches("a/foo.py"));
+        assert!(!matches("test_foo.py"));
+        assert!(!matches("a/test_foo.py"));
+        assert!(!matches("foo.c"));
+        // Only excluded from the pattern it is attached to.
+        assert!(matches("test_bar.py"));
+    }
+
+    #[test]
+    fn test_normalize_patterns_force_recursive_glob() {
+        let root = Path::new(ROOT);
+        let normalize = |force_recursive_glob| {
+            let options = NormalizeOptions {
+                force_recursive_glob,
+                glob_excludes: true,
+                ..Default::default()
+            };
+            normalize_patterns_with_options(
+                vec![
+                    "glob:src",
+                    "glob:norec:*.c",
+                    "relglob:*.h",
+                    "glob:lib/**",
+                    "glob:a/*~a/b",
+                    "path:x",
+                ],
+                PatternKind::Glob,
+                root,
+                root,
+                options,
+            )
+            .unwrap()
+        };
+        let glob = |pattern: &str| Pattern::new(PatternKind::Glob, pattern.to_string());
+        let path = Pattern::new(PatternKind::Path, "x".to_string());
+
+        let patterns = normalize(true);
+        assert_eq!(
+            patterns,
+            [
+                glob("src/**"),
+                glob("*.c"),
+                Pattern::new(PatternKind::RelGlob, "*.h/**".to_string()),
+                glob("lib/**"),
+                glob("a/*/**").with_excludes(vec!["a/b/**".to_string()]),
+                path.clone(),
+            ]
+        );
+        assert_eq!(
+            normalize(false),
+            [
+                glob("src"),
+                glob("norec:*.c"),
+                Pattern::new(PatternKind::RelGlob, "*.h".to_string()),
+                glob("lib/**"),
+                glob("a/*").with_excludes(vec!["a/b".to_string()]),
+                path,
+            ]
+        );
+
+        let matcher = crate::build_matcher(&patterns[..2], &[], &[], true).unwrap();
+        let matches = |path: &str| {
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches("src/a/b.rs"));
+        assert!(matches("a.c"));
+        assert!(!matches("d/a.c"));
+
+        // Without force_recursive_glob, `norec:` is a plain path component.
+        let patterns = normalize(false);
+        let matcher = crate::build_matcher(&patterns[..2], &[], &[], true).unwrap();
+        let matches = |path: &str| {
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches("norec:a.c"));
+        assert!(!matches("a.c"));
+    }
+
+    #[test]
+    fn test_normalize_patterns_re2_syntax() {
+        let root = Path::new(ROOT);
+        let options = NormalizeOptions {
+            re2_syntax: true,
+            ..Default::default()
+        };
+        let normalize = |pattern: &str| {
+            normalize_patterns_with_options(vec![pattern], PatternKind::Glob, root, root, options)
+        };
+
+        assert_eq!(
+            normalize(r"re:(?i)a.*\.py").unwrap(),
+            [Pattern::new(PatternKind::RE, r"(?i)a.*\.py".to_string())]
+        );
+        assert_eq!(
+            normalize(r"relre:[[:alpha:]]{2,1000}").unwrap(),
+            [Pattern::new(
+                PatternKind::RelRE,
+                r"[[:alpha:]]{2,1000}".to_string()
+            )]
+        );
+
+        // Neither RE2 nor the regex crate support backreferences.
+        let err = normalize(r"re:(a)\1").unwrap_err();
+        assert!(matches!(err, Error::InvalidRegex(ref p, _) if p == r"(a)\1"));
+        assert!(err.to_string().contains("backreferences"));
+
+        // Only supported by the regex crate.
+        assert!(normalize(r"re:[a-z&&[^x]]").is_err());
+        assert!(normalize(r"re:[a[bc]]").is_err());
+        assert!(normalize(r"re:(?x)a b").is_err());
+        assert!(normalize(r"re:\u00e9").is_err());
+        assert!(normalize(r"relre:a{1001}").is_err());
+
+        // Without the option, regexes are not validated.
+        assert_eq!(
+            normalize_patterns(vec![r"re:(a)\1"], PatternKind::Glob, root, root).unwrap(),
+            [Pattern::new(PatternKind::RE, r"(a)\1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_normalize_patterns_anchored_relglob() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("x");
+        let normalize = |relglob_base: Option<&str>| {
+            let options = NormalizeOptions {
+                relglob_base,
+                ..Default::default()
+            };
+            normalize_patterns_with_options(
+                vec![
+                    "relglob:/*.c",
+                    "relglob:*.c",
+                    "relglob:/",
+                    "relglob:/a/../b/*.h",
+                ],
+                PatternKind::Glob,
+                root,
+                &cwd,
+                options,
+            )
+            .unwrap()
+        };
+
+        let patterns = normalize(None);
+        assert_eq!(
+            patterns,
+            [
+                Pattern::new(PatternKind::Glob, "*.c".to_string()),
+                Pattern::new(PatternKind::RelGlob, "*.c".to_string()),
+                Pattern::new(PatternKind::Glob, "**".to_string()),
+                Pattern::new(PatternKind::Glob, "b/*.h".to_string()),
+            ]
+        );
+        // Anchored relglobs ignore the base, only unanchored ones use it.
+        assert_eq!(
+            normalize(Some("src")),
+            [
+                Pattern::new(PatternKind::Glob, "*.c".to_string()),
+                Pattern::new(PatternKind::Glob, "src/**/*.c".to_string()),
+                Pattern::new(PatternKind::Glob, "**".to_string()),
+                Pattern::new(PatternKind::Glob, "b/*.h".to_string()),
+            ]
+        );
+
+        let matches = |i: usize, path: &str| {
+            crate::build_matcher(&patterns[i..=i], &[], &[], true)
+                .unwrap()
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches(0, "a.c"));
+        assert!(!matches(0, "x/a.c"));
+        assert!(matches(2, "a.c"));
+        assert!(matches(2, "x/y/a.h"));
+
+        // Unlike `relglob:*.c`, `relglob:/*.c` doesn't match in subdirectories.
+        let x = RepoPath::from_str("x").unwrap();
+        assert!(!patterns[0].matches_directory_prefix(x));
+        assert!(patterns[1].matches_directory_prefix(x));
+    }
+
+    #[test]
+    fn test_normalize_patterns_relglob_base() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("x");
+        let normalize = |relglob_base: Option<&str>| {
+            let options = NormalizeOptions {
+                relglob_base,
+                ..Default::default()
+            };
+            normalize_patterns_with_options(
+                vec!["relglob:*.c", "relglob:", "relglob:a/../b/*.h", "glob:*.c"],
+                PatternKind::Glob,
+                root,
+                &cwd,
+                options,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            normalize(None),
+            [
+                Pattern::new(PatternKind::RelGlob, "*.c".to_string()),
+                Pattern::new(PatternKind::RelGlob, ".".to_string()),
+                Pattern::new(PatternKind::RelGlob, "b/*.h".to_string()),
+                Pattern::new(PatternKind::Glob, "x/*.c".to_string()),
+            ]
+        );
+        let based = [
+            Pattern::new(PatternKind::Glob, "src/**/*.c".to_string()),
+            Pattern::new(PatternKind::Glob, "src/**".to_string()),
+            Pattern::new(PatternKind::Glob, "src/**/b/*.h".to_string()),
+            Pattern::new(PatternKind::Glob, "x/*.c".to_string()),
+        ];
+        assert_eq!(normalize(Some("src")), based);
+        assert_eq!(normalize(Some("./src/")), based);
+        // The base is a path: glob characters in it are literal.
+        assert_eq!(
+            normalize(Some("a[b]"))[0],
+            Pattern::new(PatternKind::Glob, r"a\[b\]/**/*.c".to_string())
+        );
+        // The repository root as base is the same as no base.
+        assert_eq!(normalize(Some("")), normalize(None));
+
+        let patterns = normalize(Some("src"));
+        let matcher = crate::build_matcher(&patterns[..1], &[], &[], true).unwrap();
+        let matches = |path: &str| {
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches("src/a.c"));
+        assert!(matches("src/a/b/c.c"));
+        assert!(!matches("a.c"));
+        assert!(!matches("lib/src/a.c"));
+        assert!(!matches("src/a.h"));
+    }
+
+    #[test]
+    fn test_cwd_resolver_normalized_once() {
+        let dir = TempDir::new().unwrap();
+        let listfile = dir.path().join("patterns.txt");
+        let listed: Vec<String> = (0..100).map(|i| format!("glob:l{}/*.c", i)).collect();
+        fs::write(&listfile, listed.join("\n")).unwrap();
+
+        let root = Path::new(ROOT);
+        let mut patterns: Vec<String> = (0..100).map(|i| format!("glob:{}/*.c", i)).collect();
+        patterns.push(format!("listfile:{}", listfile.display()));
+
+        // The root and the cwd are normalized when the resolver is created.
+        let resolver = CwdResolver::new(&root.join("."), &root.join("bar/../foo")).unwrap();
+        assert_eq!(resolver.root, root);
+        assert_eq!(resolver.cwd, root.join("foo"));
+
+        // Every pattern, including the ones read from the listfile, is then
+        // resolved against the normalized cwd of that one resolver.
+        let result = normalize_patterns_impl(
+            &patterns,
+            PatternKind::Glob,
+            root,
+            &resolver,
+            &NormalizeOptions::default(),
+            None,
+        )
+        .unwrap();
+        let expected: Vec<String> = (0..100)
+            .map(|i| format!("foo/{}/*.c", i))
+            .chain((0..100).map(|i| format!("foo/l{}/*.c", i)))
+            .collect();
+        assert_eq!(
+            result.into_iter().map(|p| p.pattern).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
     #[test]
     fn test_normalize_patterns_unsupported_kind() {
-        assert!(normalize_patterns(vec!["set:added()"], PatternKind::Glob).is_err());
-        assert!(normalize_patterns(vec!["include:/a/b.txt"], PatternKind::Glob).is_err());
-        assert!(normalize_patterns(vec!["subinclude:/a/b.txt"], PatternKind::Glob).is_err());
+        let root = Path::new(ROOT);
+        assert!(normalize_patterns(vec!["set:added()"], PatternKind::Glob, root, root).is_err());
+        assert!(
+            normalize_patterns(vec!["include:/a/b.txt"], PatternKind::Glob, root, root).is_err()
+        );
+        assert!(
+            normalize_patterns(vec!["subinclude:/a/b.txt"], PatternKind::Glob, root, root).is_err()
+        );
     }
 
     #[test]
@@ -301,6 +2452,190 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_try_build_patterns() {
+        let root = Path::new(ROOT);
+        let cwd = root.join("x");
+        let patterns = ["re:a.py".to_string(), "a.txt".to_string()];
+
+        assert_eq!(
+            try_build_patterns(&patterns, PatternKind::Glob, root, &cwd).unwrap(),
+            [
+                Pattern::new(PatternKind::RE, "a.py".to_string()),
+                Pattern::new(PatternKind::Glob, "x/a.txt".to_string())
+            ]
+        );
+
+        // Only try_build_patterns reports invalid patterns.
+        let patterns = ["re:a(.py".to_string()];
+        assert_eq!(build_patterns(&patterns, PatternKind::Glob).len(), 1);
+        assert!(matches!(
+            try_build_patterns(&patterns, PatternKind::Glob, root, &cwd),
+            Err(Error::InvalidRegex(ref p, _)) if p == "a(.py"
+        ));
+
+        let patterns = ["globb:*.c".to_string()];
+        assert_eq!(build_patterns(&patterns, PatternKind::Glob).len(), 1);
+        assert!(matches!(
+            try_build_patterns(&patterns, PatternKind::Glob, root, &cwd),
+            Err(Error::UnknownPatternKind { .. })
+        ));
+
+        let patterns = ["listfile:/does/not/exist".to_string()];
+        assert_eq!(build_patterns(&patterns, PatternKind::Glob).len(), 1);
+        assert!(matches!(
+            try_build_patterns(&patterns, PatternKind::Glob, root, &cwd),
+            Err(Error::IOError(_))
+        ));
+    }
+
+    #[test]
+    fn test_reroot_patterns() {
+        let glob = |pattern: &str| Pattern::new(PatternKind::Glob, pattern.to_string());
+        let reroot = |patterns: &[Pattern]| reroot_patterns(patterns, "src/sub").unwrap();
+
+        assert_eq!(reroot(&[glob("src/sub/*.c")]), [glob("*.c")]);
+        assert_eq!(reroot(&[glob("src/*/*.c")]), [glob("*.c")]);
+        assert_eq!(reroot(&[glob("src/s{ub,rc}/*.c")]), [glob("*.c")]);
+        assert_eq!(reroot(&[glob("src/sub/**")]), [glob("**")]);
+        // Straddling the nested root.
+        assert_eq!(reroot(&[glob("src/**/*.c")]), [glob("**/*.c")]);
+        assert_eq!(
+            reroot(&[glob("**/sub/*.c")]),
+            [glob("*.c"), glob("**/sub/*.c")]
+        );
+        // Outside of the nested root, or only matching it.
+        assert_eq!(reroot(&[glob("src/*.c")]), []);
+        assert_eq!(reroot(&[glob("lib/sub/*.c")]), []);
+        assert_eq!(reroot(&[glob("src/sub")]), []);
+
+        let path = |kind, pattern: &str| Pattern::new(kind, pattern.to_string());
+        assert_eq!(
+            reroot(&[
+                path(PatternKind::Path, "src/sub/a"),
+                path(PatternKind::Path, "src"),
+                path(PatternKind::Path, "lib"),
+                path(PatternKind::RootFilesIn, "src/sub"),
+                path(PatternKind::RootFilesIn, "src"),
+                path(PatternKind::RelGlob, "*.c"),
+            ]),
+            [
+                path(PatternKind::Path, "a"),
+                path(PatternKind::Path, ""),
+                path(PatternKind::RootFilesIn, ""),
+                path(PatternKind::RelGlob, "*.c"),
+            ]
+        );
+
+        // Exclusions and sources are kept.
+        let p = glob("src/**")
+            .with_excludes(vec!["src/sub/gen/**".to_string(), "lib/**".to_string()])
+            .with_source("list".to_string());
+        assert_eq!(
+            reroot(&[p]),
+            [glob("**")
+                .with_excludes(vec!["gen/**".to_string()])
+                .with_source("list".to_string())]
+        );
+
+        assert!(matches!(
+            reroot_patterns(&[path(PatternKind::RE, "src/sub/.*")], "src/sub"),
+            Err(Error::UnsupportedPatternKind(_))
+        ));
+        assert_eq!(
+            reroot_patterns(&[glob("src/*.c")], "").unwrap(),
+            [glob("src/*.c")]
+        );
+    }
+
+    #[test]
+    fn test_escape_literal() {
+        let literal = "a+b(c)[d]*{e}.txt";
+        let escape = |kind| escape_literal(literal, kind).unwrap();
+
+        let glob = escape(PatternKind::Glob);
+        assert_eq!(glob, r"a+b(c)\[d\]\*\{e\}.txt");
+        assert_eq!(escape(PatternKind::RelGlob), glob);
+        let matcher = TreeMatcher::from_rules([glob].iter(), true).unwrap();
+        assert!(matcher.matches(literal));
+        assert!(!matcher.matches("a+b(c)[d]x{e}.txt"));
+
+        let re = escape(PatternKind::RE);
+        assert_eq!(re, r"a\+b\(c\)\[d\]\*\{e\}\.txt");
+        assert_eq!(escape(PatternKind::RelRE), re);
+        let matcher = RegexMatcher::new(&re, true).unwrap();
+        assert!(matcher.matches(literal));
+        assert!(!matcher.matches("aab(c)[d]*{e}.txt"));
+
+        for kind in [
+            PatternKind::Path,
+            PatternKind::RelPath,
+            PatternKind::RootFilesIn,
+            PatternKind::ListFile,
+            PatternKind::ListFile0,
+            PatternKind::ListFileJson,
+            PatternKind::GitIgnore,
+            PatternKind::Include,
+            PatternKind::SubInclude,
+        ] {
+            assert_eq!(escape(kind), literal);
+        }
+
+        assert!(matches!(
+            escape_literal(literal, PatternKind::Set),
+            Err(Error::UnsupportedPatternKind(_))
+        ));
+
+        let glob = escape_literal("norec:a", PatternKind::Glob).unwrap();
+        assert_eq!(glob, r"\norec:a");
+        let options = NormalizeOptions {
+            force_recursive_glob: true,
+            ..Default::default()
+        };
+        let root = Path::new(ROOT);
+        let patterns = normalize_patterns_with_options(
+            [format!("glob:{}", glob)],
+            PatternKind::Glob,
+            root,
+            root,
+            options,
+        )
+        .unwrap();
+        let matcher = crate::build_matcher(&patterns, &[], &[], true).unwrap();
+        assert!(
+            matcher
+                .matches_file(RepoPath::from_str("norec:a/b").unwrap())
+                .unwrap()
+        );
+
+        let literal = "a?b~c.txt";
+        let glob = escape_literal(literal, PatternKind::Glob).unwrap();
+        assert_eq!(glob, r"a\?b\~c.txt");
+        for glob_excludes in [false, true] {
+            let options = NormalizeOptions {
+                glob_excludes,
+                ..Default::default()
+            };
+            let patterns = normalize_patterns_with_options(
+                [format!("glob:{}", glob)],
+                PatternKind::Glob,
+                root,
+                root,
+                options,
+            )
+            .unwrap();
+            assert!(patterns.iter().all(|p| p.excludes.is_empty()));
+            let matcher = crate::build_matcher(&patterns, &[], &[], true).unwrap();
+            let matches = |path: &str| {
+                matcher
+                    .matches_file(RepoPath::from_str(path).unwrap())
+                    .unwrap()
+            };
+            assert!(matches(literal));
+            assert!(!matches("axb~c.txt"));
+        }
+    }
+
     #[test]
     fn test_normalize_patterns_listfile() {
         test_normalize_patterns_listfile_helper("\n");
@@ -311,6 +2646,355 @@ mod tests {
         test_normalize_patterns_listfile_helper("\0");
     }
 
+    #[cfg(windows)]
+    #[test]
+    fn test_normalize_patterns_listfile_drive_letter() {
+        let inner_patterns = [
+            r"C:\repo\src\foo.c",
+            "C:/repo/src/bar.c",
+            r"glob:C:\repo\src\*.h",
+        ];
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("patterns.txt");
+        let path_str = path.to_string_lossy();
+        fs::write(&path, inner_patterns.join("\n")).unwrap();
+
+        let root = Path::new(ROOT);
+        let result = normalize_patterns(
+            vec![format!("listfile:{}", path_str)],
+            PatternKind::RelPath,
+            root,
+            root,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            [
+                Pattern::new(PatternKind::RelPath, "src/foo.c".to_string())
+                    .with_source(path_str.to_string()),
+                Pattern::new(PatternKind::RelPath, "src/bar.c".to_string())
+                    .with_source(path_str.to_string()),
+                Pattern::new(PatternKind::Glob, "src/*.h".to_string())
+                    .with_source(path_str.to_string()),
+            ]
+        )
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_normalize_patterns_extended_length() {
+        let normalize = |pattern: &str, root: &str| {
+            let root = Path::new(root);
+            normalize_patterns(vec![pattern], PatternKind::RelPath, root, root).unwrap()
+        };
+        let relpath = |pattern: &str| vec![Pattern::new(PatternKind::RelPath, pattern.to_string())];
+
+        assert_eq!(
+            normalize(r"\\?\C:\repo\src\foo.c", ROOT),
+            relpath("src/foo.c")
+        );
+        assert_eq!(normalize(r"\\?\C:\repo", ROOT), relpath(""));
+        assert_eq!(
+            normalize(r"glob:\\?\C:\repo\src\*.h", ROOT),
+            vec![Pattern::new(PatternKind::Glob, "src/*.h".to_string())]
+        );
+        assert_eq!(
+            normalize(r"\\?\UNC\server\share\repo\a", r"\\server\share\repo"),
+            relpath("a")
+        );
+
+        // The repo root can be extended-length too.
+        assert_eq!(
+            normalize(r"C:\repo\src\foo.c", r"\\?\C:\repo"),
+            relpath("src/foo.c")
+        );
+        assert_eq!(
+            normalize(r"src\foo.c", r"\\?\C:\repo"),
+            relpath("src/foo.c")
+        );
+        assert_eq!(
+            normalize(r"\\?\C:\repo\src\foo.c", r"\\?\C:\repo"),
+            relpath("src/foo.c")
+        );
+
+        // Like other absolute paths outside of the root, kept as they are.
+        assert_eq!(
+            normalize(r"\\?\C:\other\a", ROOT),
+            relpath(r"\\?\C:\other\a")
+        );
+    }
+
+    #[test]
+    fn test_normalize_patterns_listfilejson() {
+        let inner_patterns = [format!("glob:{}/a/*", ROOT), "re:a\nb".to_string()];
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("patterns.json");
+        let path_str = path.to_string_lossy();
+        fs::write(&path, serde_json::to_string(&inner_patterns).unwrap()).unwrap();
+
+        let root = Path::new(ROOT);
+        let result = normalize_patterns(
+            vec![format!("listfilejson:{}", path_str)],
+            PatternKind::Glob,
+            root,
+            root,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            [
+                Pattern::new(PatternKind::Glob, "a/*".to_string())
+                    .with_source(path_str.to_string()),
+                Pattern::new(PatternKind::RE, "a\nb".to_string()).with_source(path_str.to_string())
+            ]
+        )
+    }
+
+    #[test]
+    fn test_normalize_patterns_listfilejson_malformed() {
+        let dir = TempDir::new().unwrap();
+        let root = Path::new(ROOT);
+        for content in [r#"["a", "b""#, r#"{"a": "b"}"#, "[1]"] {
+            let path = dir.path().join("patterns.json");
+            fs::write(&path, content).unwrap();
+            let result = normalize_patterns(
+                vec![format!("listfilejson:{}", path.to_string_lossy())],
+                PatternKind::Glob,
+                root,
+                root,
+            );
+            assert!(
+                matches!(result, Err(Error::InvalidListFileJson(..))),
+                "{:?} should be rejected",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn test_gitignore_patterns() {
+        let contents = "
+# comment
+*.log
+!keep.log
+/build
+out/
+!out/keep.txt
+docs/**/*.tmp
+trailing\\ \x20
+";
+        let glob = |pattern: &str, excludes: &[&str]| {
+            Pattern::new(PatternKind::Glob, pattern.to_string())
+                .with_excludes(excludes.iter().map(|e| e.to_string()).collect())
+        };
+
+        assert_eq!(
+            gitignore_patterns(contents, ""),
+            [
+                glob("**/*.log", &["**/keep.log", "out/keep.txt"]),
+                glob("**/*.log/*/**", &["**/keep.log/*/**", "out/keep.txt/*/**"]),
+                glob("build", &["out/keep.txt"]),
+                glob("build/*/**", &["out/keep.txt/*/**"]),
+                glob("**/out/*/**", &["out/keep.txt/*/**"]),
+                glob("docs/**/*.tmp", &[]),
+                glob("docs/**/*.tmp/*/**", &[]),
+                glob(r"**/trailing\ ", &[]),
+                glob(r"**/trailing\ /*/**", &[]),
+            ]
+        );
+        assert_eq!(
+            gitignore_patterns("/a\nb/", "sub"),
+            [
+                glob("sub/a", &[]),
+                glob("sub/a/*/**", &[]),
+                glob("sub/**/b/*/**", &[])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_patterns_gitignore() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let path = root.join("sub").join(".gitignore");
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(&path, "*.log\n!keep.log\n/build\nout/\n!out/keep.txt\n").unwrap();
+
+        let path_str = path.to_string_lossy();
+        let patterns = normalize_patterns(
+            vec![format!("gitignore:{}", path_str)],
+            PatternKind::Glob,
+            root,
+            root,
+        )
+        .unwrap();
+        assert!(
+            patterns
+                .iter()
+                .all(|p| p.source.as_deref() == Some(&path_str))
+        );
+
+        let matcher = crate::build_matcher(&patterns, &[], &[], true).unwrap();
+        let matches = |path: &str| {
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+
+        // Only under the directory of the file.
+        assert!(matches("sub/a.log"));
+        assert!(matches("sub/x/a.log"));
+        assert!(!matches("a.log"));
+
+        // Negation.
+        assert!(!matches("sub/keep.log"));
+        assert!(!matches("sub/x/keep.log"));
+
+        // Anchoring.
+        assert!(matches("sub/build"));
+        assert!(matches("sub/build/a.c"));
+        assert!(!matches("sub/x/build"));
+
+        // Directory-only, files under ignored directories can't be
+        // re-included.
+        assert!(!matches("sub/out"));
+        assert!(matches("sub/out/a.c"));
+        assert!(matches("sub/x/out/a.c"));
+        assert!(matches("sub/out/keep.txt"));
+
+        // Negated directories re-include what's under them.
+        let path = root.join(".gitignore");
+        fs::write(&path, "*\n!*/\n!*.c\n").unwrap();
+        let patterns = normalize_patterns(
+            vec![format!("gitignore:{}", path.to_string_lossy())],
+            PatternKind::Glob,
+            root,
+            root,
+        )
+        .unwrap();
+        let matcher = crate::build_matcher(&patterns, &[], &[], true).unwrap();
+        let matches = |path: &str| {
+            matcher
+                .matches_file(RepoPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        assert!(matches("a.h"));
+        assert!(matches("src/a.h"));
+        assert!(!matches("a.c"));
+        assert!(!matches("src/a.c"));
+    }
+
+    #[test]
+    fn test_normalize_patterns_report() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("patterns.txt");
+        let path_str = path.to_string_lossy();
+        fs::write(&path, "a/*.c\nre:b.*\n").unwrap();
+
+        let root = Path::new(ROOT);
+        let inputs = vec![
+            "glob:src/{a,b}/*.rs".to_string(),
+            format!("listfile:{}", path_str),
+            "path:./lib/../docs".to_string(),
+        ];
+        let report = normalize_patterns_report(
+            inputs,
+            PatternKind::Glob,
+            root,
+            root,
+            NormalizeOptions::default(),
+        )
+        .unwrap();
+
+        let reported = |pattern: Pattern, brace_expansions: &[&str]| ReportedPattern {
+            pattern,
+            brace_expansions: brace_expansions.iter().map(|s| s.to_string()).collect(),
+        };
+        assert_eq!(
+            report,
+            NormalizeReport {
+                entries: vec![
+                    NormalizeReportEntry {
+                        input: "glob:src/{a,b}/*.rs".to_string(),
+                        patterns: vec![reported(
+                            Pattern::new(PatternKind::Glob, "src/{a,b}/*.rs".to_string()),
+                            &["src/a/*.rs", "src/b/*.rs"],
+                        )],
+                    },
+                    NormalizeReportEntry {
+                        input: format!("listfile:{}", path_str),
+                        patterns: vec![
+                            reported(
+                                Pattern::new(PatternKind::Glob, "a/*.c".to_string())
+                                    .with_source(path_str.to_string()),
+                                &[],
+                            ),
+                            reported(
+                                Pattern::new(PatternKind::RE, "b.*".to_string())
+                                    .with_source(path_str.to_string()),
+                                &[],
+                            ),
+                            reported(
+                                Pattern::new(PatternKind::Glob, "".to_string())
+                                    .with_source(path_str.to_string()),
+                                &[],
+                            ),
+                        ],
+                    },
+                    NormalizeReportEntry {
+                        input: "path:./lib/../docs".to_string(),
+                        patterns: vec![reported(
+                            Pattern::new(PatternKind::Path, "docs".to_string()),
+                            &[],
+                        )],
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "glob:src/{{a,b}}/*.rs\n  glob:src/{{a,b}}/*.rs\n    src/a/*.rs\n    src/b/*.rs\n\
+                 listfile:{0}\n  glob:a/*.c (from {0})\n  re:b.* (from {0})\n  glob: (from {0})\n\
+                 path:./lib/../docs\n  path:docs\n",
+                path_str
+            )
+        );
+    }
+
+    #[test]
+    fn test_normalize_patterns_large_listfile() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("patterns.txt");
+        let path_str = path.to_string_lossy();
+        let root = Path::new(ROOT);
+        let paths: Vec<String> = (0..100_000)
+            .map(|i| format!("d{}/f{}", i % 100, i))
+            .collect();
+
+        for (kind, sep) in [("listfile", "\n"), ("listfile0", "\0")] {
+            // No separator after the last path.
+            fs::write(&path, paths.join(sep)).unwrap();
+            let result = normalize_patterns(
+                [format!("{}:{}", kind, path_str)],
+                PatternKind::Path,
+                root,
+                root,
+            )
+            .unwrap();
+            assert_eq!(result.len(), paths.len());
+            for (p, expected) in result.iter().zip(&paths) {
+                assert_eq!(
+                    p,
+                    &Pattern::new(PatternKind::Path, expected.clone())
+                        .with_source(path_str.to_string())
+                );
+            }
+        }
+    }
+
     fn test_normalize_patterns_listfile_helper(sep: &str) {
         let inner_patterns = vec!["glob:/a/*", r"re:a.*\.py"];
         let dir = TempDir::new().unwrap();
@@ -324,7 +3008,8 @@ mod tests {
             if sep == "\n" { "" } else { "0" },
             path_str
         )];
-        let result = normalize_patterns(outer_patterns, PatternKind::Glob).unwrap();
+        let root = Path::new(ROOT);
+        let result = normalize_patterns(outer_patterns, PatternKind::Glob, root, root).unwrap();
 
         assert_eq!(
             result,
@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Check that a regular expression only uses the syntax shared by RE2 and
+//! the regex crate.
+//!
+//! Regex patterns are matched by the regex crate on the client, and by RE2 on
+//! the server. A pattern needs to be accepted by both to behave the same
+//! everywhere.
+
+use regex_syntax::ast;
+use regex_syntax::ast::visit;
+use regex_syntax::ast::Ast;
+use regex_syntax::ast::ClassSetBinaryOp;
+use regex_syntax::ast::ClassSetItem;
+use regex_syntax::ast::Flag;
+use regex_syntax::ast::FlagsItemKind;
+use regex_syntax::ast::HexLiteralKind;
+use regex_syntax::ast::LiteralKind;
+use regex_syntax::ast::RepetitionKind;
+use regex_syntax::ast::RepetitionRange;
+use regex_syntax::ast::Visitor;
+
+/// Largest repetition count accepted by RE2.
+const MAX_REPEAT: u32 = 1000;
+
+/// Return the reason `pattern` would be rejected by RE2 or the regex crate,
+/// if any.
+pub(crate) fn check_syntax(pattern: &str) -> Result<(), String> {
+    let ast = ast::parse::Parser::new()
+        .parse(pattern)
+        .map_err(|e| e.kind().to_string())?;
+    visit(&ast, Re2Visitor)
+}
+
+struct Re2Visitor;
+
+impl Visitor for Re2Visitor {
+    type Output = ();
+    type Err = String;
+
+    fn finish(self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), String> {
+        match ast {
+            Ast::Flags(flags) => check_flags(&flags.flags),
+            Ast::Group(group) => match group.flags() {
+                Some(flags) => check_flags(flags),
+                None => Ok(()),
+            },
+            Ast::Literal(literal) => check_literal(literal),
+            Ast::Repetition(repetition) => match repetition.op.kind {
+                RepetitionKind::Range(
+                    RepetitionRange::Exactly(n)
+                    | RepetitionRange::AtLeast(n)
+                    | RepetitionRange::Bounded(_, n),
+                ) if n > MAX_REPEAT => Err(format!(
+                    "repetition count {} exceeds the RE2 limit of {}",
+                    n, MAX_REPEAT
+                )),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn visit_class_set_item_pre(&mut self, item: &ClassSetItem) -> Result<(), String> {
+        match item {
+            ClassSetItem::Bracketed(_) => {
+                Err("nested character classes are not supported by RE2".to_string())
+            }
+            ClassSetItem::Literal(literal) => check_literal(literal),
+            ClassSetItem::Range(range) => {
+                check_literal(&range.start)?;
+                check_literal(&range.end)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn visit_class_set_binary_op_pre(&mut self, _op: &ClassSetBinaryOp) -> Result<(), String> {
+        Err("character class set operations are not supported by RE2".to_string())
+    }
+}
+
+fn check_flags(flags: &ast::Flags) -> Result<(), String> {
+    for item in &flags.items {
+        if let FlagsItemKind::Flag(flag) = item.kind {
+            let name = match flag {
+                Flag::CaseInsensitive
+                | Flag::MultiLine
+                | Flag::DotMatchesNewLine
+                | Flag::SwapGreed => continue,
+                Flag::Unicode => 'u',
+                Flag::CRLF => 'R',
+                Flag::IgnoreWhitespace => 'x',
+            };
+            return Err(format!("flag {} is not supported by RE2", name));
+        }
+    }
+    Ok(())
+}
+
+fn check_literal(literal: &ast::Literal) -> Result<(), String> {
+    match literal.kind {
+        LiteralKind::HexFixed(HexLiteralKind::UnicodeShort | HexLiteralKind::UnicodeLong)
+        | LiteralKind::HexBrace(HexLiteralKind::UnicodeShort | HexLiteralKind::UnicodeLong) => {
+            Err("\\u and \\U escapes are not supported by RE2, use \\x{...}".to_string())
+        }
+        _ => Ok(()),
+    }
+}
@@ -9,14 +9,25 @@ use anyhow::Result;
 use regex_automata::dfa::dense;
 use regex_automata::dfa::Automaton;
 use regex_automata::dfa::StartKind;
+use regex_automata::nfa::thompson;
 use regex_automata::util::syntax;
 use regex_automata::Anchored;
 use regex_automata::Input;
 use types::RepoPath;
 
 use crate::DirectoryMatch;
+use crate::Error;
 use crate::Matcher;
 
+/// Default limit, in bytes, on the memory used to compile a regular
+/// expression. Pathological patterns like `(a|b)*a(a|b){30}` blow up
+/// exponentially when turned into a DFA and are rejected instead.
+pub const REGEX_SIZE_LIMIT: usize = 10 << 20;
+
+/// Limit, in bytes, used for regular expressions including patterns read
+/// from listfiles, which are joined into large alternations.
+pub const LISTFILE_REGEX_SIZE_LIMIT: usize = 100 << 20;
+
 /// Pattern matcher constructed by an regular expression.
 ///
 /// The regular expression syntax [1] is same as regex crate with below limitations:
@@ -46,13 +57,38 @@ pub struct RegexMatcher {
 
 impl RegexMatcher {
     pub fn new(pattern: &str, case_sensitive: bool) -> Result<Self> {
+        Self::with_size_limit(pattern, case_sensitive, REGEX_SIZE_LIMIT)
+    }
+
+    /// Like [RegexMatcher::new], but compiling `pattern` can use up to
+    /// `size_limit` bytes instead of [REGEX_SIZE_LIMIT].
+    ///
+    /// Returns [Error::RegexTooLarge] if compiling `pattern` exceeds the limit.
+    pub fn with_size_limit(pattern: &str, case_sensitive: bool, size_limit: usize) -> Result<Self> {
         // `StartKind::Anchored` makes the dfa searching at the beginning of the
         // string. This is similar to Python's `re.match` behavior, which is
         // used in the match.py
         let dfa = dense::Builder::new()
-            .configure(dense::DFA::config().start_kind(StartKind::Anchored))
+            .configure(
+                dense::DFA::config()
+                    .start_kind(StartKind::Anchored)
+                    .dfa_size_limit(Some(size_limit))
+                    .determinize_size_limit(Some(size_limit)),
+            )
             .syntax(syntax::Config::new().case_insensitive(!case_sensitive))
-            .build(pattern)?;
+            .thompson(thompson::Config::new().nfa_size_limit(Some(size_limit)))
+            .build(pattern)
+            .map_err(|err| {
+                if is_size_limit_error(&err) {
+                    Error::RegexTooLarge {
+                        pattern: pattern.to_string(),
+                        limit: size_limit,
+                    }
+                    .into()
+                } else {
+                    anyhow::Error::from(err)
+                }
+            })?;
 
         Ok(RegexMatcher {
             pattern: pattern.to_string(),
@@ -149,6 +185,21 @@ impl Matcher for RegexMatcher {
     }
 }
 
+/// Whether building the DFA failed because it exceeded one of the size limits.
+fn is_size_limit_error(err: &dense::BuildError) -> bool {
+    use std::error::Error as _;
+
+    if let Some(nfa_err) = err
+        .source()
+        .and_then(|e| e.downcast_ref::<thompson::BuildError>())
+    {
+        return nfa_err.size_limit().is_some();
+    }
+    // dense::BuildError does not expose its kind, but both the DFA and the
+    // determinization size limit errors say so.
+    err.to_string().contains("exceeded size limit")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,4 +312,32 @@ mod tests {
             assert_eq!(m.matches("B/c"), !sensitive);
         }
     }
+
+    #[test]
+    fn test_re_size_limit() {
+        // The DFA needs a state for each combination of the last 30 bytes.
+        let err = RegexMatcher::new(r"(?:a|b)*a(?:a|b){30}", true)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RegexTooLarge {
+                limit: REGEX_SIZE_LIMIT,
+                ..
+            })
+        ));
+
+        // Large alternations of paths are fine with the listfile limit.
+        let paths: Vec<String> = (0..2000)
+            .map(|i| format!("dir{}/file{}\\.txt", i % 50, i))
+            .collect();
+        let pattern = format!("(?:{})$", paths.join("|"));
+        let m = RegexMatcher::with_size_limit(&pattern, true, LISTFILE_REGEX_SIZE_LIMIT).unwrap();
+        assert!(m.matches("dir1/file1951.txt"));
+        assert!(!m.matches("dir1/file1952.txt"));
+
+        // Syntax errors are not reported as size errors.
+        let err = RegexMatcher::new("(a", true).err().unwrap();
+        assert!(err.downcast_ref::<Error>().is_none());
+    }
 }
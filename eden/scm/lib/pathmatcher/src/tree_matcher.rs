@@ -365,15 +365,20 @@ fn next_path_separator(pat: &[u8], start: usize) -> Option<usize> {
 }
 
 /// Escape `{` and `}` so they no longer have special meanings to `globset`.
+/// Brackets that are already escaped are kept as is.
 fn escape_curly_brackets(pat: &str) -> String {
     if pat.contains('{') || pat.contains('}') {
         let mut result = String::with_capacity(pat.len() * 2);
+        let mut escaped = false;
         for ch in pat.chars() {
             match ch {
-                '{' => result.push_str("\\{"),
-                '}' => result.push_str("\\}"),
+                '{' | '}' if !escaped => {
+                    result.push('\\');
+                    result.push(ch);
+                }
                 ch => result.push(ch),
             }
+            escaped = !escaped && ch == '\\';
         }
         result
     } else {
@@ -460,6 +465,11 @@ mod tests {
         assert_eq!(m.match_recursive("a/{b,c/d}"), Some(true));
         assert_eq!(m.match_recursive("a/{b,c"), None);
         assert_eq!(m.match_recursive("a/{b,d"), Some(false));
+
+        // Escaped "{" is kept as is
+        let m = TreeMatcher::from_rules([r"a/\{b\}/**"].iter(), true).unwrap();
+        assert_eq!(m.match_recursive("a/{b}"), Some(true));
+        assert_eq!(m.match_recursive(r"a/\{b\}"), Some(false));
     }
 
     #[test]
@@ -609,12 +619,12 @@ mod tests {
     fn test_matching_rule_indexes() {
         let pats = ["foo", "bar/baz", "qux/**", "z/**/z"];
         let m = TreeMatcher::from_rules(pats.iter(), true).unwrap();
-        assert_eq!(m.matching_rule_indexes("banana"), vec![]);
+        assert_eq!(m.matching_rule_indexes("banana"), Vec::<usize>::new());
         assert_eq!(m.matching_rule_indexes("foo"), vec![0]);
         assert_eq!(m.matching_rule_indexes("bar/baz"), vec![1]);
         assert_eq!(m.matching_rule_indexes("qux/some/thing"), vec![2]);
         assert_eq!(m.matching_rule_indexes("z/1/z"), vec![3]);
-        assert_eq!(m.matching_rule_indexes("z/1"), vec![]);
+        assert_eq!(m.matching_rule_indexes("z/1"), Vec::<usize>::new());
     }
 
     #[test]
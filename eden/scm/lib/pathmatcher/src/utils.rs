@@ -7,12 +7,19 @@
 
 //! Utility functions
 
+use anyhow::Result;
+
+use crate::error::Error;
+
+/// Maximum number of patterns [`expand_curly_brackets`] expands a pattern to.
+pub(crate) const MAX_CURLY_BRACKET_EXPANSIONS: usize = 4096;
+
 /// Expand csh style brace expressions (`{` `}`) used in a glob pattern.
-/// Return multiple glob patterns. If the brackets do not match, return
-/// an empty vector.
+/// Return multiple glob patterns.
 ///
-/// TODO: Fix error handling so it returns an error when the input is
-/// illegal.
+/// Curly brackets without a matching bracket are kept as literals, and so
+/// are commas outside of brackets. Empty groups expand to nothing. Patterns
+/// that would expand to more than 4096 patterns are not expanded at all.
 ///
 /// Examples:
 ///
@@ -25,8 +32,9 @@
 /// assert_eq!(expand_curly_brackets("{a,b}{}{c,d}{{e}}"), vec!["ace", "ade", "bce", "bde"]);
 /// assert_eq!(expand_curly_brackets("\\{a\\}"), vec!["\\{a\\}"]);
 /// assert_eq!(expand_curly_brackets("[{a}]"), vec!["[{a}]"]);
-/// assert!(expand_curly_brackets("a}").is_empty());
-/// assert!(expand_curly_brackets("{a").is_empty());
+/// assert_eq!(expand_curly_brackets("a}"), vec!["a}"]);
+/// assert_eq!(expand_curly_brackets("{a"), vec!["{a"]);
+/// assert_eq!(expand_curly_brackets("}{a,b}{"), vec!["}a{", "}b{"]);
 /// ```
 pub fn expand_curly_brackets(pat: &str) -> Vec<String> {
     // A DAG of string segments. Vec indexes are used as identities.
@@ -44,28 +52,16 @@ pub fn expand_curly_brackets(pat: &str) -> Vec<String> {
     //   dag[5] = ("f", [6])
     //   dag[6] = ("g", [])
 
-    let mut in_box_brackets = false;
-    let mut escaped = false;
+    let matched = matched_curly_brackets(pat);
 
     // "Current" StrNode id used before "{"
     let mut bracket_stack: Vec<usize> = Vec::new();
 
-    for ch in pat.chars() {
+    for (i, ch, special) in glob_chars(pat) {
         let mut need_write = true;
-        if escaped {
-            match ch {
-                _ => escaped = false,
-            }
-        } else if in_box_brackets {
-            match ch {
-                ']' => in_box_brackets = false,
-                _ => {}
-            }
-        } else {
+        if special {
             match ch {
-                '\\' => escaped = true,
-                '[' => in_box_brackets = true,
-                '{' => {
+                '{' if matched[i] => {
                     let next_id = dag.len();
                     let current_id = next_id - 1;
                     dag.push(StrNode::default());
@@ -73,11 +69,7 @@ pub fn expand_curly_brackets(pat: &str) -> Vec<String> {
                     dag[current_id].1.push(next_id);
                     need_write = false;
                 }
-                '}' => {
-                    if bracket_stack.is_empty() {
-                        // ill-formed pattern - '}' without '{'
-                        return Vec::new();
-                    }
+                '}' if matched[i] => {
                     // "Merge" all "heads" in "{ ... }" into one node
                     let next_id = dag.len();
                     dag.push(StrNode::default());
@@ -108,28 +100,77 @@ pub fn expand_curly_brackets(pat: &str) -> Vec<String> {
         }
     }
 
-    if !bracket_stack.is_empty() {
-        // '{' and '}' mismatched
-        return Vec::new();
-    }
-
-    // Traverse the DAG to get all expanded strings
+    // Traverse the DAG to get all expanded strings. This is done without
+    // recursion, since deeply nested brackets make long paths in the DAG.
     let mut result = Vec::new();
-    fn visit(dag: &Vec<StrNode>, result: &mut Vec<String>, prefix: String, id: usize) {
-        let prefix = prefix + &dag[id].0;
-        if id == dag.len() - 1 {
-            assert!(dag[id].1.is_empty());
-            result.push(prefix);
+    let last_id = dag.len() - 1;
+    let mut prefix = String::new();
+    // Nodes to visit, with the length of the prefix before them.
+    let mut to_visit = vec![(0, 0)];
+    while let Some((id, prefix_len)) = to_visit.pop() {
+        prefix.truncate(prefix_len);
+        prefix.push_str(&dag[id].0);
+        if id == last_id {
+            if result.len() == MAX_CURLY_BRACKET_EXPANSIONS {
+                return vec![pat.to_string()];
+            }
+            result.push(prefix.clone());
         } else {
-            for child_id in dag[id].1.iter().cloned() {
-                visit(dag, result, prefix.clone(), child_id);
+            let prefix_len = prefix.len();
+            for &child_id in dag[id].1.iter().rev() {
+                to_visit.push((child_id, prefix_len));
             }
         }
     }
-    visit(&dag, &mut result, String::new(), 0);
     result
 }
 
+/// Iterate over the characters of a glob pattern with their byte index,
+/// and whether they can have a special meaning, i.e. they are neither
+/// escaped nor between box brackets.
+fn glob_chars(pat: &str) -> impl Iterator<Item = (usize, char, bool)> + '_ {
+    let mut in_box_brackets = false;
+    let mut escaped = false;
+    pat.char_indices().map(move |(i, ch)| {
+        if escaped {
+            escaped = false;
+            (i, ch, false)
+        } else if in_box_brackets {
+            if ch == ']' {
+                in_box_brackets = false;
+            }
+            (i, ch, false)
+        } else {
+            match ch {
+                '\\' => escaped = true,
+                '[' => in_box_brackets = true,
+                _ => {}
+            }
+            (i, ch, true)
+        }
+    })
+}
+
+/// Whether the byte at each index of `pat` is a curly bracket with a
+/// matching bracket.
+fn matched_curly_brackets(pat: &str) -> Vec<bool> {
+    let mut matched = vec![false; pat.len()];
+    let mut open = Vec::new();
+    for (i, ch, special) in glob_chars(pat) {
+        match ch {
+            '{' if special => open.push(i),
+            '}' if special => {
+                if let Some(j) = open.pop() {
+                    matched[i] = true;
+                    matched[j] = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    matched
+}
+
 /// Normalize a less strict glob pattern to a strict glob pattern.
 ///
 /// In a strict glob pattern, `**` can only be a single directory component.
@@ -180,9 +221,158 @@ pub fn plain_to_glob(plain: &str) -> String {
     result
 }
 
+/// Expand bounded recursive globs in a glob pattern into the equivalent
+/// patterns of fixed depth.
+///
+/// `{**:N}` matches up to `N` directory levels, like `**` limited to that
+/// depth. It must be a whole path component, otherwise it has no special
+/// meaning. A pattern without it is returned unchanged.
+pub(crate) fn expand_bounded_globstar(pat: &str) -> Vec<String> {
+    let mut expanded: Vec<Vec<&str>> = vec![Vec::new()];
+    for component in pat.split('/') {
+        match parse_bounded_globstar(component) {
+            Some(max_depth) => {
+                expanded = expanded
+                    .into_iter()
+                    .flat_map(|prefix| {
+                        (0..=max_depth).map(move |depth| {
+                            let mut components = prefix.clone();
+                            components.resize(components.len() + depth, "*");
+                            components
+                        })
+                    })
+                    .collect();
+            }
+            None => {
+                for components in expanded.iter_mut() {
+                    components.push(component);
+                }
+            }
+        }
+    }
+    expanded
+        .into_iter()
+        .map(|components| components.join("/"))
+        .collect()
+}
+
+/// Parse a `{**:N}` path component, returning `N`.
+fn parse_bounded_globstar(component: &str) -> Option<usize> {
+    component
+        .strip_prefix("{**:")?
+        .strip_suffix('}')?
+        .parse()
+        .ok()
+}
+
+/// Convert a normalized glob pattern into an anchored regular expression
+/// matching the same paths as a [`TreeMatcher`](crate::TreeMatcher) built
+/// from that pattern.
+///
+/// `*` and `?` do not match `/`. `**` matches across directories when it is
+/// a whole path component, and behaves like `*` otherwise. A trailing `/**`
+/// also matches the directory itself. `{` and `}` are literals.
+///
+/// Examples:
+///
+/// ```
+/// use pathmatcher::glob_to_regex;
+///
+/// assert_eq!(glob_to_regex("a/*.c").unwrap(), r"^a/[^/]*\.c$");
+/// assert_eq!(glob_to_regex("a/**").unwrap(), r"^a(?:/.*)?$");
+/// assert_eq!(glob_to_regex("**/b").unwrap(), r"^(?:.*/)?b$");
+/// assert!(glob_to_regex("[a").is_err());
+/// ```
+pub fn glob_to_regex(pat: &str) -> Result<String> {
+    let invalid = |reason: &str| Error::InvalidGlob(pat.to_string(), reason.to_string());
+    let escape = |ch: char| regex_syntax::escape(ch.encode_utf8(&mut [0; 4]));
+
+    // Like TreeMatcher, ignore a leading "/".
+    let chars: Vec<char> = pat.strip_prefix('/').unwrap_or(pat).chars().collect();
+    let mut re = String::with_capacity(chars.len() * 2 + 2);
+    re.push('^');
+
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let at_end = i + 2 == chars.len() || chars[i + 2] == '/';
+                if !(at_start && at_end) {
+                    re.push_str("[^/]*");
+                } else if chars.len() == 2 {
+                    // "**" matches everything.
+                    re.push_str(".*");
+                } else if i == 0 {
+                    // "**/": any number of leading directories.
+                    re.push_str("(?:.*/)?");
+                    i += 1;
+                } else if i + 2 == chars.len() {
+                    // "/**": the directory, or anything under it. The "/"
+                    // was already emitted.
+                    re.pop();
+                    re.push_str("(?:/.*)?");
+                } else {
+                    // "/**/": any number of directories in between.
+                    re.push_str("(?:.*/)?");
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '\\' => {
+                i += 1;
+                match chars.get(i) {
+                    Some(&ch) => re.push_str(&escape(ch)),
+                    None => return Err(invalid("dangling '\\'").into()),
+                }
+            }
+            '[' => {
+                i += 1;
+                re.push('[');
+                if let Some('!' | '^') = chars.get(i) {
+                    re.push('^');
+                    i += 1;
+                }
+                let start = i;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(invalid("unclosed character class").into()),
+                        // A "]" right after "[" is a literal.
+                        Some(']') if i > start => break,
+                        Some(&lo) => match (chars.get(i + 1), chars.get(i + 2)) {
+                            (Some('-'), Some(&hi)) if hi != ']' => {
+                                if hi < lo {
+                                    return Err(invalid("invalid character range").into());
+                                }
+                                re.push_str(&escape(lo));
+                                re.push('-');
+                                re.push_str(&escape(hi));
+                                i += 3;
+                                continue;
+                            }
+                            _ => re.push_str(&escape(lo)),
+                        },
+                    }
+                    i += 1;
+                }
+                re.push(']');
+            }
+            ch => re.push_str(&escape(ch)),
+        }
+        i += 1;
+    }
+
+    re.push('$');
+    Ok(re)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::TreeMatcher;
 
     #[test]
     fn test_normalize_glob() {
@@ -201,4 +391,175 @@ mod tests {
         assert_eq!(plain_to_glob(""), "");
         assert_eq!(plain_to_glob("!a!"), "\\!a!");
     }
+
+    #[test]
+    fn test_expand_curly_brackets_malformed() {
+        assert_eq!(expand_curly_brackets("foo{bar"), vec!["foo{bar"]);
+        assert_eq!(expand_curly_brackets("foo}bar{"), vec!["foo}bar{"]);
+        assert_eq!(expand_curly_brackets("a,b"), vec!["a,b"]);
+        assert_eq!(expand_curly_brackets("{a,b"), vec!["{a,b"]);
+        assert_eq!(expand_curly_brackets("{a,{b,c}"), vec!["{a,b", "{a,c"]);
+        assert_eq!(expand_curly_brackets("{a,b}}"), vec!["a}", "b}"]);
+        assert_eq!(expand_curly_brackets("a{}b"), vec!["ab"]);
+        assert_eq!(expand_curly_brackets("a{,}b"), vec!["ab", "ab"]);
+        assert_eq!(expand_curly_brackets("{}"), vec![""]);
+        assert_eq!(expand_curly_brackets(""), vec![""]);
+    }
+
+    #[test]
+    fn test_expand_curly_brackets_limits() {
+        let depth = 1000;
+        let nested = format!("{}a{}", "{".repeat(depth), "}".repeat(depth));
+        assert_eq!(expand_curly_brackets(&nested), vec!["a"]);
+
+        let nested = format!("{}a{}", "{x,".repeat(depth), "}".repeat(depth));
+        assert_eq!(expand_curly_brackets(&nested).len(), depth + 1);
+
+        // 2^12 expansions are fine, but 2^13 are too many.
+        let pattern = "{a,b}".repeat(12);
+        assert_eq!(
+            expand_curly_brackets(&pattern).len(),
+            MAX_CURLY_BRACKET_EXPANSIONS
+        );
+        let pattern = "{a,b}".repeat(13);
+        assert_eq!(expand_curly_brackets(&pattern), vec![pattern]);
+    }
+
+    #[test]
+    fn test_expand_curly_brackets_random() {
+        // Deterministic xorshift, to generate the same patterns every run.
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let alphabet = ['{', '}', ',', 'a', 'b', '/', '*'];
+
+        for _ in 0..10000 {
+            let len = (next() % 16) as usize;
+            let pattern: String = (0..len)
+                .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                .collect();
+
+            // Count the brackets without a match.
+            let mut open = 0;
+            let mut unmatched = 0;
+            for ch in pattern.chars() {
+                match ch {
+                    '{' => open += 1,
+                    '}' if open > 0 => open -= 1,
+                    '}' => unmatched += 1,
+                    _ => {}
+                }
+            }
+            unmatched += open;
+
+            let expanded = expand_curly_brackets(&pattern);
+            assert!(!expanded.is_empty(), "{:?}", pattern);
+            for p in &expanded {
+                // Only the unmatched brackets are left.
+                let brackets = p.chars().filter(|&c| c == '{' || c == '}').count();
+                assert_eq!(brackets, unmatched, "{:?} -> {:?}", pattern, expanded);
+            }
+            if !pattern.contains('{') {
+                assert_eq!(expanded, vec![pattern.clone()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_expand_bounded_globstar() {
+        assert_eq!(expand_bounded_globstar("a/*.c"), ["a/*.c"]);
+        assert_eq!(
+            expand_bounded_globstar("{**:2}/foo"),
+            ["foo", "*/foo", "*/*/foo"]
+        );
+        assert_eq!(expand_bounded_globstar("a/{**:0}/b"), ["a/b"]);
+        assert_eq!(expand_bounded_globstar("a/{**:1}"), ["a", "a/*"]);
+        assert_eq!(expand_bounded_globstar("a/{**:1}/**"), ["a/**", "a/*/**"]);
+        assert_eq!(
+            expand_bounded_globstar("{**:1}/a/{**:1}/b"),
+            ["a/b", "a/*/b", "*/a/b", "*/a/*/b"]
+        );
+        // Not a whole component, or not a valid depth.
+        assert_eq!(expand_bounded_globstar("a{**:1}/b"), ["a{**:1}/b"]);
+        assert_eq!(expand_bounded_globstar("\\{**:1}/b"), ["\\{**:1}/b"]);
+        assert_eq!(expand_bounded_globstar("{**:x}/b"), ["{**:x}/b"]);
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!(glob_to_regex("**").unwrap(), "^.*$");
+        assert_eq!(glob_to_regex("/a/b").unwrap(), "^a/b$");
+        assert_eq!(glob_to_regex("a/**/b").unwrap(), "^a/(?:.*/)?b$");
+        assert_eq!(glob_to_regex("a**b").unwrap(), "^a[^/]*b$");
+        assert_eq!(glob_to_regex("a?[!b-d]").unwrap(), "^a[^/][^b-d]$");
+        assert_eq!(glob_to_regex("[]a-]").unwrap(), "^[\\]a\\-]$");
+        assert_eq!(glob_to_regex("\\*{a}").unwrap(), "^\\*\\{a\\}$");
+        assert!(glob_to_regex("a\\").is_err());
+        assert!(glob_to_regex("[a").is_err());
+        assert!(glob_to_regex("[z-a]").is_err());
+    }
+
+    #[test]
+    fn test_glob_to_regex_matches_tree_matcher() {
+        let globs = [
+            "**",
+            "a",
+            "a/*",
+            "a/**",
+            "**/b",
+            "**/*.c",
+            "a/**/c",
+            "a/**/*.c",
+            "*/b",
+            "a*",
+            "a**",
+            "a/b*/**",
+            "a?/b",
+            "[ab]/[!c]",
+            "[a-c]*/**",
+            "a/\\*",
+            "a/{b,c}",
+        ];
+        let paths = [
+            "a",
+            "b",
+            "a/b",
+            "a/c",
+            "a/b/c",
+            "a/b/c.c",
+            "a/x/y/c",
+            "a/c.c",
+            "ab/b",
+            "ab",
+            "ab/c/d",
+            "x/a/b",
+            "x.c",
+            "x/y/z.c",
+            "b/b",
+            "b/c",
+            "c/b",
+            "a/*",
+            "a/{b,c}",
+            "a/b/c/d.c",
+        ];
+        for glob in globs {
+            let matcher = TreeMatcher::from_rules([glob].iter(), true).unwrap();
+            let re = glob_to_regex(glob).unwrap();
+            let regex = regex_automata::meta::Regex::new(&re).unwrap();
+            for path in paths {
+                assert_eq!(
+                    regex.is_match(path),
+                    matcher.matches(path),
+                    "glob {:?} (regex {:?}) on path {:?}",
+                    glob,
+                    re,
+                    path
+                );
+            }
+        }
+    }
 }
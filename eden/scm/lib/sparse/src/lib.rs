@@ -25,6 +25,7 @@ use pathmatcher::TreeMatcher;
 use pathmatcher::UnionMatcher;
 use regex::Regex;
 use types::RepoPath;
+use types::RepoPathBuf;
 
 #[derive(Default, Debug)]
 pub struct Profile {
@@ -53,7 +54,7 @@ enum Pattern {
     Exclude(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum ProfileEntry {
     // Pattern plus additional source for this rule (e.g. "hgrc.dynamic").
     Pattern(Pattern, Option<String>),
@@ -214,8 +215,14 @@ impl Root {
     }
 }
 
+impl From<Profile> for Root {
+    fn from(profile: Profile) -> Self {
+        Self(profile)
+    }
+}
+
 impl Profile {
-    fn from_bytes(data: impl AsRef<[u8]>, source: String) -> Result<Self, io::Error> {
+    pub fn from_bytes(data: impl AsRef<[u8]>, source: String) -> Result<Self, io::Error> {
         let mut prof = Profile {
             // Case insensitive matcher support breaks w/ too many rules, so
             // leave it disabled for now. This may need to be fixed if sparse
@@ -318,6 +325,34 @@ impl Profile {
         Ok(prof)
     }
 
+    /// Merge profiles as if their contents were concatenated: the rules of
+    /// all of them are combined in order, so the excludes of any of them
+    /// apply to the includes of all of them, and their %include statements
+    /// are kept. The version is kept if all the profiles have the same one.
+    /// Other metadata is not merged.
+    pub fn merge(profiles: &[Profile]) -> Profile {
+        let version = match profiles.split_first() {
+            Some((first, rest)) if rest.iter().all(|prof| prof.version == first.version) => {
+                first.version.clone()
+            }
+            _ => None,
+        };
+        Profile {
+            source: profiles
+                .iter()
+                .map(|prof| prof.source.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            entries: profiles
+                .iter()
+                .flat_map(|prof| prof.entries.iter().cloned())
+                .collect(),
+            version,
+            case_sensitive: profiles.iter().all(|prof| prof.case_sensitive),
+            ..Default::default()
+        }
+    }
+
     fn is_v2(&self) -> bool {
         if let Some(version) = &self.version {
             version == "2"
@@ -488,6 +523,85 @@ impl Matcher {
     }
 }
 
+/// Manifests larger than this are sampled by [estimate_file_count].
+const ESTIMATE_SAMPLING_THRESHOLD: usize = 1_000_000;
+
+/// Number of paths matched when sampling a large manifest.
+const ESTIMATE_SAMPLE_SIZE: usize = 10_000;
+
+/// How many files of a manifest a sparse profile keeps in the working copy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SparseEstimate {
+    pub total_files: u64,
+    pub sparse_files: u64,
+    /// Percentage of the files that are left out by the profile.
+    pub reduction_percent: f64,
+}
+
+/// Estimate how many of the files in `manifest_iter` are matched by
+/// `matcher`, typically built from a sparse profile with [Root::matcher].
+///
+/// The manifest is streamed. If its size hint is above one million paths,
+/// only 10,000 evenly spaced paths are matched and the number of sparse
+/// files is extrapolated from them.
+pub fn estimate_file_count(
+    matcher: &Matcher,
+    manifest_iter: impl Iterator<Item = RepoPathBuf>,
+) -> anyhow::Result<SparseEstimate> {
+    estimate_file_count_impl(
+        matcher,
+        manifest_iter,
+        ESTIMATE_SAMPLING_THRESHOLD,
+        ESTIMATE_SAMPLE_SIZE,
+    )
+}
+
+fn estimate_file_count_impl(
+    matcher: &Matcher,
+    manifest_iter: impl Iterator<Item = RepoPathBuf>,
+    sampling_threshold: usize,
+    sample_size: usize,
+) -> anyhow::Result<SparseEstimate> {
+    // Manifests are sorted by path, so matching every `stride`-th path
+    // samples all the directories.
+    let (size_hint, _) = manifest_iter.size_hint();
+    let stride = if size_hint > sampling_threshold {
+        (size_hint / sample_size).max(1)
+    } else {
+        1
+    };
+
+    let mut total_files: u64 = 0;
+    let mut sampled_files: u64 = 0;
+    let mut matched_files: u64 = 0;
+    for (i, path) in manifest_iter.enumerate() {
+        total_files += 1;
+        if i % stride == 0 {
+            sampled_files += 1;
+            if matcher.matches(&path)? {
+                matched_files += 1;
+            }
+        }
+    }
+
+    let sparse_files = if sampled_files == total_files {
+        matched_files
+    } else {
+        (matched_files as f64 * total_files as f64 / sampled_files as f64).round() as u64
+    };
+    let reduction_percent = if total_files == 0 {
+        0.0
+    } else {
+        100.0 * (total_files - sparse_files) as f64 / total_files as f64
+    };
+
+    Ok(SparseEstimate {
+        total_files,
+        sparse_files,
+        reduction_percent,
+    })
+}
+
 // Convert a sparse profile pattern into what the tree matcher
 // expects. We only support "glob" and "path" pattern types.
 fn sparse_pat_to_matcher_rule(pat: &Pattern) -> Result<Vec<String>, Error> {
@@ -919,6 +1033,81 @@ version = 2
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_merge() -> anyhow::Result<()> {
+        let first = b"
+[include]
+a
+[exclude]
+a/skip
+[metadata]
+title = first
+";
+
+        let second = b"
+%include nested
+
+[include]
+b
+";
+
+        let nested = b"
+[include]
+c
+[exclude]
+glob:**/*.log
+";
+
+        let merged = Profile::merge(&[
+            Profile::from_bytes(first, "first".to_string())?,
+            Profile::from_bytes(second, "second".to_string())?,
+        ]);
+
+        assert_eq!(merged.source, "first, second");
+        assert_eq!(merged.title, None);
+        assert_eq!(merged.version, None);
+        let (inc, exc, profs) = split_prof(&merged);
+        assert_eq!(inc, vec!["a", "b"]);
+        assert_eq!(exc, vec!["a/skip"]);
+        assert_eq!(profs, vec!["nested"]);
+
+        let matcher = Root::from(merged)
+            .matcher(|path| async move {
+                match path.as_ref() {
+                    "nested" => Ok(Some(nested.to_vec())),
+                    _ => unreachable!(),
+                }
+            })
+            .await?;
+
+        assert!(matcher.matches("a/file".try_into()?)?);
+        assert!(matcher.matches("b/file".try_into()?)?);
+        assert!(matcher.matches("c/file".try_into()?)?);
+        assert!(!matcher.matches("d/file".try_into()?)?);
+
+        // Excludes apply to the includes of every merged profile.
+        assert!(!matcher.matches("a/skip/file".try_into()?)?);
+        assert!(!matcher.matches("a/file.log".try_into()?)?);
+        assert!(!matcher.matches("b/file.log".try_into()?)?);
+
+        let v2 = b"
+[metadata]
+version = 2
+";
+        let merged = Profile::merge(&[
+            Profile::from_bytes(v2, "a".to_string())?,
+            Profile::from_bytes(v2, "b".to_string())?,
+        ]);
+        assert!(merged.is_v2());
+        let merged = Profile::merge(&[
+            Profile::from_bytes(v2, "a".to_string())?,
+            Profile::from_bytes(first, "first".to_string())?,
+        ]);
+        assert_eq!(merged.version, None);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_matcher_missing_include() -> anyhow::Result<()> {
         let config = b"
@@ -1084,4 +1273,49 @@ four
             (true, "base".to_string())
         );
     }
+
+    // 1000 files sorted by path, 200 of which are in src.
+    fn synthetic_manifest() -> Vec<RepoPathBuf> {
+        let mut paths: Vec<RepoPathBuf> = (0..1000)
+            .map(|i| {
+                let dir = match i % 5 {
+                    0 => "src",
+                    1 | 2 => "docs",
+                    _ => "tests",
+                };
+                RepoPathBuf::from_string(format!("{}/file{:04}", dir, i)).unwrap()
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn test_estimate_file_count() -> anyhow::Result<()> {
+        let prof = Root::from_bytes(b"src/*", "test".to_string()).unwrap();
+        let matcher = prof.matcher(|_| async { Ok(Some(vec![])) }).await?;
+
+        let estimate = estimate_file_count(&matcher, synthetic_manifest().into_iter())?;
+        assert_eq!(
+            estimate,
+            SparseEstimate {
+                total_files: 1000,
+                sparse_files: 200,
+                reduction_percent: 80.0,
+            }
+        );
+
+        // Sample 100 of the files.
+        let estimate =
+            estimate_file_count_impl(&matcher, synthetic_manifest().into_iter(), 500, 100)?;
+        assert_eq!(estimate.total_files, 1000);
+        assert!((190..=210).contains(&estimate.sparse_files));
+        assert!((estimate.reduction_percent - 80.0).abs() <= 1.0);
+
+        let estimate = estimate_file_count(&matcher, std::iter::empty())?;
+        assert_eq!(estimate.total_files, 0);
+        assert_eq!(estimate.reduction_percent, 0.0);
+
+        Ok(())
+    }
 }
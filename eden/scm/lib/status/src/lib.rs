@@ -22,6 +22,9 @@ pub struct Status {
 
     // Invalid/unsupported file types.
     invalid_type: Vec<RepoPathBuf>,
+
+    // Copied files, mapped to where they were copied from.
+    copies: HashMap<RepoPathBuf, RepoPathBuf>,
 }
 
 pub struct StatusBuilder(Status);
@@ -80,6 +83,11 @@ impl StatusBuilder {
         self
     }
 
+    pub fn copies(mut self, copies: HashMap<RepoPathBuf, RepoPathBuf>) -> Self {
+        self.0.copies = copies;
+        self
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&RepoPath, FileStatus)> {
         self.0.iter()
     }
@@ -140,6 +148,16 @@ impl Status {
         self.all.get(file).copied()
     }
 
+    /// Copied files, with the file each was copied from.
+    pub fn copies(&self) -> impl Iterator<Item = (&RepoPathBuf, &RepoPathBuf)> {
+        self.copies.iter()
+    }
+
+    /// The file `file` was copied from, if it is a copy.
+    pub fn copy_source(&self, file: &RepoPath) -> Option<&RepoPathBuf> {
+        self.copies.get(file)
+    }
+
     fn filter_status(&self, status: FileStatus) -> impl Iterator<Item = &RepoPathBuf> {
         self.all
             .iter()
@@ -5,10 +5,48 @@
  * GNU General Public License version 2.
  */
 
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
 use criterion::Criterion;
 use sha1::Digest;
 use sha1::Sha1;
 use types::HgId;
+use types::RepoPathBuf;
+use types::RepoPathInterner;
+
+/// Keeps track of the number of bytes allocated, to compare the memory used
+/// by paths with and without interning.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of bytes allocated for the value returned by `f`.
+fn heap_size<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = f();
+    let size = ALLOCATED.load(Ordering::Relaxed) - before;
+    drop(value);
+    size
+}
 
 fn hgid_from_hex(hash: &[u8]) -> [u8; HgId::len()] {
     HgId::from_hex(hash).unwrap().into_byte_array()
@@ -65,9 +103,48 @@ fn make_hashes() -> Vec<String> {
     hashes
 }
 
+/// 1,000,000 paths, 80% of them under `src/`, as loaded from several versions
+/// of a manifest: each distinct path is seen 5 times.
+fn make_paths() -> Vec<RepoPathBuf> {
+    (0..1_000_000)
+        .map(|i| {
+            let i = i % 200_000;
+            let path = if i % 5 == 0 {
+                format!("other/dir{}/file{}", i % 100, i)
+            } else {
+                format!("src/dir{}/file{}", i % 1000, i)
+            };
+            RepoPathBuf::from_string(path).unwrap()
+        })
+        .collect()
+}
+
 fn main() {
     let mut criterion = Criterion::default();
 
+    let paths = make_paths();
+    let plain_size = heap_size(|| paths.clone());
+    let interned_size = heap_size(|| {
+        let interner = RepoPathInterner::new();
+        let interned: Vec<_> = paths.iter().map(|p| interner.intern(p.clone())).collect();
+        (interner, interned)
+    });
+    println!(
+        "heap size of {} paths: {} bytes as RepoPathBuf, {} bytes interned",
+        paths.len(),
+        plain_size,
+        interned_size
+    );
+
+    criterion.bench_function("RepoPathInterner::intern", |b| {
+        b.iter(|| {
+            let interner = RepoPathInterner::new();
+            for path in paths.iter() {
+                criterion::black_box(interner.intern(path.clone()));
+            }
+        })
+    });
+
     criterion.bench_function("HgId::from_hex", |b| {
         let hashes = make_hashes();
         b.iter(|| {
@@ -7,6 +7,7 @@
 
 #[cfg(any(test, feature = "for-tests"))]
 use std::collections::HashSet;
+use std::fmt;
 use std::io;
 use std::io::Read;
 use std::io::Write;
@@ -72,6 +73,9 @@ pub const NULL_ID: HgId = HgId::from_byte_array([0; HgId::len()]);
 /// The hard-coded 'working copy parent' Mercurial id.
 pub const WDIR_ID: HgId = HgId::from_byte_array([0xff; HgId::len()]);
 
+/// Number of hex digits shown by [`HgId::short_default`].
+pub const DEFAULT_SHORT_HEX_LEN: usize = 12;
+
 impl HgId {
     pub fn null_id() -> &'static Self {
         &NULL_ID
@@ -89,6 +93,29 @@ impl HgId {
         self == &WDIR_ID
     }
 
+    /// Abbreviated form of the id, displayed as its first `len` hex digits
+    /// (or all of them if `len` is larger).
+    pub fn short(&self, len: usize) -> HgIdShort {
+        HgIdShort {
+            id: *self,
+            len: len.min(HgId::hex_len()),
+        }
+    }
+
+    /// Abbreviated form of the id, with [`DEFAULT_SHORT_HEX_LEN`] hex digits.
+    pub fn short_default(&self) -> HgIdShort {
+        self.short(DEFAULT_SHORT_HEX_LEN)
+    }
+
+    /// Whether exactly one of `known_ids` starts with the hex `prefix`.
+    pub fn is_unambiguous_prefix(prefix: &str, known_ids: &[HgId]) -> bool {
+        let prefix = prefix.to_ascii_lowercase();
+        let mut matches = known_ids
+            .iter()
+            .filter(|id| id.to_hex().starts_with(&prefix));
+        matches.next().is_some() && matches.next().is_none()
+    }
+
     pub fn from_content(data: &[u8], parents: Parents) -> Self {
         // Parents must be hashed in sorted order.
         let (p1, p2) = match parents.into_nodes() {
@@ -138,6 +165,19 @@ impl<'a> From<&'a [u8; HgId::len()]> for HgId {
     }
 }
 
+/// An abbreviated [`HgId`], as returned by [`HgId::short`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HgIdShort {
+    id: HgId,
+    len: usize,
+}
+
+impl fmt::Display for HgIdShort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.id.to_hex()[..self.len])
+    }
+}
+
 pub trait WriteHgIdExt {
     /// Write a ``HgId`` directly to a stream.
     ///
@@ -266,6 +306,27 @@ mod tests {
         assert_eq!(decode::<Hex>(&cbor_hex).unwrap().0, id);
     }
 
+    #[test]
+    fn test_short() {
+        let id = HgId::from_hex(b"0123456789abcdef0123456789abcdef01234567").unwrap();
+        assert_eq!(id.short_default().to_string(), &id.to_hex()[..12]);
+        assert_eq!(id.short(8).to_string(), "01234567");
+        assert_eq!(id.short(0).to_string(), "");
+        assert_eq!(id.short(100).to_string(), id.to_hex());
+    }
+
+    #[test]
+    fn test_is_unambiguous_prefix() {
+        let a = HgId::from_hex(b"abcdef0000000000000000000000000000000000").unwrap();
+        let b = HgId::from_hex(b"abcd120000000000000000000000000000000000").unwrap();
+        let ids = [a, b];
+        assert!(!HgId::is_unambiguous_prefix("abcd", &ids));
+        assert!(HgId::is_unambiguous_prefix("abcde", &ids));
+        assert!(HgId::is_unambiguous_prefix("ABCD1", &ids));
+        assert!(!HgId::is_unambiguous_prefix("abce", &ids));
+        assert!(HgId::is_unambiguous_prefix(&a.to_hex(), &ids));
+    }
+
     quickcheck! {
         fn test_from_slice(hgid: HgId) -> bool {
             hgid == HgId::from_slice(hgid.as_ref()).expect("from_slice")
@@ -16,6 +16,7 @@ pub mod node;
 pub mod nodeinfo;
 pub mod parents;
 pub mod path;
+pub mod path_interner;
 pub mod repo;
 pub mod serde_with;
 pub mod sha;
@@ -29,6 +30,8 @@ pub use crate::path::PathComponent;
 pub use crate::path::PathComponentBuf;
 pub use crate::path::RepoPath;
 pub use crate::path::RepoPathBuf;
+pub use crate::path_interner::InternedPath;
+pub use crate::path_interner::RepoPathInterner;
 pub use crate::sha::Sha256;
 
 pub type Id20 = HgId;
@@ -304,7 +304,7 @@ impl RepoPath {
     }
 
     #[ref_cast_custom]
-    fn from_str_unchecked(s: &str) -> &RepoPath;
+    pub(crate) fn from_str_unchecked(s: &str) -> &RepoPath;
 
     /// Returns the underlying bytes of the `RepoPath`.
     pub fn as_byte_slice(&self) -> &[u8] {
@@ -1177,6 +1177,23 @@ mod tests {
         assert_eq!(RepoPath::empty().ancestors().next(), None)
     }
 
+    #[test]
+    fn test_repo_path_buf_components() {
+        let path = RepoPathBuf::from_string("a/b/c/d.txt".to_string()).unwrap();
+        let components: Vec<&str> = path.components().map(PathComponent::as_str).collect();
+        assert_eq!(components, ["a", "b", "c", "d.txt"]);
+        assert_eq!(
+            path.last_component().map(PathComponent::as_str),
+            Some("d.txt")
+        );
+        assert_eq!(
+            path.parent().map(RepoPath::to_owned),
+            Some(repo_path_buf("a/b/c"))
+        );
+        let ancestors: Vec<&str> = path.ancestors().map(RepoPath::as_str).collect();
+        assert_eq!(ancestors, ["a/b/c", "a/b", "a", ""]);
+    }
+
     quickcheck! {
        fn test_parents_equal_components(path: RepoPathBuf) -> bool {
            path.deref().parents().count() == path.deref().components().count()
@@ -0,0 +1,176 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Interning of repository paths.
+//!
+//! Manifests and working copy states often hold the same paths many times.
+//! Interning them keeps a single allocation per distinct path, and makes
+//! comparing interned paths a pointer comparison.
+
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::ops::Deref;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+
+use crate::path::RepoPath;
+use crate::path::RepoPathBuf;
+
+/// A pool of distinct paths, safe to share between threads.
+#[derive(Default)]
+pub struct RepoPathInterner {
+    paths: DashMap<Arc<str>, ()>,
+    total_interned: AtomicUsize,
+}
+
+/// A path interned by a [`RepoPathInterner`].
+///
+/// Equality and hashing use the address of the path rather than its
+/// contents, so interned paths should only be compared with paths interned
+/// by the same interner.
+#[derive(Clone)]
+pub struct InternedPath(Arc<str>);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InternerStats {
+    /// Number of distinct paths in the interner.
+    pub unique_paths: usize,
+    /// Number of calls to [`RepoPathInterner::intern`].
+    pub total_interned: usize,
+}
+
+impl RepoPathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the interned copy of `path`, adding it to the interner if this
+    /// is the first time it is seen.
+    pub fn intern(&self, path: RepoPathBuf) -> InternedPath {
+        self.total_interned.fetch_add(1, Ordering::Relaxed);
+        if let Some(entry) = self.paths.get(path.as_str()) {
+            return InternedPath(entry.key().clone());
+        }
+        let path: Arc<str> = Arc::from(path.into_string());
+        InternedPath(self.paths.entry(path).or_insert(()).key().clone())
+    }
+
+    pub fn stats(&self) -> InternerStats {
+        InternerStats {
+            unique_paths: self.paths.len(),
+            total_interned: self.total_interned.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl InternedPath {
+    pub fn as_repo_path(&self) -> &RepoPath {
+        // Only valid paths are interned.
+        RepoPath::from_str_unchecked(&self.0)
+    }
+}
+
+impl Deref for InternedPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<RepoPath> for InternedPath {
+    fn as_ref(&self) -> &RepoPath {
+        self.as_repo_path()
+    }
+}
+
+impl PartialEq for InternedPath {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for InternedPath {}
+
+impl Hash for InternedPath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const u8).hash(state);
+    }
+}
+
+impl fmt::Debug for InternedPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for InternedPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::testutil::repo_path_buf;
+
+    #[test]
+    fn test_intern() {
+        let interner = RepoPathInterner::new();
+        let a1 = interner.intern(repo_path_buf("src/a.rs"));
+        let a2 = interner.intern(repo_path_buf("src/a.rs"));
+        let b = interner.intern(repo_path_buf("src/b.rs"));
+
+        assert_eq!(a1, a2);
+        assert_ne!(a1, b);
+        assert_eq!(&*a1, "src/a.rs");
+        assert_eq!(a1.as_repo_path(), repo_path_buf("src/a.rs").as_repo_path());
+        assert_eq!(HashSet::from([a1, a2, b]).len(), 2);
+        assert_eq!(
+            interner.stats(),
+            InternerStats {
+                unique_paths: 2,
+                total_interned: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_intern_from_threads() {
+        let interner = RepoPathInterner::new();
+        let paths: Vec<Vec<InternedPath>> = std::thread::scope(|s| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        (0..100)
+                            .map(|i| interner.intern(repo_path_buf(&format!("d/f{}", i))))
+                            .collect()
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+
+        for other in &paths[1..] {
+            assert_eq!(&paths[0], other);
+        }
+        assert_eq!(
+            interner.stats(),
+            InternerStats {
+                unique_paths: 100,
+                total_interned: 400,
+            }
+        );
+    }
+}
@@ -131,6 +131,36 @@ pub fn strip_unc_prefix(path: &Path) -> &Path {
     path.strip_prefix(r"\\?\").unwrap_or(path)
 }
 
+/// Converts an extended-length path on Windows to its usual form:
+/// `\\?\C:\foo` becomes `C:\foo`, and `\\?\UNC\server\share` becomes
+/// `\\server\share`. Other paths, and all paths on unices, are returned
+/// as is.
+pub fn strip_extended_length_prefix(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        use std::path::Prefix;
+
+        let mut components = path.components();
+        if let Some(Component::Prefix(prefix)) = components.next() {
+            let mut stripped = match prefix.kind() {
+                // `\\?\C:` is the root of the drive, not the drive-relative `C:`.
+                Prefix::VerbatimDisk(disk) => PathBuf::from(format!(r"{}:\", disk as char)),
+                Prefix::VerbatimUNC(server, share) => {
+                    let mut unc = std::ffi::OsString::from(r"\\");
+                    unc.push(server);
+                    unc.push(r"\");
+                    unc.push(share);
+                    PathBuf::from(unc)
+                }
+                _ => return Cow::Borrowed(path),
+            };
+            stripped.push(components.as_path());
+            return Cow::Owned(stripped);
+        }
+    }
+    Cow::Borrowed(path)
+}
+
 /// Return the absolute and normalized path without accessing the filesystem.
 ///
 /// Unlike [`fs::canonicalize`], do not follow symlinks.
@@ -556,6 +586,21 @@ mod tests {
             assert_eq!(normalize(r"a/b\c\..\.".as_ref()), Path::new(r"a\b"));
             assert_eq!(normalize("z:/a//b/./".as_ref()), Path::new(r"z:\a\b"));
         }
+
+        #[test]
+        fn test_strip_extended_length_prefix() {
+            let strip = |p: &str| strip_extended_length_prefix(p.as_ref()).into_owned();
+            assert_eq!(strip(r"\\?\C:\foo\bar"), Path::new(r"C:\foo\bar"));
+            assert_eq!(strip(r"\\?\C:\"), Path::new(r"C:\"));
+            assert_eq!(strip(r"\\?\C:"), Path::new(r"C:\"));
+            assert_eq!(
+                strip(r"\\?\UNC\server\share\foo"),
+                Path::new(r"\\server\share\foo")
+            );
+            assert_eq!(strip(r"C:\foo"), Path::new(r"C:\foo"));
+            assert_eq!(strip(r"\\server\share"), Path::new(r"\\server\share"));
+            assert_eq!(strip(r"foo\bar"), Path::new(r"foo\bar"));
+        }
     }
 
     #[cfg(unix)]
@@ -8,11 +8,15 @@
 mod async_vfs;
 mod pathauditor;
 mod vfs;
+mod watch;
 
 pub use util::lock::PathLock;
 
 pub use crate::async_vfs::AsyncVfsWriter;
 pub use crate::pathauditor::AuditError;
 pub use crate::pathauditor::PathAuditor;
+pub use crate::vfs::AtomicWriteOpts;
 pub use crate::vfs::UpdateFlag;
 pub use crate::vfs::VFS;
+pub use crate::watch::VfsEvent;
+pub use crate::watch::WatchHandle;
@@ -58,6 +58,26 @@ pub enum UpdateFlag {
     Executable,
 }
 
+/// Options for [`VFS::write_atomic_opts`].
+#[derive(Clone, Copy, Debug)]
+pub struct AtomicWriteOpts {
+    /// Flush the file and its directory to disk before returning, so the
+    /// new content survives a system crash. Defaults to true.
+    pub fsync: bool,
+    /// Permissions of the file on Unix, ignored on Windows. Defaults to
+    /// 0o666 with the umask applied.
+    pub mode: Option<u32>,
+}
+
+impl Default for AtomicWriteOpts {
+    fn default() -> Self {
+        Self {
+            fsync: true,
+            mode: None,
+        }
+    }
+}
+
 impl VFS {
     pub fn new(root: PathBuf) -> Result<Self> {
         let auditor = PathAuditor::new(&root);
@@ -284,6 +304,60 @@ impl VFS {
         }
     }
 
+    /// Replace the file at `path` with `content`, such that readers, even
+    /// after a crash, see either the previous content or the new one, never
+    /// a partially written file.
+    ///
+    /// The content is written to a temporary file next to `path`, fsynced,
+    /// and renamed over `path`.
+    pub fn write_atomic(&self, path: &RepoPath, content: &[u8]) -> Result<()> {
+        self.write_atomic_opts(path, content, AtomicWriteOpts::default())
+    }
+
+    /// Same as [`VFS::write_atomic`], with options.
+    pub fn write_atomic_opts(
+        &self,
+        path: &RepoPath,
+        content: &[u8],
+        opts: AtomicWriteOpts,
+    ) -> Result<()> {
+        self.write_atomic_with(path, opts, |f| f.write_all(content))
+    }
+
+    /// Atomically replace the file at `path` with what `op` writes to the
+    /// temporary file. If `op` fails, `path` is left untouched.
+    fn write_atomic_with(
+        &self,
+        path: &RepoPath,
+        opts: AtomicWriteOpts,
+        op: impl FnOnce(&mut File) -> io::Result<()>,
+    ) -> Result<()> {
+        let filepath = match self.inner.auditor.audit(path) {
+            Ok(filepath) if filepath.parent().is_some_and(Path::is_dir) && !filepath.is_dir() => {
+                filepath
+            }
+            _ => {
+                // Unlike `write`, conflicts are cleared before writing rather
+                // than after a failure, as clearing them removes the file.
+                self.clear_conflicts(path)
+                    .with_context(|| format!("Can't clear conflicts for {}", path))?;
+                self.inner
+                    .auditor
+                    .audit(path)
+                    .with_context(|| format!("Can't write into {}", path))?
+            }
+        };
+        #[cfg(unix)]
+        let mode = opts.mode.unwrap_or_else(|| util::file::apply_umask(0o666));
+        // The mode is ignored on Windows.
+        #[cfg(windows)]
+        let mode = opts.mode.unwrap_or(0o666);
+
+        atomicfile::atomic_write(&filepath, mode, opts.fsync, op)
+            .with_context(|| format!("Can't write to {:?}", filepath))?;
+        Ok(())
+    }
+
     pub fn set_executable(&self, path: &RepoPath, flag: bool) -> Result<()> {
         let filepath = self
             .inner
@@ -424,6 +498,22 @@ mod unix_tests {
         assert_eq!(0, metadata.permissions().mode() & 0o111)
     }
 
+    #[test]
+    fn test_write_atomic_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let vfs = VFS::new(tmp.path().to_path_buf()).unwrap();
+        let path = RepoPath::from_str("a").unwrap();
+        let opts = AtomicWriteOpts {
+            fsync: false,
+            mode: Some(0o600),
+        };
+        vfs.write_atomic_opts(path, b"abc", opts).unwrap();
+        let metadata = fs::symlink_metadata(vfs.join(path)).unwrap();
+        assert_eq!(0o600, metadata.permissions().mode() & 0o777);
+    }
+
     #[test]
     fn test_update_mode() {
         assert_eq!(0o644, VFS::update_mode(0o644, false));
@@ -512,6 +602,41 @@ fn metadata_eq(m1: &Metadata, m2: &Metadata) -> Result<bool> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_write_atomic() {
+        let tmp = tempfile::tempdir().unwrap();
+        let vfs = VFS::new(tmp.path().to_path_buf()).unwrap();
+        let path = RepoPath::from_str("a/b").unwrap();
+
+        vfs.write_atomic(path, b"old").unwrap();
+        assert_eq!(vfs.read(path).unwrap(), b"old");
+        vfs.write_atomic(path, b"new").unwrap();
+        assert_eq!(vfs.read(path).unwrap(), b"new");
+
+        // A file in the way of the parent directory is cleared.
+        let conflict = RepoPath::from_str("a/b/c").unwrap();
+        vfs.write_atomic(conflict, b"c").unwrap();
+        assert_eq!(vfs.read(conflict).unwrap(), b"c");
+    }
+
+    #[test]
+    fn test_write_atomic_interrupted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let vfs = VFS::new(tmp.path().to_path_buf()).unwrap();
+        let path = RepoPath::from_str("a").unwrap();
+        vfs.write_atomic(path, b"old content").unwrap();
+
+        // Stop writing half way through the new content.
+        let result = vfs.write_atomic_with(path, AtomicWriteOpts::default(), |f| {
+            f.write_all(b"new")?;
+            Err(io::Error::new(ErrorKind::Interrupted, "interrupted"))
+        });
+        assert!(result.is_err());
+        assert_eq!(vfs.read(path).unwrap(), b"old content");
+        // The temporary file is removed.
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
     #[test]
     fn test_detect_case_sensitive() {
         let tmp = tempfile::tempdir().unwrap();
@@ -0,0 +1,521 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Change notifications for files in a `VFS`.
+//!
+//! Linux uses inotify and macOS uses FSEvents. Other platforms poll the
+//! file system through the `notify` crate.
+
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::Result;
+use futures::channel::mpsc;
+use futures::Stream;
+use futures::StreamExt;
+use types::RepoPath;
+use types::RepoPathBuf;
+
+use crate::VFS;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VfsEvent {
+    Created(RepoPathBuf),
+    Modified(RepoPathBuf),
+    Deleted(RepoPathBuf),
+    Renamed { from: RepoPathBuf, to: RepoPathBuf },
+}
+
+/// Stream of the changes under a watched path. Watching stops when it is
+/// dropped.
+pub struct WatchHandle {
+    events: mpsc::UnboundedReceiver<VfsEvent>,
+    _watcher: imp::Watcher,
+}
+
+impl Stream for WatchHandle {
+    type Item = VfsEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<VfsEvent>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+impl VFS {
+    /// Watch `path` for changes. If `path` is a directory, changes to the
+    /// files directly in it are reported, but not the ones in its
+    /// subdirectories.
+    pub fn watch(&self, path: &RepoPath) -> Result<impl Stream<Item = VfsEvent>> {
+        self.watch_impl(path, false)
+    }
+
+    /// Watch the directory `root` and everything under it for changes.
+    pub fn watch_tree(&self, root: &RepoPath) -> Result<WatchHandle> {
+        self.watch_impl(root, true)
+    }
+
+    fn watch_impl(&self, path: &RepoPath, recursive: bool) -> Result<WatchHandle> {
+        let (sender, events) = mpsc::unbounded();
+        let sender = EventSender::new(self.root(), sender);
+        let watcher = imp::Watcher::new(&self.join(path), recursive, sender)?;
+        Ok(WatchHandle {
+            events,
+            _watcher: watcher,
+        })
+    }
+}
+
+/// Turns the paths reported by the platform into `RepoPathBuf`s and sends
+/// the events to a `WatchHandle`.
+struct EventSender {
+    root: PathBuf,
+    /// The root with symlinks resolved, as FSEvents reports paths.
+    canonical_root: Option<PathBuf>,
+    sender: mpsc::UnboundedSender<VfsEvent>,
+}
+
+impl EventSender {
+    fn new(root: &Path, sender: mpsc::UnboundedSender<VfsEvent>) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            canonical_root: root.canonicalize().ok(),
+            sender,
+        }
+    }
+
+    /// The path relative to the root, or None for paths outside of it, or
+    /// that aren't valid repo paths.
+    fn repo_path(&self, path: &Path) -> Option<RepoPathBuf> {
+        let relative = path
+            .strip_prefix(&self.root)
+            .ok()
+            .or_else(|| path.strip_prefix(self.canonical_root.as_ref()?).ok())?;
+        let mut components = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => components.push(name.to_str()?),
+                _ => return None,
+            }
+        }
+        if components.is_empty() {
+            return None;
+        }
+        RepoPathBuf::from_string(components.join("/")).ok()
+    }
+
+    fn created(&self, path: &Path) -> bool {
+        self.send_with(path, VfsEvent::Created)
+    }
+
+    fn modified(&self, path: &Path) -> bool {
+        self.send_with(path, VfsEvent::Modified)
+    }
+
+    fn deleted(&self, path: &Path) -> bool {
+        self.send_with(path, VfsEvent::Deleted)
+    }
+
+    fn renamed(&self, from: &Path, to: &Path) -> bool {
+        match (self.repo_path(from), self.repo_path(to)) {
+            (Some(from), Some(to)) => self.send(VfsEvent::Renamed { from, to }),
+            // Moved in or out of the root.
+            (Some(_), None) => self.deleted(from),
+            (None, Some(_)) => self.created(to),
+            (None, None) => self.is_open(),
+        }
+    }
+
+    fn send_with(&self, path: &Path, event: impl FnOnce(RepoPathBuf) -> VfsEvent) -> bool {
+        match self.repo_path(path) {
+            Some(path) => self.send(event(path)),
+            None => self.is_open(),
+        }
+    }
+
+    /// Returns false once the `WatchHandle` is dropped.
+    fn send(&self, event: VfsEvent) -> bool {
+        self.sender.unbounded_send(event).is_ok()
+    }
+
+    fn is_open(&self) -> bool {
+        !self.sender.is_closed()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use anyhow::Result;
+    use inotify::EventMask;
+    use inotify::Inotify;
+    use inotify::WatchDescriptor;
+    use inotify::WatchMask;
+    use inotify::Watches;
+
+    use super::EventSender;
+
+    type WatchedDirs = Arc<Mutex<HashMap<WatchDescriptor, PathBuf>>>;
+
+    pub(super) struct Watcher {
+        watches: Watches,
+        watched: WatchedDirs,
+    }
+
+    impl Watcher {
+        pub(super) fn new(path: &Path, recursive: bool, sender: EventSender) -> Result<Self> {
+            let mut inotify = Inotify::init()?;
+            let mut state = State {
+                watches: inotify.watches(),
+                watched: Default::default(),
+                root: path.to_path_buf(),
+                recursive,
+                sender,
+            };
+            state.add_watch(path)?;
+            if recursive && path.is_dir() {
+                state.add_subdirs(path, false);
+            }
+            let watcher = Watcher {
+                watches: inotify.watches(),
+                watched: state.watched.clone(),
+            };
+            thread::spawn(move || {
+                let mut buffer = [0; 4096];
+                while let Ok(events) = inotify.read_events_blocking(&mut buffer) {
+                    let events: Vec<_> = events
+                        .map(|e| (e.wd, e.mask, e.cookie, e.name.map(|n| n.to_owned())))
+                        .collect();
+                    if !state.handle_events(events) {
+                        break;
+                    }
+                }
+            });
+            Ok(watcher)
+        }
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            // Removing the watches queues IN_IGNORED events, which wakes up the
+            // thread so that it notices that the `WatchHandle` is gone.
+            let watched = std::mem::take(&mut *self.watched.lock().unwrap());
+            for wd in watched.into_keys() {
+                let _ = self.watches.remove(wd);
+            }
+        }
+    }
+
+    struct State {
+        watches: Watches,
+        watched: WatchedDirs,
+        root: PathBuf,
+        recursive: bool,
+        sender: EventSender,
+    }
+
+    impl State {
+        fn add_watch(&mut self, path: &Path) -> Result<()> {
+            let mask = WatchMask::CREATE
+                | WatchMask::MODIFY
+                | WatchMask::DELETE
+                | WatchMask::DELETE_SELF
+                | WatchMask::MOVED_FROM
+                | WatchMask::MOVED_TO
+                | WatchMask::DONT_FOLLOW
+                | WatchMask::EXCL_UNLINK;
+            let wd = self.watches.add(path, mask)?;
+            self.watched.lock().unwrap().insert(wd, path.to_path_buf());
+            Ok(())
+        }
+
+        /// Watch the directories under `dir`. If `report` is set, the files
+        /// found are reported as created, as `dir` was created before it was
+        /// watched.
+        fn add_subdirs(&mut self, dir: &Path, report: bool) -> bool {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                // Already removed.
+                Err(_) => return true,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if report && !self.sender.created(&path) {
+                    return false;
+                }
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    if self.add_watch(&path).is_err() {
+                        continue;
+                    }
+                    if !self.add_subdirs(&path, report) {
+                        return false;
+                    }
+                }
+            }
+            true
+        }
+
+        /// Returns false once the `WatchHandle` is dropped.
+        fn handle_events(
+            &mut self,
+            events: Vec<(WatchDescriptor, EventMask, u32, Option<std::ffi::OsString>)>,
+        ) -> bool {
+            // Renames are reported as a MOVED_FROM event followed by a MOVED_TO
+            // event with the same cookie.
+            let mut moved_from: Option<(u32, PathBuf, bool)> = None;
+            for (wd, mask, cookie, name) in events {
+                if mask.contains(EventMask::Q_OVERFLOW) {
+                    tracing::warn!("inotify queue overflowed, some changes were not reported");
+                    continue;
+                }
+                let dir = match self.watched.lock().unwrap().get(&wd) {
+                    Some(dir) => dir.clone(),
+                    None => continue,
+                };
+                if mask.contains(EventMask::IGNORED) {
+                    self.watched.lock().unwrap().remove(&wd);
+                    continue;
+                }
+                let path = match name {
+                    Some(name) => dir.join(name),
+                    // Events about the watched path itself. Changes to
+                    // subdirectories are reported by their parents.
+                    None if dir == self.root => dir,
+                    None => continue,
+                };
+                let is_dir = mask.contains(EventMask::ISDIR);
+
+                if let Some((from_cookie, from, from_is_dir)) = moved_from.take() {
+                    if mask.contains(EventMask::MOVED_TO) && cookie == from_cookie {
+                        if !self.sender.renamed(&from, &path) {
+                            return false;
+                        }
+                        if is_dir && self.recursive {
+                            self.rewatch_moved(&from, &path);
+                        }
+                        continue;
+                    }
+                    if !self.moved_out(&from, from_is_dir) {
+                        return false;
+                    }
+                }
+
+                let open = if mask.contains(EventMask::MOVED_FROM) {
+                    moved_from = Some((cookie, path, is_dir));
+                    true
+                } else if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                    let mut open = self.sender.created(&path);
+                    if open && is_dir && self.recursive && self.add_watch(&path).is_ok() {
+                        open = self.add_subdirs(&path, true);
+                    }
+                    open
+                } else if mask.contains(EventMask::MODIFY) {
+                    self.sender.modified(&path)
+                } else if mask.intersects(EventMask::DELETE | EventMask::DELETE_SELF) {
+                    self.sender.deleted(&path)
+                } else {
+                    self.sender.is_open()
+                };
+                if !open {
+                    return false;
+                }
+            }
+            // Moved out of the watched directories.
+            match moved_from {
+                Some((_, from, is_dir)) => self.moved_out(&from, is_dir),
+                None => self.sender.is_open(),
+            }
+        }
+
+        /// Report `path` as deleted, and stop watching it if it's a
+        /// directory, as inotify keeps following it wherever it was moved.
+        fn moved_out(&mut self, path: &Path, is_dir: bool) -> bool {
+            if is_dir && self.recursive {
+                let mut watched = self.watched.lock().unwrap();
+                let moved: Vec<WatchDescriptor> = watched
+                    .iter()
+                    .filter(|(_, dir)| dir.starts_with(path))
+                    .map(|(wd, _)| wd.clone())
+                    .collect();
+                for wd in moved {
+                    watched.remove(&wd);
+                    let _ = self.watches.remove(wd);
+                }
+            }
+            self.sender.deleted(path)
+        }
+
+        /// Update the paths of the watched directories under a renamed one.
+        fn rewatch_moved(&mut self, from: &Path, to: &Path) {
+            for dir in self.watched.lock().unwrap().values_mut() {
+                if let Ok(relative) = dir.strip_prefix(from) {
+                    *dir = to.join(relative);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use anyhow::Result;
+    use fsevent::FsEvent;
+    use fsevent::StreamFlags;
+
+    use super::EventSender;
+
+    pub(super) struct Watcher {
+        fsevent: FsEvent,
+    }
+
+    impl Watcher {
+        pub(super) fn new(path: &Path, recursive: bool, sender: EventSender) -> Result<Self> {
+            let root = path.canonicalize()?;
+            let mut fsevent = FsEvent::new(vec![root.to_string_lossy().into_owned()]);
+            let (tx, rx) = mpsc::channel::<fsevent::Event>();
+            fsevent.observe_async(tx)?;
+            thread::spawn(move || {
+                // FSEvents reports renames as two events for the old and the
+                // new paths, which are told apart by whether they exist.
+                let mut moved_from: Option<PathBuf> = None;
+                for event in rx {
+                    let path = PathBuf::from(event.path);
+                    // FSEvents always watches recursively.
+                    if !recursive && path != root && path.parent() != Some(root.as_path()) {
+                        continue;
+                    }
+                    let flag = event.flag;
+                    let exists = path.symlink_metadata().is_ok();
+                    if let Some(from) = moved_from.take() {
+                        if flag.contains(StreamFlags::ITEM_RENAMED) && exists {
+                            if !sender.renamed(&from, &path) {
+                                break;
+                            }
+                            continue;
+                        }
+                        if !sender.deleted(&from) {
+                            break;
+                        }
+                    }
+                    // Flags of events close in time are combined, so the
+                    // current state of the file decides which one wins.
+                    let open = if !exists {
+                        if flag.contains(StreamFlags::ITEM_RENAMED) {
+                            moved_from = Some(path);
+                            true
+                        } else if flag.contains(StreamFlags::ITEM_REMOVED) {
+                            sender.deleted(&path)
+                        } else {
+                            sender.is_open()
+                        }
+                    } else if flag.intersects(StreamFlags::ITEM_CREATED | StreamFlags::ITEM_RENAMED)
+                    {
+                        sender.created(&path)
+                    } else if flag.contains(StreamFlags::ITEM_MODIFIED) {
+                        sender.modified(&path)
+                    } else {
+                        sender.is_open()
+                    };
+                    if !open {
+                        break;
+                    }
+                }
+            });
+            Ok(Watcher { fsevent })
+        }
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            // Stops the run loop, which closes the channel and ends the thread.
+            self.fsevent.shutdown_observe();
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod imp {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use notify::event::ModifyKind;
+    use notify::event::RenameMode;
+    use notify::Config;
+    use notify::EventKind;
+    use notify::PollWatcher;
+    use notify::RecursiveMode;
+    use notify::Watcher as _;
+
+    use super::EventSender;
+
+    /// Short enough for changes to be reported within a second.
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub(super) struct Watcher {
+        _watcher: PollWatcher,
+    }
+
+    impl Watcher {
+        pub(super) fn new(path: &Path, recursive: bool, sender: EventSender) -> Result<Self> {
+            let handler = move |event: notify::Result<notify::Event>| {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => {
+                        tracing::warn!("cannot watch for changes: {}", err);
+                        return;
+                    }
+                };
+                match (event.kind, event.paths.as_slice()) {
+                    (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                        sender.renamed(from, to);
+                    }
+                    (EventKind::Create(_), paths) => {
+                        for path in paths {
+                            sender.created(path);
+                        }
+                    }
+                    (EventKind::Modify(_), paths) => {
+                        for path in paths {
+                            sender.modified(path);
+                        }
+                    }
+                    (EventKind::Remove(_), paths) => {
+                        for path in paths {
+                            sender.deleted(path);
+                        }
+                    }
+                    _ => {}
+                }
+            };
+            let mut watcher =
+                PollWatcher::new(handler, Config::default().with_poll_interval(POLL_INTERVAL))?;
+            let mode = if recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            watcher.watch(path, mode)?;
+            Ok(Watcher { _watcher: watcher })
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use futures::Stream;
+use futures::StreamExt;
+use types::RepoPath;
+use types::RepoPathBuf;
+use vfs::UpdateFlag;
+use vfs::VfsEvent;
+use vfs::VFS;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Wait for `expected`, and return the other events received before it,
+/// like the modifications reported while a file is being created.
+async fn expect_event(
+    events: &mut (impl Stream<Item = VfsEvent> + Unpin),
+    expected: VfsEvent,
+) -> Vec<VfsEvent> {
+    let mut skipped = Vec::new();
+    loop {
+        match tokio::time::timeout(TIMEOUT, events.next()).await {
+            Ok(Some(event)) if event == expected => return skipped,
+            Ok(Some(event)) => skipped.push(event),
+            Ok(None) => panic!("stream ended while waiting for {:?}", expected),
+            Err(_) => panic!(
+                "{:?} not received within {:?}, got {:?}",
+                expected, TIMEOUT, skipped
+            ),
+        }
+    }
+}
+
+/// The paths of `events`.
+fn paths(events: &[VfsEvent]) -> Vec<&RepoPath> {
+    let mut paths = Vec::new();
+    for event in events {
+        match event {
+            VfsEvent::Created(path) | VfsEvent::Modified(path) | VfsEvent::Deleted(path) => {
+                paths.push(path.as_repo_path())
+            }
+            VfsEvent::Renamed { from, to } => {
+                paths.push(from.as_repo_path());
+                paths.push(to.as_repo_path());
+            }
+        }
+    }
+    paths
+}
+
+fn repo_path_buf(path: &str) -> RepoPathBuf {
+    RepoPathBuf::from_string(path.to_string()).unwrap()
+}
+
+#[tokio::test]
+async fn test_watch_tree() {
+    let tmp = tempfile::tempdir().unwrap();
+    let vfs = VFS::new(tmp.path().to_path_buf()).unwrap();
+    let dir = RepoPath::from_str("dir").unwrap();
+    std::fs::create_dir(vfs.join(dir)).unwrap();
+    let mut events = vfs.watch_tree(dir).unwrap();
+
+    let path = RepoPath::from_str("dir/sub/a").unwrap();
+    vfs.write(path, b"1", UpdateFlag::Regular).unwrap();
+    expect_event(&mut events, VfsEvent::Created(repo_path_buf("dir/sub"))).await;
+    expect_event(&mut events, VfsEvent::Created(path.to_owned())).await;
+
+    vfs.write(path, b"22", UpdateFlag::Regular).unwrap();
+    expect_event(&mut events, VfsEvent::Modified(path.to_owned())).await;
+
+    // Polling can't tell renames from deletions and creations.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        let to = RepoPath::from_str("dir/sub/b").unwrap();
+        std::fs::rename(vfs.join(path), vfs.join(to)).unwrap();
+        expect_event(
+            &mut events,
+            VfsEvent::Renamed {
+                from: path.to_owned(),
+                to: to.to_owned(),
+            },
+        )
+        .await;
+        std::fs::rename(vfs.join(to), vfs.join(path)).unwrap();
+    }
+
+    // Not `vfs.remove`, which also removes the empty directories.
+    std::fs::remove_file(vfs.join(path)).unwrap();
+    expect_event(&mut events, VfsEvent::Deleted(path.to_owned())).await;
+
+    // Changes outside of the watched directory aren't reported.
+    let outside = RepoPath::from_str("outside").unwrap();
+    vfs.write(outside, b"1", UpdateFlag::Regular).unwrap();
+    let other = RepoPath::from_str("dir/other").unwrap();
+    vfs.write(other, b"1", UpdateFlag::Regular).unwrap();
+    let skipped = expect_event(&mut events, VfsEvent::Created(other.to_owned())).await;
+    assert!(!paths(&skipped).contains(&outside), "{:?}", skipped);
+}
+
+#[tokio::test]
+async fn test_watch() {
+    let tmp = tempfile::tempdir().unwrap();
+    let vfs = VFS::new(tmp.path().to_path_buf()).unwrap();
+    let dir = RepoPath::from_str("dir").unwrap();
+    std::fs::create_dir_all(vfs.join(dir).join("sub")).unwrap();
+    let mut events = Box::pin(vfs.watch(dir).unwrap());
+
+    // Only the files directly in the directory are watched.
+    let nested = RepoPath::from_str("dir/sub/a").unwrap();
+    vfs.write(nested, b"1", UpdateFlag::Regular).unwrap();
+    let path = RepoPath::from_str("dir/a").unwrap();
+    vfs.write(path, b"1", UpdateFlag::Regular).unwrap();
+    let skipped = expect_event(&mut events, VfsEvent::Created(path.to_owned())).await;
+    assert!(!paths(&skipped).contains(&nested), "{:?}", skipped);
+
+    vfs.remove(path).unwrap();
+    let skipped = expect_event(&mut events, VfsEvent::Deleted(path.to_owned())).await;
+    assert!(!paths(&skipped).contains(&nested), "{:?}", skipped);
+}
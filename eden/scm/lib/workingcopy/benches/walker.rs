@@ -0,0 +1,59 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fs;
+use std::path::Path;
+
+use minibench::bench;
+use minibench::elapsed;
+use pathmatcher::AlwaysMatcher;
+use workingcopy::walker::Walker;
+
+// 50 * 100 directories of 100 files.
+const TOP_DIRS: usize = 50;
+const SUB_DIRS: usize = 100;
+const FILES: usize = 100;
+
+fn create_tree(root: &Path) {
+    for i in 0..TOP_DIRS {
+        for j in 0..SUB_DIRS {
+            let dir = root.join(format!("d{}/s{}", i, j));
+            fs::create_dir_all(&dir).unwrap();
+            for k in 0..FILES {
+                fs::write(dir.join(format!("f{}", k)), b"").unwrap();
+            }
+        }
+    }
+}
+
+// Run with: cargo bench --bench walker
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    println!("file_cnt = {}", TOP_DIRS * SUB_DIRS * FILES);
+
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut thread_counts = vec![1, 8, available];
+    thread_counts.sort();
+    thread_counts.dedup();
+    for num_threads in thread_counts {
+        bench(format!("walk_{}_threads", num_threads), || {
+            elapsed(|| {
+                let walker = Walker::new(
+                    dir.path().to_path_buf(),
+                    ".hg".to_string(),
+                    Vec::new(),
+                    AlwaysMatcher::new(),
+                    false,
+                    num_threads,
+                )
+                .unwrap();
+                assert_eq!(walker.count(), TOP_DIRS * SUB_DIRS * FILES);
+            })
+        });
+    }
+}
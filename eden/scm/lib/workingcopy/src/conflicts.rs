@@ -0,0 +1,329 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Detection of unresolved conflict markers left in files by a merge.
+
+use std::thread;
+
+use anyhow::Result;
+use serde::Serialize;
+use types::RepoPathBuf;
+use vfs::VFS;
+
+/// Default length of conflict markers, as in `<<<<<<<`.
+pub const DEFAULT_MARKER_LENGTH: usize = 7;
+
+/// A file with unresolved conflict markers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ConflictingFile {
+    pub path: RepoPathBuf,
+    /// Number of conflict regions, counted by their `<<<<<<<` markers.
+    pub conflict_count: usize,
+    /// The file has a `<<<<<<<` marker, starting our side of a conflict.
+    pub has_our_marker: bool,
+    /// The file has a `>>>>>>>` marker, ending their side of a conflict.
+    pub has_their_marker: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ConflictDetectorOptions {
+    /// Number of threads reading files.
+    pub parallelism: usize,
+    /// Files larger than this are not scanned. They are most likely binary
+    /// files, which never get conflict markers.
+    pub max_file_size: usize,
+}
+
+impl Default for ConflictDetectorOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_file_size: 10 << 20,
+        }
+    }
+}
+
+/// Boyer-Moore-Horspool search for a byte string.
+struct Finder {
+    needle: Vec<u8>,
+    skip: [usize; 256],
+}
+
+impl Finder {
+    fn new(needle: Vec<u8>) -> Self {
+        let mut skip = [needle.len(); 256];
+        if let Some((_, init)) = needle.split_last() {
+            for (i, &b) in init.iter().enumerate() {
+                skip[b as usize] = needle.len() - 1 - i;
+            }
+        }
+        Self { needle, skip }
+    }
+
+    /// Offsets of all occurrences of the needle in `haystack`, including
+    /// overlapping ones.
+    fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let n = self.needle.len();
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            if n == 0 {
+                return None;
+            }
+            while pos + n <= haystack.len() {
+                let window = &haystack[pos..pos + n];
+                let last = window[n - 1];
+                let found = pos;
+                if window == self.needle.as_slice() {
+                    pos += 1;
+                    return Some(found);
+                }
+                pos += self.skip[last as usize];
+            }
+            None
+        })
+    }
+}
+
+/// A conflict marker: the marker character repeated `marker_length` times at
+/// the start of a line, followed by a space and a label.
+struct Marker {
+    finder: Finder,
+}
+
+impl Marker {
+    fn new(c: u8, marker_length: usize) -> Self {
+        Self {
+            finder: Finder::new(vec![c; marker_length]),
+        }
+    }
+
+    fn count(&self, content: &[u8]) -> usize {
+        let n = self.finder.needle.len();
+        self.finder
+            .find_iter(content)
+            .filter(|&start| {
+                let at_line_start = start == 0 || content[start - 1] == b'\n';
+                at_line_start && content.get(start + n) == Some(&b' ')
+            })
+            .count()
+    }
+}
+
+/// Scans file contents for conflict markers.
+///
+/// Separator lines (`=======`) are not looked for, as some text formats also
+/// use them to underline headings.
+pub struct ConflictScanner {
+    ours: Marker,
+    theirs: Marker,
+}
+
+impl ConflictScanner {
+    pub fn new(marker_length: usize) -> Self {
+        Self {
+            ours: Marker::new(b'<', marker_length),
+            theirs: Marker::new(b'>', marker_length),
+        }
+    }
+
+    /// Scan the contents of the file at `path`. Returns `None` if the file
+    /// has no conflict markers.
+    pub fn scan(&self, path: RepoPathBuf, content: &[u8]) -> Option<ConflictingFile> {
+        let ours = self.ours.count(content);
+        let theirs = self.theirs.count(content);
+        if ours == 0 && theirs == 0 {
+            return None;
+        }
+        Some(ConflictingFile {
+            path,
+            conflict_count: ours,
+            has_our_marker: ours > 0,
+            has_their_marker: theirs > 0,
+        })
+    }
+}
+
+/// Scan `paths` for conflict markers. Files that are missing, or larger than
+/// `options.max_file_size`, are skipped.
+///
+/// The result is sorted by path.
+pub fn detect_conflicts(
+    vfs: &VFS,
+    paths: Vec<RepoPathBuf>,
+    marker_length: usize,
+    options: &ConflictDetectorOptions,
+) -> Result<Vec<ConflictingFile>> {
+    let scanner = ConflictScanner::new(marker_length);
+    let (path_send, path_recv) = crossbeam::channel::unbounded::<RepoPathBuf>();
+    let (result_send, result_recv) =
+        crossbeam::channel::unbounded::<Result<Option<ConflictingFile>>>();
+
+    for path in paths {
+        path_send.send(path)?;
+    }
+    drop(path_send);
+
+    thread::scope(|s| {
+        for _ in 0..options.parallelism.max(1) {
+            let path_recv = path_recv.clone();
+            let result_send = result_send.clone();
+            let scanner = &scanner;
+            s.spawn(move || {
+                for path in path_recv {
+                    let _ = result_send.send(scan_file(vfs, scanner, path, options));
+                }
+            });
+        }
+    });
+    drop(result_send);
+
+    let mut conflicts = result_recv
+        .into_iter()
+        .filter_map(|result| result.transpose())
+        .collect::<Result<Vec<_>>>()?;
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(conflicts)
+}
+
+fn scan_file(
+    vfs: &VFS,
+    scanner: &ConflictScanner,
+    path: RepoPathBuf,
+    options: &ConflictDetectorOptions,
+) -> Result<Option<ConflictingFile>> {
+    let metadata = match vfs.metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) => match e.downcast_ref::<std::io::Error>() {
+            Some(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            _ => return Err(e),
+        },
+    };
+    if !metadata.is_file() || metadata.len() > options.max_file_size as u64 {
+        tracing::trace!(?path, "not scanned for conflicts");
+        return Ok(None);
+    }
+    let content = vfs.read(&path)?;
+    Ok(scanner.scan(path, &content))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use types::testutil::*;
+
+    use super::*;
+
+    fn scan(content: &str) -> Option<(usize, bool, bool)> {
+        ConflictScanner::new(DEFAULT_MARKER_LENGTH)
+            .scan(repo_path_buf("a"), content.as_bytes())
+            .map(|c| (c.conflict_count, c.has_our_marker, c.has_their_marker))
+    }
+
+    #[test]
+    fn test_finder() {
+        let finder = Finder::new(b"aba".to_vec());
+        assert_eq!(
+            finder.find_iter(b"xabababxaba").collect::<Vec<_>>(),
+            vec![1, 3, 8]
+        );
+        assert_eq!(finder.find_iter(b"ab").count(), 0);
+    }
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(scan("a\nb\n"), None);
+        assert_eq!(
+            scan("a\n<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\nd\n"),
+            Some((1, true, true))
+        );
+        assert_eq!(
+            scan(concat!(
+                "<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\n",
+                "x\n",
+                "<<<<<<< ours\r\nb\r\n=======\r\nc\r\n>>>>>>> theirs\r\n",
+            )),
+            Some((2, true, true))
+        );
+
+        // Partly resolved conflicts.
+        assert_eq!(
+            scan("b\n=======\nc\n>>>>>>> theirs\n"),
+            Some((0, false, true))
+        );
+        assert_eq!(scan("<<<<<<< ours\nb\n"), Some((1, true, false)));
+
+        // Not conflict markers.
+        assert_eq!(scan("Title\n=======\n"), None);
+        assert_eq!(scan("a <<<<<<< b\n"), None);
+        assert_eq!(scan("<<<<<<<< too long\n"), None);
+        assert_eq!(scan("<<<<<<<no label\n"), None);
+    }
+
+    #[test]
+    fn test_scan_marker_length() {
+        let content = b"<<<<<<<<<< ours\nb\n==========\nc\n>>>>>>>>>> theirs\n";
+        assert_eq!(
+            ConflictScanner::new(DEFAULT_MARKER_LENGTH).scan(repo_path_buf("a"), content),
+            None
+        );
+        assert_eq!(
+            ConflictScanner::new(10).scan(repo_path_buf("a"), content),
+            Some(ConflictingFile {
+                path: repo_path_buf("a"),
+                conflict_count: 1,
+                has_our_marker: true,
+                has_their_marker: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_detect_conflicts() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        fs::create_dir(root.join("dir"))?;
+        fs::write(
+            root.join("two"),
+            "<<<<<<< a\n1\n=======\n2\n>>>>>>> b\n\n<<<<<<< a\n3\n=======\n4\n>>>>>>> b\n",
+        )?;
+        fs::write(
+            root.join("dir/one"),
+            "x\n<<<<<<< a\n1\n=======\n2\n>>>>>>> b\n",
+        )?;
+        fs::write(root.join("clean"), "x\n")?;
+        fs::write(root.join("large"), "<<<<<<< a\n".repeat(100))?;
+
+        let vfs = VFS::new(root.to_path_buf())?;
+        let options = ConflictDetectorOptions {
+            parallelism: 2,
+            max_file_size: 100,
+        };
+        let conflicts = detect_conflicts(
+            &vfs,
+            vec![
+                repo_path_buf("two"),
+                repo_path_buf("dir/one"),
+                repo_path_buf("clean"),
+                repo_path_buf("large"),
+                repo_path_buf("missing"),
+            ],
+            DEFAULT_MARKER_LENGTH,
+            &options,
+        )?;
+
+        assert_eq!(
+            conflicts
+                .iter()
+                .map(|c| (c.path.as_str(), c.conflict_count))
+                .collect::<Vec<_>>(),
+            vec![("dir/one", 1), ("two", 2)]
+        );
+
+        Ok(())
+    }
+}
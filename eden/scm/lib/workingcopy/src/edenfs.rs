@@ -5,67 +5,674 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::SystemTime;
 
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
 use configmodel::Config;
+use configmodel::ConfigExt;
 use io::IO;
+use parking_lot::Mutex;
 use pathmatcher::DynMatcher;
+use thiserror::Error;
+use thrift_types::edenfs::GetScmStatusResult;
 use thrift_types::edenfs::ScmFileStatus;
+use thrift_types::edenfs::ScmStatus;
+use treestate::filestate::StateFlags;
+use treestate::treestate::TreeState;
 use types::HgId;
+use types::RepoPath;
 use types::RepoPathBuf;
 use vfs::VFS;
 
 use crate::filesystem::ChangeType;
+use crate::filesystem::CopyInfo;
 use crate::filesystem::PendingChangeResult;
 use crate::filesystem::PendingChanges;
+use crate::filesystem::ProgressCallback;
+use crate::filesystem::ProgressReporter;
+use crate::filesystem::PROGRESS_INTERVAL;
+
+type GetStatus = fn(&Path, HgId) -> Result<GetScmStatusResult>;
+type GetDiff = fn(&Path, HgId, HgId) -> Result<ScmStatus>;
+
+#[derive(Debug, Error)]
+pub enum DiffError {
+    #[error("commit {0} not found in EdenFS")]
+    CommitNotFound(HgId),
+}
 
 pub struct EdenFileSystem {
+    vfs: VFS,
+    // Canonical form of the VFS root, which may be relative or a symlink.
     root: PathBuf,
+    treestate: Arc<Mutex<TreeState>>,
     p1: HgId,
+    get_status: GetStatus,
+    get_diff: GetDiff,
+    // Used when EdenFS can't be reached, if allowed by config.
+    fallback: Box<dyn PendingChanges + Send>,
+    progress: Option<ProgressCallback>,
 }
 
 impl EdenFileSystem {
-    pub fn new(vfs: VFS, p1: HgId) -> Result<Self> {
+    pub fn new(
+        vfs: VFS,
+        treestate: Arc<Mutex<TreeState>>,
+        p1: HgId,
+        fallback: Box<dyn PendingChanges + Send>,
+    ) -> Result<Self> {
+        let root = vfs
+            .root()
+            .canonicalize()
+            .with_context(|| format!("canonicalizing repo root {}", vfs.root().display()))?;
         Ok(EdenFileSystem {
+            vfs,
+            root,
+            treestate,
             p1,
-            root: vfs.root().to_path_buf(),
+            get_status: edenfs_client::status::get_status,
+            get_diff: edenfs_client::status::get_status_between_revisions,
+            fallback,
+            progress: None,
         })
     }
+
+    /// Files changed between commits `a` and `b`, as computed by EdenFS
+    /// without looking at the working copy.
+    ///
+    /// Fails with [DiffError::CommitNotFound] if EdenFS doesn't know about
+    /// one of the commits.
+    pub fn diff_commits(
+        &self,
+        a: HgId,
+        b: HgId,
+    ) -> Result<Box<dyn Iterator<Item = Result<PendingChangeResult>>>> {
+        let status = match (self.get_diff)(&self.root, a, b) {
+            Ok(status) => status,
+            Err(err) => {
+                if let Some(commit) = missing_commit(&err, [a, b]) {
+                    return Err(err.context(DiffError::CommitNotFound(commit)));
+                }
+                return Err(err.context(format!(
+                    "failed to get EdenFS diff for {} between {} and {}",
+                    self.root.display(),
+                    a,
+                    b
+                )));
+            }
+        };
+        // Copies between commits are not tracked by the treestate.
+        let changes = pending_changes_from_status(status.entries, |_| false, |_| Ok(None));
+        Ok(Box::new(changes.into_iter()))
+    }
 }
 
 impl PendingChanges for EdenFileSystem {
     fn pending_changes(
         &self,
-        _matcher: DynMatcher,
-        _ignore_matcher: DynMatcher,
-        _ignore_dirs: Vec<PathBuf>,
-        _last_write: SystemTime,
-        _config: &dyn Config,
-        _io: &IO,
+        matcher: DynMatcher,
+        ignore_matcher: DynMatcher,
+        ignore_dirs: Vec<PathBuf>,
+        last_write: SystemTime,
+        config: &dyn Config,
+        io: &IO,
     ) -> Result<Box<dyn Iterator<Item = Result<PendingChangeResult>>>> {
-        let result = edenfs_client::status::get_status(&self.root, self.p1)?;
-        Ok(Box::new(result.status.entries.into_iter().filter_map(
-            |(path, status)| {
-                {
-                    // TODO: Handle non-UTF8 encoded paths from Eden
-                    let repo_path = match RepoPathBuf::from_utf8(path) {
-                        Ok(repo_path) => repo_path,
-                        Err(err) => return Some(Err(anyhow!(err))),
-                    };
-                    match status {
-                        ScmFileStatus::REMOVED => Some(Ok(PendingChangeResult::File(
-                            ChangeType::Deleted(repo_path),
-                        ))),
-                        ScmFileStatus::IGNORED => None,
-                        _ => Some(Ok(PendingChangeResult::File(ChangeType::Changed(
-                            repo_path,
-                        )))),
-                    }
+        let fallback_to_local =
+            config.get_or_default::<bool>("workingcopy", "eden-fallback-to-local")?;
+        let result = match (self.get_status)(&self.root, self.p1) {
+            Ok(result) => result,
+            Err(err) if fallback_to_local && is_connection_error(&err) => {
+                tracing::warn!(?err, "EdenFS unreachable, falling back to local status");
+                let _ = warn_about_fallback(io, &err);
+                return self.fallback.pending_changes(
+                    matcher,
+                    ignore_matcher,
+                    ignore_dirs,
+                    last_write,
+                    config,
+                    io,
+                );
+            }
+            Err(err) => {
+                return Err(err.context(format!(
+                    "failed to get EdenFS status for {} at {}",
+                    self.root.display(),
+                    self.p1
+                )));
+            }
+        };
+        // EdenFS's journal usually makes this unnecessary, and mtimes are
+        // subject to clock skew, so only filter when asked to.
+        let filter_last_write =
+            config.get_or_default::<bool>("workingcopy", "eden-filter-last-write")?;
+        // EdenFS doesn't know about copies, they are recorded in the treestate.
+        let mut treestate = self.treestate.lock();
+        let entry_count = result.status.entries.len() as u64;
+        let changes = pending_changes_from_status(
+            result.status.entries,
+            |path| filter_last_write && modified_before(&self.vfs, path, last_write),
+            |path| copy_source(&mut treestate, path),
+        );
+        // EdenFS only reports the files that changed, once it is done.
+        let mut progress = ProgressReporter::new(self.progress.clone(), PROGRESS_INTERVAL);
+        progress.update(|p| {
+            p.files_scanned = entry_count;
+            p.changes_found = changes
+                .iter()
+                .filter(|c| matches!(c, Ok(PendingChangeResult::File(_))))
+                .count() as u64;
+        });
+        Ok(Box::new(changes.into_iter()))
+    }
+
+    fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.fallback.set_progress(callback.clone());
+        self.progress = callback;
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+}
+
+/// Convert the entries of an EdenFS status to pending changes.
+///
+/// `is_stale` returns whether a changed file should be left out, for example
+/// because it was already reported by a previous scan.
+///
+/// `copy_source` returns where a changed file was copied from, if anywhere.
+/// A file copied from a removed file is reported as renamed.
+fn pending_changes_from_status(
+    entries: impl IntoIterator<Item = (Vec<u8>, ScmFileStatus)>,
+    mut is_stale: impl FnMut(&RepoPath) -> bool,
+    mut copy_source: impl FnMut(&RepoPath) -> Result<Option<RepoPathBuf>>,
+) -> Vec<Result<PendingChangeResult>> {
+    let mut changes = Vec::new();
+    let mut removed = HashSet::new();
+    let mut copies = Vec::new();
+    for (path, status) in entries {
+        // TODO: Handle non-UTF8 encoded paths from Eden
+        let repo_path = match RepoPathBuf::from_utf8(path) {
+            Ok(repo_path) => repo_path,
+            Err(err) => {
+                changes.push(Err(anyhow!(err)));
+                continue;
+            }
+        };
+        match status {
+            ScmFileStatus::REMOVED => {
+                removed.insert(repo_path.clone());
+                changes.push(Ok(PendingChangeResult::File(ChangeType::Deleted(
+                    repo_path,
+                ))));
+            }
+            ScmFileStatus::IGNORED => {}
+            _ if is_stale(&repo_path) => {}
+            _ => {
+                match copy_source(&repo_path) {
+                    Ok(Some(source)) => copies.push((repo_path.clone(), source)),
+                    Ok(None) => {}
+                    Err(err) => changes.push(Err(err)),
                 }
-            },
-        )))
+                changes.push(Ok(PendingChangeResult::File(ChangeType::Changed(
+                    repo_path,
+                ))));
+            }
+        }
+    }
+
+    // Copies are reported once all the removed files are known, so that a
+    // rename, which EdenFS reports as a removed and an added file, can be
+    // told apart from a copy.
+    changes.extend(copies.into_iter().map(|(path, source)| {
+        let renamed = removed.contains(&source);
+        Ok(PendingChangeResult::Copied(CopyInfo {
+            path,
+            source,
+            renamed,
+        }))
+    }));
+    changes
+}
+
+/// Whether `err` means that EdenFS couldn't be reached, as opposed to EdenFS
+/// failing to compute the status.
+fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| match cause.downcast_ref::<std::io::Error>() {
+            Some(err) => matches!(
+                err.kind(),
+                ErrorKind::NotFound
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+            ),
+            None => false,
+        })
+}
+
+/// The commit in `commits` that EdenFS failed to find, according to `err`.
+///
+/// EdenFS reports unknown commits as generic errors, naming the commit in the
+/// message.
+fn missing_commit(err: &anyhow::Error, commits: [HgId; 2]) -> Option<HgId> {
+    err.chain().find_map(|cause| {
+        let message = cause.to_string().to_lowercase();
+        if !message.contains("not found") && !message.contains("unknown") {
+            return None;
+        }
+        commits
+            .into_iter()
+            .find(|commit| message.contains(&commit.to_hex()))
+    })
+}
+
+fn warn_about_fallback(io: &IO, err: &anyhow::Error) -> Result<()> {
+    let mut output = io.error();
+    writeln!(
+        &mut output,
+        "warning: EdenFS is not reachable ({}) - falling back to scanning the working copy, which is slower and may miss changes",
+        err
+    )?;
+    Ok(())
+}
+
+/// Whether the file at `path` was last modified before `last_write`.
+///
+/// Files whose mtime can't be read are never considered stale. A file with an
+/// mtime equal to `last_write` could have been modified again within the
+/// same clock tick, so it isn't considered stale either.
+fn modified_before(vfs: &VFS, path: &RepoPath, last_write: SystemTime) -> bool {
+    match vfs.metadata(path).and_then(|m| Ok(m.modified()?)) {
+        Ok(mtime) => mtime < last_write,
+        Err(_) => false,
+    }
+}
+
+fn copy_source(treestate: &mut TreeState, path: &RepoPath) -> Result<Option<RepoPathBuf>> {
+    match treestate.get(path)? {
+        Some(state) if state.state.contains(StateFlags::COPIED) => match &state.copied {
+            Some(source) => Ok(Some(RepoPathBuf::from_utf8(source.to_vec())?)),
+            None => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn path(s: &str) -> RepoPathBuf {
+        RepoPathBuf::from_string(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_pending_changes_copies() -> Result<()> {
+        let entries = vec![
+            (b"copied".to_vec(), ScmFileStatus::ADDED),
+            (b"renamed".to_vec(), ScmFileStatus::ADDED),
+            (b"modified".to_vec(), ScmFileStatus::MODIFIED),
+            (b"original".to_vec(), ScmFileStatus::MODIFIED),
+            (b"removed".to_vec(), ScmFileStatus::REMOVED),
+            (b"ignored".to_vec(), ScmFileStatus::IGNORED),
+        ];
+        let copies: HashMap<RepoPathBuf, RepoPathBuf> = [
+            (path("copied"), path("original")),
+            (path("renamed"), path("removed")),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut changed = Vec::new();
+        let mut deleted = Vec::new();
+        let mut copied = Vec::new();
+        for change in
+            pending_changes_from_status(entries, |_| false, |path| Ok(copies.get(path).cloned()))
+        {
+            match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => changed.push(path),
+                PendingChangeResult::File(ChangeType::Deleted(path)) => deleted.push(path),
+                PendingChangeResult::Copied(copy) => copied.push(copy),
+                PendingChangeResult::SeenDirectory(_) => panic!("seen directory?"),
+            }
+        }
+
+        assert_eq!(
+            changed,
+            vec![
+                path("copied"),
+                path("renamed"),
+                path("modified"),
+                path("original")
+            ]
+        );
+        assert_eq!(deleted, vec![path("removed")]);
+        assert_eq!(
+            copied,
+            vec![
+                CopyInfo {
+                    path: path("copied"),
+                    source: path("original"),
+                    renamed: false,
+                },
+                CopyInfo {
+                    path: path("renamed"),
+                    source: path("removed"),
+                    renamed: true,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_changes_last_write() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let vfs = VFS::new(root.path().to_path_buf())?;
+        std::fs::write(root.path().join("old"), b"old")?;
+        std::fs::write(root.path().join("new"), b"new")?;
+
+        let old_mtime = vfs.metadata(&path("old"))?.modified()?;
+        let last_write = old_mtime + Duration::from_secs(60);
+        let new_file = std::fs::File::options()
+            .write(true)
+            .open(root.path().join("new"))?;
+        new_file.set_modified(last_write + Duration::from_secs(60))?;
+
+        assert!(modified_before(&vfs, &path("old"), last_write));
+        assert!(!modified_before(&vfs, &path("new"), last_write));
+        assert!(!modified_before(&vfs, &path("missing"), last_write));
+
+        let entries = vec![
+            (b"old".to_vec(), ScmFileStatus::MODIFIED),
+            (b"new".to_vec(), ScmFileStatus::MODIFIED),
+            (b"missing".to_vec(), ScmFileStatus::REMOVED),
+        ];
+        let mut changed = Vec::new();
+        let mut deleted = Vec::new();
+        let mut copied = Vec::new();
+        for change in pending_changes_from_status(
+            entries,
+            |path| modified_before(&vfs, path, last_write),
+            |_| Ok(Some(path("source"))),
+        ) {
+            match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => changed.push(path),
+                PendingChangeResult::File(ChangeType::Deleted(path)) => deleted.push(path),
+                PendingChangeResult::Copied(copy) => copied.push(copy.path),
+                PendingChangeResult::SeenDirectory(_) => panic!("seen directory?"),
+            }
+        }
+
+        assert_eq!(changed, vec![path("new")]);
+        assert_eq!(deleted, vec![path("missing")]);
+        assert_eq!(copied, vec![path("new")]);
+
+        Ok(())
+    }
+
+    struct FakeFileSystem;
+
+    impl PendingChanges for FakeFileSystem {
+        fn pending_changes(
+            &self,
+            _matcher: DynMatcher,
+            _ignore_matcher: DynMatcher,
+            _ignore_dirs: Vec<PathBuf>,
+            _last_write: SystemTime,
+            _config: &dyn Config,
+            _io: &IO,
+        ) -> Result<Box<dyn Iterator<Item = Result<PendingChangeResult>>>> {
+            Ok(Box::new(std::iter::once(Ok(PendingChangeResult::File(
+                ChangeType::Changed(path("fallback")),
+            )))))
+        }
+
+        fn set_progress(&mut self, _callback: Option<ProgressCallback>) {}
+    }
+
+    fn unreachable_status(_root: &Path, _commit: HgId) -> Result<GetScmStatusResult> {
+        Err(std::io::Error::from(ErrorKind::ConnectionRefused).into())
+    }
+
+    fn failing_status(_root: &Path, _commit: HgId) -> Result<GetScmStatusResult> {
+        Err(anyhow!("commit not found"))
+    }
+
+    const KNOWN: &str = "1111111111111111111111111111111111111111";
+    const MISSING: &str = "2222222222222222222222222222222222222222";
+
+    fn canned_diff(_root: &Path, a: HgId, b: HgId) -> Result<ScmStatus> {
+        for commit in [a, b] {
+            if commit.to_hex() != KNOWN {
+                return Err(anyhow!("revision {} not found", commit));
+            }
+        }
+        Ok(ScmStatus {
+            entries: [
+                (b"added".to_vec(), ScmFileStatus::ADDED),
+                (b"modified".to_vec(), ScmFileStatus::MODIFIED),
+                (b"removed".to_vec(), ScmFileStatus::REMOVED),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn eden_with_diff(root: &Path) -> Result<EdenFileSystem> {
+        let (treestate, _) = TreeState::new(root, true)?;
+        Ok(EdenFileSystem {
+            vfs: VFS::new(root.to_path_buf())?,
+            root: root.to_path_buf(),
+            treestate: Arc::new(Mutex::new(treestate)),
+            p1: HgId::null_id().clone(),
+            get_status: failing_status,
+            get_diff: canned_diff,
+            fallback: Box::new(FakeFileSystem),
+            progress: None,
+        })
+    }
+
+    #[test]
+    fn test_diff_commits() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let eden = eden_with_diff(root.path())?;
+        let known = HgId::from_hex(KNOWN.as_bytes())?;
+
+        let mut changed = Vec::new();
+        let mut deleted = Vec::new();
+        for change in eden.diff_commits(known, known)? {
+            match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => changed.push(path),
+                PendingChangeResult::File(ChangeType::Deleted(path)) => deleted.push(path),
+                _ => panic!("unexpected change"),
+            }
+        }
+        assert_eq!(changed, vec![path("added"), path("modified")]);
+        assert_eq!(deleted, vec![path("removed")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_commits_not_found() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let eden = eden_with_diff(root.path())?;
+        let known = HgId::from_hex(KNOWN.as_bytes())?;
+        let missing = HgId::from_hex(MISSING.as_bytes())?;
+
+        for (a, b) in [(known, missing), (missing, known)] {
+            let err = match eden.diff_commits(a, b) {
+                Ok(_) => panic!("diff should fail"),
+                Err(err) => err,
+            };
+            assert!(matches!(
+                err.downcast_ref::<DiffError>(),
+                Some(DiffError::CommitNotFound(commit)) if *commit == missing
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_changes_error_context() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let vfs = VFS::new(root.path().to_path_buf())?;
+        let (treestate, _) = TreeState::new(root.path(), true)?;
+        let p1 = HgId::from_hex(b"1111111111111111111111111111111111111111")?;
+        let eden = EdenFileSystem {
+            vfs,
+            root: root.path().to_path_buf(),
+            treestate: Arc::new(Mutex::new(treestate)),
+            p1,
+            get_status: failing_status,
+            get_diff: canned_diff,
+            fallback: Box::new(FakeFileSystem),
+            progress: None,
+        };
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+
+        // Not a connection error, so there's no fallback even if allowed.
+        let mut config: BTreeMap<&str, &str> = BTreeMap::new();
+        config.insert("workingcopy.eden-fallback-to-local", "true");
+        let err = match eden.pending_changes(
+            Arc::new(pathmatcher::AlwaysMatcher::new()),
+            Arc::new(pathmatcher::NeverMatcher::new()),
+            Vec::new(),
+            SystemTime::UNIX_EPOCH,
+            &config,
+            &io,
+        ) {
+            Ok(_) => panic!("status should fail"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "failed to get EdenFS status for {} at {}",
+                root.path().display(),
+                p1
+            )
+        );
+        assert_eq!(err.root_cause().to_string(), "commit not found");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_changes_fallback() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let vfs = VFS::new(root.path().to_path_buf())?;
+        let (treestate, _) = TreeState::new(root.path(), true)?;
+        let eden = EdenFileSystem {
+            vfs,
+            root: root.path().to_path_buf(),
+            treestate: Arc::new(Mutex::new(treestate)),
+            p1: HgId::null_id().clone(),
+            get_status: unreachable_status,
+            get_diff: canned_diff,
+            fallback: Box::new(FakeFileSystem),
+            progress: None,
+        };
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+        let pending_changes = |config: &dyn Config| {
+            eden.pending_changes(
+                Arc::new(pathmatcher::AlwaysMatcher::new()),
+                Arc::new(pathmatcher::NeverMatcher::new()),
+                Vec::new(),
+                SystemTime::UNIX_EPOCH,
+                config,
+                &io,
+            )
+        };
+
+        // Without the config, the error is surfaced.
+        assert!(pending_changes(&BTreeMap::<&str, &str>::new()).is_err());
+
+        let mut config: BTreeMap<&str, &str> = BTreeMap::new();
+        config.insert("workingcopy.eden-fallback-to-local", "true");
+        let changed = pending_changes(&config)?
+            .map(|change| match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => Ok(path),
+                _ => panic!("unexpected change"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(changed, vec![path("fallback")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_virtual() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let (treestate, _) = TreeState::new(root.path(), true)?;
+        let eden = EdenFileSystem::new(
+            VFS::new(root.path().to_path_buf())?,
+            Arc::new(Mutex::new(treestate)),
+            HgId::null_id().clone(),
+            Box::new(FakeFileSystem),
+        )?;
+        assert!(eden.is_virtual());
+        assert!(!FakeFileSystem.is_virtual());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_root() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        let link = dir.path().join("link");
+        std::fs::create_dir(&root)?;
+        std::os::unix::fs::symlink(&root, &link)?;
+        let (treestate, _) = TreeState::new(dir.path(), true)?;
+        let treestate = Arc::new(Mutex::new(treestate));
+
+        let direct = EdenFileSystem::new(
+            VFS::new(root.clone())?,
+            treestate.clone(),
+            HgId::null_id().clone(),
+            Box::new(FakeFileSystem),
+        )?;
+        let linked = EdenFileSystem::new(
+            VFS::new(link.clone())?,
+            treestate.clone(),
+            HgId::null_id().clone(),
+            Box::new(FakeFileSystem),
+        )?;
+        assert_eq!(direct.root, root.canonicalize()?);
+        assert_eq!(linked.root, direct.root);
+
+        // The root must exist to be canonicalized.
+        let vfs = VFS::new(link)?;
+        std::fs::remove_dir(&root)?;
+        let res = EdenFileSystem::new(
+            vfs,
+            treestate,
+            HgId::null_id().clone(),
+            Box::new(FakeFileSystem),
+        );
+        assert!(res.is_err());
+
+        Ok(())
     }
 }
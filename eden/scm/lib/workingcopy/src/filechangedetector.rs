@@ -12,6 +12,7 @@ use anyhow::Result;
 use futures::StreamExt;
 use manifest::Manifest;
 use manifest_tree::TreeManifest;
+use parking_lot::Mutex;
 use parking_lot::RwLock;
 use pathmatcher::ExactMatcher;
 use progress_model::ProgressBar;
@@ -19,6 +20,7 @@ use storemodel::minibytes::Bytes;
 use storemodel::ReadFileContents;
 use treestate::filestate::StateFlags;
 use types::Key;
+use types::RepoPath;
 use types::RepoPathBuf;
 use vfs::VFS;
 
@@ -26,6 +28,8 @@ use crate::filesystem::ChangeType;
 use crate::metadata;
 use crate::metadata::HgModifiedTime;
 use crate::metadata::Metadata;
+use crate::mtimecache::MtimeCache;
+use crate::mtimecache::MtimeCacheEntry;
 
 pub type ArcReadFileContents = Arc<dyn ReadFileContents<Error = anyhow::Error> + Send + Sync>;
 
@@ -73,6 +77,7 @@ pub(crate) struct FileChangeDetector {
     store: ArcReadFileContents,
     worker_count: usize,
     progress: Arc<ProgressBar>,
+    mtime_cache: Option<Arc<Mutex<MtimeCache>>>,
 }
 
 impl FileChangeDetector {
@@ -93,6 +98,22 @@ impl FileChangeDetector {
             store,
             worker_count: worker_count.unwrap_or(10),
             progress: ProgressBar::register_new("comparing", 0, "files"),
+            mtime_cache: None,
+        }
+    }
+
+    /// Skip comparing the contents of files that `mtime_cache` knows to be
+    /// unchanged, and record the files found unchanged in it. The cache is
+    /// saved once all files are compared.
+    pub fn with_mtime_cache(mut self, mtime_cache: MtimeCache) -> Self {
+        self.mtime_cache = Some(Arc::new(Mutex::new(mtime_cache)));
+        self
+    }
+
+    /// Drop `path` from the mtime cache, if any.
+    pub(crate) fn forget(&self, path: &RepoPath) {
+        if let Some(mtime_cache) = &self.mtime_cache {
+            mtime_cache.lock().remove(path);
         }
     }
 }
@@ -233,19 +254,36 @@ pub(crate) fn file_changed_given_metadata(
 fn compare_repo_bytes_to_disk(
     vfs: &VFS,
     repo_bytes: Bytes,
-    path: RepoPathBuf,
+    key: Key,
+    mtime_cache: Option<&Mutex<MtimeCache>>,
 ) -> Result<ResolvedFileChangeResult> {
+    let path = key.path;
     match vfs.read_with_metadata(&path) {
         Ok((disk_bytes, metadata)) => {
             if disk_bytes == repo_bytes {
                 tracing::trace!(?path, "no (contents match)");
+                if let (Some(mtime_cache), Ok(mtime)) = (mtime_cache, metadata.modified()) {
+                    let entry = MtimeCacheEntry {
+                        mtime,
+                        size: metadata.len(),
+                        hash: key.hgid,
+                    };
+                    mtime_cache.lock().insert(path.clone(), entry);
+                }
                 Ok(ResolvedFileChangeResult::No((path, Some(metadata.into()))))
             } else {
                 tracing::trace!(?path, "changed (contents mismatch)");
+                if let Some(mtime_cache) = mtime_cache {
+                    mtime_cache.lock().remove(&path);
+                }
                 Ok(ResolvedFileChangeResult::Yes(ChangeType::Changed(path)))
             }
         }
         Err(e) => {
+            if let Some(mtime_cache) = mtime_cache {
+                mtime_cache.lock().remove(&path);
+            }
+
             if let Some(e) = e.downcast_ref::<std::io::Error>() {
                 if e.kind() == std::io::ErrorKind::NotFound {
                     tracing::trace!(?path, "deleted (file missing)");
@@ -270,8 +308,12 @@ impl FileChangeDetector {
     ) -> Result<FileChangeResult> {
         let res = file_changed_given_metadata(&self.vfs, file, self.last_write);
 
-        if let Ok(FileChangeResult::Maybe((ref path, ref meta))) = res {
-            self.lookups.insert(path.to_owned(), meta.clone());
+        match res {
+            Ok(FileChangeResult::Maybe((ref path, ref meta))) => {
+                self.lookups.insert(path.to_owned(), meta.clone());
+            }
+            Ok(FileChangeResult::Yes(ChangeType::Deleted(ref path))) => self.forget(path),
+            _ => {}
         }
 
         res
@@ -392,11 +434,40 @@ impl IntoIterator for FileChangeDetector {
             })
             .collect::<Vec<_>>();
 
+        // Skip files whose mtime and size did not change since their contents
+        // last matched the same file node.
+        let keys = match &self.mtime_cache {
+            None => keys,
+            Some(mtime_cache) => {
+                let mtime_cache = mtime_cache.lock();
+                keys.into_iter()
+                    .filter(|key| {
+                        let metadata = match self.vfs.metadata(&key.path) {
+                            Ok(metadata) => metadata,
+                            Err(_) => return true,
+                        };
+                        let unchanged = metadata.modified().map_or(false, |mtime| {
+                            mtime_cache.is_unchanged(&key.path, mtime, metadata.len(), key.hgid)
+                        });
+                        if unchanged {
+                            tracing::trace!(path=?key.path, "no (mtime cache)");
+                            self.results.push(Ok(ResolvedFileChangeResult::No((
+                                key.path.clone(),
+                                Some(metadata.into()),
+                            ))));
+                            bar.increase_position(1);
+                        }
+                        !unchanged
+                    })
+                    .collect()
+            }
+        };
+
         drop(_span);
 
         let _span = tracing::info_span!("compare contents", keys = keys.len()).entered();
 
-        let (disk_send, disk_recv) = crossbeam::channel::unbounded::<(RepoPathBuf, Bytes)>();
+        let (disk_send, disk_recv) = crossbeam::channel::unbounded::<(Key, Bytes)>();
         let (results_send, results_recv) =
             crossbeam::channel::unbounded::<Result<ResolvedFileChangeResult>>();
 
@@ -405,10 +476,16 @@ impl IntoIterator for FileChangeDetector {
             let disk_recv = disk_recv.clone();
             let results_send = results_send.clone();
             let bar = bar.clone();
+            let mtime_cache = self.mtime_cache.clone();
             std::thread::spawn(move || {
-                for (path, repo_bytes) in disk_recv {
+                for (key, repo_bytes) in disk_recv {
                     results_send
-                        .send(compare_repo_bytes_to_disk(&vfs, repo_bytes, path))
+                        .send(compare_repo_bytes_to_disk(
+                            &vfs,
+                            repo_bytes,
+                            key,
+                            mtime_cache.as_deref(),
+                        ))
                         .unwrap();
                     bar.increase_position(1);
                 }
@@ -425,7 +502,7 @@ impl IntoIterator for FileChangeDetector {
             let mut results = self.store.read_file_contents(keys).await;
             while let Some(result) = results.next().await {
                 match result {
-                    Ok((bytes, key)) => disk_send.send((key.path, bytes)).unwrap(),
+                    Ok((bytes, key)) => disk_send.send((key, bytes)).unwrap(),
                     Err(e) => results_send.send(Err(e)).unwrap(),
                 };
             }
@@ -435,6 +512,199 @@ impl IntoIterator for FileChangeDetector {
         drop(disk_send);
 
         self.results.extend(results_recv.into_iter());
+
+        if let Some(mtime_cache) = &self.mtime_cache {
+            // The cache is only an optimization. Don't fail status over it.
+            if let Err(err) = mtime_cache.lock().save() {
+                tracing::warn!(?err, "failed to save mtime cache");
+            }
+        }
+
         self.results.into_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    use futures::stream;
+    use futures::stream::BoxStream;
+    use manifest::FileMetadata;
+    use manifest_tree::testutil::make_tree_manifest_from_meta;
+    use manifest_tree::testutil::TestStore;
+    use treestate::filestate::FileStateV2;
+    use types::testutil::*;
+
+    use super::*;
+    use crate::mtimecache::MtimeCacheOptions;
+
+    /// Serves the content of a single file and counts how often it is read.
+    struct CountingStore {
+        content: Bytes,
+        reads: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl ReadFileContents for CountingStore {
+        type Error = anyhow::Error;
+
+        async fn read_file_contents(
+            &self,
+            keys: Vec<Key>,
+        ) -> BoxStream<Result<(Bytes, Key), Self::Error>> {
+            self.reads.fetch_add(keys.len(), Ordering::SeqCst);
+            let content = self.content.clone();
+            stream::iter(keys.into_iter().map(move |k| Ok((content.clone(), k)))).boxed()
+        }
+
+        async fn read_rename_metadata(
+            &self,
+            _keys: Vec<Key>,
+        ) -> BoxStream<Result<(Key, Option<Key>), Self::Error>> {
+            stream::empty().boxed()
+        }
+    }
+
+    fn set_mtime(path: &std::path::Path, secs_ago: u64) -> Result<()> {
+        let mtime = SystemTime::now() - Duration::from_secs(secs_ago);
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(mtime)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mtime_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir(&root)?;
+        fs::write(root.join("a"), b"content")?;
+        set_mtime(&root.join("a"), 100)?;
+
+        let vfs = VFS::new(root.clone())?;
+        let path = repo_path_buf("a");
+        let manifest = Arc::new(RwLock::new(make_tree_manifest_from_meta(
+            Arc::new(TestStore::new()),
+            [(path.clone(), FileMetadata::regular(hgid("1")))],
+        )));
+        let store = Arc::new(CountingStore {
+            content: Bytes::from_static(b"content"),
+            reads: AtomicUsize::new(0),
+        });
+        let options = MtimeCacheOptions::new(dir.path());
+
+        let status = || -> Result<Vec<ResolvedFileChangeResult>> {
+            let mut detector = FileChangeDetector::new(
+                vfs.clone(),
+                HgModifiedTime::from(0u64),
+                manifest.clone(),
+                store.clone(),
+                Some(1),
+            )
+            .with_mtime_cache(MtimeCache::open(options.clone())?);
+            // A negative size makes the treestate unable to tell whether the
+            // file changed.
+            detector.submit(metadata::File {
+                path: path.clone(),
+                fs_meta: None,
+                ts_state: Some(FileStateV2 {
+                    mode: 0o644,
+                    size: -1,
+                    mtime: -1,
+                    state: EXIST_P1 | StateFlags::EXIST_NEXT,
+                    copied: None,
+                }),
+            });
+            detector.into_iter().collect()
+        };
+        let assert_unchanged = |results: Vec<ResolvedFileChangeResult>| {
+            assert_eq!(results.len(), 1);
+            assert!(matches!(&results[0], ResolvedFileChangeResult::No((p, _)) if p == &path));
+        };
+
+        // The first run compares the contents and fills the cache.
+        assert_unchanged(status()?);
+        assert_eq!(store.reads.load(Ordering::SeqCst), 1);
+
+        // The file is not read again while it is untouched.
+        assert_unchanged(status()?);
+        assert_eq!(store.reads.load(Ordering::SeqCst), 1);
+
+        // A touched file is compared again, and is still unchanged.
+        set_mtime(&root.join("a"), 50)?;
+        assert_unchanged(status()?);
+        assert_eq!(store.reads.load(Ordering::SeqCst), 2);
+        assert_unchanged(status()?);
+        assert_eq!(store.reads.load(Ordering::SeqCst), 2);
+
+        // A modified file of the same size is detected.
+        fs::write(root.join("a"), b"CONTENT")?;
+        set_mtime(&root.join("a"), 10)?;
+        let results = status()?;
+        assert!(matches!(
+            &results[..],
+            [ResolvedFileChangeResult::Yes(ChangeType::Changed(p))] if p == &path
+        ));
+        assert_eq!(store.reads.load(Ordering::SeqCst), 3);
+        assert_eq!(MtimeCache::open(options.clone())?.get(&path), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mtime_cache_deleted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir(&root)?;
+
+        let options = MtimeCacheOptions::new(dir.path());
+        let mut cache = MtimeCache::open(options.clone())?;
+        let entry = MtimeCacheEntry {
+            mtime: SystemTime::now() - Duration::from_secs(100),
+            size: 7,
+            hash: hgid("1"),
+        };
+        cache.insert(repo_path_buf("a"), entry);
+        cache.save()?;
+
+        let mut detector = FileChangeDetector::new(
+            VFS::new(root)?,
+            HgModifiedTime::from(0u64),
+            Arc::new(RwLock::new(make_tree_manifest_from_meta(
+                Arc::new(TestStore::new()),
+                [],
+            ))),
+            Arc::new(CountingStore {
+                content: Bytes::new(),
+                reads: AtomicUsize::new(0),
+            }),
+            Some(1),
+        )
+        .with_mtime_cache(MtimeCache::open(options.clone())?);
+        detector.submit(metadata::File {
+            path: repo_path_buf("a"),
+            fs_meta: None,
+            ts_state: Some(FileStateV2 {
+                mode: 0o644,
+                size: 7,
+                mtime: 0,
+                state: EXIST_P1 | StateFlags::EXIST_NEXT,
+                copied: None,
+            }),
+        });
+        let results: Vec<_> = detector.into_iter().collect::<Result<_>>()?;
+        assert!(matches!(
+            &results[..],
+            [ResolvedFileChangeResult::Yes(ChangeType::Deleted(_))]
+        ));
+        assert_eq!(MtimeCache::open(options)?.get(repo_path("a")), None);
+
+        Ok(())
+    }
+}
@@ -7,9 +7,15 @@
 
 mod pendingchanges;
 
+pub use pendingchanges::merge;
 pub use pendingchanges::ChangeType;
+pub use pendingchanges::CopyInfo;
 pub use pendingchanges::PendingChangeResult;
 pub use pendingchanges::PendingChanges;
+pub use pendingchanges::ProgressCallback;
+pub use pendingchanges::ProgressReport;
+pub(crate) use pendingchanges::ProgressReporter;
+pub(crate) use pendingchanges::PROGRESS_INTERVAL;
 
 #[derive(PartialEq)]
 pub enum FileSystemType {
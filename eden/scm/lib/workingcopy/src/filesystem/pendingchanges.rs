@@ -5,7 +5,12 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
+use std::mem;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 use std::time::SystemTime;
 
 use anyhow::Result;
@@ -30,10 +35,90 @@ impl ChangeType {
     }
 }
 
+/// A changed file that was copied from another file.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CopyInfo {
+    pub path: RepoPathBuf,
+    pub source: RepoPathBuf,
+    /// The source was deleted, so the file was renamed rather than copied.
+    pub renamed: bool,
+}
+
 #[derive(Serialize)]
 pub enum PendingChangeResult {
     File(ChangeType),
     SeenDirectory(RepoPathBuf),
+    /// Where a file that is also reported as changed was copied from. Only
+    /// file systems tracking copies report this.
+    Copied(CopyInfo),
+}
+
+/// Progress of a scan for pending changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProgressReport {
+    pub files_scanned: u64,
+    pub dirs_scanned: u64,
+    pub changes_found: u64,
+    pub elapsed: Duration,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(ProgressReport) + Send + Sync>;
+
+/// Minimum time between two calls of a [ProgressCallback].
+pub(crate) const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counts the progress of a scan, and reports it to a callback at most once
+/// per interval.
+pub(crate) struct ProgressReporter {
+    callback: Option<ProgressCallback>,
+    interval: Duration,
+    start: Instant,
+    last_report: Option<Instant>,
+    report: ProgressReport,
+    /// The counts changed since they were last reported.
+    pending: bool,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: Option<ProgressCallback>, interval: Duration) -> Self {
+        Self {
+            callback,
+            interval,
+            start: Instant::now(),
+            last_report: None,
+            report: ProgressReport::default(),
+            pending: true,
+        }
+    }
+
+    /// Update the counts, and report them if the last report is old enough.
+    pub fn update(&mut self, update: impl FnOnce(&mut ProgressReport)) {
+        update(&mut self.report);
+        self.pending = true;
+        let now = Instant::now();
+        if self
+            .last_report
+            .map_or(true, |last| now.duration_since(last) >= self.interval)
+        {
+            self.report_at(now);
+        }
+    }
+
+    /// Report the final counts, unless they were already reported.
+    pub fn finish(&mut self) {
+        if self.pending {
+            self.report_at(Instant::now());
+        }
+    }
+
+    fn report_at(&mut self, now: Instant) {
+        if let Some(callback) = &self.callback {
+            self.last_report = Some(now);
+            self.report.elapsed = now.duration_since(self.start);
+            self.pending = false;
+            callback(self.report);
+        }
+    }
 }
 
 pub trait PendingChanges {
@@ -49,4 +134,135 @@ pub trait PendingChanges {
         config: &dyn Config,
         io: &IO,
     ) -> Result<Box<dyn Iterator<Item = Result<PendingChangeResult>>>>;
+
+    /// Whether the working copy is a virtual file system, like EdenFS, which
+    /// tracks changes itself. Walking such a working copy is expensive, as it
+    /// fetches the files it reads.
+    fn is_virtual(&self) -> bool {
+        false
+    }
+
+    /// Report the progress of `pending_changes` to `callback`, at most once
+    /// per second, and once more when the scan is done. `None` stops
+    /// reporting.
+    fn set_progress(&mut self, callback: Option<ProgressCallback>);
+
+    /// Builder-style version of [PendingChanges::set_progress].
+    fn with_progress(mut self, callback: ProgressCallback) -> Self
+    where
+        Self: Sized,
+    {
+        self.set_progress(Some(callback));
+        self
+    }
+}
+
+/// Merge the pending changes of two file systems, such as EdenFS and a
+/// redirected mount under it, reporting each path once.
+///
+/// The changes of `a` come first. A change of `b` is dropped if a change of
+/// the same kind, for the same path, was already reported: `a` wins over
+/// `b`, and the first change wins within each of them. A file reported as
+/// changed by `a` and deleted by `b` is changed. Errors are all kept.
+pub fn merge(
+    a: impl Iterator<Item = Result<PendingChangeResult>>,
+    b: impl Iterator<Item = Result<PendingChangeResult>>,
+) -> impl Iterator<Item = Result<PendingChangeResult>> {
+    let mut seen = HashSet::new();
+    a.chain(b).filter(move |result| match result {
+        Ok(change) => seen.insert(merge_key(change)),
+        Err(_) => true,
+    })
+}
+
+fn merge_key(
+    change: &PendingChangeResult,
+) -> (mem::Discriminant<PendingChangeResult>, RepoPathBuf) {
+    let path = match change {
+        PendingChangeResult::File(change_type) => change_type.get_path(),
+        PendingChangeResult::SeenDirectory(path) => path,
+        PendingChangeResult::Copied(copy) => &copy.path,
+    };
+    (mem::discriminant(change), path.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use types::testutil::*;
+
+    use super::*;
+
+    fn changed(path: &str) -> Result<PendingChangeResult> {
+        Ok(PendingChangeResult::File(ChangeType::Changed(
+            repo_path_buf(path),
+        )))
+    }
+
+    fn deleted(path: &str) -> Result<PendingChangeResult> {
+        Ok(PendingChangeResult::File(ChangeType::Deleted(
+            repo_path_buf(path),
+        )))
+    }
+
+    fn describe(result: Result<PendingChangeResult>) -> String {
+        match result {
+            Ok(PendingChangeResult::File(ChangeType::Changed(path))) => format!("M {}", path),
+            Ok(PendingChangeResult::File(ChangeType::Deleted(path))) => format!("! {}", path),
+            Ok(PendingChangeResult::SeenDirectory(path)) => format!("D {}", path),
+            Ok(PendingChangeResult::Copied(copy)) => format!("C {} {}", copy.source, copy.path),
+            Err(err) => format!("E {}", err),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = vec![
+            changed("a"),
+            deleted("b"),
+            Ok(PendingChangeResult::SeenDirectory(repo_path_buf("dir"))),
+            changed("dir/c"),
+        ];
+        let b = vec![
+            changed("b"),
+            deleted("a"),
+            changed("dir/c"),
+            Err(anyhow!("oops")),
+            changed("dir/d"),
+            Ok(PendingChangeResult::SeenDirectory(repo_path_buf("dir"))),
+            Ok(PendingChangeResult::Copied(CopyInfo {
+                path: repo_path_buf("dir/d"),
+                source: repo_path_buf("e"),
+                renamed: false,
+            })),
+            changed("dir/d"),
+        ];
+        assert_eq!(
+            merge(a.into_iter(), b.into_iter())
+                .map(describe)
+                .collect::<Vec<_>>(),
+            vec![
+                "M a",
+                "! b",
+                "D dir",
+                "M dir/c",
+                "E oops",
+                "M dir/d",
+                "C e dir/d",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_disjoint() {
+        let merged: Vec<_> = merge(
+            vec![changed("a")].into_iter(),
+            vec![changed("b"), deleted("c")].into_iter(),
+        )
+        .map(describe)
+        .collect();
+        assert_eq!(merged, vec!["M a", "M b", "! c"]);
+
+        assert_eq!(merge(std::iter::empty(), std::iter::empty()).count(), 0);
+    }
 }
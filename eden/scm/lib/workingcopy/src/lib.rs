@@ -8,12 +8,15 @@
 #[cfg(feature = "eden")]
 pub mod edenfs;
 
+pub mod conflicts;
 mod errors;
 mod filechangedetector;
 pub mod filesystem;
 pub mod git;
 mod metadata;
+pub mod mtimecache;
 pub mod physicalfs;
+pub mod snapshot;
 pub mod sparse;
 pub mod status;
 mod util;
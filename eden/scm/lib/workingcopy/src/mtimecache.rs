@@ -0,0 +1,266 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Cache of files known to match a manifest file node.
+//!
+//! When the treestate cannot tell whether a file changed, `pending_changes`
+//! fetches its content from the store and compares it with the disk. The
+//! cache remembers the mtime and size each such file had when its content
+//! last matched, so later runs can skip the comparison as long as the file
+//! was not touched.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use types::HgId;
+use types::RepoPath;
+use types::RepoPathBuf;
+
+/// Name of the cache file in the `.hg` directory.
+pub const MTIME_CACHE_FILE: &str = "mtimecache";
+
+/// Files modified less than this long before the cache was opened are not
+/// cached. Some file systems only store mtimes to the second, so a file
+/// written again right after being compared could keep the same mtime.
+const MTIME_GRANULARITY: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug)]
+pub struct MtimeCacheOptions {
+    /// File the cache is loaded from and saved to.
+    pub path: PathBuf,
+    /// Maximum number of files in the cache. Files are not added to a full
+    /// cache.
+    pub max_entries: usize,
+}
+
+impl MtimeCacheOptions {
+    /// Options for the default cache file in `dot_dir`.
+    pub fn new(dot_dir: &Path) -> Self {
+        Self {
+            path: dot_dir.join(MTIME_CACHE_FILE),
+            max_entries: 1_000_000,
+        }
+    }
+}
+
+/// The state of a file when its content last matched `hash`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MtimeCacheEntry {
+    #[serde(with = "mincode::time::system_time")]
+    pub mtime: SystemTime,
+    pub size: u64,
+    pub hash: HgId,
+}
+
+pub struct MtimeCache {
+    options: MtimeCacheOptions,
+    entries: HashMap<RepoPathBuf, MtimeCacheEntry>,
+    opened: SystemTime,
+    dirty: bool,
+}
+
+impl MtimeCache {
+    /// Load the cache from `options.path`.
+    ///
+    /// A missing or unreadable cache file gives an empty cache.
+    pub fn open(options: MtimeCacheOptions) -> Result<Self> {
+        let opened = SystemTime::now();
+        let entries = match util::file::exists(&options.path)? {
+            None => HashMap::new(),
+            Some(_) => {
+                let data = util::file::read(&options.path)?;
+                match mincode::deserialize(&data) {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        tracing::warn!(path=?options.path, ?err, "ignoring corrupt mtime cache");
+                        HashMap::new()
+                    }
+                }
+            }
+        };
+        Ok(Self {
+            options,
+            entries,
+            opened,
+            dirty: false,
+        })
+    }
+
+    pub fn get(&self, path: &RepoPath) -> Option<&MtimeCacheEntry> {
+        self.entries.get(path)
+    }
+
+    /// Whether `path` is known to match `hash`, given its current mtime and
+    /// size on disk.
+    pub fn is_unchanged(&self, path: &RepoPath, mtime: SystemTime, size: u64, hash: HgId) -> bool {
+        matches!(
+            self.entries.get(path),
+            Some(entry) if entry.mtime == mtime && entry.size == size && entry.hash == hash
+        )
+    }
+
+    /// Record that `path` matched `entry.hash` with the given mtime and size.
+    ///
+    /// Files modified just before the cache was opened, or before the Unix
+    /// epoch, are forgotten instead.
+    pub fn insert(&mut self, path: RepoPathBuf, entry: MtimeCacheEntry) {
+        if entry.mtime < UNIX_EPOCH || entry.mtime + MTIME_GRANULARITY > self.opened {
+            self.remove(&path);
+            return;
+        }
+        if self.entries.len() >= self.options.max_entries && !self.entries.contains_key(&path) {
+            return;
+        }
+        if self.entries.insert(path, entry) != Some(entry) {
+            self.dirty = true;
+        }
+    }
+
+    /// Forget `path`, for example because it was deleted or changed.
+    pub fn remove(&mut self, path: &RepoPath) {
+        if self.entries.remove(path).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the cache back to disk if it changed since it was loaded.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let data = mincode::serialize(&self.entries)?;
+        util::file::atomic_write(&self.options.path, |f| f.write_all(&data))?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::testutil::*;
+
+    use super::*;
+
+    fn entry(secs: u64, size: u64, hash: HgId) -> MtimeCacheEntry {
+        MtimeCacheEntry {
+            mtime: UNIX_EPOCH + Duration::from_secs(secs),
+            size,
+            hash,
+        }
+    }
+
+    #[test]
+    fn test_save_and_open() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let options = MtimeCacheOptions::new(dir.path());
+
+        let mut cache = MtimeCache::open(options.clone())?;
+        assert!(cache.is_empty());
+        cache.insert(repo_path_buf("a"), entry(10, 3, hgid("1")));
+        cache.insert(repo_path_buf("b/c"), entry(20, 5, hgid("2")));
+        cache.save()?;
+
+        let mut cache = MtimeCache::open(options.clone())?;
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(repo_path("a")), Some(&entry(10, 3, hgid("1"))));
+        assert!(cache.is_unchanged(
+            repo_path("b/c"),
+            UNIX_EPOCH + Duration::from_secs(20),
+            5,
+            hgid("2")
+        ));
+        assert!(!cache.is_unchanged(
+            repo_path("b/c"),
+            UNIX_EPOCH + Duration::from_secs(21),
+            5,
+            hgid("2")
+        ));
+        assert!(!cache.is_unchanged(
+            repo_path("b/c"),
+            UNIX_EPOCH + Duration::from_secs(20),
+            6,
+            hgid("2")
+        ));
+        assert!(!cache.is_unchanged(
+            repo_path("b/c"),
+            UNIX_EPOCH + Duration::from_secs(20),
+            5,
+            hgid("3")
+        ));
+
+        cache.remove(repo_path("a"));
+        cache.save()?;
+        let cache = MtimeCache::open(options)?;
+        assert_eq!(cache.get(repo_path("a")), None);
+        assert_eq!(cache.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_entries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut options = MtimeCacheOptions::new(dir.path());
+        options.max_entries = 1;
+
+        let mut cache = MtimeCache::open(options)?;
+        cache.insert(repo_path_buf("a"), entry(10, 3, hgid("1")));
+        cache.insert(repo_path_buf("b"), entry(10, 3, hgid("1")));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(repo_path("b")), None);
+
+        // Existing entries can still be updated.
+        cache.insert(repo_path_buf("a"), entry(11, 4, hgid("1")));
+        assert_eq!(cache.get(repo_path("a")), Some(&entry(11, 4, hgid("1"))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recent_mtime() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut cache = MtimeCache::open(MtimeCacheOptions::new(dir.path()))?;
+        let recent = MtimeCacheEntry {
+            mtime: SystemTime::now(),
+            size: 3,
+            hash: hgid("1"),
+        };
+        cache.insert(repo_path_buf("a"), entry(10, 3, hgid("1")));
+        cache.insert(repo_path_buf("a"), recent);
+        assert_eq!(cache.get(repo_path("a")), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let options = MtimeCacheOptions::new(dir.path());
+        std::fs::write(&options.path, b"\xff\xff\xff")?;
+
+        let cache = MtimeCache::open(options)?;
+        assert!(cache.is_empty());
+
+        Ok(())
+    }
+}
@@ -6,8 +6,10 @@
  */
 
 use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use std::time::SystemTime;
 
 use anyhow::Result;
@@ -31,7 +33,12 @@ use crate::filechangedetector::ResolvedFileChangeResult;
 use crate::filesystem::ChangeType;
 use crate::filesystem::PendingChangeResult;
 use crate::filesystem::PendingChanges as PendingChangesTrait;
+use crate::filesystem::ProgressCallback;
+use crate::filesystem::ProgressReporter;
+use crate::filesystem::PROGRESS_INTERVAL;
 use crate::metadata;
+use crate::mtimecache::MtimeCache;
+use crate::mtimecache::MtimeCacheOptions;
 use crate::walker::WalkEntry;
 use crate::walker::Walker;
 use crate::workingcopy::WorkingCopy;
@@ -46,6 +53,47 @@ pub struct PhysicalFileSystem {
     store: ArcReadFileContents,
     treestate: Arc<Mutex<TreeState>>,
     include_directories: bool,
+    options: PhysicalFileSystemOptions,
+    progress: Option<ProgressCallback>,
+    progress_interval: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct PhysicalFileSystemOptions {
+    /// Number of threads walking the working copy. Defaults to 8, the
+    /// number of threads the walker always used before this was
+    /// configurable.
+    pub parallelism: usize,
+    /// Remember the mtime and size of files whose contents were compared,
+    /// to skip comparing them again while they are untouched.
+    pub mtime_cache: Option<MtimeCacheOptions>,
+}
+
+impl Default for PhysicalFileSystemOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: 8,
+            mtime_cache: None,
+        }
+    }
+}
+
+impl PhysicalFileSystemOptions {
+    /// Options for the working copy whose `.hg` directory is `dot_dir`.
+    ///
+    /// The number of walker threads is read from `workingcopy.walker-threads`.
+    /// The mtime cache is enabled unless `workingcopy.use-mtime-cache` is
+    /// false.
+    pub fn from_config(config: &dyn Config, dot_dir: &Path) -> Result<Self> {
+        let mut options = Self::default();
+        if let Some(parallelism) = config.get_opt("workingcopy", "walker-threads")? {
+            options.parallelism = parallelism;
+        }
+        if config.get_or::<bool>("workingcopy", "use-mtime-cache", || true)? {
+            options.mtime_cache = Some(MtimeCacheOptions::new(dot_dir));
+        }
+        Ok(options)
+    }
 }
 
 impl PhysicalFileSystem {
@@ -62,8 +110,16 @@ impl PhysicalFileSystem {
             store,
             treestate,
             include_directories,
+            options: PhysicalFileSystemOptions::default(),
+            progress: None,
+            progress_interval: PROGRESS_INTERVAL,
         })
     }
+
+    pub fn with_options(mut self, options: PhysicalFileSystemOptions) -> Self {
+        self.options = options;
+        self
+    }
 }
 
 impl PendingChangesTrait for PhysicalFileSystem {
@@ -84,16 +140,21 @@ impl PendingChangesTrait for PhysicalFileSystem {
             ignore_dirs,
             matcher.clone(),
             false,
+            self.options.parallelism,
         )?;
         let manifests =
             WorkingCopy::current_manifests(&self.treestate.lock(), &self.tree_resolver)?;
-        let file_change_detector = FileChangeDetector::new(
+        let mut file_change_detector = FileChangeDetector::new(
             self.vfs.clone(),
             last_write.try_into()?,
             manifests[0].clone(),
             self.store.clone(),
             config.get_opt("workingcopy", "worker-count")?,
         );
+        if let Some(options) = &self.options.mtime_cache {
+            file_change_detector =
+                file_change_detector.with_mtime_cache(MtimeCache::open(options.clone())?);
+        }
         let pending_changes = PendingChanges {
             walker,
             matcher,
@@ -104,9 +165,14 @@ impl PendingChangesTrait for PhysicalFileSystem {
             tree_iter: None,
             lookup_iter: None,
             file_change_detector: Some(file_change_detector),
+            progress: ProgressReporter::new(self.progress.clone(), self.progress_interval),
         };
         Ok(Box::new(pending_changes))
     }
+
+    fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.progress = callback;
+    }
 }
 
 pub struct PendingChanges<M: Matcher + Clone + Send + Sync + 'static> {
@@ -119,6 +185,7 @@ pub struct PendingChanges<M: Matcher + Clone + Send + Sync + 'static> {
     tree_iter: Option<Box<dyn Iterator<Item = Result<PendingChangeResult>> + Send>>,
     lookup_iter: Option<Box<dyn Iterator<Item = Result<PendingChangeResult>> + Send>>,
     file_change_detector: Option<FileChangeDetector>,
+    progress: ProgressReporter,
 }
 
 #[derive(PartialEq)]
@@ -160,6 +227,7 @@ impl<M: Matcher + Clone + Send + Sync + 'static> PendingChanges<M> {
                         path = RepoPathBuf::from_utf8(normalized.into_owned())?;
                     }
                     self.seen.insert(path.clone());
+                    self.progress.update(|p| p.files_scanned += 1);
                     let changed = self
                         .file_change_detector
                         .as_mut()
@@ -175,6 +243,7 @@ impl<M: Matcher + Clone + Send + Sync + 'static> PendingChanges<M> {
                     }
                 }
                 Some(Ok(WalkEntry::Directory(dir))) => {
+                    self.progress.update(|p| p.dirs_scanned += 1);
                     if self.include_directories {
                         return Ok(Some(PendingChangeResult::SeenDirectory(dir)));
                     }
@@ -232,6 +301,9 @@ impl<M: Matcher + Clone + Send + Sync + 'static> PendingChanges<M> {
                 }
 
                 // This path is EXIST_P1 but not on disk - emit deleted event.
+                if let Some(detector) = &self.file_change_detector {
+                    detector.forget(&path);
+                }
                 Some(Ok(PendingChangeResult::File(ChangeType::Deleted(
                     path.to_owned(),
                 ))))
@@ -295,14 +367,153 @@ impl<M: Matcher + Clone + Send + Sync + 'static> Iterator for PendingChanges<M>
                 PendingChangesStage::Finished => None,
             };
 
+            if let Some(Ok(PendingChangeResult::File(_))) = change {
+                self.progress.update(|p| p.changes_found += 1);
+            }
             if change.is_some() {
                 return change;
             }
 
             self.stage = self.stage.next();
             if self.stage == PendingChangesStage::Finished {
+                self.progress.finish();
                 return None;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use futures::stream;
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use manifest_tree::testutil::make_tree_manifest_from_meta;
+    use manifest_tree::testutil::TestStore;
+    use manifest_tree::TreeManifest;
+    use parking_lot::RwLock;
+    use pathmatcher::AlwaysMatcher;
+    use storemodel::minibytes::Bytes;
+    use types::HgId;
+    use types::Key;
+
+    use super::*;
+    use crate::filesystem::ProgressReport;
+
+    struct TreeResolver(Arc<RwLock<TreeManifest>>);
+
+    impl ReadTreeManifest for TreeResolver {
+        fn get(&self, _commit_id: &HgId) -> Result<Arc<RwLock<TreeManifest>>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    struct EmptyStore;
+
+    #[async_trait::async_trait]
+    impl ReadFileContents for EmptyStore {
+        type Error = anyhow::Error;
+
+        async fn read_file_contents(
+            &self,
+            _keys: Vec<Key>,
+        ) -> BoxStream<Result<(Bytes, Key), Self::Error>> {
+            stream::empty().boxed()
+        }
+
+        async fn read_rename_metadata(
+            &self,
+            _keys: Vec<Key>,
+        ) -> BoxStream<Result<(Key, Option<Key>), Self::Error>> {
+            stream::empty().boxed()
+        }
+    }
+
+    #[test]
+    fn test_progress() -> Result<()> {
+        const DIRS: u64 = 50;
+        const FILES_PER_DIR: u64 = 40;
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir_all(root.join(".hg"))?;
+        for d in 0..DIRS {
+            let dir_path = root.join(format!("dir{}", d));
+            fs::create_dir(&dir_path)?;
+            for f in 0..FILES_PER_DIR {
+                fs::write(dir_path.join(format!("file{}", f)), "x\n")?;
+            }
+        }
+
+        let (treestate, _) = TreeState::new(dir.path(), true)?;
+        let manifest = make_tree_manifest_from_meta(Arc::new(TestStore::new()), Vec::new());
+        let reports: Arc<Mutex<Vec<ProgressReport>>> = Default::default();
+        let mut fs = PhysicalFileSystem::new(
+            VFS::new(root)?,
+            Arc::new(TreeResolver(Arc::new(RwLock::new(manifest)))),
+            Arc::new(EmptyStore),
+            Arc::new(Mutex::new(treestate)),
+            false,
+        )?;
+        fs.progress_interval = Duration::ZERO;
+        // Set the callback the way WorkingCopy does, through a trait object.
+        let mut fs: Box<dyn PendingChangesTrait + Send> = Box::new(fs);
+        fs.set_progress(Some({
+            let reports = reports.clone();
+            Arc::new(move |report| reports.lock().push(report))
+        }));
+
+        let config = BTreeMap::<&str, &str>::new();
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+        let changes = fs
+            .pending_changes(
+                Arc::new(AlwaysMatcher::new()),
+                Arc::new(AlwaysMatcher::new()),
+                Vec::new(),
+                SystemTime::UNIX_EPOCH,
+                &config,
+                &io,
+            )?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(changes.len() as u64, DIRS * FILES_PER_DIR);
+
+        let reports = reports.lock();
+        assert!(reports.len() >= 2);
+        assert!(
+            reports
+                .windows(2)
+                .all(|w| w[0].files_scanned <= w[1].files_scanned)
+        );
+        let last = reports.last().unwrap();
+        assert_eq!(last.files_scanned, DIRS * FILES_PER_DIR);
+        assert!(last.dirs_scanned >= DIRS);
+        assert_eq!(last.changes_found, DIRS * FILES_PER_DIR);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_interval() {
+        let calls: Arc<Mutex<Vec<ProgressReport>>> = Default::default();
+        let callback: ProgressCallback = {
+            let calls = calls.clone();
+            Arc::new(move |report| calls.lock().push(report))
+        };
+        let mut reporter = ProgressReporter::new(Some(callback), Duration::from_secs(3600));
+        for _ in 0..100 {
+            reporter.update(|p| p.files_scanned += 1);
+        }
+        assert_eq!(calls.lock().len(), 1);
+        assert_eq!(calls.lock()[0].files_scanned, 1);
+
+        // The final counts are reported once when the scan is done.
+        reporter.finish();
+        reporter.finish();
+        let calls = calls.lock();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].files_scanned, 100);
+    }
+}
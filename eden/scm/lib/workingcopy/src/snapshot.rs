@@ -0,0 +1,740 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Snapshots of pending changes, used to shelve and unshelve them.
+//!
+//! A snapshot records the working copy parent, and the changes of each file
+//! against it: line hunks for modified files, the full content of added
+//! files. Snapshots are stored in `.hg/shelved/{name}.snap`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use configmodel::Config;
+use futures::StreamExt;
+use io::IO;
+use manifest::Manifest;
+use pathmatcher::AlwaysMatcher;
+use serde::Deserialize;
+use serde::Serialize;
+use treestate::filestate::FileStateV2;
+use treestate::filestate::StateFlags;
+use types::hgid::NULL_ID;
+use types::HgId;
+use types::Key;
+use types::RepoPath;
+use types::RepoPathBuf;
+use vfs::UpdateFlag;
+use vfs::VFS;
+
+use crate::metadata::Metadata;
+use crate::workingcopy::WorkingCopy;
+
+/// Directory of the snapshots, in the `.hg` directory.
+const SNAPSHOT_DIR: &str = "shelved";
+
+/// Extension of the snapshot files.
+const SNAPSHOT_EXTENSION: &str = "snap";
+
+/// A saved snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotHandle {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum SnapshotFileType {
+    Regular,
+    Executable,
+    Symlink,
+}
+
+impl From<SnapshotFileType> for UpdateFlag {
+    fn from(file_type: SnapshotFileType) -> Self {
+        match file_type {
+            SnapshotFileType::Regular => UpdateFlag::Regular,
+            SnapshotFileType::Executable => UpdateFlag::Executable,
+            SnapshotFileType::Symlink => UpdateFlag::Symlink,
+        }
+    }
+}
+
+/// Lines `remove` of the base content are replaced by `add`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SnapshotHunk {
+    remove: Range<usize>,
+    add: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum SnapshotChange {
+    /// A file of p1, changed by `hunks`.
+    Modified {
+        base: HgId,
+        hunks: Vec<SnapshotHunk>,
+        file_type: SnapshotFileType,
+    },
+    Added {
+        content: Vec<u8>,
+        file_type: SnapshotFileType,
+    },
+    /// A file of p1 marked as removed.
+    Removed,
+    /// A file of p1 missing from disk, without being marked as removed.
+    Deleted,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotData {
+    p1: HgId,
+    files: Vec<(RepoPathBuf, SnapshotChange)>,
+}
+
+pub struct WorkingCopySnapshot;
+
+impl WorkingCopySnapshot {
+    /// Save the pending changes of `wc` as the snapshot `name`, replacing any
+    /// existing snapshot with that name. The working copy is not changed.
+    ///
+    /// Copy information is not kept.
+    pub fn save(
+        wc: &WorkingCopy,
+        name: &str,
+        config: &dyn Config,
+        io: &IO,
+    ) -> Result<SnapshotHandle> {
+        let handle = Self::handle(wc, name)?;
+        let p1 = wc.parents()?.into_iter().next().unwrap_or(NULL_ID);
+        let status = wc.status(
+            Arc::new(AlwaysMatcher::new()),
+            SystemTime::UNIX_EPOCH,
+            config,
+            io,
+        )?;
+
+        let manifest = wc.tree_manifest(&p1)?;
+        let mut keys = Vec::new();
+        for path in status.modified() {
+            match manifest.read().get_file(path)? {
+                Some(meta) => keys.push(Key::new(path.clone(), meta.hgid)),
+                None => bail!("modified file {} is not in {}", path, p1),
+            }
+        }
+        let mut bases = read_contents(wc, keys)?;
+
+        let vfs = wc.vfs();
+        let mut files = Vec::new();
+        for path in status.modified() {
+            let (base, base_content) = bases
+                .remove(path)
+                .ok_or_else(|| anyhow!("no contents for {}", path))?;
+            let (content, file_type) = read_file(vfs, path)?;
+            let lines = split_lines(&content);
+            let hunks = xdiff::diff_hunks(&base_content, &content)
+                .into_iter()
+                .map(|hunk| SnapshotHunk {
+                    remove: hunk.remove,
+                    add: lines[hunk.add].concat(),
+                })
+                .collect();
+            files.push((
+                path.clone(),
+                SnapshotChange::Modified {
+                    base,
+                    hunks,
+                    file_type,
+                },
+            ));
+        }
+        for path in status.added() {
+            let (content, file_type) = read_file(vfs, path)?;
+            files.push((path.clone(), SnapshotChange::Added { content, file_type }));
+        }
+        for path in status.removed() {
+            files.push((path.clone(), SnapshotChange::Removed));
+        }
+        for path in status.deleted() {
+            files.push((path.clone(), SnapshotChange::Deleted));
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        tracing::debug!(snapshot = name, %p1, files = files.len(), "saving snapshot");
+        let data = mincode::serialize(&SnapshotData { p1, files })?;
+        util::path::create_dir(snapshot_dir(wc))?;
+        util::file::atomic_write(&handle.path, |f| f.write_all(&data))?;
+        Ok(handle)
+    }
+
+    /// Apply the snapshot to `wc`.
+    ///
+    /// The working copy parent must be the parent the snapshot was saved
+    /// on, and there must be no pending changes, as the snapshot is applied
+    /// to the content of the files in that parent. The working copy cannot
+    /// check out another commit by itself, so going back to that parent is
+    /// left to the caller. Nothing is written if the snapshot does not
+    /// apply. The treestate is updated, but not flushed.
+    pub fn restore(
+        wc: &WorkingCopy,
+        handle: &SnapshotHandle,
+        config: &dyn Config,
+        io: &IO,
+    ) -> Result<()> {
+        let _lock = wc.lock()?;
+        let data: SnapshotData = mincode::deserialize(&util::file::read(&handle.path)?)?;
+        tracing::debug!(snapshot = %handle.name, p1 = %data.p1, "restoring snapshot");
+
+        let p1 = wc.parents()?.into_iter().next().unwrap_or(NULL_ID);
+        if p1 != data.p1 {
+            bail!(
+                "snapshot {} was saved on {}, but the working copy parent is {}",
+                handle.name,
+                data.p1,
+                p1
+            );
+        }
+        let status = wc.status(
+            Arc::new(AlwaysMatcher::new()),
+            SystemTime::UNIX_EPOCH,
+            config,
+            io,
+        )?;
+        if status
+            .modified()
+            .chain(status.added())
+            .chain(status.removed())
+            .chain(status.deleted())
+            .next()
+            .is_some()
+        {
+            bail!(
+                "cannot restore snapshot {} over pending changes",
+                handle.name
+            );
+        }
+
+        let keys = data
+            .files
+            .iter()
+            .filter_map(|(path, change)| match change {
+                SnapshotChange::Modified { base, .. } => Some(Key::new(path.clone(), *base)),
+                _ => None,
+            })
+            .collect();
+        let mut bases = read_contents(wc, keys)?;
+
+        // Apply the hunks of all the files before writing any of them, so
+        // that a snapshot that does not apply leaves the working copy alone.
+        let mut contents = HashMap::new();
+        for (path, change) in data.files.iter() {
+            if let SnapshotChange::Modified { hunks, .. } = change {
+                let (_, base_content) = bases
+                    .remove(path)
+                    .ok_or_else(|| anyhow!("no contents for {}", path))?;
+                let content = apply_hunks(&base_content, hunks)
+                    .ok_or_else(|| anyhow!("snapshot does not apply to {}", path))?;
+                contents.insert(path.clone(), content);
+            }
+        }
+
+        let vfs = wc.vfs().clone();
+        let treestate = wc.treestate();
+        let mut treestate = treestate.lock();
+        for (path, change) in data.files {
+            let state = treestate.get(&path)?.cloned();
+            match change {
+                SnapshotChange::Modified { file_type, .. } => {
+                    let content = contents
+                        .remove(&path)
+                        .ok_or_else(|| anyhow!("no contents for {}", path))?;
+                    vfs.write(&path, &content, file_type.into())?;
+                    if let Some(mut state) = state {
+                        state.state |= StateFlags::NEED_CHECK;
+                        treestate.insert(&path, &state)?;
+                    }
+                }
+                SnapshotChange::Added { content, file_type } => {
+                    vfs.write(&path, &content, file_type.into())?;
+                    let state = match state {
+                        Some(mut state) => {
+                            state.state |= StateFlags::EXIST_NEXT | StateFlags::NEED_CHECK;
+                            state
+                        }
+                        None => FileStateV2 {
+                            mode: 0,
+                            size: -1,
+                            mtime: -1,
+                            state: StateFlags::EXIST_NEXT,
+                            copied: None,
+                        },
+                    };
+                    treestate.insert(&path, &state)?;
+                }
+                SnapshotChange::Removed => {
+                    vfs.remove(&path)?;
+                    if let Some(mut state) = state {
+                        state.state.remove(StateFlags::EXIST_NEXT);
+                        treestate.insert(&path, &state)?;
+                    }
+                }
+                SnapshotChange::Deleted => vfs.remove(&path)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// All the snapshots of `wc`, sorted by name.
+    pub fn list(wc: &WorkingCopy) -> Result<Vec<SnapshotHandle>> {
+        let dir = snapshot_dir(wc);
+        if util::file::exists(&dir)?.is_none() {
+            return Ok(Vec::new());
+        }
+        let mut handles = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SNAPSHOT_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                handles.push(SnapshotHandle {
+                    name: name.to_string(),
+                    path: path.clone(),
+                });
+            }
+        }
+        handles.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(handles)
+    }
+
+    pub fn delete(handle: &SnapshotHandle) -> Result<()> {
+        match util::path::remove_file(&handle.path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    fn handle(wc: &WorkingCopy, name: &str) -> Result<SnapshotHandle> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("invalid snapshot name {:?}", name);
+        }
+        Ok(SnapshotHandle {
+            name: name.to_string(),
+            path: snapshot_dir(wc).join(format!("{}.{}", name, SNAPSHOT_EXTENSION)),
+        })
+    }
+}
+
+fn snapshot_dir(wc: &WorkingCopy) -> PathBuf {
+    wc.dot_hg_path().join(SNAPSHOT_DIR)
+}
+
+fn read_file(vfs: &VFS, path: &RepoPath) -> Result<(Vec<u8>, SnapshotFileType)> {
+    let (content, metadata) = vfs.read_with_metadata(path)?;
+    let metadata = Metadata::from(metadata);
+    let file_type = if metadata.is_symlink(vfs) {
+        SnapshotFileType::Symlink
+    } else if metadata.is_executable(vfs) {
+        SnapshotFileType::Executable
+    } else {
+        SnapshotFileType::Regular
+    };
+    Ok((content.into_vec(), file_type))
+}
+
+/// Read the contents of `keys` from the store, by path.
+fn read_contents(
+    wc: &WorkingCopy,
+    keys: Vec<Key>,
+) -> Result<HashMap<RepoPathBuf, (HgId, Vec<u8>)>> {
+    if keys.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let store = wc.file_store();
+    async_runtime::block_on(async {
+        let mut contents = HashMap::new();
+        let mut results = store.read_file_contents(keys).await;
+        while let Some(result) = results.next().await {
+            let (bytes, key) = result?;
+            contents.insert(key.path, (key.hgid, bytes.into_vec()));
+        }
+        Ok::<_, anyhow::Error>(contents)
+    })
+}
+
+/// Split `text` into lines, each ending with its `\n` if any, as `xdiff`
+/// does.
+fn split_lines(text: &[u8]) -> Vec<&[u8]> {
+    text.split_inclusive(|&b| b == b'\n').collect()
+}
+
+/// Apply `hunks` to `base`. Returns `None` if they do not fit.
+fn apply_hunks(base: &[u8], hunks: &[SnapshotHunk]) -> Option<Vec<u8>> {
+    let lines = split_lines(base);
+    let mut content = Vec::with_capacity(base.len());
+    let mut pos = 0;
+    for hunk in hunks {
+        if hunk.remove.start < pos
+            || hunk.remove.start > hunk.remove.end
+            || hunk.remove.end > lines.len()
+        {
+            return None;
+        }
+        content.extend(lines[pos..hunk.remove.start].concat());
+        content.extend_from_slice(&hunk.add);
+        pos = hunk.remove.end;
+    }
+    content.extend(lines[pos..].concat());
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use futures::stream;
+    use futures::stream::BoxStream;
+    use manifest::FileMetadata;
+    use manifest_tree::testutil::make_tree_manifest_from_meta;
+    use manifest_tree::testutil::TestStore;
+    use manifest_tree::ReadTreeManifest;
+    use manifest_tree::TreeManifest;
+    use parking_lot::Mutex;
+    use parking_lot::RwLock;
+    use repolock::RepoLocker;
+    use storemodel::minibytes::Bytes;
+    use storemodel::ReadFileContents;
+    use treestate::treestate::TreeState;
+    use types::repo::StorageFormat;
+    use types::testutil::*;
+
+    use super::*;
+    use crate::filesystem::FileSystemType;
+
+    /// The files of p1: path, file id and content.
+    const P1_FILES: &[(&str, &str, &str)] = &[
+        ("a", "1", "1\n2\n3\n4\n5\n"),
+        ("b", "2", "b\n"),
+        ("dir/c", "3", "c\n"),
+    ];
+
+    struct TreeResolver(Arc<RwLock<TreeManifest>>);
+
+    impl ReadTreeManifest for TreeResolver {
+        fn get(&self, _commit_id: &HgId) -> Result<Arc<RwLock<TreeManifest>>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    struct FileStore(HashMap<HgId, Bytes>);
+
+    #[async_trait::async_trait]
+    impl ReadFileContents for FileStore {
+        type Error = anyhow::Error;
+
+        async fn read_file_contents(
+            &self,
+            keys: Vec<Key>,
+        ) -> BoxStream<Result<(Bytes, Key), Self::Error>> {
+            let contents = keys
+                .into_iter()
+                .map(|k| match self.0.get(&k.hgid) {
+                    Some(content) => Ok((content.clone(), k)),
+                    None => Err(anyhow!("no such file {}", k)),
+                })
+                .collect::<Vec<_>>();
+            stream::iter(contents).boxed()
+        }
+
+        async fn read_rename_metadata(
+            &self,
+            _keys: Vec<Key>,
+        ) -> BoxStream<Result<(Key, Option<Key>), Self::Error>> {
+            stream::empty().boxed()
+        }
+    }
+
+    fn new_working_copy(
+        root: &std::path::Path,
+        treestate_dir: &std::path::Path,
+    ) -> Result<WorkingCopy> {
+        fs::create_dir(root.join(".hg"))?;
+        let (mut treestate, _) = TreeState::new(treestate_dir, true)?;
+        let mut store = HashMap::new();
+        let mut files = Vec::new();
+        for (path, id, content) in P1_FILES {
+            let path = repo_path_buf(path);
+            fs::create_dir_all(root.join(path.as_str()).parent().unwrap())?;
+            fs::write(root.join(path.as_str()), content)?;
+            treestate.insert(
+                &path,
+                &FileStateV2 {
+                    mode: 0o644,
+                    size: -1,
+                    mtime: -1,
+                    state: StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT,
+                    copied: None,
+                },
+            )?;
+            store.insert(hgid(id), Bytes::from_static(content.as_bytes()));
+            files.push((path, FileMetadata::regular(hgid(id))));
+        }
+        treestate.set_parents(&mut std::iter::once(&hgid("100")))?;
+
+        let manifest = make_tree_manifest_from_meta(Arc::new(TestStore::new()), files);
+        let config = BTreeMap::<&str, &str>::new();
+        WorkingCopy::new(
+            VFS::new(root.to_path_buf())?,
+            StorageFormat::Revlog,
+            FileSystemType::Normal,
+            Arc::new(Mutex::new(treestate)),
+            Arc::new(TreeResolver(Arc::new(RwLock::new(manifest)))),
+            Arc::new(FileStore(store)),
+            &config,
+            Arc::new(RepoLocker::new(&config, root.join(".hg/store"))?),
+        )
+    }
+
+    fn status(wc: &WorkingCopy) -> Result<Vec<String>> {
+        let config = BTreeMap::<&str, &str>::new();
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+        let status = wc.status(
+            Arc::new(AlwaysMatcher::new()),
+            SystemTime::UNIX_EPOCH,
+            &config,
+            &io,
+        )?;
+        let mut files: Vec<String> = status
+            .modified()
+            .map(|p| format!("M {}", p))
+            .chain(status.added().map(|p| format!("A {}", p)))
+            .chain(status.removed().map(|p| format!("R {}", p)))
+            .chain(status.deleted().map(|p| format!("! {}", p)))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Undo all pending changes, as `revert --all` would.
+    fn revert(wc: &WorkingCopy) -> Result<()> {
+        let root = wc.vfs().root();
+        for (path, _, content) in P1_FILES {
+            fs::write(root.join(path), content)?;
+        }
+        let treestate = wc.treestate();
+        let mut treestate = treestate.lock();
+        for (path, _, _) in P1_FILES {
+            let mut state = treestate.get(path)?.unwrap().clone();
+            state.state |= StateFlags::EXIST_NEXT | StateFlags::NEED_CHECK;
+            treestate.insert(path, &state)?;
+        }
+        treestate.remove("new")?;
+        fs::remove_file(root.join("new"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_restore() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir(&root)?;
+        let wc = new_working_copy(&root, dir.path())?;
+        let config = BTreeMap::<&str, &str>::new();
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+
+        // Modify, add, remove and delete files.
+        fs::write(root.join("a"), "0\n1\nTWO\n3\n4\n")?;
+        fs::write(root.join("new"), "new\n")?;
+        {
+            let treestate = wc.treestate();
+            let mut treestate = treestate.lock();
+            treestate.insert(
+                "new",
+                &FileStateV2 {
+                    mode: 0,
+                    size: -1,
+                    mtime: -1,
+                    state: StateFlags::EXIST_NEXT,
+                    copied: None,
+                },
+            )?;
+            let mut state = treestate.get("b")?.unwrap().clone();
+            state.state.remove(StateFlags::EXIST_NEXT);
+            treestate.insert("b", &state)?;
+        }
+        fs::remove_file(root.join("b"))?;
+        fs::remove_file(root.join("dir/c"))?;
+        let before = status(&wc)?;
+        assert_eq!(before, vec!["! dir/c", "A new", "M a", "R b"]);
+
+        let handle = WorkingCopySnapshot::save(&wc, "test", &config, &io)?;
+        assert!(root.join(".hg/shelved/test.snap").exists());
+        assert_eq!(WorkingCopySnapshot::list(&wc)?, vec![handle.clone()]);
+
+        revert(&wc)?;
+        assert!(status(&wc)?.is_empty());
+
+        WorkingCopySnapshot::restore(&wc, &handle, &config, &io)?;
+        assert_eq!(wc.parents()?, vec![hgid("100")]);
+        assert_eq!(fs::read_to_string(root.join("a"))?, "0\n1\nTWO\n3\n4\n");
+        assert_eq!(fs::read_to_string(root.join("new"))?, "new\n");
+        assert!(!root.join("b").exists());
+        assert!(!root.join("dir/c").exists());
+        assert_eq!(status(&wc)?, before);
+
+        WorkingCopySnapshot::delete(&handle)?;
+        assert!(WorkingCopySnapshot::list(&wc)?.is_empty());
+        // Deleting twice is fine.
+        WorkingCopySnapshot::delete(&handle)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_mismatch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir(&root)?;
+        let mut wc = new_working_copy(&root, dir.path())?;
+        let config = BTreeMap::<&str, &str>::new();
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+
+        fs::write(root.join("a"), "0\n1\n2\n3\n4\n5\n")?;
+        let handle = WorkingCopySnapshot::save(&wc, "test", &config, &io)?;
+
+        // Pending changes would be overwritten.
+        fs::write(root.join("a"), "1\n2\n3\n4\n5\n6\n")?;
+        assert!(WorkingCopySnapshot::restore(&wc, &handle, &config, &io).is_err());
+        assert_eq!(fs::read_to_string(root.join("a"))?, "1\n2\n3\n4\n5\n6\n");
+
+        // The snapshot does not apply to another parent.
+        fs::write(root.join("a"), "1\n2\n3\n4\n5\n")?;
+        assert!(status(&wc)?.is_empty());
+        wc.set_parents(&mut std::iter::once(&hgid("200")))?;
+        assert!(WorkingCopySnapshot::restore(&wc, &handle, &config, &io).is_err());
+        assert_eq!(wc.parents()?, vec![hgid("200")]);
+        assert_eq!(fs::read_to_string(root.join("a"))?, "1\n2\n3\n4\n5\n");
+
+        wc.set_parents(&mut std::iter::once(&hgid("100")))?;
+        WorkingCopySnapshot::restore(&wc, &handle, &config, &io)?;
+        assert_eq!(fs::read_to_string(root.join("a"))?, "0\n1\n2\n3\n4\n5\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_does_not_apply() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir(&root)?;
+        let wc = new_working_copy(&root, dir.path())?;
+        let config = BTreeMap::<&str, &str>::new();
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+
+        // The change to "a" applies, but the one to "b" does not.
+        let data = SnapshotData {
+            p1: hgid("100"),
+            files: vec![
+                (
+                    repo_path_buf("a"),
+                    SnapshotChange::Modified {
+                        base: hgid("1"),
+                        hunks: vec![SnapshotHunk {
+                            remove: 0..1,
+                            add: b"one\n".to_vec(),
+                        }],
+                        file_type: SnapshotFileType::Regular,
+                    },
+                ),
+                (
+                    repo_path_buf("b"),
+                    SnapshotChange::Modified {
+                        base: hgid("2"),
+                        hunks: vec![SnapshotHunk {
+                            remove: 5..6,
+                            add: Vec::new(),
+                        }],
+                        file_type: SnapshotFileType::Regular,
+                    },
+                ),
+            ],
+        };
+        let handle = WorkingCopySnapshot::handle(&wc, "bad")?;
+        util::path::create_dir(snapshot_dir(&wc))?;
+        fs::write(&handle.path, mincode::serialize(&data)?)?;
+
+        assert!(WorkingCopySnapshot::restore(&wc, &handle, &config, &io).is_err());
+        assert_eq!(fs::read_to_string(root.join("a"))?, "1\n2\n3\n4\n5\n");
+        assert!(status(&wc)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("repo");
+        fs::create_dir(&root)?;
+        let wc = new_working_copy(&root, dir.path())?;
+        let config = BTreeMap::<&str, &str>::new();
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+
+        assert!(WorkingCopySnapshot::list(&wc)?.is_empty());
+        WorkingCopySnapshot::save(&wc, "b", &config, &io)?;
+        WorkingCopySnapshot::save(&wc, "a", &config, &io)?;
+        fs::write(root.join(".hg/shelved/other"), "")?;
+        assert_eq!(
+            WorkingCopySnapshot::list(&wc)?
+                .into_iter()
+                .map(|h| h.name)
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        for name in ["", ".hidden", "a/b", "a\\b"] {
+            assert!(WorkingCopySnapshot::save(&wc, name, &config, &io).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_hunks() {
+        let check = |base: &str, new: &str| {
+            let lines = split_lines(new.as_bytes());
+            let hunks = xdiff::diff_hunks(base, new)
+                .into_iter()
+                .map(|hunk| SnapshotHunk {
+                    remove: hunk.remove,
+                    add: lines[hunk.add].concat(),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                apply_hunks(base.as_bytes(), &hunks),
+                Some(new.as_bytes().to_vec())
+            );
+        };
+        check("", "a\n");
+        check("a\n", "");
+        check("a\nb\nc\n", "a\nc\nd\n");
+        check("a\nb", "a\nb\n");
+        check("a\nb\n", "x\na\ny\nb");
+
+        let hunk = SnapshotHunk {
+            remove: 2..3,
+            add: Vec::new(),
+        };
+        assert_eq!(apply_hunks(b"a\n", &[hunk]), None);
+    }
+}
@@ -94,6 +94,8 @@ pub struct WalkerData<M> {
     result_sender: Sender<Result<WalkEntry>>,
     queue_sender: Sender<RepoPathBuf>,
     queue_receiver: Receiver<RepoPathBuf>,
+    // Shared by all threads. Matchers that load rules lazily, like
+    // GitignoreMatcher with nested ignore files, lock their own state.
     matcher: M,
     busy_nodes: AtomicU64,
     result_cnt: AtomicU64,
@@ -116,6 +118,7 @@ impl<M> WalkerData<M> {
 }
 
 pub struct Walker<M> {
+    num_threads: usize,
     threads: Vec<JoinHandle<Result<()>>>,
     results: Vec<Result<WalkEntry>>,
     result_receiver: Receiver<Result<WalkEntry>>,
@@ -139,12 +142,15 @@ where
         skip_dirs: Vec<PathBuf>,
         matcher: M,
         include_directories: bool,
+        num_threads: usize,
     ) -> Result<Self> {
         let (s_results, r_results) = unbounded();
         let (s_queue, r_queue) = unbounded();
+        let num_threads = num_threads.max(1);
 
         Ok(Walker {
-            threads: Vec::with_capacity(8),
+            num_threads,
+            threads: Vec::with_capacity(num_threads),
             results: Vec::new(),
             result_receiver: r_results,
             has_walked: false,
@@ -221,7 +227,7 @@ where
             self.payload.enqueue_work(RepoPathBuf::new())?;
         }
 
-        for _t in 0..self.threads.capacity() {
+        for _t in 0..self.num_threads {
             let shared_data = self.payload.clone();
 
             // TODO make sure that _t is different for each thread
@@ -329,6 +335,8 @@ mod tests {
     use std::path::PathBuf;
 
     use pathmatcher::AlwaysMatcher;
+    use pathmatcher::DifferenceMatcher;
+    use pathmatcher::GitignoreMatcher;
     use pathmatcher::NeverMatcher;
     use pathmatcher::TreeMatcher;
     use tempfile::tempdir;
@@ -365,6 +373,7 @@ mod tests {
             Vec::new(),
             NeverMatcher::new(),
             false,
+            8,
         )?;
         let walked_files: Result<Vec<_>> = walker.collect();
         let walked_files = walked_files?;
@@ -384,6 +393,7 @@ mod tests {
             Vec::new(),
             TreeMatcher::from_rules(["foo/bar/**"].iter(), true).unwrap(),
             false,
+            8,
         )?;
         let walked_files: Result<Vec<_>> = walker.collect();
         let walked_files = walked_files?;
@@ -395,6 +405,83 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_multiwalker_num_threads() -> Result<()> {
+        let directories = vec!["a/b/c", "d"];
+        let files = vec!["a/1", "a/b/2", "a/b/c/3", "d/4", "5"];
+        let root_dir = create_directory(&directories, &files)?;
+        for num_threads in [0, 1, 3, 16] {
+            let walker = Walker::new(
+                PathBuf::from(root_dir.path()),
+                ".hg".to_string(),
+                Vec::new(),
+                AlwaysMatcher::new(),
+                false,
+                num_threads,
+            )?;
+            let mut walked_files = walker
+                .map(|entry| Ok(entry?.as_ref().to_string()))
+                .collect::<Result<Vec<_>>>()?;
+            walked_files.sort();
+            assert_eq!(walked_files, ["5", "a/1", "a/b/2", "a/b/c/3", "d/4"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiwalker_gitignore() -> Result<()> {
+        let directories = vec!["a/b", "d/skip"];
+        let files = vec![
+            "x.log",
+            "y.txt",
+            "a/z.log",
+            "a/w.txt",
+            "a/b/u.tmp",
+            "a/b/v.txt",
+            "d/t.txt",
+            "d/skip/s.txt",
+        ];
+        let root_dir = create_directory(&directories, &files)?;
+        fs::write(root_dir.path().join(".gitignore"), "*.log\n")?;
+        fs::write(root_dir.path().join("a/b/.gitignore"), "*.tmp\n")?;
+        fs::write(root_dir.path().join("d/.gitignore"), "skip/\n")?;
+
+        // Nested ignore files are loaded lazily by whichever thread enters
+        // their directory first. The result must not depend on the number
+        // of threads.
+        for num_threads in [1, 8, 32] {
+            let matcher = Arc::new(DifferenceMatcher::new(
+                AlwaysMatcher::new(),
+                GitignoreMatcher::new(root_dir.path(), Vec::new(), true),
+            ));
+            let walker = Walker::new(
+                PathBuf::from(root_dir.path()),
+                ".hg".to_string(),
+                Vec::new(),
+                matcher,
+                false,
+                num_threads,
+            )?;
+            let mut walked_files = walker
+                .map(|entry| Ok(entry?.as_ref().to_string()))
+                .collect::<Result<Vec<_>>>()?;
+            walked_files.sort();
+            assert_eq!(
+                walked_files,
+                [
+                    ".gitignore",
+                    "a/b/.gitignore",
+                    "a/b/v.txt",
+                    "a/w.txt",
+                    "d/.gitignore",
+                    "d/t.txt",
+                    "y.txt",
+                ]
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_multiwalker_dirs() -> Result<()> {
         let directories = vec!["dirA", "dirB/dirC/dirD"];
@@ -407,6 +494,7 @@ mod tests {
             Vec::new(),
             AlwaysMatcher::new(),
             true,
+            8,
         )?;
         let walked_files: Result<Vec<_>> = walker.collect();
         let walked_files = walked_files?;
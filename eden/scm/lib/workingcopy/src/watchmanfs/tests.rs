@@ -189,6 +189,7 @@ fn check(mut tc: TestCase) -> Result<()> {
                     }
                 },
                 PendingChangeResult::SeenDirectory(_) => assert!(false, "seen directory?"),
+                PendingChangeResult::Copied(_) => assert!(false, "copied?"),
             }
         }
     } else {
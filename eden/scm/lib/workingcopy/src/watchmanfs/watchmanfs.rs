@@ -44,6 +44,9 @@ use crate::filechangedetector::ResolvedFileChangeResult;
 use crate::filesystem::ChangeType;
 use crate::filesystem::PendingChangeResult;
 use crate::filesystem::PendingChanges;
+use crate::filesystem::ProgressCallback;
+use crate::filesystem::ProgressReporter;
+use crate::filesystem::PROGRESS_INTERVAL;
 use crate::metadata;
 use crate::metadata::Metadata;
 use crate::util::walk_treestate;
@@ -60,6 +63,7 @@ pub struct WatchmanFileSystem {
     tree_resolver: ArcReadTreeManifest,
     store: ArcReadFileContents,
     locker: Arc<RepoLocker>,
+    progress: Option<ProgressCallback>,
 }
 
 struct WatchmanConfig {
@@ -109,6 +113,7 @@ impl WatchmanFileSystem {
             tree_resolver,
             store,
             locker,
+            progress: None,
         })
     }
 
@@ -296,6 +301,7 @@ impl PendingChanges for WatchmanFileSystem {
         let manifests = WorkingCopy::current_manifests(ts, &self.tree_resolver)?;
 
         let mut wm_errors: Vec<ParseError> = Vec::new();
+        let file_count = result.files.as_ref().map_or(0, |f| f.len()) as u64;
         let use_watchman_metadata =
             config.get_or::<bool>("workingcopy", "use-watchman-metadata", || true)?;
         let wm_needs_check: Vec<metadata::File> = result
@@ -394,8 +400,24 @@ impl PendingChanges for WatchmanFileSystem {
             maybe_flush_treestate(config, self.vfs.root(), ts, &self.locker)?;
         }
 
+        // Watchman only reports the files that may have changed, once it is
+        // done.
+        let mut progress = ProgressReporter::new(self.progress.clone(), PROGRESS_INTERVAL);
+        progress.update(|p| {
+            p.files_scanned = file_count;
+            p.changes_found = pending_changes
+                .pending_changes
+                .iter()
+                .filter(|c| matches!(c, Ok(PendingChangeResult::File(_))))
+                .count() as u64;
+        });
+
         Ok(Box::new(pending_changes.into_iter()))
     }
+
+    fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.progress = callback;
+    }
 }
 
 fn warn_about_fresh_instance(io: &IO, old_pid: Option<u32>, new_pid: Option<u32>) -> Result<()> {
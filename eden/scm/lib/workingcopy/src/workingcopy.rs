@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -45,6 +46,9 @@ use types::RepoPath;
 use types::RepoPathBuf;
 use vfs::VFS;
 
+use crate::conflicts;
+use crate::conflicts::ConflictDetectorOptions;
+use crate::conflicts::ConflictingFile;
 #[cfg(feature = "eden")]
 use crate::edenfs::EdenFileSystem;
 use crate::errors;
@@ -52,8 +56,10 @@ use crate::filesystem::ChangeType;
 use crate::filesystem::FileSystemType;
 use crate::filesystem::PendingChangeResult;
 use crate::filesystem::PendingChanges;
+use crate::filesystem::ProgressCallback;
 use crate::git::parse_submodules;
 use crate::physicalfs::PhysicalFileSystem;
+use crate::physicalfs::PhysicalFileSystemOptions;
 use crate::status::compute_status;
 use crate::util::walk_treestate;
 use crate::watchmanfs::WatchmanFileSystem;
@@ -109,15 +115,6 @@ impl WorkingCopy {
             vfs.case_sensitive(),
         ));
 
-        let filesystem = Mutex::new(Self::construct_file_system(
-            vfs.clone(),
-            file_system_type,
-            treestate.clone(),
-            tree_resolver.clone(),
-            filestore,
-            locker.clone(),
-        )?);
-
         let root = vfs.root();
         let ident = match identity::sniff_dir(&root)? {
             Some(ident) => ident,
@@ -127,6 +124,16 @@ impl WorkingCopy {
         };
         let dot_hg_path = vfs.join(RepoPath::from_str(ident.dot_dir())?);
 
+        let filesystem = Mutex::new(Self::construct_file_system(
+            vfs.clone(),
+            file_system_type,
+            treestate.clone(),
+            tree_resolver.clone(),
+            filestore,
+            locker.clone(),
+            PhysicalFileSystemOptions::from_config(config, &dot_hg_path)?,
+        )?);
+
         Ok(WorkingCopy {
             vfs,
             format,
@@ -161,6 +168,12 @@ impl WorkingCopy {
         &self.vfs
     }
 
+    /// Report the progress of the scans done by `status` to `callback`.
+    /// `None` stops reporting.
+    pub fn set_progress(&self, callback: Option<ProgressCallback>) {
+        self.filesystem.lock().inner.set_progress(callback);
+    }
+
     pub fn parents(&self) -> Result<Vec<HgId>> {
         self.treestate.lock().parents().collect()
     }
@@ -169,6 +182,14 @@ impl WorkingCopy {
         self.treestate.lock().set_parents(parents)
     }
 
+    pub(crate) fn tree_manifest(&self, commit: &HgId) -> Result<Arc<RwLock<TreeManifest>>> {
+        self.tree_resolver.get(commit)
+    }
+
+    pub(crate) fn file_store(&self) -> ArcReadFileContents {
+        self.filesystem.lock().file_store.clone()
+    }
+
     pub(crate) fn current_manifests(
         treestate: &TreeState,
         tree_resolver: &ArcReadTreeManifest,
@@ -210,15 +231,19 @@ impl WorkingCopy {
         tree_resolver: ArcReadTreeManifest,
         store: ArcReadFileContents,
         locker: Arc<RepoLocker>,
+        physical_options: PhysicalFileSystemOptions,
     ) -> Result<FileSystem> {
         let inner: Box<dyn PendingChanges + Send> = match file_system_type {
-            FileSystemType::Normal => Box::new(PhysicalFileSystem::new(
-                vfs.clone(),
-                tree_resolver,
-                store.clone(),
-                treestate.clone(),
-                false,
-            )?),
+            FileSystemType::Normal => Box::new(
+                PhysicalFileSystem::new(
+                    vfs.clone(),
+                    tree_resolver,
+                    store.clone(),
+                    treestate.clone(),
+                    false,
+                )?
+                .with_options(physical_options),
+            ),
             FileSystemType::Watchman => Box::new(WatchmanFileSystem::new(
                 vfs.clone(),
                 treestate.clone(),
@@ -232,11 +257,22 @@ impl WorkingCopy {
                 #[cfg(feature = "eden")]
                 Box::new(EdenFileSystem::new(
                     vfs.clone(),
+                    treestate.clone(),
                     treestate
                         .lock()
                         .parents()
                         .next()
                         .unwrap_or_else(|| Ok(NULL_ID))?,
+                    Box::new(
+                        PhysicalFileSystem::new(
+                            vfs.clone(),
+                            tree_resolver,
+                            store.clone(),
+                            treestate.clone(),
+                            false,
+                        )?
+                        .with_options(physical_options),
+                    ),
                 )?)
             }
         };
@@ -355,6 +391,7 @@ impl WorkingCopy {
             }
         }
 
+        let mut copies = HashMap::new();
         let pending_changes = self
             .filesystem
             .lock()
@@ -378,6 +415,14 @@ impl WorkingCopy {
                         _ => None,
                     }
                 }
+                Ok(PendingChangeResult::Copied(copy)) => match matcher.matches_file(&copy.path) {
+                    Ok(true) => {
+                        copies.insert(copy.path, copy.source);
+                        None
+                    }
+                    Ok(false) => None,
+                    Err(e) => Some(Err(e)),
+                },
                 Err(e) => Some(Err(e)),
                 _ => None,
             })
@@ -401,7 +446,8 @@ impl WorkingCopy {
             self.treestate.clone(),
             pending_changes,
             matcher.clone(),
-        )?;
+        )?
+        .copies(copies);
 
         if !self.vfs.supports_symlinks()
             && config
@@ -415,6 +461,28 @@ impl WorkingCopy {
         Ok(status_builder.build())
     }
 
+    /// Files changed in the working copy that still have conflict markers,
+    /// sorted by path. Markers are `merge.marker-length` characters long,
+    /// 7 by default.
+    pub fn detect_conflicts(
+        &self,
+        config: &dyn Config,
+        io: &IO,
+        options: &ConflictDetectorOptions,
+    ) -> Result<Vec<ConflictingFile>> {
+        let marker_length = config.get_or("merge", "marker-length", || {
+            conflicts::DEFAULT_MARKER_LENGTH
+        })?;
+        let status = self.status(
+            Arc::new(AlwaysMatcher::new()),
+            SystemTime::UNIX_EPOCH,
+            config,
+            io,
+        )?;
+        let changed = status.modified().chain(status.added()).cloned().collect();
+        conflicts::detect_conflicts(&self.vfs, changed, marker_length, options)
+    }
+
     // Filter out modified symlinks where it appears the symlink has
     // been modified to no longer be a symlink. This happens often on
     // Windows because we don't materialize symlinks in the working